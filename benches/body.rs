@@ -0,0 +1,108 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use simple_hyper_client::{blocking, to_bytes, Bytes, HttpConnector, SharedBody};
+use tokio::runtime::Runtime;
+
+use std::convert::Infallible;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+
+const CHUNK_SIZE: usize = 16 * 1024;
+const CHUNKS: usize = 64;
+
+fn shared_body(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let chunks: Vec<Bytes> = (0..CHUNKS)
+        .map(|_| Bytes::from(vec![0u8; CHUNK_SIZE]))
+        .collect();
+    let buffered = Bytes::from(vec![0u8; CHUNK_SIZE * CHUNKS]);
+
+    let mut group = c.benchmark_group("shared_body");
+    group.throughput(Throughput::Bytes((CHUNK_SIZE * CHUNKS) as u64));
+    group.bench_function("buffered", |b| {
+        b.iter(|| {
+            rt.block_on(to_bytes(SharedBody::from(buffered.clone())))
+                .unwrap()
+        })
+    });
+    group.bench_function("chunks", |b| {
+        b.iter(|| {
+            rt.block_on(to_bytes(SharedBody::from_chunks(chunks.clone())))
+                .unwrap()
+        })
+    });
+    group.bench_function("stream", |b| {
+        b.iter(|| {
+            let stream = tokio_stream::iter(chunks.clone().into_iter().map(Ok::<_, Infallible>));
+            rt.block_on(to_bytes(SharedBody::wrap_stream(stream)))
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+/// Starts a server answering every connection with a response of
+/// `CHUNK_SIZE * CHUNKS` bytes written in `CHUNK_SIZE` writes.
+fn start_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let chunk = vec![0u8; CHUNK_SIZE];
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let chunk = chunk.clone();
+            thread::spawn(move || {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf);
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    CHUNK_SIZE * CHUNKS
+                );
+                stream.write_all(head.as_bytes()).unwrap();
+                for _ in 0..CHUNKS {
+                    stream.write_all(&chunk).unwrap();
+                }
+            });
+        }
+    });
+    addr
+}
+
+fn blocking_body(c: &mut Criterion) {
+    let uri = format!("http://{}/", start_server());
+    let client = blocking::Client::with_connector(HttpConnector::new());
+
+    let mut group = c.benchmark_group("blocking_body");
+    group.throughput(Throughput::Bytes((CHUNK_SIZE * CHUNKS) as u64));
+    group.bench_function("read_to_end", |b| {
+        b.iter(|| {
+            let mut response = client.get(uri.as_str()).unwrap().send().unwrap();
+            let mut buf = Vec::new();
+            response.body_mut().read_to_end(&mut buf).unwrap();
+            buf
+        })
+    });
+    group.bench_function("read_8k", |b| {
+        b.iter(|| {
+            let mut response = client.get(uri.as_str()).unwrap().send().unwrap();
+            let mut buf = [0u8; 8 * 1024];
+            let mut total = 0;
+            loop {
+                match response.body_mut().read(&mut buf).unwrap() {
+                    0 => break total,
+                    n => total += n,
+                }
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, shared_body, blocking_body);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use simple_hyper_client::{Client, HttpConnector, Observer};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+
+use std::net::SocketAddr;
+
+const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+/// Starts a server answering one request per connection, so that every
+/// request sent by the client goes through the connector.
+fn start_server(rt: &Runtime) -> SocketAddr {
+    let listener = rt.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+    let addr = listener.local_addr().unwrap();
+    rt.spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream.write_all(RESPONSE).await;
+            });
+        }
+    });
+    addr
+}
+
+struct NoopObserver;
+
+impl Observer for NoopObserver {}
+
+fn connect(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let uri = format!("http://{}/", start_server(&rt));
+
+    let plain = Client::builder()
+        .pool_max_idle_per_host(0)
+        .build(HttpConnector::new());
+    let observed = Client::builder()
+        .pool_max_idle_per_host(0)
+        .observer(NoopObserver)
+        .build(HttpConnector::new());
+
+    let mut group = c.benchmark_group("connect");
+    for (name, client) in [("plain", plain), ("observed", observed)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                rt.block_on(async { client.get(uri.as_str()).unwrap().send().await.unwrap() })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, connect);
+criterion_main!(benches);
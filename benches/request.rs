@@ -0,0 +1,37 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use headers::{ContentType, UserAgent};
+use simple_hyper_client::{Client, HttpConnector};
+
+fn request_build(c: &mut Criterion) {
+    let client = Client::with_connector(HttpConnector::new());
+    let body = vec![0u8; 1024];
+
+    let mut group = c.benchmark_group("request_build");
+    group.bench_function("get", |b| {
+        b.iter(|| {
+            let req = client.get("http://example.com/path?q=1").unwrap();
+            black_box(req.build().unwrap())
+        })
+    });
+    group.bench_function("post_with_headers", |b| {
+        b.iter(|| {
+            let req = client
+                .post("http://example.com/path?q=1")
+                .unwrap()
+                .header(ContentType::octet_stream())
+                .header(UserAgent::from_static("bench"))
+                .body(body.clone());
+            black_box(req.build().unwrap())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, request_build);
+criterion_main!(benches);
@@ -4,19 +4,63 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+#[cfg(feature = "cache")]
+use crate::cache::{Cache, CacheStore, MemoryCacheStore};
+use crate::capture::CapturedBodies;
+#[cfg(feature = "cbor")]
+use crate::cbor;
+#[cfg(feature = "checksum")]
+use crate::checksum::{request_checksum, verify_response_checksum, ChecksumAlgorithm};
+use crate::clock::{Clock, SystemClock};
+#[cfg(feature = "compression")]
+use crate::compression::{accept_encoding, compress, decompress_response, Encoding};
+use crate::conditional::{Revalidation, Validators};
+#[cfg(feature = "tcp")]
+use crate::connector::Proxy;
 use crate::connector::{ConnectorAdapter, NetworkConnector};
-use crate::error::Error;
-use crate::shared_body::SharedBody;
+use crate::error::{Error, RequestContext};
+#[cfg(feature = "graphql")]
+use crate::graphql;
+use crate::in_flight::{attach_permit, InFlightLimit};
+use crate::middleware::{Middleware, Next};
+#[cfg(feature = "msgpack")]
+use crate::msgpack;
+use crate::observer::{ObservedRequest, Observer};
+use crate::pagination::Pages;
+#[cfg(feature = "log")]
+use crate::request_ext::SENSITIVE_HEADERS;
+use crate::request_id::RequestId;
+use crate::retry::{copy_request, RetryPolicy};
+use crate::retry_budget::RetryBudget;
+use crate::session::Session;
+use crate::shared_body::{ProgressCallback, SharedBody};
+use crate::validation::{limit_response_size, validate_response, validate_uri};
+#[cfg(feature = "log")]
+use crate::wire_log::WireLog;
+#[cfg(feature = "xml")]
+use crate::xml;
 use crate::Response;
 
-use headers::{ContentLength, Header, HeaderMap, HeaderMapExt};
-use hyper::{Client as HyperClient, Method, Request, Uri};
+use headers::authorization::Authorization;
+use headers::{ContentLength, Header, HeaderMap, HeaderMapExt, IfModifiedSince};
+use http::uri::Authority;
+use hyper::client::connect::Connect;
+use hyper::client::ResponseFuture;
+#[cfg(feature = "compression")]
+use hyper::header::ACCEPT_ENCODING;
+use hyper::header::{HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONNECTION, IF_NONE_MATCH};
+use hyper::upgrade::Upgraded;
+use hyper::{Client as HyperClient, Method, Request, StatusCode, Uri, Version};
+use tokio::io::AsyncRead;
+use tokio::time;
 
+use std::any::Any;
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
 use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 /// A wrapper for [hyper's `Client` type] providing a simpler interface
 ///
@@ -30,7 +74,82 @@ use std::time::Duration;
 /// [hyper's `Client` type]: https://docs.rs/hyper/latest/hyper/client/struct.Client.html
 #[derive(Clone)]
 pub struct Client {
-    inner: Arc<HyperClient<ConnectorAdapter, SharedBody>>,
+    inner: Arc<ClientInner>,
+}
+
+struct ClientInner {
+    hyper: Arc<dyn SendRequest>,
+    // the configuration this client was built with
+    config: ClientBuilder,
+    max_response_header_size: Option<usize>,
+    strict_response_validation: bool,
+    http1_keepalive: bool,
+    max_response_size: Option<u64>,
+    request_id_header: Option<HeaderName>,
+    error_context: bool,
+    error_context_redact_query: bool,
+    observers: Arc<[Arc<dyn Observer>]>,
+    middleware: Arc<[Arc<dyn Middleware>]>,
+    capture_bodies: Option<usize>,
+    default_accept: Option<HeaderValue>,
+    #[cfg(feature = "compression")]
+    accept_encodings: Vec<Encoding>,
+    #[cfg(feature = "compression")]
+    accept_encoding: Option<HeaderValue>,
+    retry_budget: Option<RetryBudget>,
+    retry_policy: Option<RetryPolicy>,
+    in_flight: Option<InFlightLimit>,
+    #[cfg(feature = "checksum")]
+    verify_response_checksums: bool,
+    #[cfg(feature = "cache")]
+    cache: Option<Cache>,
+    #[cfg(feature = "log")]
+    wire_log: Option<WireLog>,
+    // the proxy of the connector, see `NetworkConnector::forward_proxy()`
+    #[cfg(feature = "tcp")]
+    proxy: Option<Proxy>,
+}
+
+/// Object-safe subset of [hyper's `Client`] so that clients with any
+/// connector type can be wrapped, see [`Client::from_hyper()`].
+///
+/// [hyper's `Client`]: HyperClient
+trait SendRequest: Send + Sync + 'static {
+    fn request(&self, request: Request<SharedBody>) -> ResponseFuture;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<C> SendRequest for HyperClient<C, SharedBody>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn request(&self, request: Request<SharedBody>) -> ResponseFuture {
+        HyperClient::request(self, request)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Per-request override of [`ClientBuilder::max_response_size()`], stored
+/// in the request extensions.
+#[derive(Clone, Copy)]
+struct MaxResponseSize(Option<u64>);
+
+/// The HTTP version required by [`RequestBuilder::require_version()`],
+/// stored in the request extensions.
+#[derive(Clone, Copy)]
+struct RequiredVersion(Version);
+
+/// Copy the extensions of a request that are set by this crate.
+pub(crate) fn copy_extensions(from: &http::Extensions, to: &mut http::Extensions) {
+    if let Some(limit) = from.get::<MaxResponseSize>() {
+        to.insert(*limit);
+    }
+    if let Some(version) = from.get::<RequiredVersion>() {
+        to.insert(*version);
+    }
 }
 
 macro_rules! define_method_fn {
@@ -64,27 +183,333 @@ impl Client {
         ClientBuilder::new().build(connector)
     }
 
+    /// Create a new `Client` wrapping an existing hyper client.
+    ///
+    /// This is the same as `Client::builder().build_from_hyper(hyper)`, see
+    /// [`ClientBuilder::build_from_hyper()`].
+    pub fn from_hyper<C>(hyper: HyperClient<C, SharedBody>) -> Self
+    where
+        C: Connect + Clone + Send + Sync + 'static,
+    {
+        ClientBuilder::new().build_from_hyper(hyper)
+    }
+
+    /// Returns the clock the client was built with, see
+    /// [`ClientBuilder::clock()`].
+    pub(crate) fn clock(&self) -> &dyn Clock {
+        &*self.inner.config.clock
+    }
+
+    /// Returns the wrapped hyper client if this client was created through
+    /// [`Client::from_hyper()`] or [`ClientBuilder::build_from_hyper()`]
+    /// with connector type `C`.
+    pub fn hyper_client<C: 'static>(&self) -> Option<&HyperClient<C, SharedBody>> {
+        self.inner.hyper.as_any().downcast_ref()
+    }
+
+    /// Returns a builder with the configuration of this client.
+    ///
+    /// Building a client from it creates a new connection pool, use
+    /// [`with_overrides()`] to share the pool of this client instead.
+    ///
+    /// [`with_overrides()`]: #method.with_overrides
+    pub fn to_builder(&self) -> ClientBuilder {
+        self.inner.config.clone()
+    }
+
+    /// Create a client with the configuration of this client changed by
+    /// `f`, sharing the connector and connection pool of this client.
+    ///
+    /// This is useful for variations such as a different
+    /// [`max_response_size()`] or [`request_id_header()`] per tenant. The
+    /// client shares the [`max_in_flight()`] limit of this client unless
+    /// it is changed by `f`. The
+    /// options that configure the connection pool, i.e. those that have no
+    /// effect in [`ClientBuilder::build_from_hyper()`], cannot be changed
+    /// this way. Observers added by `f` are not notified of connection
+    /// attempts.
+    ///
+    /// [`max_response_size()`]: ClientBuilder::max_response_size
+    /// [`request_id_header()`]: ClientBuilder::request_id_header
+    /// [`max_in_flight()`]: ClientBuilder::max_in_flight
+    pub fn with_overrides<F: FnOnce(&mut ClientBuilder)>(&self, f: F) -> Client {
+        let mut config = self.to_builder();
+        f(&mut config);
+        let mut client = config.wrap(self.inner.hyper.clone(), config.observers.clone().into());
+        let inner = Arc::get_mut(&mut client.inner).expect("client was just created");
+        if let (Some(limit), Some(max)) = (&self.inner.in_flight, config.max_in_flight) {
+            inner.in_flight = Some(limit.derive(max, config.max_in_flight_timeout));
+        }
+        #[cfg(feature = "tcp")]
+        {
+            inner.proxy = self.inner.proxy.clone();
+        }
+        client
+    }
+
     /// This method can be used instead of [Client::request]
     /// if the caller already has a [Request].
     pub async fn send(&self, request: Request<SharedBody>) -> Result<Response, Error> {
-        Ok(self.inner.request(request).await?)
+        let context = match self.inner.error_context {
+            true => Some((request.method().clone(), request.uri().clone())),
+            false => None,
+        };
+        let res = match self.inner.middleware.is_empty() {
+            true => self.send_without_middleware(request).await,
+            false => Next::new(self, &self.inner.middleware).run(request).await,
+        };
+        match (res, context) {
+            (Err(e), Some((method, uri))) => {
+                let redact_query = self.inner.error_context_redact_query;
+                let context = RequestContext::new(method, &uri, redact_query);
+                Err(Error::WithContext(context, Box::new(e)))
+            }
+            (res, _) => res,
+        }
+    }
+
+    /// Send `request` after the middleware has run, see [`Next::run()`].
+    pub(crate) async fn send_without_middleware(
+        &self,
+        mut request: Request<SharedBody>,
+    ) -> Result<Response, Error> {
+        if let Some(ref budget) = self.inner.retry_budget {
+            budget.deposit();
+        }
+        if let Some(ref accept) = self.inner.default_accept {
+            request
+                .headers_mut()
+                .entry(ACCEPT)
+                .or_insert_with(|| accept.clone());
+        }
+        #[cfg(feature = "compression")]
+        let decompress = match self.inner.accept_encoding {
+            Some(ref accept) if !request.headers().contains_key(ACCEPT_ENCODING) => {
+                request
+                    .headers_mut()
+                    .insert(ACCEPT_ENCODING, accept.clone());
+                true
+            }
+            _ => false,
+        };
+        // The size limit applies to the decompressed body as well
+        #[cfg(feature = "compression")]
+        let max_response_size = match request.extensions().get::<MaxResponseSize>() {
+            Some(MaxResponseSize(limit)) => *limit,
+            None => self.inner.max_response_size,
+        };
+        let request_id = self
+            .inner
+            .request_id_header
+            .as_ref()
+            .map(|header| RequestId::apply(header, request.headers_mut()));
+        let observed = ObservedRequest::start(&self.inner.observers, &request);
+        let captured = self
+            .inner
+            .capture_bodies
+            .map(|limit| CapturedBodies::new(&request, limit));
+        let res = self.send_with_retries(request).await;
+        #[cfg(feature = "compression")]
+        let res = match decompress {
+            true => res.and_then(|r| {
+                decompress_response(r, &self.inner.accept_encodings, max_response_size)
+            }),
+            false => res,
+        };
+        let res = match captured {
+            Some(captured) => res.map(|r| captured.attach(r)),
+            None => res,
+        };
+        let res = match (res, request_id) {
+            (Ok(mut response), Some(id)) => {
+                response.extensions_mut().insert(id);
+                Ok(response)
+            }
+            (Err(e), Some(id)) => Err(Error::WithRequestId(id, Box::new(e))),
+            (res, None) => res,
+        };
+        if let Some(observed) = observed {
+            observed.finish(&res);
+        }
+        res
+    }
+
+    async fn send_with_retries(&self, request: Request<SharedBody>) -> Result<Response, Error> {
+        let policy = match self.inner.retry_policy {
+            Some(ref policy) if policy.applies_to(&request) => policy,
+            _ => return self.send_request(request).await,
+        };
+        for retry in 1..policy.max_attempts() {
+            let res = self.send_request(copy_request(&request)).await;
+            if !policy.should_retry(&res) {
+                return res;
+            }
+            if let Some(ref budget) = self.inner.retry_budget {
+                if !budget.withdraw() {
+                    return res;
+                }
+            }
+            for observer in self.inner.observers.iter() {
+                observer.on_retry(request.method(), request.uri(), retry, &res);
+            }
+            // Release the connection before waiting
+            drop(res);
+            time::sleep(policy.backoff(retry)).await;
+        }
+        self.send_request(request).await
+    }
+
+    async fn send_request(&self, mut request: Request<SharedBody>) -> Result<Response, Error> {
+        if !self.inner.http1_keepalive && request.version() <= Version::HTTP_11 {
+            request
+                .headers_mut()
+                .entry(CONNECTION)
+                .or_insert(HeaderValue::from_static("close"));
+        }
+        #[cfg(feature = "tcp")]
+        if let Some(ref proxy) = self.inner.proxy {
+            if let Some(headers) = proxy.http_headers(request.uri()) {
+                for (name, value) in headers {
+                    request
+                        .headers_mut()
+                        .entry(name)
+                        .or_insert_with(|| value.clone());
+                }
+            }
+        }
+        #[cfg(feature = "cache")]
+        let cache_request = match self.inner.cache {
+            Some(ref cache) => {
+                let cache_request = cache.request(&request);
+                if let Some(response) = cache.lookup(&cache_request).await {
+                    return Ok(response);
+                }
+                Some(cache_request)
+            }
+            None => None,
+        };
+        let permit = match self.inner.in_flight {
+            Some(ref limit) => Some(limit.acquire().await?),
+            None => None,
+        };
+        let method = request.method().clone();
+        let max_response_size = match request.extensions_mut().remove::<MaxResponseSize>() {
+            Some(MaxResponseSize(limit)) => limit,
+            None => self.inner.max_response_size,
+        };
+        let required_version = request.extensions_mut().remove::<RequiredVersion>();
+        #[cfg(feature = "log")]
+        if let Some(ref log) = self.inner.wire_log {
+            log.request(&request);
+        }
+        let response = self.inner.hyper.request(request).await?;
+        #[cfg(feature = "log")]
+        if let Some(ref log) = self.inner.wire_log {
+            log.response(&response);
+        }
+        if let Some(RequiredVersion(version)) = required_version {
+            if response.version() != version {
+                return Err(Error::UnexpectedVersion(response.version()));
+            }
+        }
+        if let Some(limit) = self.inner.max_response_header_size {
+            if response_header_size(&response) > limit {
+                return Err(Error::ResponseHeadersTooLarge(limit));
+            }
+        }
+        let response = match max_response_size {
+            Some(limit) => limit_response_size(&method, response, limit)?,
+            None => response,
+        };
+        #[cfg(feature = "checksum")]
+        let response = match self.inner.verify_response_checksums {
+            true => verify_response_checksum(&method, response),
+            false => response,
+        };
+        let response = match self.inner.strict_response_validation {
+            true => validate_response(&method, response)?,
+            false => response,
+        };
+        #[cfg(feature = "cache")]
+        let response = match (&self.inner.cache, cache_request) {
+            (Some(cache), Some(request)) => cache.update(request, response).await,
+            _ => response,
+        };
+        Ok(match permit {
+            Some(permit) => attach_permit(response, permit),
+            None => response,
+        })
+    }
+
+    /// Returns the retry budget of the client, see
+    /// [`ClientBuilder::retry_budget()`].
+    pub fn retry_budget(&self) -> Option<&RetryBudget> {
+        self.inner.retry_budget.as_ref()
     }
 
     /// Initiate a request with the specified method and URI.
     ///
-    /// Returns an error if `uri` is invalid.
+    /// Returns an error if `uri` is invalid or is not an absolute URI with
+    /// a host. The host is lowercased and a default port such as `:443` for
+    /// `https` is removed. A fragment (`#...`) is not part of the target and
+    /// is never sent.
     pub fn request<U>(&self, method: Method, uri: U) -> Result<RequestBuilder<'_>, Error>
     where
         Uri: TryFrom<U>,
         <Uri as TryFrom<U>>::Error: Into<http::Error>,
     {
         let uri = uri.try_into().map_err(Into::into).map_err(Error::Http)?;
+        let uri = validate_uri(uri)?;
         Ok(RequestBuilder {
             client: self,
             details: RequestDetails::new(method, uri),
+            session: None,
         })
     }
 
+    /// Send `request` and follow the links with relation type `next` in
+    /// the `Link` headers of the responses, see RFC 8288.
+    ///
+    /// Requests for later pages use `GET` and the headers of `request`,
+    /// except the ones describing its body. Relative links are resolved
+    /// against the URI of the request for the current page. See [`Pages`]
+    /// for details.
+    pub fn paginate(&self, request: Request<SharedBody>) -> Pages {
+        Pages::new(self.clone(), request)
+    }
+
+    /// Establish a tunnel to `authority` by sending a `CONNECT` request.
+    ///
+    /// The request goes through the client's connector and connection pool
+    /// like any other request, so in order to tunnel through an HTTP proxy
+    /// the connector needs to connect to the proxy. If the proxy responds
+    /// with a 2xx status code, the raw bidirectional stream is returned and
+    /// can be used to run any protocol on top of it.
+    ///
+    /// Returns an error if `authority` is invalid or the tunnel could not be
+    /// established.
+    pub async fn connect_tunnel<A>(&self, authority: A) -> Result<Upgraded, Error>
+    where
+        Authority: TryFrom<A>,
+        <Authority as TryFrom<A>>::Error: Into<http::Error>,
+    {
+        let authority = Authority::try_from(authority)
+            .map_err(Into::into)
+            .map_err(Error::Http)?;
+        let mut parts = http::uri::Parts::default();
+        parts.authority = Some(authority);
+        let uri = Uri::from_parts(parts).map_err(|e| Error::Http(e.into()))?;
+        let req = Request::builder()
+            .method(Method::CONNECT)
+            .uri(uri)
+            .body(SharedBody::empty())?;
+        let response = self.send(req).await?;
+        if !response.status().is_success() {
+            return Err(Error::TunnelRejected(response.status()));
+        }
+        Ok(hyper::upgrade::on(response).await?)
+    }
+
     define_method_fn!(get, GET);
     define_method_fn!(head, HEAD);
     define_method_fn!(post, POST);
@@ -102,13 +527,84 @@ impl Client {
 pub struct ClientBuilder {
     max_idle_per_host: usize,
     idle_timeout: Option<Duration>,
+    http1_read_buf_exact_size: Option<usize>,
+    http1_max_buf_size: Option<usize>,
+    http1_allow_obsolete_multiline_headers_in_responses: bool,
+    http1_allow_spaces_after_header_name_in_responses: bool,
+    http1_ignore_invalid_headers_in_responses: bool,
+    http1_title_case_headers: bool,
+    http1_preserve_header_case: bool,
+    max_response_header_size: Option<usize>,
+    strict_response_validation: bool,
+    http1_keepalive: bool,
+    max_response_size: Option<u64>,
+    request_id_header: Option<HeaderName>,
+    error_context: bool,
+    error_context_redact_query: bool,
+    observers: Vec<Arc<dyn Observer>>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    executor: Option<SharedExecutor>,
+    capture_bodies: Option<usize>,
+    default_accept: Option<HeaderValue>,
+    #[cfg(feature = "compression")]
+    accept_encodings: Vec<Encoding>,
+    retry_budget: Option<RetryBudget>,
+    retry_policy: Option<RetryPolicy>,
+    max_in_flight: Option<usize>,
+    max_in_flight_timeout: Option<Duration>,
+    #[cfg(feature = "checksum")]
+    verify_response_checksums: bool,
+    #[cfg(feature = "cache")]
+    cache_store: Option<Arc<dyn CacheStore>>,
+    clock: Arc<dyn Clock>,
+    #[cfg(feature = "log")]
+    log_wire: bool,
+    #[cfg(feature = "log")]
+    log_redacted_headers: Vec<HeaderName>,
 }
 
+// See hyper's `proto::h1::io::MINIMUM_MAX_BUFFER_SIZE`.
+const HTTP1_MINIMUM_MAX_BUF_SIZE: usize = 8192;
+
 impl ClientBuilder {
     pub(crate) fn new() -> Self {
         ClientBuilder {
             max_idle_per_host: usize::MAX,
             idle_timeout: Some(Duration::from_secs(90)),
+            http1_read_buf_exact_size: None,
+            http1_max_buf_size: None,
+            http1_allow_obsolete_multiline_headers_in_responses: false,
+            http1_allow_spaces_after_header_name_in_responses: false,
+            http1_ignore_invalid_headers_in_responses: false,
+            http1_title_case_headers: false,
+            http1_preserve_header_case: false,
+            max_response_header_size: None,
+            strict_response_validation: false,
+            http1_keepalive: true,
+            max_response_size: None,
+            request_id_header: None,
+            error_context: false,
+            error_context_redact_query: true,
+            observers: Vec::new(),
+            middleware: Vec::new(),
+            executor: None,
+            capture_bodies: None,
+            default_accept: None,
+            #[cfg(feature = "compression")]
+            accept_encodings: Vec::new(),
+            retry_budget: None,
+            retry_policy: None,
+            max_in_flight: None,
+            max_in_flight_timeout: None,
+            #[cfg(feature = "checksum")]
+            verify_response_checksums: false,
+            #[cfg(feature = "cache")]
+            cache_store: None,
+            clock: Arc::new(SystemClock),
+            #[cfg(feature = "log")]
+            log_wire: false,
+            #[cfg(feature = "log")]
+            log_redacted_headers: SENSITIVE_HEADERS.to_vec(),
         }
     }
 
@@ -130,219 +626,2582 @@ impl ClientBuilder {
         self
     }
 
-    /// Combine the configuration of this builder with a connector to create a
-    /// `Client`.
-    pub fn build<C: NetworkConnector>(&self, connector: C) -> Client {
-        Client {
-            inner: Arc::new(
-                HyperClient::builder()
-                    .pool_max_idle_per_host(self.max_idle_per_host)
-                    .pool_idle_timeout(self.idle_timeout)
-                    .executor(TokioExecutor)
-                    .build(ConnectorAdapter::new(connector)),
-            ),
-        }
+    /// Sets the exact size of the read buffer to *always* use.
+    ///
+    /// Note that setting this option unsets the `http1_max_buf_size` option.
+    ///
+    /// Default is an adaptive read buffer.
+    pub fn http1_read_buf_exact_size(&mut self, sz: usize) -> &mut Self {
+        self.http1_read_buf_exact_size = Some(sz);
+        self.http1_max_buf_size = None;
+        self
     }
-}
 
-pub(crate) struct RequestDetails {
-    pub(crate) method: Method,
-    pub(crate) uri: Uri,
-    pub(crate) headers: HeaderMap,
-    pub(crate) body: Option<SharedBody>,
-}
+    /// Set the maximum buffer size for HTTP/1 connections.
+    ///
+    /// Note that setting this option unsets the `http1_read_buf_exact_size`
+    /// option.
+    ///
+    /// Default is ~400kb.
+    ///
+    /// # Panics
+    ///
+    /// The minimum value allowed is 8192. This method panics if the passed
+    /// `max` is less than the minimum.
+    pub fn http1_max_buf_size(&mut self, max: usize) -> &mut Self {
+        assert!(
+            max >= HTTP1_MINIMUM_MAX_BUF_SIZE,
+            "the max_buf_size cannot be smaller than {}",
+            HTTP1_MINIMUM_MAX_BUF_SIZE
+        );
+        self.http1_max_buf_size = Some(max);
+        self.http1_read_buf_exact_size = None;
+        self
+    }
 
-impl fmt::Debug for RequestDetails {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("RequestDetails")
-            .field("method", &self.method)
-            .field("uri", &self.uri)
-            .field("headers", &self.headers.len())
-            .field("body", &self.body.as_ref().map_or("None", |_| "Some(...)"))
-            .finish()
+    /// Set whether HTTP/1 connections will accept obsolete line folding for
+    /// header values in responses.
+    ///
+    /// Newline codepoints (`\r` and `\n`) will be transformed to spaces when
+    /// parsing.
+    ///
+    /// NOTE: hyper always rejects responses with more than 100 headers, this
+    /// limit is not configurable.
+    ///
+    /// Has no effect if [`strict_response_validation()`] is enabled.
+    ///
+    /// Default is false.
+    ///
+    /// [`strict_response_validation()`]: #method.strict_response_validation
+    pub fn http1_allow_obsolete_multiline_headers_in_responses(&mut self, val: bool) -> &mut Self {
+        self.http1_allow_obsolete_multiline_headers_in_responses = val;
+        self
     }
-}
 
-impl RequestDetails {
-    pub fn new(method: Method, uri: Uri) -> Self {
-        RequestDetails {
-            method,
-            uri,
-            headers: HeaderMap::new(),
-            body: None,
-        }
+    /// Set whether HTTP/1 connections will accept spaces between header
+    /// names and the colon in responses, which RFC 7230 section 3.2.4
+    /// forbids.
+    ///
+    /// Has no effect if [`strict_response_validation()`] is enabled.
+    ///
+    /// Default is false.
+    ///
+    /// [`strict_response_validation()`]: #method.strict_response_validation
+    pub fn http1_allow_spaces_after_header_name_in_responses(&mut self, val: bool) -> &mut Self {
+        self.http1_allow_spaces_after_header_name_in_responses = val;
+        self
     }
 
-    pub async fn send(self, client: &Client) -> Result<Response, Error> {
-        let req = self.into_request()?;
-        Ok(client.inner.request(req).await?)
+    /// Set whether HTTP/1 connections will silently ignore invalid header
+    /// lines in responses instead of failing, mimicking browsers.
+    ///
+    /// Header lines containing `\0` are always rejected.
+    ///
+    /// Has no effect if [`strict_response_validation()`] is enabled.
+    ///
+    /// Default is false.
+    ///
+    /// [`strict_response_validation()`]: #method.strict_response_validation
+    pub fn http1_ignore_invalid_headers_in_responses(&mut self, val: bool) -> &mut Self {
+        self.http1_ignore_invalid_headers_in_responses = val;
+        self
     }
 
-    pub fn into_request(mut self) -> Result<Request<SharedBody>, Error> {
-        let can_have_body = match self.method {
-            // See RFC 7231 section 4.3
-            Method::GET | Method::HEAD | Method::DELETE => false,
-            _ => true,
-        };
-        let body = match can_have_body {
-            true => {
-                let body = self.body.unwrap_or_else(|| SharedBody::empty());
-                // NOTE: body cannot be chunked in this implementation, so we
-                // don't worry about chunked encoding here. But if this changes
-                // then we should not set `ContentLength` automatically if the
-                // request body is chunked, see RFC 7230 section 3.3.2.
-                self.headers.typed_insert(ContentLength(body.len() as u64));
-                body
-            }
-            false if self.body.is_some() => return Err(Error::BodyNotAllowed(self.method)),
-            false => SharedBody::empty(),
-        };
-        let mut req = Request::builder().method(self.method).uri(self.uri);
-        match req.headers_mut() {
-            Some(headers) => {
-                *headers = self.headers;
-            }
-            // There is an error in req, but the only way to extract the error is through `req.body()`
-            None => match req.body(SharedBody::empty()) {
-                Err(e) => return Err(e.into()),
-                Ok(_) => {
-                    panic!("request builder must have errors if `fn headers_mut()` returns None")
-                }
-            },
-        }
+    /// Set whether HTTP/1 connections will write header names as title case
+    /// at the socket level, e.g. `Content-Type` instead of `content-type`.
+    ///
+    /// This is useful for legacy servers that parse header names in a
+    /// case-sensitive manner.
+    ///
+    /// Default is false.
+    pub fn http1_title_case_headers(&mut self, val: bool) -> &mut Self {
+        self.http1_title_case_headers = val;
+        self
+    }
 
-        Ok(req.body(body)?)
+    /// Set whether to support preserving original header cases.
+    ///
+    /// When enabled, hyper records the original header cases of responses
+    /// and uses recorded cases when writing a request that carries them,
+    /// e.g. when forwarding a request received by a hyper server. Headers
+    /// without recorded cases are written in lower case (or title case if
+    /// [`http1_title_case_headers()`] is also enabled).
+    ///
+    /// Default is false.
+    ///
+    /// [`http1_title_case_headers()`]: #method.http1_title_case_headers
+    pub fn http1_preserve_header_case(&mut self, val: bool) -> &mut Self {
+        self.http1_preserve_header_case = val;
+        self
     }
-}
 
-/// An HTTP request builder
-///
-/// This is created through [`Client::get()`], [`Client::post()`] etc.
-/// You need to call [`send()`] to actually send the request over the network.
-/// If you don't want to send it and just want the resultant [Request], you
-/// can call [RequestBuilder::build].
-///
-/// [`Client::get()`]: struct.Client.html#method.get
-/// [`Client::post()`]: struct.Client.html#method.post
-/// [`send()`]: struct.RequestBuilder.html#method.send
-pub struct RequestBuilder<'a> {
-    client: &'a Client,
-    details: RequestDetails,
-}
+    /// Set an optional limit on the total size of response headers in bytes.
+    ///
+    /// The size of each header is computed as it would appear on the wire,
+    /// i.e. the length of its name and value plus 4 bytes for `": "` and
+    /// `"\r\n"`. Responses with headers exceeding this limit result in
+    /// [`Error::ResponseHeadersTooLarge`].
+    ///
+    /// NOTE: the memory used while parsing HTTP/1 responses is bounded by
+    /// [`http1_max_buf_size()`] regardless of this setting.
+    ///
+    /// Default is None (no limit).
+    ///
+    /// [`http1_max_buf_size()`]: #method.http1_max_buf_size
+    pub fn max_response_header_size(&mut self, val: Option<usize>) -> &mut Self {
+        self.max_response_header_size = val;
+        self
+    }
 
-impl<'a> RequestBuilder<'a> {
-    /// Set the request body.
-    pub fn body<B: Into<SharedBody>>(mut self, body: B) -> Self {
-        self.details.body = Some(body.into());
+    /// Enable or disable strict response validation.
+    ///
+    /// When enabled, the following responses are rejected with
+    /// [`Error::InvalidResponse`]:
+    /// - responses with both `Content-Length` and `Transfer-Encoding` headers,
+    /// - responses with multiple `Content-Length` headers or values, even if
+    ///   they are identical,
+    /// - responses with header values containing characters other than
+    ///   visible ASCII characters, spaces and tabs,
+    /// - responses with a body that is longer or shorter than declared in
+    ///   `Content-Length`. Since this can only be detected while reading the
+    ///   body, the error is returned from the response body stream as the
+    ///   source of a `hyper::Error`.
+    ///
+    /// Lenient HTTP/1 parsing options such as
+    /// [`http1_allow_obsolete_multiline_headers_in_responses()`] are ignored,
+    /// so responses using obsolete line folding or other deprecated
+    /// constructs fail to parse. [`try_build()`] rejects such conflicting
+    /// configurations.
+    ///
+    /// This is meant for security-sensitive use cases that prefer to fail
+    /// closed on anomalies commonly involved in request smuggling attacks.
+    ///
+    /// Default is false.
+    ///
+    /// [`http1_allow_obsolete_multiline_headers_in_responses()`]: #method.http1_allow_obsolete_multiline_headers_in_responses
+    /// [`try_build()`]: #method.try_build
+    pub fn strict_response_validation(&mut self, val: bool) -> &mut Self {
+        self.strict_response_validation = val;
         self
     }
 
-    /// Set the request headers.
-    pub fn headers(mut self, headers: HeaderMap) -> Self {
-        self.details.headers = headers;
+    /// Enable or disable HTTP/1 keep-alive.
+    ///
+    /// When disabled, every request uses a fresh connection: requests are
+    /// sent with a `Connection: close` header (unless the request already
+    /// has a `Connection` header) and connections are never returned to the
+    /// pool, regardless of [`pool_max_idle_per_host()`].
+    ///
+    /// Default is true.
+    ///
+    /// [`pool_max_idle_per_host()`]: #method.pool_max_idle_per_host
+    pub fn http1_keepalive(&mut self, val: bool) -> &mut Self {
+        self.http1_keepalive = val;
         self
     }
 
-    /// Set a single header using [`HeaderMapExt::typed_insert()`].
+    /// Set an optional limit on the size of response bodies in bytes.
     ///
-    /// [`HeaderMapExt::typed_insert()`]: https://docs.rs/headers/0.3.5/headers/trait.HeaderMapExt.html#tymethod.typed_insert
-    pub fn header<H: Header>(mut self, header: H) -> Self {
-        self.details.headers.typed_insert(header);
+    /// Responses declaring a larger `Content-Length` result in
+    /// [`Error::ResponseBodyTooLarge`]. Since `Content-Length` cannot be
+    /// trusted (and might be absent), reading the body also fails once more
+    /// than the allowed number of bytes have been received. In that case the
+    /// error is returned from the response body stream as the source of a
+    /// `hyper::Error`. This can be overridden for individual requests using
+    /// [`RequestBuilder::max_response_size()`].
+    ///
+    /// Default is None (no limit).
+    pub fn max_response_size(&mut self, val: Option<u64>) -> &mut Self {
+        self.max_response_size = val;
         self
     }
 
-    /// Get the resultant [Request].
+    /// Set an optional header carrying a unique ID for each request, such as
+    /// [`X_REQUEST_ID`].
     ///
-    /// Prefer [RequestBuilder::send] unless you have a specific
-    /// need to get the resultant [Request].
-    pub fn build(self) -> Result<Request<SharedBody>, Error> {
-        self.details.into_request()
+    /// If the request does not have the header, it is set to a random UUID.
+    /// The ID is stored as a [`RequestId`] in the response extensions, and
+    /// errors returned when sending the request are wrapped in
+    /// [`Error::WithRequestId`], which allows correlating them with server
+    /// logs.
+    ///
+    /// Default is None (no request IDs).
+    ///
+    /// [`X_REQUEST_ID`]: crate::X_REQUEST_ID
+    pub fn request_id_header(&mut self, val: Option<HeaderName>) -> &mut Self {
+        self.request_id_header = val;
+        self
     }
 
-    /// Send the request over the network.
+    /// If true, errors returned when sending requests are wrapped in
+    /// [`Error::WithContext`] with the method and URI of the request, so that
+    /// logged errors identify the request that failed.
     ///
-    /// Returns an error before sending the request if there is something wrong
-    /// with the request parameters (method, uri, etc.).
-    pub async fn send(self) -> Result<Response, Error> {
-        self.details.send(&self.client).await
+    /// Use [`Error::context()`] to access them. Credentials in the userinfo
+    /// of the URI are always removed, the query is removed unless
+    /// [`error_context_redact_query(false)`] is set.
+    ///
+    /// Default is false.
+    ///
+    /// [`error_context_redact_query(false)`]: #method.error_context_redact_query
+    pub fn error_context(&mut self, val: bool) -> &mut Self {
+        self.error_context = val;
+        self
     }
-}
 
-#[derive(Copy, Clone)]
-pub(crate) struct TokioExecutor;
+    /// If true, the query of the URI is removed from the context attached
+    /// to errors by [`error_context()`], as it may contain credentials or
+    /// personal data.
+    ///
+    /// Default is true.
+    ///
+    /// [`error_context()`]: #method.error_context
+    pub fn error_context_redact_query(&mut self, val: bool) -> &mut Self {
+        self.error_context_redact_query = val;
+        self
+    }
 
-impl<F> hyper::rt::Executor<F> for TokioExecutor
-where
-    F: Future + Send + 'static,
-    F::Output: Send + 'static,
-{
-    fn execute(&self, fut: F) {
-        tokio::spawn(fut);
+    /// Set the executor used to spawn the background tasks of the client,
+    /// such as the tasks driving connections.
+    ///
+    /// This allows using the client where `tokio::spawn()` is not
+    /// available, e.g. inside a `tokio::task::LocalSet`. Note that the
+    /// client still requires a tokio runtime for timers and for the I/O of
+    /// [`HttpConnector`].
+    ///
+    /// Default uses `tokio::spawn()`.
+    ///
+    /// [`HttpConnector`]: crate::HttpConnector
+    pub fn executor<E>(&mut self, executor: E) -> &mut Self
+    where
+        E: hyper::rt::Executor<BoxSendFuture> + Send + Sync + 'static,
+    {
+        self.executor = Some(SharedExecutor(Arc::new(executor)));
+        self
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::connector::HttpConnector;
-    use headers::ContentType;
-    use hyper::body::to_bytes;
-    use hyper::StatusCode;
+    /// Register an observer that is notified about connections, requests,
+    /// responses and errors.
+    ///
+    /// Multiple observers can be registered, they are called in the order
+    /// of registration.
+    pub fn observer<O: Observer>(&mut self, observer: O) -> &mut Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    /// Add a middleware intercepting requests and responses, see
+    /// [`Middleware`].
+    ///
+    /// Multiple middleware can be added. The middleware added first is the
+    /// outermost, i.e. it sees requests first and responses last.
+    pub fn with_middleware<M: Middleware>(&mut self, middleware: M) -> &mut Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Set an optional number of bytes of request and response bodies to
+    /// capture for debugging.
+    ///
+    /// The captured bytes are available through the [`CapturedBodies`]
+    /// stored in the response extensions, which helps diagnosing responses
+    /// that fail to deserialize. Streaming request bodies are not captured.
+    ///
+    /// Default is None (no capture).
+    pub fn capture_bodies(&mut self, val: Option<usize>) -> &mut Self {
+        self.capture_bodies = val;
+        self
+    }
+
+    /// Set an optional `Accept` header to add to requests that do not have
+    /// one, e.g. to prefer a compact format such as
+    /// `application/cbor, application/json;q=0.5`.
+    ///
+    /// Responses can then be deserialized according to their content type
+    /// with [`ResponseExt::deserialize()`].
+    ///
+    /// Default is None (no `Accept` header).
+    ///
+    /// [`ResponseExt::deserialize()`]: crate::ResponseExt::deserialize
+    pub fn default_accept(&mut self, val: Option<HeaderValue>) -> &mut Self {
+        self.default_accept = val;
+        self
+    }
+
+    /// Set the content codings to accept for response bodies, in order of
+    /// preference.
+    ///
+    /// The client adds an `Accept-Encoding` header listing `encodings` to
+    /// requests that do not have one, and transparently decompresses the
+    /// responses to these requests. The `Content-Encoding` and
+    /// `Content-Length` headers are removed from decompressed responses.
+    /// Requests with an `Accept-Encoding` header set by the caller are left
+    /// alone, as are their responses.
+    ///
+    /// The [`max_response_size()`] limit applies to both the compressed and
+    /// the decompressed body, so a small compressed body cannot expand to an
+    /// unbounded amount of data. Bodies are decompressed in chunks of at
+    /// most 32 KiB, and reading fails as soon as the limit is exceeded.
+    ///
+    /// Default is no encodings (responses are not decompressed).
+    ///
+    /// [`max_response_size()`]: #method.max_response_size
+    #[cfg(feature = "compression")]
+    pub fn accept_encodings(&mut self, encodings: &[Encoding]) -> &mut Self {
+        self.accept_encodings = encodings.to_vec();
+        self
+    }
+
+    /// Set an optional [`RetryBudget`] shared by all requests of the client.
+    ///
+    /// Each request sent is deposited in the budget, and retries of the
+    /// [`retry_policy()`] are withdrawn from it. Pass a clone of the same
+    /// budget to several builders to share it across clients.
+    ///
+    /// Default is None (no budget).
+    ///
+    /// [`retry_policy()`]: #method.retry_policy
+    pub fn retry_budget(&mut self, val: Option<RetryBudget>) -> &mut Self {
+        self.retry_budget = val;
+        self
+    }
+
+    /// Set an optional [`RetryPolicy`] for retrying failed idempotent
+    /// requests.
+    ///
+    /// Retries are withdrawn from the [`retry_budget()`], if any. Each
+    /// attempt is subject to the [`max_in_flight()`] limit.
+    ///
+    /// Default is None (no retries).
+    ///
+    /// [`retry_budget()`]: #method.retry_budget
+    /// [`max_in_flight()`]: #method.max_in_flight
+    pub fn retry_policy(&mut self, val: Option<RetryPolicy>) -> &mut Self {
+        self.retry_policy = val;
+        self
+    }
+
+    /// Set an optional limit on the number of concurrent requests of the
+    /// client.
+    ///
+    /// Requests beyond the limit wait for a request in flight to complete,
+    /// see [`max_in_flight_timeout()`]. A request is in flight until its
+    /// response body is read to the end or dropped, so make sure to consume
+    /// or drop responses to avoid stalling other requests. Cached responses
+    /// do not count against the limit.
+    ///
+    /// This protects the process from unbounded task and memory growth
+    /// under load spikes.
+    ///
+    /// Default is None (no limit).
+    ///
+    /// [`max_in_flight_timeout()`]: #method.max_in_flight_timeout
+    pub fn max_in_flight(&mut self, val: Option<usize>) -> &mut Self {
+        self.max_in_flight = val;
+        self
+    }
+
+    /// Set an optional timeout for requests waiting for a slot when
+    /// [`max_in_flight()`] requests are in flight. Requests that time out
+    /// fail with [`Error::InFlightLimit`].
+    ///
+    /// Default is None (wait indefinitely).
+    ///
+    /// [`max_in_flight()`]: #method.max_in_flight
+    pub fn max_in_flight_timeout(&mut self, val: Option<Duration>) -> &mut Self {
+        self.max_in_flight_timeout = val;
+        self
+    }
+
+    /// Enable or disable verification of response body checksums.
+    ///
+    /// When enabled, the body of responses with a `Content-Digest` header
+    /// containing a `sha-256` digest (RFC 9530) or a `Content-MD5` header
+    /// (RFC 1864) is checked against the declared checksum. Since this can
+    /// only be done once the whole body is read, a mismatch is returned from
+    /// the response body stream as [`Error::ChecksumMismatch`] wrapped in a
+    /// `hyper::Error`. Responses without these headers are not affected,
+    /// nor are responses without a body, i.e. responses to `HEAD` requests,
+    /// `204` and `304` responses and responses with an empty body.
+    ///
+    /// Default is false.
+    #[cfg(feature = "checksum")]
+    pub fn verify_response_checksums(&mut self, val: bool) -> &mut Self {
+        self.verify_response_checksums = val;
+        self
+    }
+
+    /// Enable an in-memory HTTP cache holding up to `val` bytes of response
+    /// headers and bodies.
+    ///
+    /// This is a shorthand for [`cache_store()`] with a
+    /// [`MemoryCacheStore`], see there for details. Passing `None` disables
+    /// the cache.
+    ///
+    /// [`cache_store()`]: #method.cache_store
+    #[cfg(feature = "cache")]
+    pub fn cache_max_size(&mut self, val: Option<usize>) -> &mut Self {
+        self.cache_store = match val {
+            Some(max_size) => Some(Arc::new(MemoryCacheStore::new(max_size))),
+            None => None,
+        };
+        self
+    }
+
+    /// Enable an HTTP cache storing responses in `store`.
+    ///
+    /// The cache is a private cache as defined in RFC 9111. Responses to
+    /// `GET` requests are stored if they are cacheable according to their
+    /// `Cache-Control`, `Expires` and `Vary` headers and have an explicit
+    /// freshness lifetime. Fresh stored responses are returned without
+    /// sending the request. Stale responses are not revalidated but replaced
+    /// by the response to the next request. Successful responses to requests
+    /// with unsafe methods such as `POST` invalidate the stored response for
+    /// the same URI.
+    ///
+    /// Requests with `Cache-Control: no-cache` bypass the cache, requests
+    /// with `Cache-Control: no-store` are additionally not stored.
+    ///
+    /// Responses to requests with an `Authorization` header are not stored.
+    ///
+    /// A response is stored once its body has been read to the end. The body
+    /// is buffered in memory while it is read, up to
+    /// [`CacheStore::max_entry_size()`]; larger responses are not stored.
+    ///
+    /// Default is no cache.
+    #[cfg(feature = "cache")]
+    pub fn cache_store<S: CacheStore>(&mut self, store: S) -> &mut Self {
+        self.cache_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Set the clock used to determine the age of cached responses and the
+    /// expiry of the cookies of a [`Session`].
+    ///
+    /// Use [`TokioClock`] to test caching and cookie expiry with
+    /// `tokio::time::pause()`. Timeouts always use the time of the tokio
+    /// runtime.
+    ///
+    /// Default is [`SystemClock`].
+    ///
+    /// [`Session`]: crate::Session
+    /// [`TokioClock`]: crate::TokioClock
+    /// [`SystemClock`]: crate::SystemClock
+    pub fn clock<C: Clock>(&mut self, clock: C) -> &mut Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Enable or disable logging of request and response lines and headers.
+    ///
+    /// The heads of requests sent over the network and of the received
+    /// responses are logged at debug level through the [`log`] crate. The
+    /// values of headers listed in [`log_redacted_headers()`] are replaced
+    /// by `<redacted>` and credentials in the userinfo of request URIs are
+    /// removed. Bodies are not logged.
+    ///
+    /// Default is false.
+    ///
+    /// [`log`]: https://docs.rs/log/0.4
+    /// [`log_redacted_headers()`]: #method.log_redacted_headers
+    #[cfg(feature = "log")]
+    pub fn log_wire(&mut self, val: bool) -> &mut Self {
+        self.log_wire = val;
+        self
+    }
+
+    /// Set the headers whose values are redacted when logging with
+    /// [`log_wire()`].
+    ///
+    /// Default is `Authorization`, `Proxy-Authorization`, `Cookie` and
+    /// `Set-Cookie`.
+    ///
+    /// [`log_wire()`]: #method.log_wire
+    #[cfg(feature = "log")]
+    pub fn log_redacted_headers<I>(&mut self, headers: I) -> &mut Self
+    where
+        I: IntoIterator<Item = HeaderName>,
+    {
+        self.log_redacted_headers = headers.into_iter().collect();
+        self
+    }
+
+    /// Validate the configuration of this builder and combine it with a
+    /// connector to create a `Client`.
+    ///
+    /// Returns [`Error::InvalidConfig`] if a timeout or size limit is set to
+    /// zero, which would make the client fail every request, or if lenient
+    /// HTTP/1 parsing is enabled along with strict response validation.
+    pub fn try_build<C: NetworkConnector>(&self, connector: C) -> Result<Client, Error> {
+        if self.idle_timeout == Some(Duration::ZERO) {
+            return Err(Error::InvalidConfig("pool idle timeout must not be zero"));
+        }
+        if self.max_response_header_size == Some(0) {
+            return Err(Error::InvalidConfig(
+                "max response header size must not be zero",
+            ));
+        }
+        if self.capture_bodies == Some(0) {
+            return Err(Error::InvalidConfig("body capture size must not be zero"));
+        }
+        if self.max_in_flight == Some(0) {
+            return Err(Error::InvalidConfig(
+                "max in-flight requests must not be zero",
+            ));
+        }
+        if self.strict_response_validation && self.lenient_http1_parsing() {
+            return Err(Error::InvalidConfig(
+                "lenient HTTP/1 parsing conflicts with strict response validation",
+            ));
+        }
+        #[cfg(feature = "cache")]
+        if let Some(ref store) = self.cache_store {
+            if store.max_entry_size() == Some(0) {
+                return Err(Error::InvalidConfig("cache size must not be zero"));
+            }
+        }
+        Ok(self.build(connector))
+    }
+
+    /// Combine the configuration of this builder with a connector to create a
+    /// `Client`.
+    ///
+    /// Unlike [`try_build()`], this does not validate the configuration.
+    ///
+    /// [`try_build()`]: #method.try_build
+    pub fn build<C: NetworkConnector>(&self, connector: C) -> Client {
+        let mut builder = HyperClient::builder();
+        builder
+            .pool_max_idle_per_host(match self.http1_keepalive {
+                true => self.max_idle_per_host,
+                false => 0,
+            })
+            .pool_idle_timeout(self.idle_timeout)
+            .http1_title_case_headers(self.http1_title_case_headers)
+            .http1_preserve_header_case(self.http1_preserve_header_case);
+        if !self.strict_response_validation {
+            builder
+                .http1_allow_obsolete_multiline_headers_in_responses(
+                    self.http1_allow_obsolete_multiline_headers_in_responses,
+                )
+                .http1_allow_spaces_after_header_name_in_responses(
+                    self.http1_allow_spaces_after_header_name_in_responses,
+                )
+                .http1_ignore_invalid_headers_in_responses(
+                    self.http1_ignore_invalid_headers_in_responses,
+                );
+        }
+        match self.executor {
+            Some(ref executor) => builder.executor(executor.clone()),
+            None => builder.executor(TokioExecutor),
+        };
+        if let Some(sz) = self.http1_read_buf_exact_size {
+            builder.http1_read_buf_exact_size(sz);
+        }
+        if let Some(max) = self.http1_max_buf_size {
+            builder.http1_max_buf_size(max);
+        }
+        #[cfg(feature = "tcp")]
+        let proxy = connector.forward_proxy().cloned();
+        let observers: Arc<[_]> = self.observers.clone().into();
+        let hyper = builder.build(ConnectorAdapter::new(connector, observers.clone()));
+        let client = self.wrap(Arc::new(hyper), observers);
+        #[cfg(feature = "tcp")]
+        let client = {
+            let mut client = client;
+            Arc::get_mut(&mut client.inner)
+                .expect("client was just created")
+                .proxy = proxy;
+            client
+        };
+        client
+    }
+
+    /// Combine the configuration of this builder with an existing hyper
+    /// client to create a `Client`.
+    ///
+    /// The options that configure hyper's client, i.e. the `pool_*` and
+    /// `http1_*` options and [`executor()`], have no effect as the hyper
+    /// client is already configured. [`http1_keepalive(false)`] still adds
+    /// `Connection: close` to requests but does not disable the pool.
+    /// Observers are not notified of connection attempts.
+    ///
+    /// [`executor()`]: #method.executor
+    /// [`http1_keepalive(false)`]: #method.http1_keepalive
+    pub fn build_from_hyper<C>(&self, hyper: HyperClient<C, SharedBody>) -> Client
+    where
+        C: Connect + Clone + Send + Sync + 'static,
+    {
+        self.wrap(Arc::new(hyper), self.observers.clone().into())
+    }
+
+    fn lenient_http1_parsing(&self) -> bool {
+        self.http1_allow_obsolete_multiline_headers_in_responses
+            || self.http1_allow_spaces_after_header_name_in_responses
+            || self.http1_ignore_invalid_headers_in_responses
+    }
+
+    fn wrap(&self, hyper: Arc<dyn SendRequest>, observers: Arc<[Arc<dyn Observer>]>) -> Client {
+        Client {
+            inner: Arc::new(ClientInner {
+                hyper,
+                config: self.clone(),
+                max_response_header_size: self.max_response_header_size,
+                strict_response_validation: self.strict_response_validation,
+                http1_keepalive: self.http1_keepalive,
+                max_response_size: self.max_response_size,
+                request_id_header: self.request_id_header.clone(),
+                error_context: self.error_context,
+                error_context_redact_query: self.error_context_redact_query,
+                observers,
+                middleware: self.middleware.clone().into(),
+                capture_bodies: self.capture_bodies,
+                default_accept: self.default_accept.clone(),
+                #[cfg(feature = "compression")]
+                accept_encodings: self.accept_encodings.clone(),
+                #[cfg(feature = "compression")]
+                accept_encoding: accept_encoding(&self.accept_encodings),
+                retry_budget: self.retry_budget.clone(),
+                retry_policy: self.retry_policy.clone(),
+                in_flight: self
+                    .max_in_flight
+                    .map(|max| InFlightLimit::new(max, self.max_in_flight_timeout)),
+                #[cfg(feature = "checksum")]
+                verify_response_checksums: self.verify_response_checksums,
+                #[cfg(feature = "cache")]
+                cache: self
+                    .cache_store
+                    .clone()
+                    .map(|store| Cache::new(store, self.clock.clone())),
+                #[cfg(feature = "log")]
+                wire_log: match self.log_wire {
+                    true => Some(WireLog::new(self.log_redacted_headers.clone())),
+                    false => None,
+                },
+                #[cfg(feature = "tcp")]
+                proxy: None,
+            }),
+        }
+    }
+}
+
+pub(crate) struct RequestDetails {
+    pub(crate) method: Method,
+    pub(crate) uri: Uri,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Option<SharedBody>,
+    pub(crate) upload_progress: Option<ProgressCallback>,
+    pub(crate) max_response_size: Option<Option<u64>>,
+    pub(crate) required_version: Option<Version>,
+    pub(crate) allow_body_on_any_method: bool,
+    #[cfg(feature = "compression")]
+    pub(crate) compression: Option<Encoding>,
+    #[cfg(feature = "checksum")]
+    pub(crate) checksum: Option<ChecksumAlgorithm>,
+}
+
+impl fmt::Debug for RequestDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestDetails")
+            .field("method", &self.method)
+            .field("uri", &self.uri)
+            .field("headers", &self.headers.len())
+            .field("body", &self.body.as_ref().map_or("None", |_| "Some(...)"))
+            .finish()
+    }
+}
+
+impl RequestDetails {
+    pub fn new(method: Method, uri: Uri) -> Self {
+        RequestDetails {
+            method,
+            uri,
+            headers: HeaderMap::new(),
+            body: None,
+            upload_progress: None,
+            max_response_size: None,
+            required_version: None,
+            allow_body_on_any_method: false,
+            #[cfg(feature = "compression")]
+            compression: None,
+            #[cfg(feature = "checksum")]
+            checksum: None,
+        }
+    }
+
+    pub async fn send(self, client: &Client) -> Result<Response, Error> {
+        let req = self.into_request()?;
+        client.send(req).await
+    }
+
+    /// Set the `Authorization` header, marked as sensitive.
+    pub(crate) fn set_authorization(&mut self, mut value: HeaderValue) {
+        value.set_sensitive(true);
+        self.headers.insert(AUTHORIZATION, value);
+    }
+
+    pub(crate) fn basic_auth(&mut self, username: &str, password: &str) {
+        let mut values = Vec::with_capacity(1);
+        Authorization::basic(username, password).encode(&mut values);
+        self.set_authorization(values.pop().expect("encoded header value"));
+    }
+
+    pub(crate) fn bearer_auth(&mut self, token: &str) -> Result<(), Error> {
+        let value =
+            HeaderValue::try_from(format!("Bearer {}", token)).map_err(http::Error::from)?;
+        self.set_authorization(value);
+        Ok(())
+    }
+
+    pub fn into_request(mut self) -> Result<Request<SharedBody>, Error> {
+        let can_have_body = match self.method {
+            // See RFC 7231 section 4.3. Without a body, these requests are
+            // sent without `Content-Length` even if bodies are allowed.
+            Method::GET | Method::HEAD | Method::DELETE => {
+                self.allow_body_on_any_method && self.body.is_some()
+            }
+            _ => true,
+        };
+        let body = match can_have_body {
+            true => {
+                let body = self.body.unwrap_or_else(SharedBody::empty);
+                #[cfg(feature = "compression")]
+                let body = match self.compression {
+                    Some(encoding) => {
+                        self.headers
+                            .insert(hyper::header::CONTENT_ENCODING, encoding.header_value());
+                        compress(body, encoding)?
+                    }
+                    None => body,
+                };
+                #[cfg(feature = "checksum")]
+                if let Some(algorithm) = self.checksum {
+                    let (name, value) = request_checksum(&body, algorithm)?;
+                    self.headers.insert(name, value);
+                }
+                // NOTE: streaming bodies of unknown length are sent using
+                // chunked encoding, so we must not set `ContentLength` for
+                // them, see RFC 7230 section 3.3.2.
+                if let Some(len) = body.content_length() {
+                    self.headers.typed_insert(ContentLength(len));
+                }
+                body
+            }
+            false if self.body.is_some() => return Err(Error::BodyNotAllowed(self.method)),
+            false => SharedBody::empty(),
+        };
+        let body = match self.upload_progress {
+            Some(callback) => body.with_progress_callback(callback),
+            None => body,
+        };
+        // Method and URI are already parsed, so the request is assembled
+        // directly rather than through the fallible `http::request::Builder`.
+        let mut req = Request::new(body);
+        *req.method_mut() = self.method;
+        *req.uri_mut() = self.uri;
+        *req.headers_mut() = self.headers;
+        if let Some(limit) = self.max_response_size {
+            req.extensions_mut().insert(MaxResponseSize(limit));
+        }
+        if let Some(version) = self.required_version {
+            req.extensions_mut().insert(RequiredVersion(version));
+        }
+        Ok(req)
+    }
+}
+
+/// An HTTP request builder
+///
+/// This is created through [`Client::get()`], [`Client::post()`] etc.
+/// You need to call [`send()`] to actually send the request over the network.
+/// If you don't want to send it and just want the resultant [Request], you
+/// can call [RequestBuilder::build].
+///
+/// [`Client::get()`]: struct.Client.html#method.get
+/// [`Client::post()`]: struct.Client.html#method.post
+/// [`send()`]: struct.RequestBuilder.html#method.send
+pub struct RequestBuilder<'a> {
+    client: &'a Client,
+    details: RequestDetails,
+    session: Option<&'a Session>,
+}
+
+impl<'a> RequestBuilder<'a> {
+    /// Set the request body.
+    ///
+    /// Sending a `GET`, `HEAD` or `DELETE` request with a body fails with
+    /// [`Error::BodyNotAllowed`], unless [`allow_body_on_any_method()`] is
+    /// used.
+    ///
+    /// [`allow_body_on_any_method()`]: #method.allow_body_on_any_method
+    pub fn body<B: Into<SharedBody>>(mut self, body: B) -> Self {
+        self.details.body = Some(body.into());
+        self
+    }
+
+    /// Allow sending a body with `GET`, `HEAD` and `DELETE` requests.
+    ///
+    /// These methods have no defined semantics for a body and some servers
+    /// and proxies reject or drop it, but a few APIs require one, e.g.
+    /// search endpoints that take the query in the body.
+    pub fn allow_body_on_any_method(mut self) -> Self {
+        self.details.allow_body_on_any_method = true;
+        self
+    }
+
+    /// Set the request body to a GraphQL request for `query` with
+    /// `variables`, using the JSON encoding described in the GraphQL over
+    /// HTTP specification.
+    ///
+    /// This also sets the `Content-Type` and `Accept` headers, so it should
+    /// be called after [`headers()`]. Use it with [`Client::post()`] and
+    /// read the response with [`ResponseExt::graphql()`]. Returns
+    /// [`Error::Json`] if `variables` cannot be serialized.
+    ///
+    /// [`headers()`]: RequestBuilder::headers
+    /// [`ResponseExt::graphql()`]: crate::ResponseExt::graphql
+    #[cfg(feature = "graphql")]
+    pub fn graphql<V>(mut self, query: &str, variables: &V) -> Result<Self, Error>
+    where
+        V: serde::Serialize + ?Sized,
+    {
+        graphql::set_request(
+            &mut self.details.headers,
+            &mut self.details.body,
+            query,
+            variables,
+        )?;
+        Ok(self)
+    }
+
+    /// Set the request body to the XML serialization of `value` and the
+    /// `Content-Type` header to `application/xml`.
+    ///
+    /// The root element is named after the type of `value`, use
+    /// `#[serde(rename = "...")]` to change it. APIs that expect a different
+    /// media type, such as `application/soap+xml`, can be served by setting
+    /// the `Content-Type` header after calling this. Returns
+    /// [`Error::XmlSerialize`] if `value` cannot be serialized.
+    #[cfg(feature = "xml")]
+    pub fn xml<T>(mut self, value: &T) -> Result<Self, Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        xml::set_request(&mut self.details.headers, &mut self.details.body, value)?;
+        Ok(self)
+    }
+
+    /// Set the request body to the CBOR encoding of `value` and the
+    /// `Content-Type` header to `application/cbor`.
+    ///
+    /// Returns [`Error::CborSerialize`] if `value` cannot be serialized.
+    #[cfg(feature = "cbor")]
+    pub fn cbor<T>(mut self, value: &T) -> Result<Self, Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        cbor::set_request(&mut self.details.headers, &mut self.details.body, value)?;
+        Ok(self)
+    }
+
+    /// Set the request body to the MessagePack encoding of `value` and the
+    /// `Content-Type` header to `application/msgpack`.
+    ///
+    /// Structs are encoded as maps keyed by field name. Returns
+    /// [`Error::MsgPackSerialize`] if `value` cannot be serialized.
+    #[cfg(feature = "msgpack")]
+    pub fn msgpack<T>(mut self, value: &T) -> Result<Self, Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        msgpack::set_request(&mut self.details.headers, &mut self.details.body, value)?;
+        Ok(self)
+    }
+
+    /// Set the request body to stream the contents of `reader`.
+    ///
+    /// If `len` is provided, the `Content-Length` header is set and `reader`
+    /// must produce exactly `len` bytes. Otherwise the body is sent using
+    /// chunked transfer-encoding. See [`SharedBody::from_reader()`].
+    pub fn body_from_reader<R>(self, reader: R, len: Option<u64>) -> Self
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        self.body(SharedBody::from_reader(reader, len))
+    }
+
+    /// Register a callback that is invoked as the request body is sent.
+    ///
+    /// The callback receives the number of bytes sent so far and the total
+    /// length of the body if it is known. See [`SharedBody::on_progress()`].
+    pub fn upload_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u64, Option<u64>) + Send + Sync + 'static,
+    {
+        self.details.upload_progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Compress the request body using `encoding` and set the
+    /// `Content-Encoding` header accordingly.
+    ///
+    /// Note that the server must support the encoding, otherwise it will
+    /// most likely reject the request with `415 Unsupported Media Type`.
+    #[cfg(feature = "compression")]
+    pub fn compress(mut self, encoding: Encoding) -> Self {
+        self.details.compression = Some(encoding);
+        self
+    }
+
+    /// Compute the checksum of the request body using `algorithm` and send
+    /// it in the corresponding header, see
+    /// [`ChecksumAlgorithm::header_name()`]. If the body is compressed, the
+    /// checksum is computed over the compressed body.
+    ///
+    /// Sending the request fails if the body is streaming, since its
+    /// checksum cannot be computed before it is sent.
+    #[cfg(feature = "checksum")]
+    pub fn checksum(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.details.checksum = Some(algorithm);
+        self
+    }
+
+    /// Override [`ClientBuilder::max_response_size()`] for this request.
+    pub fn max_response_size(mut self, val: Option<u64>) -> Self {
+        self.details.max_response_size = Some(val);
+        self
+    }
+
+    /// Fail with [`Error::UnexpectedVersion`] if the response is received
+    /// over a connection using a different HTTP version.
+    ///
+    /// This detects misconfigurations such as a TLS connector not
+    /// negotiating HTTP/2 through ALPN or a proxy downgrading connections to
+    /// HTTP/1.1. The response body is not read in that case.
+    pub fn require_version(mut self, version: Version) -> Self {
+        self.details.required_version = Some(version);
+        self
+    }
+
+    /// Set the request headers.
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.details.headers = headers;
+        self
+    }
+
+    /// Set a single header using [`HeaderMapExt::typed_insert()`], replacing
+    /// any existing values.
+    ///
+    /// [`HeaderMapExt::typed_insert()`]: https://docs.rs/headers/0.3.5/headers/trait.HeaderMapExt.html#tymethod.typed_insert
+    pub fn header<H: Header>(mut self, header: H) -> Self {
+        self.details.headers.typed_insert(header);
+        self
+    }
+
+    /// Set a single header, replacing any existing values of `name`.
+    ///
+    /// Unlike [`header()`], this works for any header, including custom
+    /// headers such as `X-Fortanix-Session` that have no typed
+    /// representation.
+    ///
+    /// [`header()`]: #method.header
+    pub fn header_raw(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.details.headers.insert(name, value);
+        self
+    }
+
+    /// Add a value to the header `name`, keeping its existing values.
+    ///
+    /// This builds multi-valued headers, e.g. several `Cookie` or `Accept`
+    /// entries, whereas [`header()`] and [`header_raw()`] replace existing
+    /// values.
+    ///
+    /// [`header()`]: #method.header
+    /// [`header_raw()`]: #method.header_raw
+    pub fn header_append(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.details.headers.append(name, value);
+        self
+    }
+
+    /// Remove all values of the header `name`.
+    pub fn remove_header(mut self, name: HeaderName) -> Self {
+        self.details.headers.remove(name);
+        self
+    }
+
+    /// Returns a mutable reference to the request headers.
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.details.headers
+    }
+
+    /// Set the `Authorization` header for HTTP Basic authentication with
+    /// `username` and `password`, see RFC 7617.
+    ///
+    /// The credentials are encoded as UTF-8 and the header is marked as
+    /// sensitive.
+    pub fn basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.details.basic_auth(username, password);
+        self
+    }
+
+    /// Set the `Authorization` header to the bearer `token`, see RFC 6750.
+    ///
+    /// The header is marked as sensitive. Returns [`Error::Http`] if `token`
+    /// contains characters that are not allowed in header values.
+    pub fn bearer_auth(mut self, token: &str) -> Result<Self, Error> {
+        self.details.bearer_auth(token)?;
+        Ok(self)
+    }
+
+    /// Get the resultant [Request].
+    ///
+    /// Prefer [RequestBuilder::send] unless you have a specific
+    /// need to get the resultant [Request].
+    pub fn build(self) -> Result<Request<SharedBody>, Error> {
+        self.details.into_request()
+    }
+
+    /// Set the `If-None-Match` header to `etag`, making the request
+    /// conditional on the representation not matching the entity tag.
+    pub fn if_none_match(mut self, etag: HeaderValue) -> Self {
+        self.details.headers.insert(IF_NONE_MATCH, etag);
+        self
+    }
+
+    /// Set the `If-Modified-Since` header, making the request conditional on
+    /// the representation having been modified after `time`.
+    pub fn if_modified_since(self, time: SystemTime) -> Self {
+        self.header(IfModifiedSince::from(time))
+    }
+
+    /// Send the request conditioned on `validators` and return `cached` if
+    /// the server responds with `304 Not Modified`.
+    ///
+    /// This is useful for polling resources efficiently: the response body
+    /// is only transferred if the resource has changed since `cached` was
+    /// received. Use [`Validators::from_headers()`] to obtain the validators
+    /// of the response `cached` was obtained from. If `validators` is empty,
+    /// the request is sent unconditionally.
+    pub async fn revalidate<T>(
+        mut self,
+        cached: T,
+        validators: &Validators,
+    ) -> Result<Revalidation<T>, Error> {
+        validators.apply(&mut self.details.headers);
+        let response = self.send().await?;
+        Ok(match response.status() {
+            StatusCode::NOT_MODIFIED => Revalidation::Fresh(cached),
+            _ => Revalidation::Modified(response),
+        })
+    }
+
+    /// Send the request over the network.
+    ///
+    /// Returns an error before sending the request if there is something wrong
+    /// with the request parameters (method, uri, etc.).
+    pub async fn send(self) -> Result<Response, Error> {
+        match self.session {
+            Some(session) => session.send(self.details).await,
+            None => self.details.send(self.client).await,
+        }
+    }
+
+    pub(crate) fn session(mut self, session: &'a Session) -> Self {
+        self.session = Some(session);
+        self
+    }
+}
+
+fn response_header_size(response: &Response) -> usize {
+    response
+        .headers()
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len() + 4)
+        .sum()
+}
+
+/// A boxed future spawned by the executor of a [`Client`], see
+/// [`ClientBuilder::executor()`]
+///
+/// [`ClientBuilder::executor()`]: ClientBuilder::executor
+pub type BoxSendFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+#[derive(Clone)]
+struct SharedExecutor(Arc<dyn hyper::rt::Executor<BoxSendFuture> + Send + Sync>);
+
+impl hyper::rt::Executor<BoxSendFuture> for SharedExecutor {
+    fn execute(&self, fut: BoxSendFuture) {
+        self.0.execute(fut)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub(crate) struct TokioExecutor;
+
+impl<F> hyper::rt::Executor<F> for TokioExecutor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        tokio::spawn(fut);
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+pub(crate) mod tests {
+    use super::*;
+    use crate::connector::{ConnectError, HttpConnector, NetworkConnection};
+    use headers::ContentType;
+    use hyper::body::to_bytes;
+    use hyper::StatusCode;
+    use std::error::Error as StdError;
     use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
-    use tokio::net::TcpListener;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+
+    const RESPONSE_OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\nHello, world!\r\n";
+    const RESPONSE_404: &str =
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 23\r\n\r\nResource was not found.\r\n";
+
+    pub(crate) async fn test_http_server(resp: &'static str) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut input = [0u8; 1024];
+            let _ = stream.read(&mut input).await.unwrap();
+            stream.write_all(resp.as_bytes()).await.unwrap();
+        });
+        addr
+    }
+
+    pub(crate) async fn test_http_server_capture_request<R>(
+        resp: R,
+    ) -> (SocketAddr, oneshot::Receiver<Vec<u8>>)
+    where
+        R: AsRef<[u8]> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let input = read_request(&mut stream).await;
+            let _ = tx.send(input);
+            stream.write_all(resp.as_ref()).await.unwrap();
+        });
+        (addr, rx)
+    }
+
+    // Sends `responses` in order to the requests it receives, over as many
+    // connections as the client opens, and returns the requests once all
+    // responses have been sent.
+    pub(crate) async fn test_http_server_responses(
+        responses: Vec<&'static str>,
+    ) -> (SocketAddr, JoinHandle<Vec<Vec<u8>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            let mut responses = responses.into_iter().peekable();
+            while responses.peek().is_some() {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                while let Some(resp) = responses.peek() {
+                    let input = read_request(&mut stream).await;
+                    if input.is_empty() {
+                        break;
+                    }
+                    requests.push(input);
+                    stream.write_all(resp.as_bytes()).await.unwrap();
+                    responses.next();
+                }
+            }
+            requests
+        });
+        (addr, server)
+    }
+
+    // Reads a complete HTTP/1.1 request assuming the request body, if any,
+    // is either delimited by `Content-Length` or chunked.
+    pub(crate) async fn read_request(stream: &mut TcpStream) -> Vec<u8> {
+        let mut input = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request_complete(&input) {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                break;
+            }
+            input.extend_from_slice(&buf[..n]);
+        }
+        input
+    }
+
+    fn request_complete(input: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(input).to_lowercase();
+        let headers_end = match text.find("\r\n\r\n") {
+            Some(i) => i + 4,
+            None => return false,
+        };
+        let headers = &text[..headers_end];
+        if headers.contains("\r\ntransfer-encoding: chunked\r\n") {
+            return text.ends_with("\r\n0\r\n\r\n");
+        }
+        let content_length = headers
+            .split("\r\n")
+            .find_map(|line| line.strip_prefix("content-length: "))
+            .map_or(0, |len| len.parse().unwrap());
+        input.len() >= headers_end + content_length
+    }
+
+    #[tokio::test]
+    async fn http_client() {
+        let addr = test_http_server(RESPONSE_OK).await;
+        let url = format!("http://{}/", addr);
+
+        let connector = HttpConnector::new();
+        let client = Client::with_connector(connector);
+        let response = client
+            .post(url)
+            .unwrap()
+            .header(ContentType::json())
+            .body(r#"{"key":"value"}"#)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response).await.unwrap();
+        assert_eq!(body, "Hello, world!".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn streaming_request_body() {
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        let chunks: Vec<Result<_, std::io::Error>> = vec![Ok("hello"), Ok(", "), Ok("world!")];
+        let body = SharedBody::wrap_stream(futures_util::stream::iter(chunks));
+
+        let client = Client::with_connector(HttpConnector::new());
+        let response = client
+            .put(format!("http://{}/", addr))
+            .unwrap()
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("\r\ntransfer-encoding: chunked\r\n"));
+        assert!(!request.contains("content-length"));
+        assert!(request.ends_with("\r\n\r\n5\r\nhello\r\n2\r\n, \r\n6\r\nworld!\r\n0\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn request_framing_from_size_hint() {
+        // Requests sent directly have no `Content-Length` header, so hyper
+        // frames them according to the size hint of the body.
+        let bodies = [
+            (SharedBody::from_chunks(vec!["hello", ", ", "world!"]), true),
+            (
+                SharedBody::from_reader(&b"hello, world!"[..], Some(13)),
+                true,
+            ),
+            (SharedBody::from_reader(&b"hello, world!"[..], None), false),
+        ];
+        let client = Client::with_connector(HttpConnector::new());
+        for (body, exact) in bodies {
+            let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+            let mut request = Request::new(body);
+            *request.method_mut() = Method::POST;
+            *request.uri_mut() = format!("http://{}/", addr).parse().unwrap();
+            client.send(request).await.unwrap();
+
+            let request = String::from_utf8(rx.await.unwrap()).unwrap();
+            assert_eq!(request.contains("\r\ncontent-length: 13\r\n"), exact);
+            assert_eq!(
+                request.contains("\r\ntransfer-encoding: chunked\r\n"),
+                !exact
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn request_body_from_reader() {
+        let client = Client::with_connector(HttpConnector::new());
+
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        client
+            .post(format!("http://{}/", addr))
+            .unwrap()
+            .body_from_reader(&b"hello, world!"[..], Some(13))
+            .send()
+            .await
+            .unwrap();
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("\r\ncontent-length: 13\r\n"));
+        assert!(request.ends_with("\r\n\r\nhello, world!"));
+
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        client
+            .post(format!("http://{}/", addr))
+            .unwrap()
+            .body_from_reader(&b"hello, world!"[..], None)
+            .send()
+            .await
+            .unwrap();
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("\r\ntransfer-encoding: chunked\r\n"));
+        assert!(request.ends_with("\r\n\r\nD\r\nhello, world!\r\n0\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn max_response_size() {
+        const RESPONSE: &str = "HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\nhello, world!";
+        let addr = test_http_server(RESPONSE).await;
+        let client = Client::builder()
+            .max_response_size(Some(12))
+            .build(HttpConnector::new());
+        let err = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ResponseBodyTooLarge(12)));
+
+        let addr = test_http_server(RESPONSE).await;
+        let resp = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .max_response_size(None)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "hello, world!");
+
+        // The limit is enforced while reading bodies of unknown length
+        let addr = test_http_server(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nD\r\nhello, world!\r\n0\r\n\r\n",
+        )
+        .await;
+        let resp = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let err = to_bytes(resp.into_body()).await.unwrap_err();
+        let err = err.source().unwrap().downcast_ref::<Error>().unwrap();
+        assert!(matches!(err, Error::ResponseBodyTooLarge(12)));
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn compressed_request_body() {
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        let client = Client::with_connector(HttpConnector::new());
+        client
+            .post(format!("http://{}/", addr))
+            .unwrap()
+            .compress(Encoding::Gzip)
+            .body("hello, world!")
+            .send()
+            .await
+            .unwrap();
+
+        let request = rx.await.unwrap();
+        let request = String::from_utf8_lossy(&request);
+        assert!(request.contains("\r\ncontent-encoding: gzip\r\n"));
+        assert!(!request.contains("hello, world!"));
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn accept_encodings() {
+        use hyper::header::{CONTENT_ENCODING, CONTENT_LENGTH};
+
+        async fn gzip_server(data: String) -> (SocketAddr, oneshot::Receiver<Vec<u8>>) {
+            let body = compress(SharedBody::from(data), Encoding::Gzip).unwrap();
+            let body = to_bytes(body).await.unwrap();
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let (tx, rx) = oneshot::channel();
+            tokio::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let request = read_request(&mut stream).await;
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(head.as_bytes()).await.unwrap();
+                stream.write_all(&body).await.unwrap();
+                let _ = tx.send(request);
+            });
+            (addr, rx)
+        }
+
+        let client = Client::builder()
+            .accept_encodings(&[Encoding::Gzip, Encoding::Deflate])
+            .build(HttpConnector::new());
+        let (addr, rx) = gzip_server("hello, world!".to_owned()).await;
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert!(response.headers().get(CONTENT_ENCODING).is_none());
+        assert!(response.headers().get(CONTENT_LENGTH).is_none());
+        let body = to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "hello, world!");
+        let request = rx.await.unwrap();
+        let request = String::from_utf8_lossy(&request);
+        assert!(request.contains("\r\naccept-encoding: gzip, deflate\r\n"));
+
+        // Responses to requests with their own `Accept-Encoding` are left alone
+        let (addr, _rx) = gzip_server("hello, world!".to_owned()).await;
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .headers(headers)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+        let body = to_bytes(response.into_body()).await.unwrap();
+        assert_ne!(body, "hello, world!");
+
+        // The size limit applies to the decompressed body
+        let (addr, _rx) = gzip_server("x".repeat(100_000)).await;
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .max_response_size(Some(1000))
+            .send()
+            .await
+            .unwrap();
+        let err = to_bytes(response.into_body()).await.unwrap_err();
+        let source = err.into_cause().unwrap().downcast::<Error>().unwrap();
+        assert!(matches!(*source, Error::ResponseBodyTooLarge(1000)));
+    }
+
+    #[cfg(feature = "checksum")]
+    #[tokio::test]
+    async fn request_body_checksum() {
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        let client = Client::with_connector(HttpConnector::new());
+        client
+            .put(format!("http://{}/", addr))
+            .unwrap()
+            .checksum(ChecksumAlgorithm::Md5)
+            .body("hello, world!")
+            .send()
+            .await
+            .unwrap();
+
+        let request = rx.await.unwrap();
+        let request = String::from_utf8_lossy(&request);
+        assert!(request.contains("\r\ncontent-md5: Otu60XkfuuPskIiUxJY4cA==\r\n"));
+    }
+
+    #[cfg(feature = "cache")]
+    #[tokio::test]
+    async fn cache() {
+        let addr = test_http_server(
+            "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 5\r\n\r\nhello",
+        )
+        .await;
+        let client = Client::builder()
+            .cache_max_size(Some(1024))
+            .build(HttpConnector::new());
+        // The test server only responds to the first request
+        for _ in 0..2 {
+            let resp = client
+                .get(format!("http://{}/", addr))
+                .unwrap()
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "hello");
+        }
+    }
+
+    #[tokio::test]
+    async fn revalidate() {
+        let (addr, rx) =
+            test_http_server_capture_request("HTTP/1.1 304 Not Modified\r\n\r\n").await;
+        let client = Client::with_connector(HttpConnector::new());
+        let validators = Validators {
+            etag: Some(HeaderValue::from_static("\"v1\"")),
+            last_modified: None,
+        };
+        let res = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .revalidate("cached", &validators)
+            .await
+            .unwrap();
+        assert!(matches!(res, Revalidation::Fresh("cached")));
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("\r\nif-none-match: \"v1\"\r\n"));
+
+        let addr = test_http_server(RESPONSE_OK).await;
+        let res = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .revalidate("cached", &validators)
+            .await
+            .unwrap();
+        match res {
+            Revalidation::Modified(resp) => assert_eq!(resp.status(), StatusCode::OK),
+            Revalidation::Fresh(_) => panic!("expected a modified response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_progress() {
+        let (addr, _) = test_http_server_capture_request(RESPONSE_OK).await;
+        let progress = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let progress2 = progress.clone();
+
+        let client = Client::with_connector(HttpConnector::new());
+        client
+            .post(format!("http://{}/", addr))
+            .unwrap()
+            .upload_progress(move |sent, total| progress2.lock().unwrap().push((sent, total)))
+            .body("hello, world!")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(*progress.lock().unwrap(), vec![(13, Some(13))]);
+    }
+
+    #[tokio::test]
+    async fn drop_client_before_response() {
+        let addr = test_http_server(RESPONSE_404).await;
+        let url = format!("http://{}/", addr);
+
+        let connector = HttpConnector::new();
+        let client = Client::with_connector(connector);
+        let response = client.get(url).unwrap().send().await.unwrap();
+        drop(client);
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers().len(), 1);
+        let body = to_bytes(response).await.unwrap();
+        assert_eq!(body, "Resource was not found.");
+    }
+
+    #[tokio::test]
+    async fn http1_obsolete_multiline_headers() {
+        const RESPONSE: &str =
+            "HTTP/1.1 200 OK\r\nX-Folded: hello,\r\n world\r\nContent-Length: 0\r\n\r\n";
+
+        let addr = test_http_server(RESPONSE).await;
+        let client = Client::with_connector(HttpConnector::new());
+        let err = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await;
+        assert!(err.is_err());
+
+        let addr = test_http_server(RESPONSE).await;
+        let client = Client::builder()
+            .http1_allow_obsolete_multiline_headers_in_responses(true)
+            .build(HttpConnector::new());
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-folded"], "hello,   world");
+
+        // Strict response validation overrides lenient parsing
+        let addr = test_http_server(RESPONSE).await;
+        let mut builder = Client::builder();
+        builder
+            .http1_allow_obsolete_multiline_headers_in_responses(true)
+            .strict_response_validation(true);
+        assert!(matches!(
+            builder.try_build(HttpConnector::new()),
+            Err(Error::InvalidConfig(_))
+        ));
+        let client = builder.build(HttpConnector::new());
+        let err = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await;
+        assert!(err.is_err());
+    }
+
+    #[tokio::test]
+    async fn http1_lenient_header_parsing() {
+        const RESPONSE: &str =
+            "HTTP/1.1 200 OK\r\nX-Space : a\r\nX-Bad\x01: b\r\nContent-Length: 0\r\n\r\n";
+
+        let addr = test_http_server(RESPONSE).await;
+        let client = Client::with_connector(HttpConnector::new());
+        let err = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await;
+        assert!(err.is_err());
+
+        let addr = test_http_server(RESPONSE).await;
+        let client = Client::builder()
+            .http1_allow_spaces_after_header_name_in_responses(true)
+            .http1_ignore_invalid_headers_in_responses(true)
+            .build(HttpConnector::new());
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-space"], "a");
+        assert!(!response.headers().contains_key("x-bad"));
+    }
+
+    #[tokio::test]
+    async fn http1_title_case_headers() {
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        let client = Client::builder()
+            .http1_title_case_headers(true)
+            .build(HttpConnector::new());
+        client
+            .post(format!("http://{}/", addr))
+            .unwrap()
+            .header(ContentType::json())
+            .body("{}")
+            .send()
+            .await
+            .unwrap();
+
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("\r\nContent-Type: application/json\r\n"));
+        assert!(request.contains("\r\nContent-Length: 2\r\n"));
+    }
+
+    #[tokio::test]
+    async fn max_response_header_size() {
+        // content-length: 13\r\n
+        let addr = test_http_server(RESPONSE_OK).await;
+        let client = Client::builder()
+            .max_response_header_size(Some(20))
+            .build(HttpConnector::new());
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let addr = test_http_server(RESPONSE_OK).await;
+        let client = Client::builder()
+            .max_response_header_size(Some(19))
+            .build(HttpConnector::new());
+        let err = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ResponseHeadersTooLarge(19)));
+    }
+
+    #[tokio::test]
+    async fn http1_keepalive_disabled() {
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        let client = Client::builder()
+            .http1_keepalive(false)
+            .build(HttpConnector::new());
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("\r\nconnection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn connect_tunnel() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut input = [0u8; 1024];
+            let n = stream.read(&mut input).await.unwrap();
+            let expected = format!("CONNECT {} HTTP/1.1\r\n", addr);
+            assert!(input[..n].starts_with(expected.as_bytes()));
+            stream
+                .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                .await
+                .unwrap();
+            // echo everything back
+            let n = stream.read(&mut input).await.unwrap();
+            stream.write_all(&input[..n]).await.unwrap();
+        });
+
+        let client = Client::with_connector(HttpConnector::new());
+        let mut tunnel = client.connect_tunnel(addr.to_string()).await.unwrap();
+        tunnel.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        tunnel.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[tokio::test]
+    async fn connect_tunnel_rejected() {
+        let addr = test_http_server(
+            "HTTP/1.1 407 Proxy Authentication Required\r\nContent-Length: 0\r\n\r\n",
+        )
+        .await;
+        let client = Client::with_connector(HttpConnector::new());
+        match client.connect_tunnel(addr.to_string()).await {
+            Err(Error::TunnelRejected(status)) => {
+                assert_eq!(status, StatusCode::PROXY_AUTHENTICATION_REQUIRED)
+            }
+            res => panic!("unexpected result: {:?}", res.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn request_id() {
+        let mut builder = Client::builder();
+        builder.request_id_header(Some(crate::X_REQUEST_ID));
+        let client = builder.build(HttpConnector::new());
+
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let id = response.extensions().get::<RequestId>().unwrap();
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains(&format!("\r\nx-request-id: {}\r\n", id)));
+
+        builder.max_response_header_size(Some(1));
+        let client = builder.build(HttpConnector::new());
+        let addr = test_http_server(RESPONSE_OK).await;
+        let err = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+        match err {
+            Error::WithRequestId(_, e) => {
+                assert!(matches!(*e, Error::ResponseHeadersTooLarge(1)))
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn error_context() {
+        let mut builder = Client::builder();
+        builder
+            .error_context(true)
+            .request_id_header(Some(crate::X_REQUEST_ID))
+            .max_response_header_size(Some(1));
+        let client = builder.build(HttpConnector::new());
+        let addr = test_http_server(RESPONSE_OK).await;
+        let uri = format!("http://{}/items?token=secret", addr);
+        let err = client.get(&uri).unwrap().send().await.unwrap_err();
+        let context = err.context().unwrap();
+        assert_eq!(context.method(), Method::GET);
+        assert_eq!(context.uri(), &*format!("http://{}/items", addr));
+        assert!(err
+            .to_string()
+            .starts_with(&format!("GET http://{}/items: ", addr)));
+        assert!(!err.to_string().contains("secret"));
+        // The classification of the error is unchanged
+        let source = err.source().unwrap().downcast_ref::<Error>().unwrap();
+        assert!(matches!(source, Error::WithRequestId(..)));
+        assert!(matches!(
+            err.without_context(),
+            Error::ResponseHeadersTooLarge(1)
+        ));
+
+        builder.error_context_redact_query(false);
+        let client = builder.build(HttpConnector::new());
+        let addr = test_http_server(RESPONSE_OK).await;
+        let uri = format!("http://{}/items?token=secret", addr);
+        let err = client.get(&uri).unwrap().send().await.unwrap_err();
+        assert_eq!(err.context().unwrap().uri(), &*uri);
+
+        let client = Client::with_connector(HttpConnector::new());
+        let addr = test_http_server(RESPONSE_OK).await;
+        let res = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await;
+        assert!(res.is_ok());
+        let err = client
+            .get("http://127.0.0.1:1/")
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.context().is_none());
+    }
+
+    #[tokio::test]
+    async fn observer_dropped_request() {
+        use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+
+        #[derive(Clone, Default)]
+        struct InFlight(Arc<AtomicI64>, Arc<AtomicBool>);
+
+        impl Observer for InFlight {
+            fn on_request(&self, _: &Request<SharedBody>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+
+            fn on_response(&self, _: &Method, _: &Uri, _: &Response, _: Duration) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+            }
+
+            fn on_error(&self, _: &Method, _: &Uri, error: &Error, _: Duration) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+                self.1.store(error.is_cancelled(), Ordering::SeqCst);
+            }
+        }
+
+        // The server accepts the connection but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let observer = InFlight::default();
+        let client = Client::builder()
+            .observer(observer.clone())
+            .build(HttpConnector::new());
+        let send = client.get(format!("http://{}/", addr)).unwrap().send();
+        let res = time::timeout(Duration::from_millis(100), send).await;
+        assert!(res.is_err());
+        assert_eq!(observer.0.load(Ordering::SeqCst), 0);
+        assert!(observer.1.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn observer() {
+        use std::sync::Mutex;
+
+        #[derive(Clone, Default)]
+        struct Events(Arc<Mutex<Vec<String>>>);
+
+        impl Observer for Events {
+            fn on_connect(&self, uri: &Uri, _elapsed: Duration) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("connect {}", uri.path()));
+            }
 
-    const RESPONSE_OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\nHello, world!\r\n";
-    const RESPONSE_404: &str =
-        "HTTP/1.1 404 Not Found\r\nContent-Length: 23\r\n\r\nResource was not found.\r\n";
+            fn on_connect_error(&self, _uri: &Uri, _error: &(dyn std::error::Error + Send + Sync)) {
+                self.0.lock().unwrap().push("connect error".to_owned());
+            }
+
+            fn on_request(&self, request: &Request<SharedBody>) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("request {}", request.method()));
+            }
+
+            fn on_response(&self, _: &Method, _: &Uri, response: &Response, _: Duration) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("response {}", response.status()));
+            }
+
+            fn on_error(&self, method: &Method, _: &Uri, _: &Error, _: Duration) {
+                self.0.lock().unwrap().push(format!("error {}", method));
+            }
+        }
+
+        let events = Events::default();
+        let client = Client::builder()
+            .observer(events.clone())
+            .build(HttpConnector::new());
+        let addr = test_http_server(RESPONSE_OK).await;
+        client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
 
-    async fn test_http_server(resp: &'static str) -> SocketAddr {
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
         let addr = listener.local_addr().unwrap();
-        tokio::spawn(async move {
+        drop(listener);
+        client
+            .post(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            *events.0.lock().unwrap(),
+            [
+                "request GET",
+                "connect /",
+                "response 200 OK",
+                "request POST",
+                "connect error",
+                "error POST",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn error_classification() {
+        let client = Client::with_connector(HttpConnector::new());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let err = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_connect());
+        assert!(!err.is_timeout());
+        let connect_error = err.as_connect_error().unwrap();
+        assert_eq!(connect_error.message(), "I/O error");
+        assert_eq!(
+            connect_error.io_error().unwrap().kind(),
+            std::io::ErrorKind::ConnectionRefused
+        );
+
+        let (addr, _rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        let chunks = vec![Ok("hello"), Err(std::io::Error::other("oops"))];
+        let err = client
+            .put(format!("http://{}/", addr))
+            .unwrap()
+            .body(SharedBody::wrap_stream(futures_util::stream::iter(chunks)))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_body());
+        assert!(!err.is_connect());
+
+        struct TimeoutConnector;
+
+        impl NetworkConnector for TimeoutConnector {
+            fn connect(
+                &self,
+                _uri: Uri,
+            ) -> Pin<
+                Box<
+                    dyn Future<Output = Result<NetworkConnection, Box<dyn StdError + Send + Sync>>>
+                        + Send,
+                >,
+            > {
+                let err = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+                Box::pin(async move { Err(ConnectError::new("I/O error").cause(err).into()) })
+            }
+        }
+
+        let client = Client::with_connector(TimeoutConnector);
+        let err = client
+            .get("http://example.com/")
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_timeout());
+        assert!(err.is_connect());
+    }
+
+    #[cfg(feature = "tokio-native-tls")]
+    #[tokio::test]
+    async fn tls_error() {
+        use crate::HttpsConnector;
+        use tokio_native_tls::native_tls;
+
+        let addr = test_http_server(RESPONSE_OK).await;
+        let tls = native_tls::TlsConnector::new().unwrap();
+        let client = Client::with_connector(HttpsConnector::new(tls.into()));
+        let err = client
+            .get(format!("https://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_tls());
+        assert!(err.is_connect());
+        assert!(!err.is_timeout());
+    }
+
+    #[tokio::test]
+    async fn custom_executor() {
+        #[derive(Clone, Default)]
+        struct CountingExecutor(Arc<AtomicUsize>);
+
+        impl hyper::rt::Executor<BoxSendFuture> for CountingExecutor {
+            fn execute(&self, fut: BoxSendFuture) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(fut);
+            }
+        }
+
+        let executor = CountingExecutor::default();
+        let client = Client::builder()
+            .executor(executor.clone())
+            .build(HttpConnector::new());
+        let addr = test_http_server(RESPONSE_OK).await;
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(executor.0.load(Ordering::SeqCst) > 0);
+    }
+
+    #[tokio::test]
+    async fn from_hyper() {
+        let hyper =
+            HyperClient::builder().build::<_, SharedBody>(hyper::client::HttpConnector::new());
+        let client = Client::builder()
+            .request_id_header(Some(crate::X_REQUEST_ID))
+            .build_from_hyper(hyper);
+        assert!(client
+            .hyper_client::<hyper::client::HttpConnector>()
+            .is_some());
+        assert!(client.hyper_client::<HttpConnector>().is_none());
+
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("x-request-id: "));
+
+        let client = Client::with_connector(HttpConnector::new());
+        assert!(client.hyper_client::<HttpConnector>().is_none());
+    }
+
+    #[tokio::test]
+    async fn with_overrides() {
+        const RESPONSE: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Serve two requests over a single connection
+        let server = tokio::spawn(async move {
             let (mut stream, _) = listener.accept().await.unwrap();
-            let mut input = Vec::new();
-            stream.read(&mut input).await.unwrap();
-            stream.write_all(resp.as_bytes()).await.unwrap();
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                requests.push(String::from_utf8(read_request(&mut stream).await).unwrap());
+                stream.write_all(RESPONSE.as_bytes()).await.unwrap();
+            }
+            requests
         });
-        addr
+
+        let client = Client::builder()
+            .max_response_size(Some(2))
+            .build(HttpConnector::new());
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(to_bytes(response.into_body()).await.unwrap(), "ok");
+
+        let derived = client.with_overrides(|builder| {
+            builder.request_id_header(Some(crate::X_REQUEST_ID));
+        });
+        let send = derived.get(format!("http://{}/", addr)).unwrap().send();
+        let response = tokio::time::timeout(Duration::from_secs(5), send)
+            .await
+            .expect("connection should be reused")
+            .unwrap();
+        assert_eq!(to_bytes(response.into_body()).await.unwrap(), "ok");
+
+        let requests = server.await.unwrap();
+        assert!(!requests[0].contains("x-request-id: "));
+        assert!(requests[1].contains("x-request-id: "));
+        assert_eq!(derived.to_builder().max_response_size, Some(2));
+        assert!(client.to_builder().request_id_header.is_none());
+    }
+
+    #[test]
+    fn try_build() {
+        let mut builder = Client::builder();
+        builder.pool_idle_timeout(Some(Duration::ZERO));
+        match builder.try_build(HttpConnector::new()) {
+            Err(Error::InvalidConfig(msg)) => assert_eq!(msg, "pool idle timeout must not be zero"),
+            res => panic!("unexpected result: {:?}", res.map(|_| ())),
+        }
+        builder.pool_idle_timeout(None);
+        assert!(builder.try_build(HttpConnector::new()).is_ok());
     }
 
     #[tokio::test]
-    async fn http_client() {
+    async fn remote_addr() {
+        use crate::ResponseExt;
+
         let addr = test_http_server(RESPONSE_OK).await;
-        let url = format!("http://{}/", addr);
+        let client = Client::with_connector(HttpConnector::new());
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.remote_addr(), Some(addr));
+        let info = response.extensions().get::<crate::HttpInfo>().unwrap();
+        assert_eq!(info.local_addr().ip(), addr.ip());
 
-        let connector = HttpConnector::new();
+        // hyper's connector reports its own `HttpInfo`
+        let hyper =
+            HyperClient::builder().build::<_, SharedBody>(hyper::client::HttpConnector::new());
+        let client = Client::builder().build_from_hyper(hyper);
+        let addr = test_http_server(RESPONSE_OK).await;
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.remote_addr(), Some(addr));
+    }
+
+    #[tokio::test]
+    async fn connect_policy() {
+        use crate::ConnectPolicy;
+
+        let addr = test_http_server(RESPONSE_OK).await;
+        let policy = ConnectPolicy::new().deny_private_ips(true);
+        let client = Client::with_connector(HttpConnector::new().policy(policy));
+        for uri in [
+            format!("http://{}/", addr),
+            format!("http://localhost:{}/", addr.port()),
+        ] {
+            let err = client.get(uri).unwrap().send().await.unwrap_err();
+            assert!(err.is_connect());
+            assert!(err.as_connect_error().unwrap().is_denied());
+        }
+
+        let policy = ConnectPolicy::new().allow_host("127.0.0.1");
+        let client = Client::with_connector(HttpConnector::new().policy(policy));
+        let response = client.get(format!("http://{}/", addr)).unwrap().send();
+        assert_eq!(response.await.unwrap().status(), StatusCode::OK);
+        let uri = format!("http://localhost:{}/", addr.port());
+        let err = client.get(uri).unwrap().send().await.unwrap_err();
+        assert!(err.as_connect_error().unwrap().is_denied());
+    }
+
+    #[tokio::test]
+    async fn http_proxy() {
+        use crate::{Proxy, ResponseExt};
+
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        let proxy = Proxy::http(format!("http://{}", addr))
+            .unwrap()
+            .basic_auth("foo", "bar")
+            .no_proxy("127.0.0.1");
+        let connector = HttpConnector::new().proxy(Some(proxy));
         let client = Client::with_connector(connector);
+        let response = client.get("http://example.com/path?q=1").unwrap().send();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // The peer is the proxy
+        assert_eq!(response.remote_addr(), Some(addr));
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.starts_with("GET http://example.com/path?q=1 HTTP/1.1\r\n"));
+        assert!(request.contains("proxy-authorization: Basic Zm9vOmJhcg==\r\n"));
+
+        // The proxy credentials are not sent to targets bypassing the proxy
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        let response = client.get(format!("http://{}/", addr)).unwrap().send();
+        assert_eq!(response.await.unwrap().status(), StatusCode::OK);
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.starts_with("GET / HTTP/1.1\r\n"));
+        assert!(!request.contains("proxy-authorization"));
+    }
+
+    #[tokio::test]
+    async fn retry_policy() {
+        // Responds with 503 to all but every third request
+        async fn flaky_server() -> (SocketAddr, Arc<AtomicUsize>) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let count = Arc::new(AtomicUsize::new(0));
+            let counter = count.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (mut stream, _) = listener.accept().await.unwrap();
+                    let mut input = [0u8; 1024];
+                    let _ = stream.read(&mut input).await.unwrap();
+                    let status = match counter.fetch_add(1, Ordering::SeqCst) % 3 {
+                        2 => "200 OK",
+                        _ => "503 Service Unavailable",
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        status
+                    );
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                }
+            });
+            (addr, count)
+        }
+
+        #[derive(Clone, Default)]
+        struct Retries(Arc<std::sync::Mutex<Vec<String>>>);
+
+        impl Observer for Retries {
+            fn on_retry(&self, m: &Method, _: &Uri, attempt: u32, res: &Result<Response, Error>) {
+                let outcome = match res {
+                    Ok(response) => response.status().to_string(),
+                    Err(e) => e.to_string(),
+                };
+                let event = format!("{} {} {}", m, attempt, outcome);
+                self.0.lock().unwrap().push(event);
+            }
+        }
+
+        let retries = Retries::default();
+        let policy = RetryPolicy::new(3).base_delay(Duration::from_millis(1));
+        let client = Client::builder()
+            .retry_policy(Some(policy))
+            .observer(retries.clone())
+            .build(HttpConnector::new());
+        let (addr, count) = flaky_server().await;
+        let uri = format!("http://{}/", addr);
+        let response = client.get(&uri).unwrap().send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            *retries.0.lock().unwrap(),
+            [
+                "GET 1 503 Service Unavailable",
+                "GET 2 503 Service Unavailable"
+            ]
+        );
+
+        // Not idempotent
+        let response = client.post(&uri).unwrap().send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(count.load(Ordering::SeqCst), 4);
+
+        // The budget allows a single retry
+        let budget = RetryBudget::new(Duration::from_secs(10), 0, 1.0);
+        let client = client.with_overrides(|config| {
+            config.retry_budget(Some(budget));
+        });
+        let (addr, count) = flaky_server().await;
+        let response = client.get(format!("http://{}/", addr)).unwrap().send();
+        assert_eq!(
+            response.await.unwrap().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        // Connection errors are retried
+        #[derive(Clone, Default)]
+        struct ConnectErrors(Arc<AtomicUsize>);
+
+        impl Observer for ConnectErrors {
+            fn on_connect_error(&self, _: &Uri, _: &(dyn std::error::Error + Send + Sync)) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let errors = ConnectErrors::default();
+        let client = Client::builder()
+            .retry_policy(Some(RetryPolicy::new(2).base_delay(Duration::ZERO)))
+            .observer(errors.clone())
+            .build(HttpConnector::new());
+        let err = client.get("http://127.0.0.1:1/").unwrap().send().await;
+        assert!(err.unwrap_err().is_connect());
+        assert_eq!(errors.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn middleware() {
+        use crate::{Middleware, MiddlewareFuture, Next};
+        use std::sync::Mutex;
+
+        struct AddHeader(&'static str, Arc<Mutex<Vec<String>>>);
+
+        impl Middleware for AddHeader {
+            fn handle<'a>(
+                &'a self,
+                mut request: Request<SharedBody>,
+                next: Next<'a>,
+            ) -> MiddlewareFuture<'a> {
+                let value = HeaderValue::from_static(self.0);
+                request.headers_mut().append("x-middleware", value);
+                Box::pin(async move {
+                    let mut response = next.run(request).await?;
+                    self.1.lock().unwrap().push(self.0.to_owned());
+                    response
+                        .headers_mut()
+                        .append("x-middleware", self.0.parse().unwrap());
+                    Ok(response)
+                })
+            }
+        }
+
+        struct Reject;
+
+        impl Middleware for Reject {
+            fn handle<'a>(&'a self, _: Request<SharedBody>, _: Next<'a>) -> MiddlewareFuture<'a> {
+                Box::pin(async { Err(Error::InvalidConfig("rejected")) })
+            }
+        }
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let client = Client::builder()
+            .with_middleware(AddHeader("outer", order.clone()))
+            .with_middleware(AddHeader("inner", order.clone()))
+            .build(HttpConnector::new());
+        let (addr, rx) = test_http_server_capture_request(RESPONSE_OK).await;
+        let response = client.get(format!("http://{}/", addr)).unwrap().send();
+        let response = response.await.unwrap();
+        let values: Vec<_> = response.headers().get_all("x-middleware").iter().collect();
+        assert_eq!(values, ["inner", "outer"]);
+        assert_eq!(*order.lock().unwrap(), ["inner", "outer"]);
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("x-middleware: outer\r\nx-middleware: inner\r\n"));
+
+        let client = client.with_overrides(|config| {
+            config.with_middleware(Reject);
+        });
+        let err = client.get("http://127.0.0.1:1/").unwrap().send().await;
+        assert!(matches!(err.unwrap_err(), Error::InvalidConfig("rejected")));
+    }
+
+    #[test]
+    fn auth_helpers() {
+        let client = Client::with_connector(HttpConnector::new());
+        let request = client
+            .get("http://example.com/")
+            .unwrap()
+            .basic_auth("Aladdin", "open sesame")
+            .build()
+            .unwrap();
+        let value = &request.headers()[AUTHORIZATION];
+        assert_eq!(value, "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+        assert!(value.is_sensitive());
+
+        let request = client
+            .get("http://example.com/")
+            .unwrap()
+            .bearer_auth("mF_9.B5f-4.1JqM")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()[AUTHORIZATION], "Bearer mF_9.B5f-4.1JqM");
+
+        let err = client
+            .get("http://example.com/")
+            .unwrap()
+            .bearer_auth("a\nb");
+        assert!(matches!(err, Err(Error::Http(_))));
+    }
+
+    #[test]
+    fn raw_headers() {
+        let client = Client::with_connector(HttpConnector::new());
+        let session = HeaderName::from_static("x-fortanix-session");
+        let mut builder = client
+            .get("http://example.com/")
+            .unwrap()
+            .header_raw(session.clone(), HeaderValue::from_static("first"))
+            .header_raw(session.clone(), HeaderValue::from_static("second"));
+        builder
+            .headers_mut()
+            .insert(ACCEPT, HeaderValue::from_static("text/plain"));
+        assert_eq!(builder.headers_mut()[&session], "second");
+        let request = builder.build().unwrap();
+        assert_eq!(request.headers().get_all(&session).iter().count(), 1);
+        assert_eq!(request.headers()[&session], "second");
+        assert_eq!(request.headers()[ACCEPT], "text/plain");
+    }
+
+    #[test]
+    fn append_and_remove_headers() {
+        let client = Client::with_connector(HttpConnector::new());
+        let request = client
+            .get("http://example.com/")
+            .unwrap()
+            .header_append(ACCEPT, HeaderValue::from_static("application/json"))
+            .header_append(ACCEPT, HeaderValue::from_static("text/plain;q=0.5"))
+            .header_raw(CONNECTION, HeaderValue::from_static("close"))
+            .remove_header(CONNECTION)
+            .build()
+            .unwrap();
+        let accept: Vec<_> = request.headers().get_all(ACCEPT).iter().collect();
+        assert_eq!(accept, ["application/json", "text/plain;q=0.5"]);
+        assert!(!request.headers().contains_key(CONNECTION));
+    }
+
+    #[test]
+    fn allow_body_on_any_method() {
+        let client = Client::with_connector(HttpConnector::new());
+        let res = client
+            .get("http://example.com/_search")
+            .unwrap()
+            .body("{}")
+            .build();
+        assert!(matches!(res, Err(Error::BodyNotAllowed(Method::GET))));
+
+        let request = client
+            .get("http://example.com/_search")
+            .unwrap()
+            .body("{}")
+            .allow_body_on_any_method()
+            .build()
+            .unwrap();
+        assert_eq!(request.body().as_bytes(), Some(&b"{}"[..]));
+        assert_eq!(request.headers()[hyper::header::CONTENT_LENGTH], "2");
+
+        // Requests without a body are unchanged
+        let request = client
+            .delete("http://example.com/")
+            .unwrap()
+            .allow_body_on_any_method()
+            .build()
+            .unwrap();
+        assert!(request.body().is_empty());
+        assert!(!request
+            .headers()
+            .contains_key(hyper::header::CONTENT_LENGTH));
+    }
+
+    #[tokio::test]
+    async fn require_version() {
+        use crate::ResponseExt;
+
+        let client = Client::with_connector(HttpConnector::new());
+        let addr = test_http_server(RESPONSE_OK).await;
         let response = client
-            .post(url)
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .require_version(Version::HTTP_11)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.effective_version(), Version::HTTP_11);
+
+        let addr = test_http_server(RESPONSE_OK).await;
+        let err = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .require_version(Version::HTTP_2)
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::UnexpectedVersion(Version::HTTP_11)));
+        assert_eq!(err.to_string(), "unexpected HTTP version: HTTP/1.1");
+    }
+
+    #[tokio::test]
+    async fn max_in_flight() {
+        let client = Client::builder()
+            .max_in_flight(Some(1))
+            .max_in_flight_timeout(Some(Duration::from_millis(100)))
+            .build(HttpConnector::new());
+        let addr = test_http_server(RESPONSE_OK).await;
+        let response = client
+            .get(format!("http://{}/", addr))
             .unwrap()
-            .header(ContentType::json())
-            .body(r#"{"key":"value"}"#)
             .send()
             .await
             .unwrap();
 
+        let addr = test_http_server(RESPONSE_OK).await;
+        let err = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InFlightLimit(_)));
+
+        // Derived clients share the limit
+        let derived = client.with_overrides(|builder| {
+            builder.max_response_size(Some(100));
+        });
+        let err = derived
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+        assert!(err.is_timeout());
+
+        assert_eq!(
+            to_bytes(response.into_body()).await.unwrap(),
+            "Hello, world!"
+        );
+        let response = derived
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
         assert_eq!(response.status(), StatusCode::OK);
-        let body = to_bytes(response).await.unwrap();
-        assert_eq!(body, "Hello, world!".as_bytes());
     }
 
     #[tokio::test]
-    async fn drop_client_before_response() {
-        let addr = test_http_server(RESPONSE_404).await;
-        let url = format!("http://{}/", addr);
+    async fn http_connector_read_timeout() {
+        const RESPONSE: &str = "HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello";
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_request(&mut stream).await;
+            stream.write_all(RESPONSE.as_bytes()).await.unwrap();
+            // Stall in the middle of the body
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        });
 
-        let connector = HttpConnector::new();
+        let connector = HttpConnector::new().read_timeout(Some(Duration::from_millis(100)));
         let client = Client::with_connector(connector);
-        let response = client.get(url).unwrap().send().await.unwrap();
-        drop(client);
-
-        assert_eq!(response.status(), StatusCode::NOT_FOUND);
-        assert_eq!(response.headers().len(), 1);
-        let body = to_bytes(response).await.unwrap();
-        assert_eq!(body, "Resource was not found.");
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let err = Error::from(to_bytes(response.into_body()).await.unwrap_err());
+        assert!(err.is_timeout());
     }
 
     #[tokio::test]
@@ -352,9 +3211,7 @@ mod tests {
         let connector = HttpConnector::new().connect_timeout(Some(Duration::from_millis(100)));
         let client = Client::with_connector(connector);
         let err = client.get(url).unwrap().send().await.unwrap_err();
-        assert_eq!(
-            err.to_string(),
-            "error trying to connect: I/O error: connection timed out"
-        );
+        assert!(err.is_timeout());
+        assert!(err.is_connect());
     }
 }
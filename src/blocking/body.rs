@@ -5,16 +5,24 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use super::client::KeepClientAlive;
+use crate::error::Error;
 
 use hyper::body::{Buf, Bytes};
 use hyper::Body as HyperBody;
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
+use tokio_util::sync::CancellationToken;
 
+use std::fmt;
 use std::future::Future;
-use std::{fmt, io};
+use std::io::{self, BufRead};
 
-/// A body type for HTTP responses that implement `std::io::Read`
+/// A body type for HTTP responses that implement `std::io::Read` and
+/// `std::io::BufRead`
+///
+/// Errors encountered while receiving the body are returned as
+/// `std::io::Error`s wrapping a `hyper::Error`, which can be recovered with
+/// [`Body::hyper_error()`].
 pub struct Body {
     pub(super) keep_client_alive: KeepClientAlive,
     bytes: Bytes,
@@ -28,24 +36,42 @@ impl fmt::Debug for Body {
 }
 
 impl Body {
+    /// Returns the future that receives `hyper_body` into a channel that
+    /// holds up to `buffer` chunks, and the `Body` reading from it.
+    ///
+    /// If `cancel` is cancelled, the reader receives an error wrapping
+    /// [`Error::Cancelled`].
     pub(super) fn new(
         mut hyper_body: HyperBody,
+        buffer: usize,
+        cancel: Option<CancellationToken>,
     ) -> (impl Future<Output = ()> + Send + 'static, Self) {
-        let (tx, rx) = mpsc::channel(1);
+        let (tx, rx) = mpsc::channel(buffer);
         let fut = async move {
+            let cancelled = async {
+                match cancel {
+                    Some(ref cancel) => cancel.cancelled().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::pin!(cancelled);
             loop {
                 tokio::select! {
                     _ = tx.closed() => {
                         break; // body has been dropped.
                     }
+                    _ = &mut cancelled => {
+                        let _ = tx.send(Err(io::Error::other(Error::Cancelled))).await;
+                        break;
+                    }
                     res = hyper_body.next() => {
                         let res = match res {
                             None => break, // EOF
                             Some(Ok(chunk)) if chunk.is_empty() => continue,
                             Some(Ok(chunk)) => Ok(chunk),
-                            Some(Err(e)) => Err(io::Error::new(io::ErrorKind::Other, e)),
+                            Some(Err(e)) => Err(io::Error::other(e)),
                         };
-                        if let Err(_) = tx.send(res).await {
+                        if tx.send(res).await.is_err() {
                             break; // body has been dropped.
                         }
                     }
@@ -59,20 +85,81 @@ impl Body {
         };
         (fut, body)
     }
+
+    /// Returns an iterator over the chunks of the body as they are received.
+    ///
+    /// Data that has been buffered but not yet read is returned first.
+    pub fn chunks(&mut self) -> Chunks<'_> {
+        Chunks(self)
+    }
+
+    /// Returns the `hyper::Error` wrapped by an error returned from
+    /// [`read()`], if any.
+    ///
+    /// This allows distinguishing e.g. timeouts from protocol errors with
+    /// methods such as `hyper::Error::is_timeout()`.
+    ///
+    /// [`read()`]: std::io::Read::read
+    pub fn hyper_error(err: &io::Error) -> Option<&hyper::Error> {
+        err.get_ref()?.downcast_ref()
+    }
+}
+
+/// An iterator over the chunks of a [`Body`]
+///
+/// This is created by [`Body::chunks()`].
+#[derive(Debug)]
+pub struct Chunks<'a>(&'a mut Body);
+
+impl Iterator for Chunks<'_> {
+    type Item = io::Result<Bytes>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.0.bytes.is_empty() {
+            return Some(Ok(std::mem::take(&mut self.0.bytes)));
+        }
+        self.0.rx.blocking_recv()
+    }
 }
 
 impl io::Read for Body {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let mut chunk = self.fill_buf()?;
+            chunk.read(buf)?
+        };
+        self.consume(n);
+        Ok(n)
+    }
+
+    // Appends whole chunks to `buf`, which avoids the small reads and the
+    // zero-initialization of spare capacity done by the default
+    // implementation.
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let start = buf.len();
+        for chunk in self.chunks() {
+            buf.extend_from_slice(&chunk?);
+        }
+        Ok(buf.len() - start)
+    }
+}
+
+impl io::BufRead for Body {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
         if self.bytes.is_empty() {
             match self.rx.blocking_recv() {
                 Some(Ok(bytes)) => {
                     self.bytes = bytes;
                 }
                 Some(Err(e)) => return Err(e),
-                None => return Ok(0),
+                None => {}
             }
         }
-        (&mut self.bytes).reader().read(buf)
+        Ok(&self.bytes)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.bytes.advance(amt);
     }
 }
 
@@ -81,7 +168,9 @@ mod tests {
     use super::*;
     use hyper::Body as HyperBody;
     use std::future::Future;
-    use std::io::{self, Read};
+    use std::io::{self, BufRead, Read};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
     use std::thread;
     use tokio::time::{self, Duration};
 
@@ -98,7 +187,7 @@ mod tests {
     #[test]
     fn single_chunk() {
         let body = HyperBody::from("hello, world!");
-        let (fut, mut reader) = Body::new(body);
+        let (fut, mut reader) = Body::new(body, 1, None);
         run_future(fut);
 
         let mut bytes = Vec::<u8>::new();
@@ -109,7 +198,7 @@ mod tests {
     #[test]
     fn multiple_chunks() {
         let (mut sender, body) = HyperBody::channel();
-        let (fut, mut reader) = Body::new(body);
+        let (fut, mut reader) = Body::new(body, 1, None);
 
         run_future(async move {
             let h = tokio::spawn(fut);
@@ -131,7 +220,7 @@ mod tests {
     #[test]
     fn with_empty_chunk() {
         let (mut sender, body) = HyperBody::channel();
-        let (fut, mut reader) = Body::new(body);
+        let (fut, mut reader) = Body::new(body, 1, None);
 
         run_future(async move {
             let h = tokio::spawn(fut);
@@ -150,6 +239,68 @@ mod tests {
         assert_eq!(bytes, b"hello, world!");
     }
 
+    #[test]
+    fn buf_read() {
+        let (mut sender, body) = HyperBody::channel();
+        let (fut, mut reader) = Body::new(body, 1, None);
+
+        run_future(async move {
+            let h = tokio::spawn(fut);
+
+            sender.send_data("first\nsec".into()).await.unwrap();
+            sender.send_data("ond\nthird".into()).await.unwrap();
+
+            drop(sender);
+            h.await.unwrap();
+        });
+
+        let lines: Vec<String> = reader.by_ref().lines().map(Result::unwrap).collect();
+        assert_eq!(lines, ["first", "second", "third"]);
+        assert!(reader.fill_buf().unwrap().is_empty());
+    }
+
+    #[test]
+    fn chunks() {
+        let (mut sender, body) = HyperBody::channel();
+        let (fut, mut reader) = Body::new(body, 1, None);
+
+        run_future(async move {
+            let h = tokio::spawn(fut);
+
+            sender.send_data("hello".into()).await.unwrap();
+            sender.send_data(", ".into()).await.unwrap();
+            sender.send_data("world!".into()).await.unwrap();
+
+            drop(sender);
+            h.await.unwrap();
+        });
+
+        let mut buf = [0u8; 2];
+        reader.read_exact(&mut buf).unwrap();
+        let chunks: Vec<Bytes> = reader.chunks().map(Result::unwrap).collect();
+        assert_eq!(chunks, ["llo", ", ", "world!"]);
+    }
+
+    #[test]
+    fn buffer_chunks() {
+        let pulled = Arc::new(AtomicUsize::new(0));
+        let pulled2 = pulled.clone();
+        let stream = futures_util::stream::iter(vec!["a", "b", "c", "d", "e"]).map(move |chunk| {
+            pulled2.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, io::Error>(chunk)
+        });
+        let (fut, mut reader) = Body::new(HyperBody::wrap_stream(stream), 3, None);
+        run_future(fut);
+
+        // 3 chunks in the channel and 1 waiting for capacity
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(pulled.load(Ordering::SeqCst), 4);
+
+        let mut bytes = Vec::<u8>::new();
+        reader.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, b"abcde");
+    }
+
     #[test]
     fn hyper_error() {
         let chunks: Vec<Result<_, io::Error>> = vec![
@@ -160,13 +311,14 @@ mod tests {
         ];
         let stream = futures_util::stream::iter(chunks);
         let body = HyperBody::wrap_stream(stream);
-        let (fut, mut reader) = Body::new(body);
+        let (fut, mut reader) = Body::new(body, 1, None);
 
         run_future(fut);
 
         let mut bytes = Vec::<u8>::new();
         let err = reader.read_to_end(&mut bytes).unwrap_err();
         assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert!(Body::hyper_error(&err).is_some());
         assert_eq!(bytes, b"hello world");
 
         let mut buf = [0u8; 8];
@@ -0,0 +1,40 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use tokio_util::sync::CancellationToken;
+
+/// A handle for cancelling blocking requests from another thread
+///
+/// Attach it to requests with [`RequestBuilder::cancel_handle()`]. After
+/// [`cancel()`] is called, a blocked [`send()`] returns
+/// [`Error::Cancelled`], and reading the response body returns an
+/// `std::io::Error` wrapping [`Error::Cancelled`]. A handle can be attached to
+/// multiple requests, which are all cancelled together.
+///
+/// [`RequestBuilder::cancel_handle()`]: crate::blocking::RequestBuilder::cancel_handle
+/// [`cancel()`]: CancelHandle::cancel
+/// [`send()`]: crate::blocking::RequestBuilder::send
+/// [`Error::Cancelled`]: crate::Error::Cancelled
+#[derive(Clone, Debug, Default)]
+pub struct CancelHandle(pub(super) CancellationToken);
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        CancelHandle::default()
+    }
+
+    /// Cancel the requests this handle is attached to.
+    pub fn cancel(&self) {
+        self.0.cancel();
+    }
+
+    /// Returns true if [`cancel()`] has been called.
+    ///
+    /// [`cancel()`]: CancelHandle::cancel
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+}
@@ -5,22 +5,51 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use super::body::Body;
-use super::Response;
-use crate::async_client::{ClientBuilder as AsyncClientBuilder, RequestDetails};
+use super::cancel::CancelHandle;
+use super::pagination::Pages;
+use super::{Request, Response};
+use crate::async_client::{
+    BoxSendFuture, Client as AsyncClient, ClientBuilder as AsyncClientBuilder, RequestDetails,
+};
+#[cfg(feature = "cache")]
+use crate::cache::CacheStore;
+#[cfg(feature = "cbor")]
+use crate::cbor;
+#[cfg(feature = "checksum")]
+use crate::checksum::ChecksumAlgorithm;
+#[cfg(feature = "cache")]
+use crate::clock::Clock;
+#[cfg(feature = "compression")]
+use crate::compression::Encoding;
+use crate::conditional::{Revalidation, Validators};
 use crate::connector::NetworkConnector;
 use crate::error::Error;
+#[cfg(feature = "graphql")]
+use crate::graphql;
+use crate::middleware::Middleware;
+#[cfg(feature = "msgpack")]
+use crate::msgpack;
+use crate::observer::Observer;
+use crate::retry::RetryPolicy;
+use crate::retry_budget::RetryBudget;
 use crate::shared_body::SharedBody;
+use crate::validation::validate_uri;
+#[cfg(feature = "xml")]
+use crate::xml;
 
-use futures_executor::block_on;
-use headers::{Header, HeaderMap, HeaderMapExt};
-use hyper::{Method, Uri};
-use tokio::runtime;
+use headers::{Header, HeaderMap, HeaderMapExt, IfModifiedSince};
+use hyper::client::connect::Connect;
+use hyper::header::{HeaderName, HeaderValue, IF_NONE_MATCH};
+use hyper::{Client as HyperClient, Method, StatusCode, Uri, Version};
 use tokio::sync::{mpsc, oneshot};
+use tokio::{runtime, task};
+use tokio_util::sync::CancellationToken;
 
 use std::convert::{TryFrom, TryInto};
+use std::io;
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 /// A wrapper for [hyper's `Client` type] providing a blocking interface
 ///
@@ -39,14 +68,24 @@ pub struct Client {
 
 type ResponseSender = oneshot::Sender<Result<Response, Error>>;
 
+struct Job {
+    request: Request,
+    timeout: Option<Duration>,
+    cancel: Option<CancellationToken>,
+    resp_tx: ResponseSender,
+}
+
 struct ClientInner {
-    tx: Option<mpsc::UnboundedSender<(RequestDetails, ResponseSender)>>,
+    tx: Option<mpsc::UnboundedSender<Job>>,
+    // `None` if the client runs on an existing runtime
     thread: Option<JoinHandle<()>>,
+    // set if the runtime could not be created by `ClientBuilder::build()`
+    runtime_error: Option<io::Error>,
 }
 
 impl Drop for ClientInner {
     fn drop(&mut self) {
-        // signal shutdown to the thread
+        // signal shutdown to the thread or task
         self.tx.take();
         self.thread.take().map(|h| h.join());
     }
@@ -59,7 +98,7 @@ macro_rules! define_method_fn {
         #[doc = " request with the specified URI."]
         ///
         /// Returns an error if `uri` is invalid.
-        pub fn $name<U>(&self, uri: U) -> Result<RequestBuilder, Error>
+        pub fn $name<U>(&self, uri: U) -> Result<RequestBuilder<'_>, Error>
         where
             Uri: TryFrom<U>,
             <Uri as TryFrom<U>>::Error: Into<http::Error>,
@@ -82,18 +121,89 @@ impl Client {
         ClientBuilder::new().build(connector)
     }
 
+    /// Create a new `Client` wrapping an existing hyper client.
+    ///
+    /// See [`AsyncClient::from_hyper()`].
+    ///
+    /// [`AsyncClient::from_hyper()`]: crate::Client::from_hyper
+    pub fn from_hyper<C>(hyper: HyperClient<C, SharedBody>) -> Self
+    where
+        C: Connect + Clone + Send + Sync + 'static,
+    {
+        ClientBuilder::new().build_from_hyper(hyper)
+    }
+
     /// Initiate a request with the specified method and URI.
     ///
-    /// Returns an error if `uri` is invalid.
-    pub fn request<U>(&self, method: Method, uri: U) -> Result<RequestBuilder, Error>
+    /// Returns an error if `uri` is invalid or is not an absolute URI with
+    /// a host. The host is lowercased and a default port such as `:443` for
+    /// `https` is removed. A fragment (`#...`) is not part of the target and
+    /// is never sent.
+    pub fn request<U>(&self, method: Method, uri: U) -> Result<RequestBuilder<'_>, Error>
     where
         Uri: TryFrom<U>,
         <Uri as TryFrom<U>>::Error: Into<http::Error>,
     {
         let uri = uri.try_into().map_err(Into::into).map_err(Error::Http)?;
+        let uri = validate_uri(uri)?;
         Ok(RequestBuilder {
             client: self,
             details: RequestDetails::new(method, uri),
+            timeout: None,
+            cancel: None,
+        })
+    }
+
+    /// This method can be used instead of [Client::request]
+    /// if the caller already has a [Request].
+    ///
+    /// [Request]: crate::blocking::Request
+    pub fn send(&self, request: Request) -> Result<Response, Error> {
+        self.send_job(request, None, None)
+    }
+
+    /// Send `request` and follow the links with relation type `next` in
+    /// the `Link` headers of the responses.
+    ///
+    /// See [`AsyncClient::paginate()`].
+    ///
+    /// [`AsyncClient::paginate()`]: crate::Client::paginate
+    pub fn paginate(&self, request: Request) -> Pages<'_> {
+        Pages::new(self, request)
+    }
+
+    fn send_job(
+        &self,
+        request: Request,
+        timeout: Option<Duration>,
+        cancel: Option<CancellationToken>,
+    ) -> Result<Response, Error> {
+        if let Some(ref e) = self.inner.runtime_error {
+            return Err(Error::Io(io::Error::new(e.kind(), e.to_string())));
+        }
+        let (tx, rx) = oneshot::channel();
+        let job = Job {
+            request,
+            timeout,
+            cancel,
+            resp_tx: tx,
+        };
+        match self.inner.tx {
+            // the receiver is dropped if the runtime thread has exited
+            Some(ref tx) if tx.send(job).is_ok() => {}
+            _ => return Err(Error::RuntimeStopped),
+        }
+
+        // the sender is dropped if the task sending the request panicked or
+        // the runtime was shut down
+        let wait = || rx.blocking_recv().unwrap_or(Err(Error::RuntimeStopped));
+        let res = match self.inner.thread {
+            None if runtime::Handle::try_current().is_ok() => task::block_in_place(wait),
+            _ => wait(),
+        };
+        res.map(|mut resp| {
+            resp.body_mut().keep_client_alive = KeepClientAlive(Some(self.inner.clone()));
+            resp
         })
     }
 
@@ -109,18 +219,28 @@ impl Client {
 ///
 /// [`Client`]: struct.Client.html
 #[derive(Clone)]
-pub struct ClientBuilder(AsyncClientBuilder);
+pub struct ClientBuilder {
+    inner: AsyncClientBuilder,
+    worker_threads: Option<usize>,
+    runtime_handle: Option<runtime::Handle>,
+    body_buffer_chunks: usize,
+}
 
 impl ClientBuilder {
     fn new() -> Self {
-        ClientBuilder(AsyncClientBuilder::new())
+        ClientBuilder {
+            inner: AsyncClientBuilder::new(),
+            worker_threads: None,
+            runtime_handle: None,
+            body_buffer_chunks: 1,
+        }
     }
 
     /// Sets the maximum idle connection per host allowed in the pool.
     ///
     /// Default is usize::MAX (no limit).
     pub fn pool_max_idle_per_host(&mut self, max_idle: usize) -> &mut Self {
-        self.0.pool_max_idle_per_host(max_idle);
+        self.inner.pool_max_idle_per_host(max_idle);
         self
     }
 
@@ -130,48 +250,579 @@ impl ClientBuilder {
     ///
     /// Default is 90 seconds.
     pub fn pool_idle_timeout(&mut self, val: Option<Duration>) -> &mut Self {
-        self.0.pool_idle_timeout(val);
+        self.inner.pool_idle_timeout(val);
+        self
+    }
+
+    /// Sets the exact size of the read buffer to *always* use.
+    ///
+    /// Note that setting this option unsets the `http1_max_buf_size` option.
+    ///
+    /// Default is an adaptive read buffer.
+    pub fn http1_read_buf_exact_size(&mut self, sz: usize) -> &mut Self {
+        self.inner.http1_read_buf_exact_size(sz);
+        self
+    }
+
+    /// Set the maximum buffer size for HTTP/1 connections.
+    ///
+    /// Note that setting this option unsets the `http1_read_buf_exact_size`
+    /// option.
+    ///
+    /// Default is ~400kb.
+    ///
+    /// # Panics
+    ///
+    /// The minimum value allowed is 8192. This method panics if the passed
+    /// `max` is less than the minimum.
+    pub fn http1_max_buf_size(&mut self, max: usize) -> &mut Self {
+        self.inner.http1_max_buf_size(max);
+        self
+    }
+
+    /// Set whether HTTP/1 connections will accept obsolete line folding for
+    /// header values in responses.
+    ///
+    /// Default is false.
+    pub fn http1_allow_obsolete_multiline_headers_in_responses(&mut self, val: bool) -> &mut Self {
+        self.inner
+            .http1_allow_obsolete_multiline_headers_in_responses(val);
+        self
+    }
+
+    /// Set whether HTTP/1 connections will accept spaces between header
+    /// names and the colon in responses.
+    ///
+    /// See [`AsyncClientBuilder::http1_allow_spaces_after_header_name_in_responses()`]
+    /// for details.
+    ///
+    /// Default is false.
+    ///
+    /// [`AsyncClientBuilder::http1_allow_spaces_after_header_name_in_responses()`]: crate::ClientBuilder::http1_allow_spaces_after_header_name_in_responses
+    pub fn http1_allow_spaces_after_header_name_in_responses(&mut self, val: bool) -> &mut Self {
+        self.inner
+            .http1_allow_spaces_after_header_name_in_responses(val);
+        self
+    }
+
+    /// Set whether HTTP/1 connections will silently ignore invalid header
+    /// lines in responses.
+    ///
+    /// See [`AsyncClientBuilder::http1_ignore_invalid_headers_in_responses()`]
+    /// for details.
+    ///
+    /// Default is false.
+    ///
+    /// [`AsyncClientBuilder::http1_ignore_invalid_headers_in_responses()`]: crate::ClientBuilder::http1_ignore_invalid_headers_in_responses
+    pub fn http1_ignore_invalid_headers_in_responses(&mut self, val: bool) -> &mut Self {
+        self.inner.http1_ignore_invalid_headers_in_responses(val);
+        self
+    }
+
+    /// Set whether HTTP/1 connections will write header names as title case
+    /// at the socket level, e.g. `Content-Type` instead of `content-type`.
+    ///
+    /// Default is false.
+    pub fn http1_title_case_headers(&mut self, val: bool) -> &mut Self {
+        self.inner.http1_title_case_headers(val);
+        self
+    }
+
+    /// Set whether to support preserving original header cases.
+    ///
+    /// See [`AsyncClientBuilder::http1_preserve_header_case()`] for details.
+    ///
+    /// Default is false.
+    ///
+    /// [`AsyncClientBuilder::http1_preserve_header_case()`]: crate::ClientBuilder::http1_preserve_header_case
+    pub fn http1_preserve_header_case(&mut self, val: bool) -> &mut Self {
+        self.inner.http1_preserve_header_case(val);
+        self
+    }
+
+    /// Set an optional limit on the total size of response headers in bytes.
+    ///
+    /// See [`AsyncClientBuilder::max_response_header_size()`] for details.
+    ///
+    /// Default is None (no limit).
+    ///
+    /// [`AsyncClientBuilder::max_response_header_size()`]: crate::ClientBuilder::max_response_header_size
+    pub fn max_response_header_size(&mut self, val: Option<usize>) -> &mut Self {
+        self.inner.max_response_header_size(val);
+        self
+    }
+
+    /// Enable or disable strict response validation.
+    ///
+    /// See [`AsyncClientBuilder::strict_response_validation()`] for details.
+    /// Errors detected while reading the response body are returned as
+    /// `std::io::Error`s.
+    ///
+    /// Default is false.
+    ///
+    /// [`AsyncClientBuilder::strict_response_validation()`]: crate::ClientBuilder::strict_response_validation
+    pub fn strict_response_validation(&mut self, val: bool) -> &mut Self {
+        self.inner.strict_response_validation(val);
+        self
+    }
+
+    /// Enable or disable HTTP/1 keep-alive.
+    ///
+    /// When disabled, every request uses a fresh connection regardless of
+    /// [`pool_max_idle_per_host()`].
+    ///
+    /// Default is true.
+    ///
+    /// [`pool_max_idle_per_host()`]: #method.pool_max_idle_per_host
+    pub fn http1_keepalive(&mut self, val: bool) -> &mut Self {
+        self.inner.http1_keepalive(val);
+        self
+    }
+
+    /// Set an optional limit on the size of response bodies in bytes.
+    ///
+    /// See [`AsyncClientBuilder::max_response_size()`] for details. Limit
+    /// violations detected while reading the response body are returned as
+    /// `std::io::Error`s.
+    ///
+    /// Default is None (no limit).
+    ///
+    /// [`AsyncClientBuilder::max_response_size()`]: crate::ClientBuilder::max_response_size
+    pub fn max_response_size(&mut self, val: Option<u64>) -> &mut Self {
+        self.inner.max_response_size(val);
+        self
+    }
+
+    /// Set an optional header carrying a unique ID for each request.
+    ///
+    /// See [`AsyncClientBuilder::request_id_header()`] for details.
+    ///
+    /// Default is None (no request IDs).
+    ///
+    /// [`AsyncClientBuilder::request_id_header()`]: crate::ClientBuilder::request_id_header
+    pub fn request_id_header(&mut self, val: Option<HeaderName>) -> &mut Self {
+        self.inner.request_id_header(val);
+        self
+    }
+
+    /// If true, errors returned when sending requests are wrapped in
+    /// [`Error::WithContext`] with the method and URI of the request.
+    ///
+    /// See [`AsyncClientBuilder::error_context()`] for details.
+    ///
+    /// Default is false.
+    ///
+    /// [`AsyncClientBuilder::error_context()`]: crate::ClientBuilder::error_context
+    pub fn error_context(&mut self, val: bool) -> &mut Self {
+        self.inner.error_context(val);
+        self
+    }
+
+    /// If true, the query of the URI is removed from the context attached
+    /// to errors by [`error_context()`].
+    ///
+    /// Default is true.
+    ///
+    /// [`error_context()`]: #method.error_context
+    pub fn error_context_redact_query(&mut self, val: bool) -> &mut Self {
+        self.inner.error_context_redact_query(val);
+        self
+    }
+
+    /// Register an observer that is notified about connections, requests,
+    /// responses and errors.
+    ///
+    /// See [`AsyncClientBuilder::observer()`] for details.
+    ///
+    /// [`AsyncClientBuilder::observer()`]: crate::ClientBuilder::observer
+    pub fn observer<O: Observer>(&mut self, observer: O) -> &mut Self {
+        self.inner.observer(observer);
+        self
+    }
+
+    /// Add a middleware intercepting requests and responses.
+    ///
+    /// See [`AsyncClientBuilder::with_middleware()`] for details.
+    ///
+    /// [`AsyncClientBuilder::with_middleware()`]: crate::ClientBuilder::with_middleware
+    pub fn with_middleware<M: Middleware>(&mut self, middleware: M) -> &mut Self {
+        self.inner.with_middleware(middleware);
+        self
+    }
+
+    /// Set the executor used to spawn the background tasks of the client.
+    ///
+    /// The executor is called on the runtime of the client and spawned tasks
+    /// must run within a tokio runtime. See [`AsyncClientBuilder::executor()`]
+    /// for details.
+    ///
+    /// [`AsyncClientBuilder::executor()`]: crate::ClientBuilder::executor
+    pub fn executor<E>(&mut self, executor: E) -> &mut Self
+    where
+        E: hyper::rt::Executor<BoxSendFuture> + Send + Sync + 'static,
+    {
+        self.inner.executor(executor);
+        self
+    }
+
+    /// Set an optional number of bytes of request and response bodies to
+    /// capture for debugging.
+    ///
+    /// See [`AsyncClientBuilder::capture_bodies()`] for details.
+    ///
+    /// Default is None (no capture).
+    ///
+    /// [`AsyncClientBuilder::capture_bodies()`]: crate::ClientBuilder::capture_bodies
+    pub fn capture_bodies(&mut self, val: Option<usize>) -> &mut Self {
+        self.inner.capture_bodies(val);
+        self
+    }
+
+    /// Set an optional `Accept` header to add to requests that do not have
+    /// one.
+    ///
+    /// See [`AsyncClientBuilder::default_accept()`] for details.
+    ///
+    /// Default is None (no `Accept` header).
+    ///
+    /// [`AsyncClientBuilder::default_accept()`]: crate::ClientBuilder::default_accept
+    pub fn default_accept(&mut self, val: Option<HeaderValue>) -> &mut Self {
+        self.inner.default_accept(val);
+        self
+    }
+
+    /// Set the content codings to accept for response bodies, in order of
+    /// preference.
+    ///
+    /// See [`AsyncClientBuilder::accept_encodings()`] for details.
+    ///
+    /// Default is no encodings (responses are not decompressed).
+    ///
+    /// [`AsyncClientBuilder::accept_encodings()`]: crate::ClientBuilder::accept_encodings
+    #[cfg(feature = "compression")]
+    pub fn accept_encodings(&mut self, encodings: &[Encoding]) -> &mut Self {
+        self.inner.accept_encodings(encodings);
+        self
+    }
+
+    /// Set an optional [`RetryBudget`] shared by all requests of the client.
+    ///
+    /// See [`AsyncClientBuilder::retry_budget()`] for details. Keep a clone
+    /// of the budget to observe it.
+    ///
+    /// Default is None (no budget).
+    ///
+    /// [`AsyncClientBuilder::retry_budget()`]: crate::ClientBuilder::retry_budget
+    pub fn retry_budget(&mut self, val: Option<RetryBudget>) -> &mut Self {
+        self.inner.retry_budget(val);
+        self
+    }
+
+    /// Set an optional [`RetryPolicy`] for retrying failed idempotent
+    /// requests.
+    ///
+    /// See [`AsyncClientBuilder::retry_policy()`] for details.
+    ///
+    /// Default is None (no retries).
+    ///
+    /// [`AsyncClientBuilder::retry_policy()`]: crate::ClientBuilder::retry_policy
+    pub fn retry_policy(&mut self, val: Option<RetryPolicy>) -> &mut Self {
+        self.inner.retry_policy(val);
+        self
+    }
+
+    /// Set an optional limit on the number of concurrent requests of the
+    /// client.
+    ///
+    /// See [`AsyncClientBuilder::max_in_flight()`] for details.
+    ///
+    /// Default is None (no limit).
+    ///
+    /// [`AsyncClientBuilder::max_in_flight()`]: crate::ClientBuilder::max_in_flight
+    pub fn max_in_flight(&mut self, val: Option<usize>) -> &mut Self {
+        self.inner.max_in_flight(val);
+        self
+    }
+
+    /// Set an optional timeout for requests waiting for a slot when
+    /// [`max_in_flight()`] requests are in flight.
+    ///
+    /// Default is None (wait indefinitely).
+    ///
+    /// [`max_in_flight()`]: #method.max_in_flight
+    pub fn max_in_flight_timeout(&mut self, val: Option<Duration>) -> &mut Self {
+        self.inner.max_in_flight_timeout(val);
+        self
+    }
+
+    /// Enable or disable verification of response body checksums.
+    ///
+    /// See [`AsyncClientBuilder::verify_response_checksums()`] for details.
+    /// Mismatches are returned as `std::io::Error`s when reading the
+    /// response body.
+    ///
+    /// Default is false.
+    ///
+    /// [`AsyncClientBuilder::verify_response_checksums()`]: crate::ClientBuilder::verify_response_checksums
+    #[cfg(feature = "checksum")]
+    pub fn verify_response_checksums(&mut self, val: bool) -> &mut Self {
+        self.inner.verify_response_checksums(val);
+        self
+    }
+
+    /// Enable or disable logging of request and response lines and headers.
+    ///
+    /// See [`AsyncClientBuilder::log_wire()`] for details.
+    ///
+    /// Default is false.
+    ///
+    /// [`AsyncClientBuilder::log_wire()`]: crate::ClientBuilder::log_wire
+    #[cfg(feature = "log")]
+    pub fn log_wire(&mut self, val: bool) -> &mut Self {
+        self.inner.log_wire(val);
         self
     }
 
+    /// Set the headers whose values are redacted when logging with
+    /// [`log_wire()`].
+    ///
+    /// Default is `Authorization`, `Proxy-Authorization`, `Cookie` and
+    /// `Set-Cookie`.
+    ///
+    /// [`log_wire()`]: #method.log_wire
+    #[cfg(feature = "log")]
+    pub fn log_redacted_headers<I>(&mut self, headers: I) -> &mut Self
+    where
+        I: IntoIterator<Item = HeaderName>,
+    {
+        self.inner.log_redacted_headers(headers);
+        self
+    }
+
+    /// Enable an in-memory HTTP cache holding up to `val` bytes of response
+    /// headers and bodies.
+    ///
+    /// See [`AsyncClientBuilder::cache_max_size()`] for details.
+    ///
+    /// Default is None (no cache).
+    ///
+    /// [`AsyncClientBuilder::cache_max_size()`]: crate::ClientBuilder::cache_max_size
+    #[cfg(feature = "cache")]
+    pub fn cache_max_size(&mut self, val: Option<usize>) -> &mut Self {
+        self.inner.cache_max_size(val);
+        self
+    }
+
+    /// Enable an HTTP cache storing responses in `store`.
+    ///
+    /// See [`AsyncClientBuilder::cache_store()`] for details.
+    ///
+    /// [`AsyncClientBuilder::cache_store()`]: crate::ClientBuilder::cache_store
+    #[cfg(feature = "cache")]
+    pub fn cache_store<S: CacheStore>(&mut self, store: S) -> &mut Self {
+        self.inner.cache_store(store);
+        self
+    }
+
+    /// Set the clock used to determine the age of cached responses.
+    ///
+    /// See [`AsyncClientBuilder::clock()`] for details.
+    ///
+    /// [`AsyncClientBuilder::clock()`]: crate::ClientBuilder::clock
+    #[cfg(feature = "cache")]
+    pub fn clock<C: Clock>(&mut self, clock: C) -> &mut Self {
+        self.inner.clock(clock);
+        self
+    }
+
+    /// Set the number of worker threads of the runtime used by the client.
+    ///
+    /// By default (`None`), requests are processed on a single thread. With
+    /// `Some(n)`, a multi-threaded runtime with `n` worker threads is used
+    /// instead, which can improve throughput if the client is shared by many
+    /// threads sending requests concurrently. `Some(0)` is rejected by
+    /// [`try_build()`].
+    ///
+    /// [`try_build()`]: #method.try_build
+    pub fn worker_threads(&mut self, val: Option<usize>) -> &mut Self {
+        self.worker_threads = val;
+        self
+    }
+
+    /// Run the client on an existing tokio runtime instead of spawning a
+    /// thread with a separate runtime. [`worker_threads()`] is ignored if
+    /// this is set.
+    ///
+    /// Requests can then be sent from threads outside the runtime as well as
+    /// from worker threads of a multi-threaded runtime, in which case
+    /// [`RequestBuilder::send()`] waits for the response using
+    /// `tokio::task::block_in_place()`. Sending requests from the thread of
+    /// a current-thread runtime panics. Response bodies must be read outside
+    /// of asynchronous contexts, e.g. in `tokio::task::spawn_blocking()`.
+    ///
+    /// [`worker_threads()`]: #method.worker_threads
+    /// [`RequestBuilder::send()`]: struct.RequestBuilder.html#method.send
+    pub fn runtime_handle(&mut self, handle: runtime::Handle) -> &mut Self {
+        self.runtime_handle = Some(handle);
+        self
+    }
+
+    /// Set the number of chunks of a response body that are received ahead
+    /// of being read from [`Body`].
+    ///
+    /// Larger values can improve throughput of large downloads at the cost
+    /// of memory. A value of 0 is treated as 1. Default is 1.
+    ///
+    /// [`Body`]: crate::blocking::Body
+    pub fn body_buffer_chunks(&mut self, val: usize) -> &mut Self {
+        self.body_buffer_chunks = val.max(1);
+        self
+    }
+
+    fn spawn(&self, async_client: AsyncClient) -> io::Result<Client> {
+        let (tx, rx) = mpsc::unbounded_channel::<Job>();
+        let buffer = self.body_buffer_chunks;
+        let thread = match self.runtime_handle {
+            Some(ref handle) => {
+                handle.spawn(dispatch(rx, async_client, buffer));
+                None
+            }
+            None => {
+                let rt = self.runtime()?;
+                let thread = thread::Builder::new()
+                    .name("simple-hyper-client-blocking".to_owned())
+                    .spawn(move || rt.block_on(dispatch(rx, async_client, buffer)))?;
+                Some(thread)
+            }
+        };
+        Ok(Client {
+            inner: Arc::new(ClientInner {
+                tx: Some(tx),
+                thread,
+                runtime_error: None,
+            }),
+        })
+    }
+
+    fn runtime(&self) -> io::Result<runtime::Runtime> {
+        let mut builder = match self.worker_threads {
+            None => runtime::Builder::new_current_thread(),
+            Some(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "worker thread count must not be zero",
+                ))
+            }
+            Some(n) => {
+                let mut builder = runtime::Builder::new_multi_thread();
+                builder.worker_threads(n);
+                builder
+            }
+        };
+        builder.enable_all().build()
+    }
+
     /// Combine the configuration of this builder with a connector to create a
     /// `Client`.
+    ///
+    /// If the runtime of the client cannot be created, e.g. because
+    /// [`worker_threads()`] is `Some(0)`, all requests sent with the client
+    /// fail with the error. Use [`try_build()`] to handle it immediately.
+    ///
+    /// [`worker_threads()`]: #method.worker_threads
+    /// [`try_build()`]: #method.try_build
     pub fn build<C: NetworkConnector>(&self, connector: C) -> Client {
-        let async_client = self.0.build(connector);
-        let (tx, mut rx) = mpsc::unbounded_channel::<(RequestDetails, ResponseSender)>();
-
-        let thread = thread::spawn(move || {
-            let rt = runtime::Builder::new_current_thread()
-                .enable_all()
-                .build()
-                .unwrap(); // TODO: send back an error through a oneshot channel
-
-            rt.block_on(async move {
-                while let Some((req_details, resp_tx)) = rx.recv().await {
-                    let async_client = async_client.clone();
-                    tokio::spawn(async move {
-                        match req_details.send(&async_client).await {
-                            Ok(resp) => {
-                                let (parts, hyper_body) = resp.into_parts();
-                                let (fut, body) = Body::new(hyper_body);
-                                let _ = resp_tx.send(Ok(Response::from_parts(parts, body)));
-                                fut.await;
-                            }
-                            Err(e) => {
-                                let _: Result<_, _> = resp_tx.send(Err(e));
-                            }
-                        }
-                    });
-                }
-            })
-        });
+        self.spawn_or_fail(self.inner.build(connector))
+    }
 
-        Client {
+    /// Combine the configuration of this builder with an existing hyper
+    /// client to create a `Client`.
+    ///
+    /// See [`AsyncClientBuilder::build_from_hyper()`] for the options that
+    /// have no effect, and [`build()`] regarding runtime errors.
+    ///
+    /// [`AsyncClientBuilder::build_from_hyper()`]: crate::ClientBuilder::build_from_hyper
+    /// [`build()`]: #method.build
+    pub fn build_from_hyper<C>(&self, hyper: HyperClient<C, SharedBody>) -> Client
+    where
+        C: Connect + Clone + Send + Sync + 'static,
+    {
+        self.spawn_or_fail(self.inner.build_from_hyper(hyper))
+    }
+
+    fn spawn_or_fail(&self, async_client: AsyncClient) -> Client {
+        self.spawn(async_client).unwrap_or_else(|e| Client {
             inner: Arc::new(ClientInner {
-                tx: Some(tx),
-                thread: Some(thread),
+                tx: None,
+                thread: None,
+                runtime_error: Some(e),
             }),
+        })
+    }
+
+    /// Validate the configuration of this builder and combine it with a
+    /// connector to create a `Client`.
+    ///
+    /// See [`AsyncClientBuilder::try_build()`] for the validated options.
+    /// Also returns an error if [`worker_threads()`] is `Some(0)` or the
+    /// runtime of the client cannot be created.
+    ///
+    /// [`AsyncClientBuilder::try_build()`]: crate::ClientBuilder::try_build
+    /// [`worker_threads()`]: #method.worker_threads
+    pub fn try_build<C: NetworkConnector>(&self, connector: C) -> Result<Client, Error> {
+        if self.worker_threads == Some(0) {
+            return Err(Error::InvalidConfig("worker thread count must not be zero"));
         }
+        let async_client = self.inner.try_build(connector)?;
+        Ok(self.spawn(async_client)?)
+    }
+}
+
+async fn dispatch(
+    mut rx: mpsc::UnboundedReceiver<Job>,
+    async_client: AsyncClient,
+    body_buffer_chunks: usize,
+) {
+    while let Some(job) = rx.recv().await {
+        let Job {
+            request,
+            timeout,
+            cancel,
+            resp_tx,
+        } = job;
+        let async_client = async_client.clone();
+        tokio::spawn(async move {
+            let send = async {
+                match timeout {
+                    Some(duration) => {
+                        let send = async_client.send(request);
+                        match tokio::time::timeout(duration, send).await {
+                            Ok(res) => res,
+                            Err(_) => Err(Error::RequestTimeout(duration)),
+                        }
+                    }
+                    None => async_client.send(request).await,
+                }
+            };
+            let res = match cancel {
+                Some(ref cancel) => tokio::select! {
+                    res = send => res,
+                    _ = cancel.cancelled() => Err(Error::Cancelled),
+                },
+                None => send.await,
+            };
+            match res {
+                Ok(resp) => {
+                    let (parts, hyper_body) = resp.into_parts();
+                    let (fut, body) = Body::new(hyper_body, body_buffer_chunks, cancel);
+                    let _ = resp_tx.send(Ok(Response::from_parts(parts, body)));
+                    fut.await;
+                }
+                Err(e) => {
+                    let _: Result<_, _> = resp_tx.send(Err(e));
+                }
+            }
+        });
     }
 }
 
@@ -186,22 +837,159 @@ impl ClientBuilder {
 pub struct RequestBuilder<'a> {
     client: &'a Client,
     details: RequestDetails,
+    timeout: Option<Duration>,
+    cancel: Option<CancellationToken>,
 }
 
 impl<'a> RequestBuilder<'a> {
     /// Set the request body.
+    ///
+    /// See [`AsyncRequestBuilder::body()`] regarding `GET`, `HEAD` and
+    /// `DELETE` requests.
+    ///
+    /// [`AsyncRequestBuilder::body()`]: crate::RequestBuilder::body
     pub fn body<B: Into<SharedBody>>(mut self, body: B) -> Self {
         self.details.body = Some(body.into());
         self
     }
 
+    /// Allow sending a body with `GET`, `HEAD` and `DELETE` requests.
+    ///
+    /// See [`AsyncRequestBuilder::allow_body_on_any_method()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::allow_body_on_any_method()`]: crate::RequestBuilder::allow_body_on_any_method
+    pub fn allow_body_on_any_method(mut self) -> Self {
+        self.details.allow_body_on_any_method = true;
+        self
+    }
+
+    /// Set the request body to a GraphQL request for `query` with
+    /// `variables`.
+    ///
+    /// See [`AsyncRequestBuilder::graphql()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::graphql()`]: crate::RequestBuilder::graphql
+    #[cfg(feature = "graphql")]
+    pub fn graphql<V>(mut self, query: &str, variables: &V) -> Result<Self, Error>
+    where
+        V: serde::Serialize + ?Sized,
+    {
+        graphql::set_request(
+            &mut self.details.headers,
+            &mut self.details.body,
+            query,
+            variables,
+        )?;
+        Ok(self)
+    }
+
+    /// Set the request body to the XML serialization of `value` and the
+    /// `Content-Type` header to `application/xml`.
+    ///
+    /// See [`AsyncRequestBuilder::xml()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::xml()`]: crate::RequestBuilder::xml
+    #[cfg(feature = "xml")]
+    pub fn xml<T>(mut self, value: &T) -> Result<Self, Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        xml::set_request(&mut self.details.headers, &mut self.details.body, value)?;
+        Ok(self)
+    }
+
+    /// Set the request body to the CBOR encoding of `value` and the
+    /// `Content-Type` header to `application/cbor`.
+    ///
+    /// See [`AsyncRequestBuilder::cbor()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::cbor()`]: crate::RequestBuilder::cbor
+    #[cfg(feature = "cbor")]
+    pub fn cbor<T>(mut self, value: &T) -> Result<Self, Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        cbor::set_request(&mut self.details.headers, &mut self.details.body, value)?;
+        Ok(self)
+    }
+
+    /// Set the request body to the MessagePack encoding of `value` and the
+    /// `Content-Type` header to `application/msgpack`.
+    ///
+    /// See [`AsyncRequestBuilder::msgpack()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::msgpack()`]: crate::RequestBuilder::msgpack
+    #[cfg(feature = "msgpack")]
+    pub fn msgpack<T>(mut self, value: &T) -> Result<Self, Error>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        msgpack::set_request(&mut self.details.headers, &mut self.details.body, value)?;
+        Ok(self)
+    }
+
+    /// Compress the request body using `encoding` and set the
+    /// `Content-Encoding` header accordingly.
+    ///
+    /// See [`AsyncRequestBuilder::compress()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::compress()`]: crate::RequestBuilder::compress
+    #[cfg(feature = "compression")]
+    pub fn compress(mut self, encoding: Encoding) -> Self {
+        self.details.compression = Some(encoding);
+        self
+    }
+
+    /// Compute the checksum of the request body using `algorithm` and send
+    /// it in the corresponding header.
+    ///
+    /// See [`AsyncRequestBuilder::checksum()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::checksum()`]: crate::RequestBuilder::checksum
+    #[cfg(feature = "checksum")]
+    pub fn checksum(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        self.details.checksum = Some(algorithm);
+        self
+    }
+
+    /// Override [`ClientBuilder::max_response_size()`] for this request.
+    pub fn max_response_size(mut self, val: Option<u64>) -> Self {
+        self.details.max_response_size = Some(val);
+        self
+    }
+
+    /// Fail if the response is received over a connection using a
+    /// different HTTP version.
+    ///
+    /// See [`AsyncRequestBuilder::require_version()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::require_version()`]: crate::RequestBuilder::require_version
+    pub fn require_version(mut self, version: Version) -> Self {
+        self.details.required_version = Some(version);
+        self
+    }
+
+    /// Register a callback that is invoked as the request body is sent.
+    ///
+    /// The callback receives the number of bytes sent so far and the total
+    /// length of the body if it is known. Note that the callback is invoked
+    /// on the client's runtime thread.
+    pub fn upload_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u64, Option<u64>) + Send + Sync + 'static,
+    {
+        self.details.upload_progress = Some(Arc::new(callback));
+        self
+    }
+
     /// Set the request headers.
     pub fn headers(mut self, headers: HeaderMap) -> Self {
         self.details.headers = headers;
         self
     }
 
-    /// Set a single header using [`HeaderMapExt::typed_insert()`].
+    /// Set a single header using [`HeaderMapExt::typed_insert()`], replacing
+    /// any existing values.
     ///
     /// [`HeaderMapExt::typed_insert()`]: https://docs.rs/headers/0.3.5/headers/trait.HeaderMapExt.html#tymethod.typed_insert
     pub fn header<H: Header>(mut self, header: H) -> Self {
@@ -209,36 +997,123 @@ impl<'a> RequestBuilder<'a> {
         self
     }
 
+    /// Set a single header, replacing any existing values of `name`.
+    ///
+    /// See [`AsyncRequestBuilder::header_raw()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::header_raw()`]: crate::RequestBuilder::header_raw
+    pub fn header_raw(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.details.headers.insert(name, value);
+        self
+    }
+
+    /// Add a value to the header `name`, keeping its existing values.
+    ///
+    /// See [`AsyncRequestBuilder::header_append()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::header_append()`]: crate::RequestBuilder::header_append
+    pub fn header_append(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.details.headers.append(name, value);
+        self
+    }
+
+    /// Remove all values of the header `name`.
+    pub fn remove_header(mut self, name: HeaderName) -> Self {
+        self.details.headers.remove(name);
+        self
+    }
+
+    /// Returns a mutable reference to the request headers.
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.details.headers
+    }
+
+    /// Set the `Authorization` header for HTTP Basic authentication.
+    ///
+    /// See [`AsyncRequestBuilder::basic_auth()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::basic_auth()`]: crate::RequestBuilder::basic_auth
+    pub fn basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.details.basic_auth(username, password);
+        self
+    }
+
+    /// Set the `Authorization` header to the bearer `token`.
+    ///
+    /// See [`AsyncRequestBuilder::bearer_auth()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::bearer_auth()`]: crate::RequestBuilder::bearer_auth
+    pub fn bearer_auth(mut self, token: &str) -> Result<Self, Error> {
+        self.details.bearer_auth(token)?;
+        Ok(self)
+    }
+
+    /// Set the `If-None-Match` header to `etag`, making the request
+    /// conditional on the representation not matching the entity tag.
+    pub fn if_none_match(mut self, etag: HeaderValue) -> Self {
+        self.details.headers.insert(IF_NONE_MATCH, etag);
+        self
+    }
+
+    /// Set the `If-Modified-Since` header, making the request conditional on
+    /// the representation having been modified after `time`.
+    pub fn if_modified_since(self, time: SystemTime) -> Self {
+        self.header(IfModifiedSince::from(time))
+    }
+
+    /// Send the request conditioned on `validators` and return `cached` if
+    /// the server responds with `304 Not Modified`.
+    ///
+    /// See [`AsyncRequestBuilder::revalidate()`] for details.
+    ///
+    /// [`AsyncRequestBuilder::revalidate()`]: crate::RequestBuilder::revalidate
+    pub fn revalidate<T>(
+        mut self,
+        cached: T,
+        validators: &Validators,
+    ) -> Result<Revalidation<T, Response>, Error> {
+        validators.apply(&mut self.details.headers);
+        let response = self.send()?;
+        Ok(match response.status() {
+            StatusCode::NOT_MODIFIED => Revalidation::Fresh(cached),
+            _ => Revalidation::Modified(response),
+        })
+    }
+
+    /// Set a timeout for receiving the response head.
+    ///
+    /// The timeout covers connecting, sending the request and waiting for
+    /// the response headers, but not reading the response body. If it
+    /// elapses, the request is cancelled and [`Error::RequestTimeout`] is
+    /// returned.
+    ///
+    /// Default is no timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Allow cancelling the request from another thread with `handle`.
+    ///
+    /// This also applies to reading the response body. See [`CancelHandle`].
+    ///
+    /// [`CancelHandle`]: crate::blocking::CancelHandle
+    pub fn cancel_handle(mut self, handle: &CancelHandle) -> Self {
+        self.cancel = Some(handle.0.clone());
+        self
+    }
+
     /// Send the request over the network.
     ///
     /// Returns an error before sending the request if there is something wrong
     /// with the request parameters (method, uri, etc.).
     pub fn send(self) -> Result<Response, Error> {
-        let RequestBuilder { client, details } = self;
-        let (tx, rx) = oneshot::channel();
-        client
-            .inner
-            .tx
-            .as_ref()
-            .expect("runtime thread exited early")
-            .send((details, tx))
-            .expect("runtime thread panicked");
-
-        // TODO: replace `block_on` with `rx.blocking_recv()` once we move to tokio 1.16+
-        block_on(async move {
-            match rx.await {
-                Ok(res) => res,
-                Err(_) => panic!("event loop panicked"),
-            }
-        })
-        .map(|mut resp| {
-            resp.body_mut().keep_client_alive = KeepClientAlive(Some(client.inner.clone()));
-            resp
-        })
+        let request = self.details.into_request()?;
+        self.client.send_job(request, self.timeout, self.cancel)
     }
 }
 
-pub(super) struct KeepClientAlive(Option<Arc<ClientInner>>);
+pub(super) struct KeepClientAlive(#[allow(dead_code)] Option<Arc<ClientInner>>);
 
 impl KeepClientAlive {
     pub fn empty() -> Self {
@@ -246,8 +1121,8 @@ impl KeepClientAlive {
     }
 }
 
-#[cfg(test)]
-mod tests {
+#[cfg(all(test, feature = "tcp"))]
+pub(crate) mod tests {
     use super::*;
     use crate::connector::HttpConnector;
     use headers::ContentType;
@@ -260,13 +1135,13 @@ mod tests {
     const RESPONSE_404: &str =
         "HTTP/1.1 404 Not Found\r\nContent-Length: 23\r\n\r\nResource was not found.\r\n";
 
-    fn test_http_server(resp: &'static str) -> SocketAddr {
+    pub(crate) fn test_http_server(resp: &'static str) -> SocketAddr {
         let listener = TcpListener::bind("127.0.0.1:0").unwrap();
         let addr = listener.local_addr().unwrap();
         thread::spawn(move || {
             let (mut stream, _) = listener.accept().unwrap();
-            let mut input = Vec::new();
-            stream.read(&mut input).unwrap();
+            let mut input = [0u8; 1024];
+            let _ = stream.read(&mut input).unwrap();
             stream.write_all(resp.as_bytes()).unwrap();
         });
         addr
@@ -309,4 +1184,253 @@ mod tests {
         response.body_mut().read_to_string(&mut body).unwrap();
         assert_eq!(body, "Resource was not found.");
     }
+
+    #[test]
+    fn try_build() {
+        let mut builder = Client::builder();
+        builder.max_response_header_size(Some(0));
+        assert!(matches!(
+            builder.try_build(HttpConnector::new()),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        builder.max_response_header_size(None);
+        let client = builder.try_build(HttpConnector::new()).unwrap();
+        let addr = test_http_server(RESPONSE_OK);
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn worker_threads() {
+        let mut builder = Client::builder();
+        builder.worker_threads(Some(0));
+        assert!(matches!(
+            builder.try_build(HttpConnector::new()),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        let client = builder.worker_threads(Some(2)).build(HttpConnector::new());
+        let addr = test_http_server(RESPONSE_OK);
+        let mut response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = String::new();
+        response.body_mut().read_to_string(&mut body).unwrap();
+        assert_eq!(body, "Hello, world!");
+    }
+
+    #[test]
+    fn runtime_error() {
+        let client = Client::builder()
+            .worker_threads(Some(0))
+            .build(HttpConnector::new());
+        let err = client.get("http://127.0.0.1/").unwrap().send().unwrap_err();
+        assert!(matches!(err, Error::Io(ref e) if e.kind() == io::ErrorKind::InvalidInput));
+    }
+
+    #[test]
+    fn runtime_handle() {
+        let rt = runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .unwrap();
+        let client = Client::builder()
+            .runtime_handle(rt.handle().clone())
+            .build(HttpConnector::new());
+
+        let addr = test_http_server(RESPONSE_OK);
+        let mut response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .unwrap();
+        let mut body = String::new();
+        response.body_mut().read_to_string(&mut body).unwrap();
+        assert_eq!(body, "Hello, world!");
+
+        // Send from a worker thread of the runtime
+        let addr = test_http_server(RESPONSE_404);
+        let status = rt.block_on(async move {
+            let send = move || {
+                let response = client.get(format!("http://{}/", addr)).unwrap().send();
+                response.unwrap().status()
+            };
+            tokio::spawn(async move { send() }).await.unwrap()
+        });
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn cancel() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            // Never respond to the first request and send part of the body
+            // of the second
+            let first = listener.accept().unwrap();
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut input = [0u8; 1024];
+            let _ = stream.read(&mut input).unwrap();
+            let resp = "HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nHello";
+            stream.write_all(resp.as_bytes()).unwrap();
+            (first, stream)
+        });
+
+        let client = Client::with_connector(HttpConnector::new());
+        let handle = CancelHandle::new();
+        let handle2 = handle.clone();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            handle2.cancel();
+        });
+        let err = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .cancel_handle(&handle)
+            .send()
+            .unwrap_err();
+        assert!(err.is_cancelled());
+        canceller.join().unwrap();
+
+        let handle = CancelHandle::new();
+        let mut response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .cancel_handle(&handle)
+            .send()
+            .unwrap();
+        let mut buf = [0u8; 5];
+        response.body_mut().read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"Hello");
+        handle.cancel();
+        let err = response.body_mut().read(&mut buf).unwrap_err();
+        let err = err.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert!(err.is_cancelled());
+        drop(server.join().unwrap());
+    }
+
+    #[test]
+    fn runtime_stopped() {
+        struct PanickingObserver(std::sync::Mutex<Option<String>>);
+
+        impl Observer for Arc<PanickingObserver> {
+            fn on_request(&self, _request: &Request) {
+                *self.0.lock().unwrap() = thread::current().name().map(ToOwned::to_owned);
+                panic!("observer panicked");
+            }
+        }
+
+        let observer = Arc::new(PanickingObserver(Default::default()));
+        let client = Client::builder()
+            .observer(observer.clone())
+            .build(HttpConnector::new());
+        let err = client.get("http://127.0.0.1/").unwrap().send().unwrap_err();
+        assert!(matches!(err, Error::RuntimeStopped));
+        assert_eq!(
+            observer.0.lock().unwrap().as_deref(),
+            Some("simple-hyper-client-blocking")
+        );
+
+        let rt = runtime::Builder::new_current_thread().build().unwrap();
+        let client = Client::builder()
+            .runtime_handle(rt.handle().clone())
+            .build(HttpConnector::new());
+        drop(rt);
+        let err = client.get("http://127.0.0.1/").unwrap().send().unwrap_err();
+        assert!(matches!(err, Error::RuntimeStopped));
+    }
+
+    #[test]
+    fn request_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Accept the connection but never respond
+        let server = thread::spawn(move || listener.accept().unwrap());
+
+        let client = Client::with_connector(HttpConnector::new());
+        let err = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .timeout(Duration::from_millis(100))
+            .send()
+            .unwrap_err();
+        assert!(matches!(err, Error::RequestTimeout(_)));
+        assert!(err.is_timeout());
+        drop(server.join().unwrap());
+    }
+
+    #[test]
+    fn from_hyper() {
+        let addr = test_http_server(RESPONSE_OK);
+        let hyper =
+            HyperClient::builder().build::<_, SharedBody>(hyper::client::HttpConnector::new());
+        let client = Client::from_hyper(hyper);
+        let mut response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .unwrap();
+        let mut body = String::new();
+        response.body_mut().read_to_string(&mut body).unwrap();
+        assert_eq!(body, "Hello, world!");
+    }
+
+    #[test]
+    fn paginate() {
+        const PAGE_1: &str = "HTTP/1.1 200 OK\r\nLink: </items?page=2>; rel=next\r\n\
+                              Content-Length: 1\r\n\r\n1";
+        const PAGE_2: &str = "HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\n2";
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            for response in [PAGE_1, PAGE_2] {
+                let mut input = [0u8; 1024];
+                let _ = stream.read(&mut input).unwrap();
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let client = Client::with_connector(HttpConnector::new());
+        let request = hyper::Request::get(format!("http://{}/items", addr))
+            .body(SharedBody::empty())
+            .unwrap();
+        let bodies: Vec<_> = client
+            .paginate(request)
+            .map(|response| {
+                let mut body = String::new();
+                response
+                    .unwrap()
+                    .body_mut()
+                    .read_to_string(&mut body)
+                    .unwrap();
+                body
+            })
+            .collect();
+        assert_eq!(bodies, ["1", "2"]);
+    }
+
+    #[test]
+    fn send_request() {
+        let addr = test_http_server(RESPONSE_OK);
+        let request = hyper::Request::get(format!("http://{}/", addr))
+            .body(SharedBody::empty())
+            .unwrap();
+
+        let client = Client::with_connector(HttpConnector::new());
+        let mut response = client.send(request).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = String::new();
+        response.body_mut().read_to_string(&mut body).unwrap();
+        assert_eq!(body, "Hello, world!");
+    }
 }
@@ -7,16 +7,23 @@
 //! This module provides a blocking interface on top of `hyper`'s HTTP client.
 //!
 //! The [`Client`] type in this module spawns a separate thread for running
-//! async tasks. Additionally, since the client holds a connection pool
+//! async tasks, unless it is configured to use an existing runtime with
+//! [`ClientBuilder::runtime_handle()`]. Additionally, since the client holds a connection pool
 //! internally, it is advised that instances be reused as much as possible.
 
 use crate::shared_body::SharedBody;
 
 mod body;
+mod cancel;
 mod client;
+mod pagination;
+mod response_ext;
 
-pub use self::body::Body;
+pub use self::body::{Body, Chunks};
+pub use self::cancel::CancelHandle;
 pub use self::client::{Client, ClientBuilder, RequestBuilder};
+pub use self::pagination::Pages;
+pub use self::response_ext::{ResponseExt, SaveOptions};
 
 pub type Request = hyper::Request<SharedBody>;
 pub type Response = hyper::Response<Body>;
@@ -0,0 +1,61 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::{Client, Request, Response};
+use crate::error::Error;
+use crate::pagination::PageRequest;
+
+use std::fmt;
+
+/// An iterator over the pages of a paginated resource
+///
+/// This is created by [`Client::paginate()`] and is the blocking equivalent
+/// of [`AsyncPages`].
+///
+/// [`Client::paginate()`]: crate::blocking::Client::paginate
+/// [`AsyncPages`]: crate::Pages
+pub struct Pages<'a> {
+    client: &'a Client,
+    page: Option<PageRequest>,
+    first: Option<Request>,
+}
+
+impl fmt::Debug for Pages<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pages").finish()
+    }
+}
+
+impl<'a> Pages<'a> {
+    pub(super) fn new(client: &'a Client, request: Request) -> Self {
+        Pages {
+            client,
+            page: Some(PageRequest::first(&request)),
+            first: Some(request),
+        }
+    }
+}
+
+impl Iterator for Pages<'_> {
+    type Item = Result<Response, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let request = match (self.first.take(), &self.page) {
+            (Some(request), _) => request,
+            (None, Some(page)) => page.to_request(),
+            (None, None) => return None,
+        };
+        let res = self.client.send(request);
+        self.page = match res {
+            Ok(ref response) => self
+                .page
+                .take()
+                .and_then(|page| page.next(response.headers())),
+            Err(_) => None,
+        };
+        Some(res)
+    }
+}
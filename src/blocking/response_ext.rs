@@ -0,0 +1,409 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::Response;
+#[cfg(feature = "cbor")]
+use crate::cbor;
+use crate::connector::remote_addr;
+use crate::error::Error;
+#[cfg(feature = "graphql")]
+use crate::graphql::{parse_response, GraphQlResponse};
+#[cfg(feature = "msgpack")]
+use crate::msgpack;
+#[cfg(any(
+    feature = "cbor",
+    feature = "json",
+    feature = "msgpack",
+    feature = "xml"
+))]
+use crate::negotiate;
+use crate::pagination::next_link;
+#[cfg(feature = "xml")]
+use crate::xml;
+
+use headers::{ContentLength, HeaderMapExt};
+use hyper::{Uri, Version};
+#[cfg(any(
+    feature = "cbor",
+    feature = "graphql",
+    feature = "json",
+    feature = "msgpack",
+    feature = "xml"
+))]
+use serde::de::DeserializeOwned;
+
+use std::fmt;
+use std::fs::{self, File};
+#[cfg(any(
+    feature = "cbor",
+    feature = "graphql",
+    feature = "json",
+    feature = "msgpack",
+    feature = "xml"
+))]
+use std::io::Read;
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+type ProgressCallback = Arc<dyn Fn(u64, Option<u64>) + Send + Sync>;
+
+/// Extension methods for blocking [`Response`]
+///
+/// [`Response`]: crate::blocking::Response
+pub trait ResponseExt {
+    /// Write the response body to the file at `path`, creating the file if
+    /// it does not exist and truncating it otherwise.
+    ///
+    /// The file is synced to disk before returning. Returns the number of
+    /// bytes written. This is the blocking equivalent of
+    /// [`AsyncResponseExt::save_to_file()`].
+    ///
+    /// [`AsyncResponseExt::save_to_file()`]: crate::ResponseExt::save_to_file
+    fn save_to_file<P: AsRef<Path>>(self, path: P) -> Result<u64, Error>;
+
+    /// Same as [`save_to_file()`] with additional [`SaveOptions`].
+    ///
+    /// [`save_to_file()`]: ResponseExt::save_to_file
+    fn save_to_file_with<P: AsRef<Path>>(self, path: P, options: SaveOptions)
+        -> Result<u64, Error>;
+
+    /// Returns the target of the first link with relation type `next` in
+    /// the `Link` headers of the response.
+    ///
+    /// See [`AsyncResponseExt::next_page_uri()`].
+    ///
+    /// [`AsyncResponseExt::next_page_uri()`]: crate::ResponseExt::next_page_uri
+    fn next_page_uri(&self) -> Option<Uri>;
+
+    /// Returns the address of the peer the response was received from.
+    ///
+    /// See [`AsyncResponseExt::remote_addr()`].
+    ///
+    /// [`AsyncResponseExt::remote_addr()`]: crate::ResponseExt::remote_addr
+    fn remote_addr(&self) -> Option<SocketAddr>;
+
+    /// Returns the HTTP version of the connection the response was received
+    /// on.
+    ///
+    /// See [`AsyncResponseExt::effective_version()`].
+    ///
+    /// [`AsyncResponseExt::effective_version()`]: crate::ResponseExt::effective_version
+    fn effective_version(&self) -> Version;
+
+    /// Read the response body and parse it as a [`GraphQlResponse`].
+    ///
+    /// See [`AsyncResponseExt::graphql()`].
+    ///
+    /// [`GraphQlResponse`]: crate::GraphQlResponse
+    /// [`AsyncResponseExt::graphql()`]: crate::ResponseExt::graphql
+    #[cfg(feature = "graphql")]
+    fn graphql<T: DeserializeOwned>(self) -> Result<GraphQlResponse<T>, Error>;
+
+    /// Read the response body and deserialize it from XML.
+    ///
+    /// See [`AsyncResponseExt::xml()`].
+    ///
+    /// [`AsyncResponseExt::xml()`]: crate::ResponseExt::xml
+    #[cfg(feature = "xml")]
+    fn xml<T: DeserializeOwned>(self) -> Result<T, Error>;
+
+    /// Read the response body and deserialize it from CBOR.
+    ///
+    /// See [`AsyncResponseExt::cbor()`].
+    ///
+    /// [`AsyncResponseExt::cbor()`]: crate::ResponseExt::cbor
+    #[cfg(feature = "cbor")]
+    fn cbor<T: DeserializeOwned>(self) -> Result<T, Error>;
+
+    /// Read the response body and deserialize it from MessagePack.
+    ///
+    /// See [`AsyncResponseExt::msgpack()`].
+    ///
+    /// [`AsyncResponseExt::msgpack()`]: crate::ResponseExt::msgpack
+    #[cfg(feature = "msgpack")]
+    fn msgpack<T: DeserializeOwned>(self) -> Result<T, Error>;
+
+    /// Read the response body and deserialize it according to the
+    /// `Content-Type` header of the response.
+    ///
+    /// See [`AsyncResponseExt::deserialize()`].
+    ///
+    /// [`AsyncResponseExt::deserialize()`]: crate::ResponseExt::deserialize
+    #[cfg(any(
+        feature = "cbor",
+        feature = "json",
+        feature = "msgpack",
+        feature = "xml"
+    ))]
+    fn deserialize<T: DeserializeOwned>(self) -> Result<T, Error>;
+}
+
+impl ResponseExt for Response {
+    fn save_to_file<P: AsRef<Path>>(self, path: P) -> Result<u64, Error> {
+        self.save_to_file_with(path, SaveOptions::new())
+    }
+
+    fn save_to_file_with<P: AsRef<Path>>(
+        self,
+        path: P,
+        options: SaveOptions,
+    ) -> Result<u64, Error> {
+        let total = self.headers().typed_get::<ContentLength>().map(|l| l.0);
+        if let (Some(limit), Some(total)) = (options.max_size, total) {
+            if total > limit {
+                return Err(Error::ResponseBodyTooLarge(limit));
+            }
+        }
+
+        let path = path.as_ref();
+        let mut file = File::create(path)?;
+        match write_body(self, &mut file, &options, total) {
+            Ok(written) => Ok(written),
+            Err(e) => {
+                drop(file);
+                let _ = fs::remove_file(path);
+                Err(e)
+            }
+        }
+    }
+
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        remote_addr(self.extensions())
+    }
+
+    fn effective_version(&self) -> Version {
+        self.version()
+    }
+
+    fn next_page_uri(&self) -> Option<Uri> {
+        next_link(self.headers())?.parse().ok()
+    }
+
+    #[cfg(feature = "graphql")]
+    fn graphql<T: DeserializeOwned>(mut self) -> Result<GraphQlResponse<T>, Error> {
+        let mut body = Vec::new();
+        self.body_mut().read_to_end(&mut body).map_err(body_error)?;
+        parse_response(self.status(), &body)
+    }
+
+    #[cfg(feature = "xml")]
+    fn xml<T: DeserializeOwned>(mut self) -> Result<T, Error> {
+        let mut body = Vec::new();
+        self.body_mut().read_to_end(&mut body).map_err(body_error)?;
+        xml::parse_response(&body)
+    }
+
+    #[cfg(feature = "cbor")]
+    fn cbor<T: DeserializeOwned>(mut self) -> Result<T, Error> {
+        let mut body = Vec::new();
+        self.body_mut().read_to_end(&mut body).map_err(body_error)?;
+        cbor::parse_response(&body)
+    }
+
+    #[cfg(feature = "msgpack")]
+    fn msgpack<T: DeserializeOwned>(mut self) -> Result<T, Error> {
+        let mut body = Vec::new();
+        self.body_mut().read_to_end(&mut body).map_err(body_error)?;
+        msgpack::parse_response(&body)
+    }
+
+    #[cfg(any(
+        feature = "cbor",
+        feature = "json",
+        feature = "msgpack",
+        feature = "xml"
+    ))]
+    fn deserialize<T: DeserializeOwned>(mut self) -> Result<T, Error> {
+        let mut body = Vec::new();
+        self.body_mut().read_to_end(&mut body).map_err(body_error)?;
+        negotiate::deserialize(self.headers(), &body)
+    }
+}
+
+fn write_body(
+    mut response: Response,
+    file: &mut File,
+    options: &SaveOptions,
+    total: Option<u64>,
+) -> Result<u64, Error> {
+    let mut written = 0;
+    for chunk in response.body_mut().chunks() {
+        let chunk = chunk.map_err(body_error)?;
+        written += chunk.len() as u64;
+        if let Some(limit) = options.max_size {
+            if written > limit {
+                return Err(Error::ResponseBodyTooLarge(limit));
+            }
+        }
+        file.write_all(&chunk)?;
+        if let Some(ref callback) = options.progress {
+            callback(written, total);
+        }
+    }
+    file.sync_all()?;
+    Ok(written)
+}
+
+fn body_error(e: io::Error) -> Error {
+    if !matches!(e.get_ref(), Some(inner) if inner.is::<hyper::Error>() || inner.is::<Error>()) {
+        return Error::Io(e);
+    }
+    let inner = e.into_inner().expect("checked above");
+    match inner.downcast::<hyper::Error>() {
+        Ok(e) => Error::from(*e),
+        Err(inner) => *inner.downcast::<Error>().expect("checked above"),
+    }
+}
+
+/// Options for [`ResponseExt::save_to_file_with()`]
+#[derive(Clone, Default)]
+pub struct SaveOptions {
+    progress: Option<ProgressCallback>,
+    max_size: Option<u64>,
+}
+
+impl fmt::Debug for SaveOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SaveOptions")
+            .field("max_size", &self.max_size)
+            .finish()
+    }
+}
+
+impl SaveOptions {
+    pub fn new() -> Self {
+        SaveOptions::default()
+    }
+
+    /// Set a callback that is called after each chunk of the body has been
+    /// written with the number of bytes written so far and the total size of
+    /// the body if known from the `Content-Length` header.
+    pub fn progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u64, Option<u64>) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Set the maximum number of bytes to write. If the body is larger,
+    /// [`Error::ResponseBodyTooLarge`] is returned and the file is removed.
+    /// Default is `None` (no limit).
+    pub fn max_size(mut self, val: Option<u64>) -> Self {
+        self.max_size = val;
+        self
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::*;
+    use crate::blocking::client::tests::test_http_server;
+    use crate::blocking::Client;
+    use crate::connector::HttpConnector;
+    use std::sync::Mutex;
+
+    fn get(resp: &'static str) -> Response {
+        let addr = test_http_server(resp);
+        let client = Client::with_connector(HttpConnector::new());
+        client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .unwrap()
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "simple-hyper-client-blocking-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    const RESPONSE: &str = "HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\nHello, world!";
+
+    #[test]
+    fn save_to_file() {
+        let path = temp_path("save-to-file");
+        fs::write(&path, b"previous contents").unwrap();
+
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let progress2 = progress.clone();
+        let options =
+            SaveOptions::new().progress(move |n, total| progress2.lock().unwrap().push((n, total)));
+        let written = get(RESPONSE).save_to_file_with(&path, options).unwrap();
+        assert_eq!(written, 13);
+        assert_eq!(fs::read(&path).unwrap(), b"Hello, world!");
+        assert_eq!(progress.lock().unwrap().last(), Some(&(13, Some(13))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_to_file_max_size() {
+        let path = temp_path("save-to-file-max-size");
+        let options = SaveOptions::new().max_size(Some(5));
+        let err = get(RESPONSE).save_to_file_with(&path, options).unwrap_err();
+        assert!(matches!(err, Error::ResponseBodyTooLarge(5)));
+        assert!(!path.exists());
+
+        let response = "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nHello, world!";
+        let options = SaveOptions::new().max_size(Some(5));
+        let err = get(response).save_to_file_with(&path, options).unwrap_err();
+        assert!(matches!(err, Error::ResponseBodyTooLarge(5)));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn remote_addr() {
+        let addr = test_http_server("HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n");
+        let client = Client::with_connector(HttpConnector::new());
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .unwrap();
+        assert_eq!(response.remote_addr(), Some(addr));
+        assert_eq!(response.effective_version(), Version::HTTP_11);
+    }
+
+    #[cfg(feature = "graphql")]
+    #[test]
+    fn graphql() {
+        let response = "HTTP/1.1 400 Bad Request\r\nContent-Length: 41\r\n\r\n\
+                        {\"errors\": [{\"message\": \"Syntax Error\"}]}";
+        let response = get(response).graphql::<serde_json::Value>().unwrap();
+        assert!(response.data.is_none());
+        let err = response.into_data().unwrap_err();
+        assert_eq!(err.to_string(), "GraphQL error: Syntax Error");
+    }
+
+    #[cfg(feature = "xml")]
+    #[test]
+    fn xml() {
+        #[derive(Debug, serde::Deserialize)]
+        struct S3Error {
+            #[serde(rename = "Code")]
+            code: String,
+        }
+
+        let response = "HTTP/1.1 404 Not Found\r\nContent-Length: 41\r\n\r\n\
+                        <Error><Code>NoSuchBucket</Code></Error>\n";
+        let error: S3Error = get(response).xml().unwrap();
+        assert_eq!(error.code, "NoSuchBucket");
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack() {
+        // A positive fixint
+        let response = "HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\n*";
+        let value: u8 = get(response).msgpack().unwrap();
+        assert_eq!(value, 42);
+    }
+}
@@ -0,0 +1,188 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::{CacheKey, CacheStore, CachedResponse};
+
+use hyper::body::Bytes;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A [`CacheStore`] keeping responses in files in a directory
+///
+/// Responses survive process restarts, which makes this suitable for caching
+/// large artifacts. Each response is stored in its own file which is written
+/// atomically. I/O errors are treated as cache misses. The size of the
+/// directory is not bounded, but the size of individual responses is limited
+/// with [`DiskCacheStore::set_max_entry_size()`].
+///
+/// A response is held in memory in full while it is being stored and when it
+/// is read back, so the entry size limit also bounds the memory used per
+/// cached response.
+pub struct DiskCacheStore {
+    dir: PathBuf,
+    max_entry_size: Option<usize>,
+    tmp_counter: AtomicU64,
+}
+
+impl DiskCacheStore {
+    /// Create a store keeping responses in `dir`, creating the directory if
+    /// it does not exist.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(DiskCacheStore {
+            dir,
+            max_entry_size: Some(DEFAULT_MAX_ENTRY_SIZE),
+            tmp_counter: AtomicU64::new(0),
+        })
+    }
+
+    /// Set an optional limit on the size of stored responses in bytes.
+    ///
+    /// Larger responses are not stored. Raising the limit (or removing it)
+    /// allows caching larger artifacts at the cost of holding each of them
+    /// in memory while storing and serving it.
+    ///
+    /// Default is 64 MiB.
+    pub fn set_max_entry_size(&mut self, val: Option<usize>) -> &mut Self {
+        self.max_entry_size = val;
+        self
+    }
+
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(format!("{:016x}", fnv1a(&key_bytes(key))))
+    }
+}
+
+const DEFAULT_MAX_ENTRY_SIZE: usize = 64 * 1024 * 1024;
+
+/// Files start with this, followed by the key and the encoded response.
+/// Files written in another format are treated as cache misses.
+const FILE_MAGIC: &[u8] = b"simple-hyper-client cache v2\n";
+
+fn key_bytes(key: &CacheKey) -> Vec<u8> {
+    format!("{} {}\n", key.method(), key.uri()).into_bytes()
+}
+
+/// The 64-bit FNV-1a hash function, which unlike `DefaultHasher` is stable
+/// across Rust releases.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Write the entry piece by piece so the body is not copied.
+async fn write_entry(path: &Path, key: &CacheKey, response: &CachedResponse) -> io::Result<()> {
+    let mut file = fs::File::create(path).await?;
+    file.write_all(FILE_MAGIC).await?;
+    file.write_all(&key_bytes(key)).await?;
+    file.write_all(&response.encode_head()).await?;
+    file.write_all(&response.body_len().to_be_bytes()).await?;
+    file.write_all(response.body()).await?;
+    file.flush().await
+}
+
+impl CacheStore for DiskCacheStore {
+    fn get<'a>(
+        &'a self,
+        key: &'a CacheKey,
+    ) -> Pin<Box<dyn Future<Output = Option<CachedResponse>> + Send + 'a>> {
+        Box::pin(async move {
+            let data = fs::read(self.path(key)).await.ok()?;
+            // The key is stored to detect hash collisions
+            let prefix = [FILE_MAGIC, &key_bytes(key)].concat();
+            if !data.starts_with(&prefix) {
+                return None;
+            }
+            CachedResponse::decode_bytes(Bytes::from(data).slice(prefix.len()..)).ok()
+        })
+    }
+
+    fn put(
+        &self,
+        key: CacheKey,
+        response: CachedResponse,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(async move {
+            let path = self.path(&key);
+            let tmp = path.with_extension(format!(
+                "tmp-{}-{}",
+                std::process::id(),
+                self.tmp_counter.fetch_add(1, Ordering::Relaxed)
+            ));
+            let res = match write_entry(&tmp, &key, &response).await {
+                Ok(()) => fs::rename(&tmp, &path).await,
+                Err(e) => Err(e),
+            };
+            if res.is_err() {
+                let _ = fs::remove_file(&tmp).await;
+            }
+        })
+    }
+
+    fn invalidate<'a>(
+        &'a self,
+        key: &'a CacheKey,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(async move {
+            let _ = fs::remove_file(self.path(key)).await;
+        })
+    }
+
+    fn max_entry_size(&self) -> Option<usize> {
+        self.max_entry_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::Bytes;
+    use hyper::{HeaderMap, Method, StatusCode, Version};
+    use std::time::{Duration, SystemTime};
+
+    #[tokio::test]
+    async fn persistence() {
+        let dir = std::env::temp_dir().join(format!(
+            "simple-hyper-client-disk-cache-{}",
+            std::process::id()
+        ));
+        let key = CacheKey::new(Method::GET, "http://example.com/".parse().unwrap());
+        let response = CachedResponse {
+            status: StatusCode::OK,
+            version: Version::HTTP_11,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(b"hello"),
+            vary: Vec::new(),
+            stored_at: SystemTime::now(),
+            initial_age: Duration::ZERO,
+            lifetime: Duration::from_secs(60),
+        };
+
+        let store = DiskCacheStore::new(&dir).unwrap();
+        assert_eq!(store.max_entry_size(), Some(DEFAULT_MAX_ENTRY_SIZE));
+        assert!(store.get(&key).await.is_none());
+        store.put(key.clone(), response).await;
+        drop(store);
+
+        let store = DiskCacheStore::new(&dir).unwrap();
+        let response = store.get(&key).await.unwrap();
+        assert_eq!(response.body(), "hello");
+        let other = CacheKey::new(Method::GET, "http://example.com/other".parse().unwrap());
+        assert!(store.get(&other).await.is_none());
+
+        store.invalidate(&key).await;
+        assert!(store.get(&key).await.is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use super::{CacheKey, CacheStore, CachedResponse};
+
+use std::collections::HashMap;
+use std::future::{ready, Future};
+use std::pin::Pin;
+use std::sync::Mutex;
+
+/// A bounded in-memory [`CacheStore`]
+///
+/// When the total size of the stored responses exceeds the capacity, the
+/// least recently used responses are evicted.
+pub struct MemoryCacheStore {
+    max_size: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<CacheKey, (CachedResponse, u64)>,
+    size: usize,
+    clock: u64,
+}
+
+impl MemoryCacheStore {
+    /// Create a store holding up to `max_size` bytes of responses, see
+    /// [`CachedResponse::size()`].
+    pub fn new(max_size: usize) -> Self {
+        MemoryCacheStore {
+            max_size,
+            state: Mutex::new(State::default()),
+        }
+    }
+}
+
+impl CacheStore for MemoryCacheStore {
+    fn get<'a>(
+        &'a self,
+        key: &'a CacheKey,
+    ) -> Pin<Box<dyn Future<Output = Option<CachedResponse>> + Send + 'a>> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        let res = state.entries.get_mut(key).map(|(response, last_used)| {
+            *last_used = clock;
+            response.clone()
+        });
+        Box::pin(ready(res))
+    }
+
+    fn put(
+        &self,
+        key: CacheKey,
+        response: CachedResponse,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        let size = response.size();
+        if size > self.max_size {
+            return Box::pin(ready(()));
+        }
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        if let Some((old, _)) = state.entries.insert(key, (response, clock)) {
+            state.size -= old.size();
+        }
+        state.size += size;
+        while state.size > self.max_size {
+            let lru = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone())
+                .expect("store is not empty");
+            let (evicted, _) = state.entries.remove(&lru).expect("entry exists");
+            state.size -= evicted.size();
+        }
+        Box::pin(ready(()))
+    }
+
+    fn invalidate<'a>(
+        &'a self,
+        key: &'a CacheKey,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        let mut state = self.state.lock().unwrap();
+        if let Some((response, _)) = state.entries.remove(key) {
+            state.size -= response.size();
+        }
+        Box::pin(ready(()))
+    }
+
+    fn max_entry_size(&self) -> Option<usize> {
+        Some(self.max_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::Bytes;
+    use hyper::{HeaderMap, Method, StatusCode, Version};
+    use std::time::{Duration, SystemTime};
+
+    fn response(body: &'static str) -> CachedResponse {
+        CachedResponse {
+            status: StatusCode::OK,
+            version: Version::HTTP_11,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(body.as_bytes()),
+            vary: Vec::new(),
+            stored_at: SystemTime::now(),
+            initial_age: Duration::ZERO,
+            lifetime: Duration::from_secs(60),
+        }
+    }
+
+    fn key(uri: &'static str) -> CacheKey {
+        CacheKey::new(Method::GET, uri.parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn eviction() {
+        let store = MemoryCacheStore::new(10);
+        store.put(key("/1"), response("hello")).await;
+        store.put(key("/2"), response("hello")).await;
+        // `/1` is now the most recently used entry
+        assert!(store.get(&key("/1")).await.is_some());
+        store.put(key("/3"), response("hello")).await;
+        assert!(store.get(&key("/1")).await.is_some());
+        assert!(store.get(&key("/2")).await.is_none());
+        assert!(store.get(&key("/3")).await.is_some());
+
+        store.invalidate(&key("/1")).await;
+        assert!(store.get(&key("/1")).await.is_none());
+        store.put(key("/4"), response("hello, world!")).await;
+        assert!(store.get(&key("/4")).await.is_none());
+    }
+}
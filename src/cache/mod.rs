@@ -0,0 +1,801 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A private HTTP cache, see RFC 9111.
+
+mod disk;
+mod memory;
+
+pub use self::disk::DiskCacheStore;
+pub use self::memory::MemoryCacheStore;
+
+use crate::clock::Clock;
+use crate::shared_body::SharedBody;
+use crate::Response;
+
+use headers::{Age, CacheControl, Date, Expires, HeaderMapExt};
+use hyper::body::Bytes;
+use hyper::header::{HeaderName, HeaderValue, AUTHORIZATION, VARY};
+use hyper::{Body, HeaderMap, Method, Request, StatusCode, Uri, Version};
+use tokio_stream::Stream;
+
+use std::convert::{TryFrom, TryInto};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Storage backend for the HTTP cache, see [`ClientBuilder::cache_store()`].
+///
+/// The cache decides which responses to store and whether a stored response
+/// can be used, the store is only responsible for keeping the responses.
+/// Stores are free to drop responses at any time, e.g. to bound their size.
+///
+/// [`ClientBuilder::cache_store()`]: crate::ClientBuilder::cache_store
+pub trait CacheStore: Send + Sync + 'static {
+    /// Returns the response stored for `key`, if any.
+    fn get<'a>(
+        &'a self,
+        key: &'a CacheKey,
+    ) -> Pin<Box<dyn Future<Output = Option<CachedResponse>> + Send + 'a>>;
+
+    /// Store `response` for `key`, replacing any previously stored response.
+    fn put(
+        &self,
+        key: CacheKey,
+        response: CachedResponse,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
+
+    /// Remove the response stored for `key`, if any.
+    fn invalidate<'a>(&'a self, key: &'a CacheKey)
+        -> Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+    /// Returns the maximum size of a response that should be passed to
+    /// [`put()`]. The body of a response is buffered in memory as it is
+    /// read until it exceeds this size, larger responses are not stored.
+    ///
+    /// Default is None (no limit).
+    ///
+    /// [`put()`]: #tymethod.put
+    fn max_entry_size(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// The key responses are stored under in a [`CacheStore`]
+///
+/// Only one response is stored per key. The values of the request headers
+/// nominated by the response's `Vary` header are stored along with the
+/// response, see [`CachedResponse::vary()`], and a stored response is only
+/// used for requests with matching header values.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    method: Method,
+    uri: Uri,
+}
+
+impl CacheKey {
+    /// Create the key for a request with `method` to `uri`.
+    pub fn new(method: Method, uri: Uri) -> Self {
+        CacheKey { method, uri }
+    }
+
+    /// Returns the request method.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the request URI.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+}
+
+/// The request headers nominated by the `Vary` header of a response and
+/// their values
+type Vary = Vec<(HeaderName, Option<HeaderValue>)>;
+
+/// A response stored in a [`CacheStore`]
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    body: Bytes,
+    vary: Vary,
+    stored_at: SystemTime,
+    initial_age: Duration,
+    lifetime: Duration,
+}
+
+impl CachedResponse {
+    /// Returns the status code of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns the headers of the response, without the `Age` header.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the body of the response.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Returns the request headers nominated by the `Vary` header of this
+    /// response and their values in the original request.
+    pub fn vary(&self) -> &[(HeaderName, Option<HeaderValue>)] {
+        &self.vary
+    }
+
+    /// Returns the approximate size of this response in bytes.
+    pub fn size(&self) -> usize {
+        let headers: usize = self
+            .headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum();
+        headers + self.body.len()
+    }
+
+    fn age(&self, now: SystemTime) -> Duration {
+        let resident_time = now.duration_since(self.stored_at).unwrap_or_default();
+        self.initial_age.saturating_add(resident_time)
+    }
+
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| headers.get(name) == value.as_ref())
+    }
+
+    /// Serialize this response, e.g. for storing it in a file.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = self.encode_head();
+        buf.reserve(8 + self.body.len());
+        buf.extend_from_slice(&self.body_len().to_be_bytes());
+        buf.extend_from_slice(&self.body);
+        buf
+    }
+
+    /// Serialize everything but the body, which [`encode()`] appends
+    /// prefixed with its length as returned by [`body_len()`].
+    ///
+    /// [`encode()`]: CachedResponse::encode
+    /// [`body_len()`]: CachedResponse::body_len
+    pub(crate) fn encode_head(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.size() - self.body.len() + 64);
+        let version: u8 = match self.version {
+            Version::HTTP_09 => 0,
+            Version::HTTP_10 => 1,
+            Version::HTTP_11 => 2,
+            Version::HTTP_2 => 3,
+            _ => 4,
+        };
+        buf.push(version);
+        buf.extend_from_slice(&self.status.as_u16().to_be_bytes());
+        let stored_at = self
+            .stored_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        for duration in [stored_at, self.initial_age, self.lifetime] {
+            buf.extend_from_slice(&(duration.as_millis() as u64).to_be_bytes());
+        }
+        put_u32(&mut buf, self.headers.len());
+        for (name, value) in &self.headers {
+            put_bytes(&mut buf, name.as_str().as_bytes());
+            put_bytes(&mut buf, value.as_bytes());
+        }
+        put_u32(&mut buf, self.vary.len());
+        for (name, value) in &self.vary {
+            put_bytes(&mut buf, name.as_str().as_bytes());
+            match value {
+                Some(value) => {
+                    buf.push(1);
+                    put_bytes(&mut buf, value.as_bytes());
+                }
+                None => buf.push(0),
+            }
+        }
+        buf
+    }
+
+    pub(crate) fn body_len(&self) -> u64 {
+        self.body.len() as u64
+    }
+
+    /// Deserialize a response serialized with [`CachedResponse::encode()`].
+    pub fn decode(data: &[u8]) -> io::Result<Self> {
+        Self::decode_bytes(Bytes::copy_from_slice(data))
+    }
+
+    /// Like [`decode()`], but the body of the returned response shares
+    /// `data` instead of copying it.
+    ///
+    /// [`decode()`]: CachedResponse::decode
+    pub(crate) fn decode_bytes(data: Bytes) -> io::Result<Self> {
+        let mut reader = Reader(&data);
+        let version = match reader.take(1)?[0] {
+            0 => Version::HTTP_09,
+            1 => Version::HTTP_10,
+            2 => Version::HTTP_11,
+            3 => Version::HTTP_2,
+            _ => Version::HTTP_3,
+        };
+        let status = u16::from_be_bytes(reader.array()?);
+        let status = StatusCode::from_u16(status).map_err(invalid_data)?;
+        let mut durations = [Duration::ZERO; 3];
+        for duration in durations.iter_mut() {
+            *duration = Duration::from_millis(u64::from_be_bytes(reader.array()?));
+        }
+        let [stored_at, initial_age, lifetime] = durations;
+        let mut headers = HeaderMap::new();
+        for _ in 0..reader.u32()? {
+            let name = HeaderName::from_bytes(reader.bytes()?).map_err(invalid_data)?;
+            let value = HeaderValue::from_bytes(reader.bytes()?).map_err(invalid_data)?;
+            headers.append(name, value);
+        }
+        let mut vary = Vec::new();
+        for _ in 0..reader.u32()? {
+            let name = HeaderName::from_bytes(reader.bytes()?).map_err(invalid_data)?;
+            let value = match reader.take(1)?[0] {
+                0 => None,
+                _ => Some(HeaderValue::from_bytes(reader.bytes()?).map_err(invalid_data)?),
+            };
+            vary.push((name, value));
+        }
+        let body_len = usize::try_from(u64::from_be_bytes(reader.array()?))
+            .map_err(|_| invalid_data("cache entry too large"))?;
+        let body = reader.take(body_len)?;
+        let start = data.len() - reader.0.len() - body.len();
+        let body = data.slice(start..start + body_len);
+        Ok(CachedResponse {
+            status,
+            version,
+            headers,
+            body,
+            vary,
+            stored_at: UNIX_EPOCH + stored_at,
+            initial_age,
+            lifetime,
+        })
+    }
+}
+
+/// Used for header counts and lengths, which hyper's header size limits keep
+/// far below 4 GiB. Bodies use a 64-bit length.
+fn put_u32(buf: &mut Vec<u8>, val: usize) {
+    let val = u32::try_from(val).expect("length fits in u32");
+    buf.extend_from_slice(&val.to_be_bytes());
+}
+
+fn put_bytes(buf: &mut Vec<u8>, data: &[u8]) {
+    put_u32(buf, data.len());
+    buf.extend_from_slice(data);
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < n {
+            return Err(invalid_data("truncated cache entry"));
+        }
+        let (data, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(data)
+    }
+
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("correct length"))
+    }
+
+    fn u32(&mut self) -> io::Result<usize> {
+        Ok(u32::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn bytes(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.u32()?;
+        self.take(len)
+    }
+}
+
+/// The HTTP cache, storing responses in a [`CacheStore`].
+///
+/// Only responses to `GET` requests with explicit freshness information
+/// (`Cache-Control: max-age` or `Expires`) are stored. Stale responses are
+/// not revalidated, they are simply replaced by the next response.
+#[derive(Clone)]
+pub(crate) struct Cache {
+    store: Arc<dyn CacheStore>,
+    clock: Arc<dyn Clock>,
+}
+
+/// The parts of a request the cache needs after the request has been sent.
+pub(crate) struct CacheRequest {
+    key: CacheKey,
+    headers: HeaderMap,
+    sent_at: SystemTime,
+}
+
+impl CacheRequest {
+    fn new(request: &Request<SharedBody>, sent_at: SystemTime) -> Self {
+        CacheRequest {
+            key: CacheKey::new(request.method().clone(), request.uri().clone()),
+            headers: match *request.method() {
+                Method::GET => request.headers().clone(),
+                _ => HeaderMap::new(),
+            },
+            sent_at,
+        }
+    }
+}
+
+impl Cache {
+    pub(crate) fn new(store: Arc<dyn CacheStore>, clock: Arc<dyn Clock>) -> Self {
+        Cache { store, clock }
+    }
+
+    /// Returns the parts of `request` needed by [`lookup()`] and
+    /// [`update()`].
+    ///
+    /// [`lookup()`]: Cache::lookup
+    /// [`update()`]: Cache::update
+    pub(crate) fn request(&self, request: &Request<SharedBody>) -> CacheRequest {
+        CacheRequest::new(request, self.clock.now())
+    }
+
+    /// Returns a fresh stored response for `request`, if any.
+    pub(crate) async fn lookup(&self, request: &CacheRequest) -> Option<Response> {
+        if *request.key.method() != Method::GET {
+            return None;
+        }
+        let cache_control = request.headers.typed_get::<CacheControl>();
+        if let Some(ref cc) = cache_control {
+            if cc.no_store() || cc.no_cache() {
+                return None;
+            }
+        }
+        let entry = self.store.get(&request.key).await?;
+        if !entry.matches(&request.headers) {
+            return None;
+        }
+        let age = entry.age(self.clock.now());
+        if age >= entry.lifetime {
+            return None;
+        }
+        if let Some(max_age) = cache_control.and_then(|cc| cc.max_age()) {
+            if age > max_age {
+                return None;
+            }
+        }
+
+        let mut response = hyper::Response::new(Body::from(entry.body));
+        *response.status_mut() = entry.status;
+        *response.version_mut() = entry.version;
+        *response.headers_mut() = entry.headers;
+        response
+            .headers_mut()
+            .typed_insert(Age::from_secs(age.as_secs()));
+        Some(response)
+    }
+
+    /// Update the cache with `response` and return it.
+    ///
+    /// A storable response is stored once its body has been read to the
+    /// end. The body is buffered as it is read, up to the maximum entry size
+    /// of the store; if it is larger, not read to the end or fails to be
+    /// read, the response is not stored.
+    pub(crate) async fn update(&self, request: CacheRequest, response: Response) -> Response {
+        let status = response.status();
+        let method = request.key.method();
+        if !is_safe(method) && (status.is_success() || status.is_redirection()) {
+            // See RFC 9111 section 4.4
+            let key = CacheKey::new(Method::GET, request.key.uri().clone());
+            self.store.invalidate(&key).await;
+            return response;
+        }
+        if *method != Method::GET {
+            return response;
+        }
+        if let Some(cc) = request.headers.typed_get::<CacheControl>() {
+            if cc.no_store() {
+                return response;
+            }
+        }
+        // Responses to authenticated requests are not stored, see RFC 9111
+        // section 3.5. The store may be shared between clients or persisted.
+        if request.headers.contains_key(AUTHORIZATION) {
+            return response;
+        }
+        let (lifetime, vary) = match storable(&request, &response) {
+            Some(res) => res,
+            None => return response,
+        };
+        let (parts, body) = response.into_parts();
+        let mut headers = parts.headers.clone();
+        headers.remove(hyper::header::AGE);
+        let now = self.clock.now();
+        let entry = CachedResponse {
+            status: parts.status,
+            version: parts.version,
+            headers,
+            body: Bytes::new(),
+            vary,
+            stored_at: now,
+            initial_age: initial_age(&request, &parts.headers, now),
+            lifetime,
+        };
+        let limit = self.store.max_entry_size().unwrap_or(usize::MAX);
+        if entry.size() > limit {
+            return Response::from_parts(parts, body);
+        }
+        let body = Body::wrap_stream(StoringBody {
+            inner: body,
+            pending: Some(PendingEntry {
+                store: self.store.clone(),
+                key: request.key,
+                size: entry.size(),
+                entry,
+                chunks: Vec::new(),
+                limit,
+            }),
+            storing: None,
+        });
+        Response::from_parts(parts, body)
+    }
+}
+
+/// A response body that stores the response once it has been read to the
+/// end, see [`Cache::update()`]
+struct StoringBody {
+    inner: Body,
+    pending: Option<PendingEntry>,
+    storing: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+struct PendingEntry {
+    store: Arc<dyn CacheStore>,
+    key: CacheKey,
+    /// The response to store, without its body
+    entry: CachedResponse,
+    chunks: Vec<Bytes>,
+    /// The size of the entry including the chunks read so far
+    size: usize,
+    limit: usize,
+}
+
+impl PendingEntry {
+    /// Returns false if the entry has become too large to be stored.
+    fn push(&mut self, chunk: &Bytes) -> bool {
+        self.chunks.push(chunk.clone());
+        self.size += chunk.len();
+        self.size <= self.limit
+    }
+
+    fn store(mut self) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        self.entry.body = self.chunks.concat().into();
+        Box::pin(async move { self.store.put(self.key, self.entry).await })
+    }
+}
+
+impl Stream for StoringBody {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(ref mut storing) = this.storing {
+            ready!(storing.as_mut().poll(cx));
+            this.storing = None;
+            return Poll::Ready(None);
+        }
+        let res = ready!(Pin::new(&mut this.inner).poll_next(cx));
+        match res {
+            Some(Ok(ref chunk)) => {
+                if let Some(ref mut pending) = this.pending {
+                    if !pending.push(chunk) {
+                        this.pending = None;
+                    }
+                }
+            }
+            Some(Err(_)) => this.pending = None,
+            None => {
+                // Store the response before signalling the end of the body
+                if let Some(pending) = this.pending.take() {
+                    this.storing = Some(pending.store());
+                    return Pin::new(this).poll_next(cx);
+                }
+            }
+        }
+        Poll::Ready(res)
+    }
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    )
+}
+
+/// Returns the freshness lifetime of `response` and the request headers
+/// nominated by its `Vary` header if it can be stored, see RFC 9111
+/// section 3.
+fn storable(request: &CacheRequest, response: &Response) -> Option<(Duration, Vary)> {
+    // Status codes that are heuristically cacheable, see RFC 9110 section 15.1
+    match response.status().as_u16() {
+        200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501 => {}
+        _ => return None,
+    }
+    let headers = response.headers();
+    let cache_control = headers.typed_get::<CacheControl>();
+    if let Some(ref cc) = cache_control {
+        // We don't revalidate stored responses, so there is no point in
+        // storing responses that must be revalidated before use.
+        if cc.no_store() || cc.no_cache() {
+            return None;
+        }
+    }
+    let lifetime = match cache_control.and_then(|cc| cc.max_age()) {
+        Some(max_age) => max_age,
+        None => {
+            let expires = SystemTime::from(headers.typed_get::<Expires>()?);
+            let date = headers
+                .typed_get::<Date>()
+                .map_or(request.sent_at, SystemTime::from);
+            expires.duration_since(date).unwrap_or_default()
+        }
+    };
+    if lifetime.is_zero() {
+        return None;
+    }
+
+    let mut vary = Vec::new();
+    for value in headers.get_all(VARY) {
+        for name in value.to_str().ok()?.split(',') {
+            let name = name.trim();
+            if name == "*" {
+                return None;
+            }
+            let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+            let value = request.headers.get(&name).cloned();
+            vary.push((name, value));
+        }
+    }
+    Some((lifetime, vary))
+}
+
+/// Returns the age of a response when it is received, see RFC 9111
+/// section 4.2.3.
+fn initial_age(request: &CacheRequest, headers: &HeaderMap, now: SystemTime) -> Duration {
+    let apparent_age = headers
+        .typed_get::<Date>()
+        .and_then(|date| now.duration_since(SystemTime::from(date)).ok())
+        .unwrap_or_default();
+    let response_delay = now.duration_since(request.sent_at).unwrap_or_default();
+    let age_value = headers
+        .typed_get::<Age>()
+        .map_or(Duration::ZERO, |age| Duration::from_secs(age.as_secs()));
+    apparent_age.max(age_value.saturating_add(response_delay))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{SystemClock, TokioClock};
+    use hyper::body::to_bytes;
+
+    fn request(uri: &str, headers: &[(&str, &str)]) -> Request<SharedBody> {
+        let mut builder = Request::get(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(SharedBody::empty()).unwrap()
+    }
+
+    fn response(headers: &[(&str, &str)], body: &'static str) -> Response {
+        let mut builder = hyper::Response::builder();
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(Body::from(body)).unwrap()
+    }
+
+    async fn store(cache: &Cache, req: &Request<SharedBody>, resp: Response) {
+        let resp = cache.update(cache.request(req), resp).await;
+        to_bytes(resp.into_body()).await.unwrap();
+    }
+
+    async fn lookup(cache: &Cache, req: &Request<SharedBody>) -> Option<Response> {
+        cache.lookup(&cache.request(req)).await
+    }
+
+    fn memory_cache(max_size: usize) -> Cache {
+        Cache::new(
+            Arc::new(MemoryCacheStore::new(max_size)),
+            Arc::new(SystemClock),
+        )
+    }
+
+    #[tokio::test]
+    async fn fresh_response_is_served() {
+        let cache = memory_cache(1024);
+        let req = request("http://example.com/", &[]);
+        assert!(lookup(&cache, &req).await.is_none());
+        let resp = response(&[("cache-control", "max-age=60")], "hello");
+        store(&cache, &req, resp).await;
+
+        let resp = lookup(&cache, &req).await.unwrap();
+        assert_eq!(resp.headers()["age"], "0");
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "hello");
+
+        let req = request("http://example.com/", &[("cache-control", "no-cache")]);
+        assert!(lookup(&cache, &req).await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expiry() {
+        let cache = Cache::new(
+            Arc::new(MemoryCacheStore::new(1024)),
+            Arc::new(TokioClock::new()),
+        );
+        let req = request("http://example.com/", &[]);
+        let resp = response(&[("cache-control", "max-age=60")], "hello");
+        store(&cache, &req, resp).await;
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        let resp = lookup(&cache, &req).await.unwrap();
+        assert_eq!(resp.headers()["age"], "30");
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(lookup(&cache, &req).await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn huge_age() {
+        let cache = Cache::new(
+            Arc::new(MemoryCacheStore::new(1024)),
+            Arc::new(TokioClock::new()),
+        );
+        let req = request("http://example.com/", &[]);
+        let headers = [
+            ("cache-control", "max-age=60"),
+            ("age", "18446744073709551615"),
+        ];
+        let cache_req = cache.request(&req);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let resp = cache.update(cache_req, response(&headers, "hello")).await;
+        to_bytes(resp.into_body()).await.unwrap();
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(lookup(&cache, &req).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn uncacheable_responses() {
+        let cache = memory_cache(1024);
+        let req = request("http://example.com/", &[]);
+        for headers in [
+            &[][..],
+            &[("cache-control", "no-store, max-age=60")][..],
+            &[("cache-control", "no-cache, max-age=60")][..],
+            &[("cache-control", "max-age=0")][..],
+            &[("cache-control", "max-age=60"), ("vary", "*")][..],
+        ] {
+            store(&cache, &req, response(headers, "hello")).await;
+            assert!(lookup(&cache, &req).await.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn vary() {
+        let cache = memory_cache(1024);
+        let req = request("http://example.com/", &[("accept", "text/plain")]);
+        let resp = response(&[("cache-control", "max-age=60"), ("vary", "Accept")], "hi");
+        store(&cache, &req, resp).await;
+
+        assert!(lookup(&cache, &req).await.is_some());
+        let req = request("http://example.com/", &[("accept", "text/html")]);
+        assert!(lookup(&cache, &req).await.is_none());
+        let req = request("http://example.com/", &[]);
+        assert!(lookup(&cache, &req).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn invalidation() {
+        let cache = memory_cache(1024);
+        let req = request("http://example.com/", &[]);
+        store(
+            &cache,
+            &req,
+            response(&[("cache-control", "max-age=60")], "hello"),
+        )
+        .await;
+        assert!(lookup(&cache, &req).await.is_some());
+
+        let post = Request::post("http://example.com/")
+            .body(SharedBody::empty())
+            .unwrap();
+        cache.update(cache.request(&post), response(&[], "")).await;
+        assert!(lookup(&cache, &req).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn stored_when_body_is_read() {
+        let cache = memory_cache(1024);
+        let req = request("http://example.com/", &[]);
+        let resp = response(&[("cache-control", "max-age=60")], "hello");
+        let resp = cache.update(cache.request(&req), resp).await;
+        assert!(lookup(&cache, &req).await.is_none());
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "hello");
+        assert!(lookup(&cache, &req).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn authorized_request_is_not_stored() {
+        let cache = memory_cache(1024);
+        let req = request("http://example.com/", &[("authorization", "Bearer x")]);
+        let resp = response(&[("cache-control", "max-age=60")], "hello");
+        store(&cache, &req, resp).await;
+        assert!(lookup(&cache, &req).await.is_none());
+        assert!(lookup(&cache, &request("http://example.com/", &[]))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn large_body_is_not_stored() {
+        let cache = memory_cache(4);
+        let req = request("http://example.com/", &[]);
+        let resp = response(&[("cache-control", "max-age=60")], "hello");
+        let resp = cache.update(cache.request(&req), resp).await;
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "hello");
+        assert!(lookup(&cache, &req).await.is_none());
+    }
+
+    #[test]
+    fn encode_decode() {
+        let mut headers = HeaderMap::new();
+        headers.insert("cache-control", HeaderValue::from_static("max-age=60"));
+        headers.append("x-test", HeaderValue::from_static("a"));
+        headers.append("x-test", HeaderValue::from_static("b"));
+        let response = CachedResponse {
+            status: StatusCode::NOT_FOUND,
+            version: Version::HTTP_11,
+            headers,
+            body: Bytes::from_static(b"hello"),
+            vary: vec![
+                (HeaderName::from_static("accept"), None),
+                (
+                    HeaderName::from_static("accept-language"),
+                    Some(HeaderValue::from_static("en")),
+                ),
+            ],
+            stored_at: UNIX_EPOCH + Duration::from_millis(1_600_000_000_123),
+            initial_age: Duration::from_secs(3),
+            lifetime: Duration::from_secs(60),
+        };
+        let encoded = response.encode();
+        let decoded = CachedResponse::decode(&encoded).unwrap();
+        assert_eq!(decoded.status, response.status);
+        assert_eq!(decoded.version, response.version);
+        assert_eq!(decoded.headers, response.headers);
+        assert_eq!(decoded.body, response.body);
+        assert_eq!(decoded.vary, response.vary);
+        assert_eq!(decoded.stored_at, response.stored_at);
+        assert_eq!(decoded.initial_age, response.initial_age);
+        assert_eq!(decoded.lifetime, response.lifetime);
+
+        assert!(CachedResponse::decode(&encoded[..encoded.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::shared_body::SharedBody;
+use crate::Response;
+
+use hyper::body::Bytes;
+use hyper::{Body, Request};
+use tokio_stream::Stream;
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// The beginning of the request and response bodies of an exchange, see
+/// [`ClientBuilder::capture_bodies()`]
+///
+/// This is stored in the response extensions. The response body is captured
+/// as it is read, so clone this out of the extensions before consuming the
+/// response:
+///
+/// ```ignore
+/// let captured = response.extensions().get::<CapturedBodies>().cloned();
+/// if let Err(e) = serde_json::from_slice::<T>(&to_bytes(response.into_body()).await?) {
+///     log::warn!("invalid response {:?}: {}", captured.map(|c| c.response_body()), e);
+/// }
+/// ```
+///
+/// [`ClientBuilder::capture_bodies()`]: crate::ClientBuilder::capture_bodies
+#[derive(Clone, Debug)]
+pub struct CapturedBodies {
+    request: Option<Bytes>,
+    response: Arc<Mutex<Vec<u8>>>,
+    limit: usize,
+}
+
+impl CapturedBodies {
+    /// Capture the beginning of the body of `request`.
+    pub(crate) fn new(request: &Request<SharedBody>, limit: usize) -> Self {
+        let request = request.body().buffers().map(|buffers| {
+            let mut captured = Vec::new();
+            for buf in buffers {
+                let n = buf.len().min(limit - captured.len());
+                captured.extend_from_slice(&buf[..n]);
+            }
+            Bytes::from(captured)
+        });
+        CapturedBodies {
+            request,
+            response: Arc::new(Mutex::new(Vec::new())),
+            limit,
+        }
+    }
+
+    /// Store `self` in the extensions of `response` and capture its body as
+    /// it is read.
+    pub(crate) fn attach(self, response: Response) -> Response {
+        let (mut parts, body) = response.into_parts();
+        let body = Body::wrap_stream(CapturingBody {
+            inner: body,
+            captured: self.clone(),
+        });
+        parts.extensions.insert(self);
+        Response::from_parts(parts, body)
+    }
+
+    /// Returns up to the configured number of bytes of the request body, or
+    /// `None` if the request body was streaming.
+    pub fn request_body(&self) -> Option<Bytes> {
+        self.request.clone()
+    }
+
+    /// Returns up to the configured number of bytes of the response body
+    /// that have been read so far.
+    pub fn response_body(&self) -> Bytes {
+        Bytes::from(self.response.lock().unwrap().clone())
+    }
+}
+
+struct CapturingBody {
+    inner: Body,
+    captured: CapturedBodies,
+}
+
+impl Stream for CapturingBody {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_next(cx);
+        if let Poll::Ready(Some(Ok(ref chunk))) = res {
+            let mut captured = this.captured.response.lock().unwrap();
+            let n = chunk.len().min(this.captured.limit - captured.len());
+            captured.extend_from_slice(&chunk[..n]);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::to_bytes;
+
+    #[tokio::test]
+    async fn capture() {
+        let request = Request::post("/")
+            .body(SharedBody::from_chunks(vec!["hello", ", world!"]))
+            .unwrap();
+        let captured = CapturedBodies::new(&request, 8);
+        assert_eq!(captured.request_body().unwrap(), "hello, w");
+
+        let response = captured.attach(Response::new(Body::from("0123456789")));
+        let captured = response
+            .extensions()
+            .get::<CapturedBodies>()
+            .cloned()
+            .unwrap();
+        assert_eq!(captured.response_body(), "");
+        assert_eq!(to_bytes(response.into_body()).await.unwrap(), "0123456789");
+        assert_eq!(captured.response_body(), "01234567");
+
+        let request = Request::post("/")
+            .body(SharedBody::from_reader(&b"hello"[..], None))
+            .unwrap();
+        assert!(CapturedBodies::new(&request, 8).request_body().is_none());
+    }
+}
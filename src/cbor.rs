@@ -0,0 +1,104 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+use crate::shared_body::SharedBody;
+
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Set the body to the CBOR encoding of `value` and the `Content-Type`
+/// header to `application/cbor`, see RFC 8949.
+pub(crate) fn set_request<T: Serialize + ?Sized>(
+    headers: &mut HeaderMap,
+    body: &mut Option<SharedBody>,
+    value: &T,
+) -> Result<(), Error> {
+    let mut buf = Vec::new();
+    ciborium::into_writer(value, &mut buf).map_err(Error::CborSerialize)?;
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/cbor"));
+    *body = Some(SharedBody::from(buf));
+    Ok(())
+}
+
+/// Deserialize a CBOR response body.
+pub(crate) fn parse_response<T: DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+    ciborium::from_reader(body).map_err(Error::CborDeserialize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::to_bytes;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: String,
+        value: f32,
+    }
+
+    #[tokio::test]
+    async fn request_body() {
+        let mut headers = HeaderMap::new();
+        let mut body = None;
+        let reading = Reading {
+            sensor: "t1".to_owned(),
+            value: 1.5,
+        };
+        set_request(&mut headers, &mut body, &reading).unwrap();
+        assert_eq!(headers[CONTENT_TYPE], "application/cbor");
+        let body = to_bytes(body.unwrap()).await.unwrap();
+        // {"sensor": "t1", "value": 1.5}, with the float in half precision
+        assert_eq!(&body[..], b"\xa2\x66sensor\x62t1\x65value\xf9\x3e\x00");
+        assert_eq!(parse_response::<Reading>(&body).unwrap(), reading);
+
+        let err = parse_response::<Reading>(&body[..5]).unwrap_err();
+        assert!(matches!(err, Error::CborDeserialize(_)));
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn cbor_request() {
+        use crate::async_client::tests::read_request;
+        use crate::{Client, HttpConnector, ResponseExt};
+        use tokio::io::AsyncWriteExt;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // Echo the request body
+            let input = read_request(&mut stream).await;
+            let pos = input.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+            let body = &input[pos + 4..];
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/cbor\r\n\
+                 Content-Length: {}\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(body).await.unwrap();
+        });
+
+        let client = Client::with_connector(HttpConnector::new());
+        let reading = Reading {
+            sensor: "t1".to_owned(),
+            value: -40.0,
+        };
+        let response = client
+            .post(format!("http://{}/readings", addr))
+            .unwrap()
+            .cbor(&reading)
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.cbor::<Reading>().await.unwrap(), reading);
+    }
+}
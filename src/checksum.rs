@@ -0,0 +1,286 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+use crate::shared_body::SharedBody;
+use crate::Response;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Body, Method, StatusCode};
+use md5::{Digest, Md5};
+use sha2::Sha256;
+use tokio_stream::Stream;
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The `Content-MD5` header, see RFC 1864.
+pub const CONTENT_MD5: HeaderName = HeaderName::from_static("content-md5");
+
+/// The `Content-Digest` header, see RFC 9530.
+pub const CONTENT_DIGEST: HeaderName = HeaderName::from_static("content-digest");
+
+/// Checksum algorithms supported for request and response bodies, see
+/// [`RequestBuilder::checksum()`].
+///
+/// [`RequestBuilder::checksum()`]: crate::RequestBuilder::checksum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    /// MD5, sent in the `Content-MD5` header.
+    Md5,
+    /// SHA-256, sent in the `Content-Digest` header as `sha-256`.
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    /// Returns the name of the header the checksum is sent in by default.
+    pub fn header_name(&self) -> HeaderName {
+        match self {
+            ChecksumAlgorithm::Md5 => CONTENT_MD5,
+            ChecksumAlgorithm::Sha256 => CONTENT_DIGEST,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "MD5",
+            ChecksumAlgorithm::Sha256 => "SHA-256",
+        }
+    }
+
+    fn hasher(&self) -> Hasher {
+        match self {
+            ChecksumAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+            ChecksumAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    /// Returns the header value for a checksum computed with this algorithm.
+    fn header_value(&self, checksum: &[u8]) -> HeaderValue {
+        let encoded = BASE64.encode(checksum);
+        let value = match self {
+            ChecksumAlgorithm::Md5 => encoded,
+            ChecksumAlgorithm::Sha256 => format!("sha-256=:{}:", encoded),
+        };
+        HeaderValue::from_str(&value).expect("base64 is a valid header value")
+    }
+}
+
+enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Md5(h) => h.finalize().to_vec(),
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+        }
+    }
+}
+
+/// Compute the checksum of `body` and return the header to send it in.
+///
+/// Returns an error for streaming bodies since their checksum is not known
+/// before they are sent.
+pub(crate) fn request_checksum(
+    body: &SharedBody,
+    algorithm: ChecksumAlgorithm,
+) -> Result<(HeaderName, HeaderValue), Error> {
+    let buffers = body.buffers().ok_or_else(|| {
+        Error::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot compute the checksum of a streaming body",
+        ))
+    })?;
+    let mut hasher = algorithm.hasher();
+    for buf in buffers {
+        hasher.update(&buf);
+    }
+    let value = algorithm.header_value(&hasher.finalize());
+    Ok((algorithm.header_name(), value))
+}
+
+/// Returns the expected checksum of the response body according to
+/// `Content-Digest` or `Content-MD5`, preferring the former.
+fn expected_checksum(headers: &HeaderMap) -> Option<(ChecksumAlgorithm, Vec<u8>)> {
+    let digests = headers.get_all(CONTENT_DIGEST).iter();
+    for value in digests.filter_map(|value| value.to_str().ok()) {
+        for digest in value.split(',') {
+            let (name, value) = match digest.trim().split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            if !name.trim().eq_ignore_ascii_case("sha-256") {
+                continue;
+            }
+            let value = value.trim().trim_matches(':');
+            if let Ok(checksum) = BASE64.decode(value) {
+                return Some((ChecksumAlgorithm::Sha256, checksum));
+            }
+        }
+    }
+    let value = headers.get(CONTENT_MD5)?;
+    let checksum = BASE64.decode(value.as_bytes()).ok()?;
+    Some((ChecksumAlgorithm::Md5, checksum))
+}
+
+/// Wraps the body of `response` so that reading it fails with
+/// [`Error::ChecksumMismatch`] if it does not match the checksum declared in
+/// the response headers. Responses without a supported checksum header are
+/// returned unchanged.
+///
+/// Responses without a body are not verified, since their checksum header
+/// describes the representation that would have been sent, e.g. in
+/// response to a `HEAD` request or in a `304 Not Modified` response.
+pub(crate) fn verify_response_checksum(method: &Method, response: Response) -> Response {
+    let status = response.status();
+    if *method == Method::HEAD
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+        || response.body().is_end_stream()
+    {
+        return response;
+    }
+    let (algorithm, expected) = match expected_checksum(response.headers()) {
+        Some(expected) => expected,
+        None => return response,
+    };
+    let (parts, body) = response.into_parts();
+    let body = Body::wrap_stream(ChecksumVerifiedBody {
+        inner: body,
+        algorithm,
+        hasher: Some(algorithm.hasher()),
+        expected,
+    });
+    Response::from_parts(parts, body)
+}
+
+struct ChecksumVerifiedBody {
+    inner: Body,
+    algorithm: ChecksumAlgorithm,
+    hasher: Option<Hasher>,
+    expected: Vec<u8>,
+}
+
+impl Stream for ChecksumVerifiedBody {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(hasher) = this.hasher.as_mut() {
+                    hasher.update(&chunk);
+                }
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e.into()))),
+            Poll::Ready(None) => match this.hasher.take().map(Hasher::finalize) {
+                Some(checksum) if checksum != this.expected => {
+                    Poll::Ready(Some(Err(Error::ChecksumMismatch(this.algorithm.name()))))
+                }
+                _ => Poll::Ready(None),
+            },
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::to_bytes;
+    use std::error::Error as StdError;
+
+    fn response(name: HeaderName, value: &str, body: &'static str) -> Response {
+        hyper::Response::builder()
+            .header(name, value)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[test]
+    fn request_checksums() {
+        let body = SharedBody::from_chunks(vec!["hello, ", "world!"]);
+        let (name, value) = request_checksum(&body, ChecksumAlgorithm::Md5).unwrap();
+        assert_eq!(name, CONTENT_MD5);
+        assert_eq!(value, "Otu60XkfuuPskIiUxJY4cA==");
+
+        let (name, value) = request_checksum(&body, ChecksumAlgorithm::Sha256).unwrap();
+        assert_eq!(name, CONTENT_DIGEST);
+        assert_eq!(
+            value,
+            "sha-256=:aOZWslHmfoNYvvhIOrDVHGYZ8+ehqfDnWDjUH/No9yg=:"
+        );
+
+        let body = SharedBody::from_reader(&b"hello, world!"[..], None);
+        assert!(request_checksum(&body, ChecksumAlgorithm::Md5).is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_checksums() {
+        let resp = response(CONTENT_MD5, "Otu60XkfuuPskIiUxJY4cA==", "hello, world!");
+        let resp = verify_response_checksum(&Method::GET, resp);
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "hello, world!");
+
+        let resp = response(
+            CONTENT_DIGEST,
+            "sha-512=:AAAA:, sha-256=:aOZWslHmfoNYvvhIOrDVHGYZ8+ehqfDnWDjUH/No9yg=:",
+            "hello, world!",
+        );
+        let resp = verify_response_checksum(&Method::GET, resp);
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "hello, world!");
+
+        let resp = response(CONTENT_MD5, "Otu60XkfuuPskIiUxJY4cA==", "hello, world?");
+        let resp = verify_response_checksum(&Method::GET, resp);
+        let err = to_bytes(resp.into_body()).await.unwrap_err();
+        assert!(matches!(
+            err.source().unwrap().downcast_ref::<Error>(),
+            Some(Error::ChecksumMismatch("MD5"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn responses_without_body_are_not_verified() {
+        // The length of streaming bodies is not known in advance
+        let empty_stream = || {
+            let chunks: Vec<Result<Bytes, io::Error>> = Vec::new();
+            Body::wrap_stream(tokio_stream::iter(chunks))
+        };
+        let checksum = "Otu60XkfuuPskIiUxJY4cA==";
+        let resp = response(CONTENT_MD5, checksum, "").map(|_| empty_stream());
+        let resp = verify_response_checksum(&Method::HEAD, resp);
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "");
+
+        for status in [StatusCode::NO_CONTENT, StatusCode::NOT_MODIFIED] {
+            let mut resp = response(CONTENT_MD5, checksum, "").map(|_| empty_stream());
+            *resp.status_mut() = status;
+            let resp = verify_response_checksum(&Method::GET, resp);
+            assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "");
+        }
+
+        let resp = response(CONTENT_MD5, checksum, "").map(|_| Body::empty());
+        let resp = verify_response_checksum(&Method::GET, resp);
+        assert_eq!(to_bytes(resp.into_body()).await.unwrap(), "");
+
+        let resp = response(CONTENT_MD5, checksum, "").map(|_| empty_stream());
+        let resp = verify_response_checksum(&Method::GET, resp);
+        assert!(to_bytes(resp.into_body()).await.is_err());
+    }
+}
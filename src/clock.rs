@@ -0,0 +1,60 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use tokio::time::Instant;
+
+use std::time::SystemTime;
+
+/// Source of the current time for cache freshness and cookie expiry, see
+/// [`ClientBuilder::clock()`]
+///
+/// [`ClientBuilder::clock()`]: crate::ClientBuilder::clock
+pub trait Clock: Send + Sync + 'static {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// A [`Clock`] returning the system time
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A [`Clock`] that follows the time of the tokio runtime
+///
+/// It starts at the system time when it is created and advances with
+/// `tokio::time::Instant`, so it can be paused and advanced in tests with
+/// `tokio::time::pause()` and `tokio::time::advance()`.
+#[derive(Clone, Copy, Debug)]
+pub struct TokioClock {
+    start: SystemTime,
+    start_instant: Instant,
+}
+
+impl TokioClock {
+    pub fn new() -> Self {
+        TokioClock {
+            start: SystemTime::now(),
+            start_instant: Instant::now(),
+        }
+    }
+}
+
+impl Default for TokioClock {
+    fn default() -> Self {
+        TokioClock::new()
+    }
+}
+
+impl Clock for TokioClock {
+    fn now(&self) -> SystemTime {
+        self.start + self.start_instant.elapsed()
+    }
+}
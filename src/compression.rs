@@ -0,0 +1,833 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+use crate::shared_body::SharedBody;
+use crate::Response;
+
+#[cfg(feature = "brotli")]
+use brotli_crate::{
+    BrotliDecompressStream, BrotliResult, BrotliState, CompressorWriter, HeapAlloc, HuffmanCode,
+};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::{Compression, Crc, Decompress, FlushDecompress, Status};
+use hyper::body::{Buf, Bytes, HttpBody};
+use hyper::header::{HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH};
+use hyper::Body;
+use tokio_stream::Stream;
+
+use std::io::{self, Write};
+use std::mem;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Content codings that can be applied to request bodies, see
+/// [`RequestBuilder::compress()`], and removed from response bodies, see
+/// [`ClientBuilder::accept_encodings()`].
+///
+/// [`RequestBuilder::compress()`]: crate::RequestBuilder::compress
+/// [`ClientBuilder::accept_encodings()`]: crate::ClientBuilder::accept_encodings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Encoding {
+    /// The `gzip` content coding (RFC 1952).
+    Gzip,
+    /// The `deflate` content coding, i.e. the zlib format (RFC 1950).
+    Deflate,
+    /// The `br` content coding (RFC 7932), requires the `brotli` feature.
+    #[cfg(feature = "brotli")]
+    Brotli,
+    /// The `zstd` content coding (RFC 8878), requires the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+// Brotli's default quality of 11 is too slow for compressing on the fly.
+#[cfg(feature = "brotli")]
+const BROTLI_QUALITY: u32 = 5;
+#[cfg(feature = "brotli")]
+const BROTLI_WINDOW_BITS: u32 = 22;
+#[cfg(feature = "brotli")]
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// The maximum size of the chunks of decompressed response bodies.
+const DECODE_BUFFER_SIZE: usize = 32 * 1024;
+
+/// The maximum size of a gzip member header, which may contain arbitrarily
+/// long file names and comments.
+const MAX_GZIP_HEADER_SIZE: usize = 128 * 1024;
+
+impl Encoding {
+    /// Returns the value of the `Content-Encoding` header for this encoding.
+    pub fn header_value(&self) -> HeaderValue {
+        HeaderValue::from_static(self.name())
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => "br",
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => "zstd",
+        }
+    }
+
+    fn encoder(&self) -> io::Result<Encoder> {
+        Ok(match self {
+            Encoding::Gzip => Encoder::Gzip(GzEncoder::new(Vec::new(), Compression::default())),
+            Encoding::Deflate => {
+                Encoder::Deflate(ZlibEncoder::new(Vec::new(), Compression::default()))
+            }
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => Encoder::Brotli(Box::new(CompressorWriter::new(
+                Vec::new(),
+                BROTLI_BUFFER_SIZE,
+                BROTLI_QUALITY,
+                BROTLI_WINDOW_BITS,
+            ))),
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => Encoder::Zstd(zstd_crate::Encoder::new(Vec::new(), 0)?),
+        })
+    }
+
+    fn decoder(&self) -> io::Result<Decoder> {
+        Ok(match self {
+            Encoding::Gzip => Decoder::Gzip(GzipDecoder {
+                state: GzipState::Header,
+                inflate: Decompress::new(false),
+                crc: Crc::new(),
+                buf: Vec::new(),
+            }),
+            Encoding::Deflate => Decoder::Deflate(DeflateDecoder {
+                inner: Decompress::new(true),
+                ended: false,
+            }),
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => Decoder::Brotli(Box::new(BrotliDecoder {
+                state: BrotliState::new(
+                    HeapAlloc::new(0),
+                    HeapAlloc::new(0),
+                    HeapAlloc::new(HuffmanCode { value: 0, bits: 0 }),
+                ),
+                ended: false,
+            })),
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => Decoder::Zstd(ZstdDecoder {
+                inner: zstd_crate::stream::raw::Decoder::new()?,
+                ended: false,
+            }),
+        })
+    }
+}
+
+/// Returns the value of the `Accept-Encoding` header for `encodings`.
+pub(crate) fn accept_encoding(encodings: &[Encoding]) -> Option<HeaderValue> {
+    if encodings.is_empty() {
+        return None;
+    }
+    let names: Vec<_> = encodings.iter().map(Encoding::name).collect();
+    Some(HeaderValue::from_str(&names.join(", ")).expect("encoding names are valid"))
+}
+
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+    #[cfg(feature = "brotli")]
+    Brotli(Box<CompressorWriter<Vec<u8>>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd_crate::Encoder<'static, Vec<u8>>),
+}
+
+impl Encoder {
+    /// Compress `data` and return the compressed output that is available.
+    fn write(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(e) => {
+                e.write_all(data)?;
+                Ok(mem::take(e.get_mut()))
+            }
+            Encoder::Deflate(e) => {
+                e.write_all(data)?;
+                Ok(mem::take(e.get_mut()))
+            }
+            #[cfg(feature = "brotli")]
+            Encoder::Brotli(e) => {
+                e.write_all(data)?;
+                Ok(mem::take(e.get_mut()))
+            }
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(e) => {
+                e.write_all(data)?;
+                Ok(mem::take(e.get_mut()))
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Gzip(e) => e.finish(),
+            Encoder::Deflate(e) => e.finish(),
+            #[cfg(feature = "brotli")]
+            Encoder::Brotli(e) => Ok(e.into_inner()),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(e) => e.finish(),
+        }
+    }
+}
+
+/// A decoder that decompresses into buffers provided by the caller, so
+/// that the output produced at once is bounded regardless of the
+/// compression ratio.
+enum Decoder {
+    Gzip(GzipDecoder),
+    Deflate(DeflateDecoder),
+    #[cfg(feature = "brotli")]
+    Brotli(Box<BrotliDecoder>),
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdDecoder),
+}
+
+impl Decoder {
+    /// Decompress a prefix of `input` into `output`.
+    ///
+    /// Returns the number of bytes consumed from `input` and written to
+    /// `output`. If `output` is filled, the decoder may hold more output
+    /// that is returned by the next call, even without further input.
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<(usize, usize)> {
+        match self {
+            Decoder::Gzip(d) => d.decode(input, output),
+            Decoder::Deflate(d) => d.decode(input, output),
+            #[cfg(feature = "brotli")]
+            Decoder::Brotli(d) => d.decode(input, output),
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd(d) => d.decode(input, output),
+        }
+    }
+
+    /// Returns an error if the compressed stream is incomplete.
+    fn finish(&self) -> io::Result<()> {
+        let (ended, coding) = match self {
+            Decoder::Gzip(d) => (matches!(d.state, GzipState::End), "gzip"),
+            Decoder::Deflate(d) => (d.ended, "deflate"),
+            #[cfg(feature = "brotli")]
+            Decoder::Brotli(d) => (d.ended, "brotli"),
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd(d) => (d.ended, "zstd"),
+        };
+        match ended {
+            true => Ok(()),
+            false => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("truncated {} stream", coding),
+            )),
+        }
+    }
+}
+
+/// Compress `body` using `encoding`.
+///
+/// Bodies that are not streaming are compressed eagerly so that the length
+/// of the compressed body is known, streaming bodies are compressed as they
+/// are sent.
+pub(crate) fn compress(body: SharedBody, encoding: Encoding) -> io::Result<SharedBody> {
+    let mut encoder = encoding.encoder()?;
+    match body.buffers() {
+        Some(buffers) => {
+            let mut compressed = Vec::new();
+            for buf in buffers {
+                compressed.extend(encoder.write(&buf)?);
+            }
+            compressed.extend(encoder.finish()?);
+            Ok(SharedBody::from(compressed))
+        }
+        None => Ok(SharedBody::wrap_stream(CompressedStream {
+            body,
+            encoder: Some(encoder),
+        })),
+    }
+}
+
+struct CompressedStream {
+    body: SharedBody,
+    encoder: Option<Encoder>,
+}
+
+impl Stream for CompressedStream {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let encoder = match this.encoder.as_mut() {
+                Some(encoder) => encoder,
+                None => return Poll::Ready(None),
+            };
+            let output = match ready!(Pin::new(&mut this.body).poll_data(cx)) {
+                Some(Ok(chunk)) => encoder.write(&chunk),
+                Some(Err(e)) => {
+                    this.encoder = None;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => this.encoder.take().expect("encoder is present").finish(),
+            };
+            match output {
+                // The encoder might buffer small inputs
+                Ok(output) if output.is_empty() => continue,
+                Ok(output) => return Poll::Ready(Some(Ok(output.into()))),
+                Err(e) => {
+                    this.encoder = None;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+        }
+    }
+}
+
+/// A zlib decoder that, unlike `flate2::write::ZlibDecoder`, detects
+/// truncated streams.
+struct DeflateDecoder {
+    inner: Decompress,
+    ended: bool,
+}
+
+impl DeflateDecoder {
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<(usize, usize)> {
+        // Data after the end of the stream is ignored
+        if self.ended {
+            return Ok((input.len(), 0));
+        }
+        let (total_in, total_out) = (self.inner.total_in(), self.inner.total_out());
+        let status = self
+            .inner
+            .decompress(input, output, FlushDecompress::None)?;
+        self.ended = status == Status::StreamEnd;
+        Ok((
+            (self.inner.total_in() - total_in) as usize,
+            (self.inner.total_out() - total_out) as usize,
+        ))
+    }
+}
+
+/// A gzip decoder that supports streams of several members, see RFC 1952.
+struct GzipDecoder {
+    state: GzipState,
+    inflate: Decompress,
+    crc: Crc,
+    /// The header or trailer bytes received so far.
+    buf: Vec<u8>,
+}
+
+enum GzipState {
+    Header,
+    Body,
+    Trailer,
+    End,
+}
+
+impl GzipDecoder {
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<(usize, usize)> {
+        let mut consumed = 0;
+        loop {
+            let rest = &input[consumed..];
+            match self.state {
+                GzipState::Header => {
+                    if rest.is_empty() {
+                        return Ok((consumed, 0));
+                    }
+                    let start = self.buf.len();
+                    self.buf.extend_from_slice(rest);
+                    match gzip_header_len(&self.buf)? {
+                        Some(len) => {
+                            consumed += len - start;
+                            self.buf.clear();
+                            self.state = GzipState::Body;
+                        }
+                        None if self.buf.len() > MAX_GZIP_HEADER_SIZE => {
+                            return Err(invalid_gzip("header is too long"));
+                        }
+                        None => consumed += rest.len(),
+                    }
+                }
+                GzipState::Body => {
+                    let (total_in, total_out) = (self.inflate.total_in(), self.inflate.total_out());
+                    let status = self
+                        .inflate
+                        .decompress(rest, output, FlushDecompress::None)?;
+                    let read = (self.inflate.total_in() - total_in) as usize;
+                    let written = (self.inflate.total_out() - total_out) as usize;
+                    consumed += read;
+                    self.crc.update(&output[..written]);
+                    if status == Status::StreamEnd {
+                        self.state = GzipState::Trailer;
+                    }
+                    if written > 0 || (read == 0 && status != Status::StreamEnd) {
+                        return Ok((consumed, written));
+                    }
+                }
+                GzipState::Trailer => {
+                    let n = rest.len().min(8 - self.buf.len());
+                    self.buf.extend_from_slice(&rest[..n]);
+                    consumed += n;
+                    if self.buf.len() < 8 {
+                        return Ok((consumed, 0));
+                    }
+                    let b = &self.buf;
+                    let crc = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+                    let size = u32::from_le_bytes([b[4], b[5], b[6], b[7]]);
+                    if crc != self.crc.sum() || size != self.crc.amount() {
+                        return Err(invalid_gzip("checksum mismatch"));
+                    }
+                    self.buf.clear();
+                    self.state = GzipState::End;
+                }
+                GzipState::End => {
+                    if rest.is_empty() {
+                        return Ok((consumed, 0));
+                    }
+                    // Another member follows
+                    self.inflate.reset(false);
+                    self.crc.reset();
+                    self.state = GzipState::Header;
+                }
+            }
+        }
+    }
+}
+
+/// Returns the length of the gzip member header at the start of `buf`, or
+/// None if `buf` does not contain the complete header yet.
+fn gzip_header_len(buf: &[u8]) -> io::Result<Option<usize>> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if buf.len() < 10 {
+        return Ok(None);
+    }
+    if buf[..3] != [0x1f, 0x8b, 8] {
+        return Err(invalid_gzip("invalid header"));
+    }
+    let flags = buf[3];
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        match buf.get(pos..pos + 2) {
+            Some(len) => pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize,
+            None => return Ok(None),
+        }
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            match buf
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+            {
+                Some(len) => pos += len + 1,
+                None => return Ok(None),
+            }
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    Ok(Some(pos).filter(|&pos| pos <= buf.len()))
+}
+
+fn invalid_gzip(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("gzip: {}", msg))
+}
+
+#[cfg(feature = "brotli")]
+struct BrotliDecoder {
+    state: BrotliState<HeapAlloc<u8>, HeapAlloc<u32>, HeapAlloc<HuffmanCode>>,
+    ended: bool,
+}
+
+#[cfg(feature = "brotli")]
+impl BrotliDecoder {
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<(usize, usize)> {
+        if self.ended {
+            return Ok((0, 0));
+        }
+        let (mut available_in, mut input_offset) = (input.len(), 0);
+        let (mut available_out, mut output_offset, mut total_out) = (output.len(), 0, 0);
+        let result = BrotliDecompressStream(
+            &mut available_in,
+            &mut input_offset,
+            input,
+            &mut available_out,
+            &mut output_offset,
+            output,
+            &mut total_out,
+            &mut self.state,
+        );
+        match result {
+            BrotliResult::ResultFailure => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid brotli stream",
+            )),
+            BrotliResult::ResultSuccess => {
+                self.ended = true;
+                Ok((input_offset, output_offset))
+            }
+            BrotliResult::NeedsMoreInput | BrotliResult::NeedsMoreOutput => {
+                Ok((input_offset, output_offset))
+            }
+        }
+    }
+}
+
+/// A zstd decoder that, unlike `zstd::stream::write::Decoder`, detects
+/// truncated streams.
+#[cfg(feature = "zstd")]
+struct ZstdDecoder {
+    inner: zstd_crate::stream::raw::Decoder<'static>,
+    ended: bool,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdDecoder {
+    fn decode(&mut self, input: &[u8], output: &mut [u8]) -> io::Result<(usize, usize)> {
+        use zstd_crate::stream::raw::{InBuffer, Operation, OutBuffer};
+
+        let mut input = InBuffer::around(input);
+        let mut out = OutBuffer::around(output);
+        // Returns 0 when a frame is complete, the input may contain several
+        // frames.
+        self.ended = self.inner.run(&mut input, &mut out)? == 0;
+        Ok((input.pos(), out.pos()))
+    }
+}
+
+/// Decompress the body of `response` if it is encoded with one of
+/// `encodings`.
+///
+/// The `Content-Encoding` and `Content-Length` headers are removed from
+/// decompressed responses. Responses encoded with several codings are
+/// returned unchanged. The body is decompressed in chunks of bounded size,
+/// and reading it fails as soon as it yields more than `limit` bytes.
+pub(crate) fn decompress_response(
+    response: Response,
+    encodings: &[Encoding],
+    limit: Option<u64>,
+) -> Result<Response, Error> {
+    let encoding = match response.headers().get(CONTENT_ENCODING) {
+        Some(value) => encodings.iter().find(|encoding| {
+            value
+                .as_bytes()
+                .eq_ignore_ascii_case(encoding.name().as_bytes())
+        }),
+        None => None,
+    };
+    let encoding = match encoding {
+        // e.g. the empty body of a response to a `HEAD` request
+        Some(_) if response.body().is_end_stream() => return Ok(response),
+        Some(encoding) => encoding,
+        None => return Ok(response),
+    };
+    let decoder = encoding.decoder().map_err(Error::Io)?;
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(CONTENT_ENCODING);
+    parts.headers.remove(CONTENT_LENGTH);
+    let body = Body::wrap_stream(DecompressedBody {
+        inner: body,
+        decoder: Some(decoder),
+        input: Bytes::new(),
+        pending_output: false,
+        produced: 0,
+        limit,
+    });
+    Ok(Response::from_parts(parts, body))
+}
+
+struct DecompressedBody {
+    inner: Body,
+    decoder: Option<Decoder>,
+    /// Compressed data that has not been consumed by the decoder yet.
+    input: Bytes,
+    /// True if the decoder filled the last output buffer and may have more
+    /// output without further input.
+    pending_output: bool,
+    produced: u64,
+    limit: Option<u64>,
+}
+
+impl DecompressedBody {
+    /// Returns the size of the next output buffer, which is just large
+    /// enough to detect when the size limit is exceeded.
+    fn output_size(&self) -> usize {
+        match self.limit {
+            Some(limit) => {
+                let remaining = limit.saturating_sub(self.produced).saturating_add(1);
+                remaining.min(DECODE_BUFFER_SIZE as u64) as usize
+            }
+            None => DECODE_BUFFER_SIZE,
+        }
+    }
+
+    fn fail(&mut self, e: Error) -> Poll<Option<Result<Bytes, Error>>> {
+        // Drop the connection instead of reading the rest of the body.
+        self.decoder = None;
+        self.inner = Body::empty();
+        Poll::Ready(Some(Err(e)))
+    }
+}
+
+impl Stream for DecompressedBody {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.decoder.is_none() {
+                return Poll::Ready(None);
+            }
+            if !this.input.is_empty() || this.pending_output {
+                let mut output = vec![0; this.output_size()];
+                let decoder = this.decoder.as_mut().expect("decoder is present");
+                let (read, written) = match decoder.decode(&this.input, &mut output) {
+                    Ok(res) => res,
+                    Err(e) => return this.fail(Error::Io(e)),
+                };
+                this.input.advance(read);
+                this.pending_output = written == output.len();
+                if written > 0 {
+                    this.produced += written as u64;
+                    if let Some(limit) = this.limit.filter(|&limit| this.produced > limit) {
+                        return this.fail(Error::ResponseBodyTooLarge(limit));
+                    }
+                    output.truncate(written);
+                    return Poll::Ready(Some(Ok(output.into())));
+                }
+                if read == 0 && !this.input.is_empty() {
+                    return this.fail(Error::Io(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "unexpected data after the end of the compressed stream",
+                    )));
+                }
+                continue;
+            }
+            match ready!(Pin::new(&mut this.inner).poll_data(cx)) {
+                Some(Ok(chunk)) => this.input = chunk,
+                Some(Err(e)) => {
+                    this.decoder = None;
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+                None => {
+                    let decoder = this.decoder.take().expect("decoder is present");
+                    return match decoder.finish() {
+                        Ok(()) => Poll::Ready(None),
+                        Err(e) => Poll::Ready(Some(Err(Error::Io(e)))),
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use hyper::body::to_bytes;
+    use std::io::Read;
+
+    fn encodings() -> Vec<Encoding> {
+        vec![
+            Encoding::Gzip,
+            Encoding::Deflate,
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli,
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd,
+        ]
+    }
+
+    fn decompress(encoding: Encoding, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        match encoding {
+            Encoding::Gzip => GzDecoder::new(data).read_to_end(&mut out).unwrap(),
+            Encoding::Deflate => ZlibDecoder::new(data).read_to_end(&mut out).unwrap(),
+            #[cfg(feature = "brotli")]
+            Encoding::Brotli => brotli_crate::Decompressor::new(data, 4096)
+                .read_to_end(&mut out)
+                .unwrap(),
+            #[cfg(feature = "zstd")]
+            Encoding::Zstd => zstd_crate::Decoder::new(data)
+                .unwrap()
+                .read_to_end(&mut out)
+                .unwrap(),
+        };
+        out
+    }
+
+    #[tokio::test]
+    async fn compress_buffered() {
+        for encoding in encodings() {
+            let body = SharedBody::from_chunks(vec!["hello", ", ", "world!"]);
+            let body = compress(body, encoding).unwrap();
+            let len = body.content_length().unwrap();
+            let compressed = to_bytes(body).await.unwrap();
+            assert_eq!(compressed.len() as u64, len);
+            assert_eq!(decompress(encoding, &compressed), b"hello, world!");
+        }
+    }
+
+    #[tokio::test]
+    async fn compress_streaming() {
+        for encoding in encodings() {
+            let body = SharedBody::from_reader(&b"hello, world!"[..], Some(13));
+            let body = compress(body, encoding).unwrap();
+            assert_eq!(body.content_length(), None);
+            let compressed = to_bytes(body).await.unwrap();
+            assert_eq!(decompress(encoding, &compressed), b"hello, world!");
+        }
+    }
+
+    fn compressed_response(encoding: &str, body: Vec<u8>) -> Response {
+        hyper::Response::builder()
+            .header(CONTENT_ENCODING, encoding)
+            .header(CONTENT_LENGTH, body.len())
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn decompress_responses() {
+        let data = "hello, world!".repeat(1000);
+        for encoding in encodings() {
+            let body = compress(SharedBody::from(data.clone()), encoding).unwrap();
+            let body = to_bytes(body).await.unwrap().to_vec();
+            let response = compressed_response(encoding.name(), body.clone());
+            let response = decompress_response(response, &encodings(), None).unwrap();
+            assert!(response.headers().get(CONTENT_ENCODING).is_none());
+            assert!(response.headers().get(CONTENT_LENGTH).is_none());
+            let decompressed = to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(decompressed, data.as_bytes());
+
+            // Truncated bodies are an error
+            let truncated = body[..body.len() / 2].to_vec();
+            let response = compressed_response(encoding.name(), truncated);
+            let response = decompress_response(response, &encodings(), None).unwrap();
+            assert!(
+                to_bytes(response.into_body()).await.is_err(),
+                "{:?}",
+                encoding
+            );
+        }
+
+        // Encodings that are not accepted are left alone
+        let response = compressed_response("gzip", b"not gzip".to_vec());
+        let response = decompress_response(response, &[Encoding::Deflate], None).unwrap();
+        assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+        assert_eq!(to_bytes(response.into_body()).await.unwrap(), "not gzip");
+
+        // So are empty bodies, e.g. in responses to `HEAD` requests
+        let response = compressed_response("gzip", Vec::new());
+        let response = decompress_response(response, &[Encoding::Gzip], None).unwrap();
+        assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+    }
+
+    #[tokio::test]
+    async fn decompressed_size_limit() {
+        let data = vec![0; 100_000];
+        for encoding in encodings() {
+            let body = compress(SharedBody::from(data.clone()), encoding).unwrap();
+            let body = to_bytes(body).await.unwrap().to_vec();
+            assert!(body.len() < 1000);
+            let response = compressed_response(encoding.name(), body.clone());
+            let response = decompress_response(response, &encodings(), Some(1000)).unwrap();
+            let err = to_bytes(response.into_body()).await.unwrap_err();
+            let source = err.into_cause().unwrap().downcast::<Error>().unwrap();
+            assert!(matches!(*source, Error::ResponseBodyTooLarge(1000)));
+
+            let response = compressed_response(encoding.name(), body);
+            let response = decompress_response(response, &encodings(), Some(100_000)).unwrap();
+            assert_eq!(to_bytes(response.into_body()).await.unwrap().len(), 100_000);
+        }
+    }
+
+    #[tokio::test]
+    async fn decompression_bomb() {
+        let data = vec![0; 16 << 20];
+        for encoding in encodings() {
+            let body = compress(SharedBody::from(data.clone()), encoding).unwrap();
+            let body = to_bytes(body).await.unwrap().to_vec();
+            assert!(body.len() < 20_000, "{:?}: {}", encoding, body.len());
+            let response = compressed_response(encoding.name(), body);
+            let response = decompress_response(response, &encodings(), Some(100_000)).unwrap();
+            let mut body = response.into_body();
+            let mut total = 0;
+            let err = loop {
+                match body.data().await {
+                    Some(Ok(chunk)) => {
+                        assert!(chunk.len() <= DECODE_BUFFER_SIZE);
+                        total += chunk.len();
+                    }
+                    Some(Err(e)) => break e,
+                    None => panic!("{:?}: body was not limited", encoding),
+                }
+            };
+            assert!(total <= 100_000, "{:?}: {}", encoding, total);
+            let source = err.into_cause().unwrap().downcast::<Error>().unwrap();
+            assert!(matches!(*source, Error::ResponseBodyTooLarge(100_000)));
+        }
+    }
+
+    #[tokio::test]
+    async fn decompress_split_input() {
+        let data = "hello, world!".repeat(10_000);
+        for encoding in encodings() {
+            let body = compress(SharedBody::from(data.clone()), encoding).unwrap();
+            let body = to_bytes(body).await.unwrap();
+            let chunks: Vec<Result<_, io::Error>> =
+                body.chunks(7).map(|c| Ok(c.to_vec())).collect();
+            let response = hyper::Response::builder()
+                .header(CONTENT_ENCODING, encoding.name())
+                .body(Body::wrap_stream(tokio_stream::iter(chunks)))
+                .unwrap();
+            let response = decompress_response(response, &encodings(), None).unwrap();
+            let decompressed = to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(decompressed, data.as_bytes(), "{:?}", encoding);
+        }
+    }
+
+    #[tokio::test]
+    async fn decompress_gzip_members() {
+        let mut body = Vec::new();
+        for (name, data) in [("a.txt", "hello, "), ("b.txt", "world!")] {
+            let mut encoder = flate2::GzBuilder::new()
+                .filename(name)
+                .comment("comment")
+                .extra(vec![1, 2, 3])
+                .write(Vec::new(), Compression::default());
+            encoder.write_all(data.as_bytes()).unwrap();
+            body.extend(encoder.finish().unwrap());
+        }
+        let response = compressed_response("gzip", body.clone());
+        let response = decompress_response(response, &[Encoding::Gzip], None).unwrap();
+        assert_eq!(
+            to_bytes(response.into_body()).await.unwrap(),
+            "hello, world!"
+        );
+
+        // The checksum is verified
+        let len = body.len();
+        body[len - 5] ^= 1;
+        let response = compressed_response("gzip", body);
+        let response = decompress_response(response, &[Encoding::Gzip], None).unwrap();
+        assert!(to_bytes(response.into_body()).await.is_err());
+    }
+
+    #[test]
+    fn accept_encoding_header() {
+        assert_eq!(accept_encoding(&[]), None);
+        let value = accept_encoding(&[Encoding::Deflate, Encoding::Gzip]).unwrap();
+        assert_eq!(value, "deflate, gzip");
+    }
+}
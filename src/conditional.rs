@@ -0,0 +1,100 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use headers::{HeaderMapExt, IfModifiedSince, LastModified};
+use hyper::header::{HeaderMap, HeaderValue, ETAG, IF_NONE_MATCH};
+
+use std::time::SystemTime;
+
+/// The validators of a previously received response, used to make
+/// conditional requests
+///
+/// See [`RequestBuilder::revalidate()`].
+///
+/// [`RequestBuilder::revalidate()`]: crate::RequestBuilder::revalidate
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<HeaderValue>,
+    pub last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    /// Extract the `ETag` and `Last-Modified` values from response headers.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        Validators {
+            etag: headers.get(ETAG).cloned(),
+            last_modified: headers.typed_get::<LastModified>().map(Into::into),
+        }
+    }
+
+    /// Returns true if there are no validators.
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// Add the conditional request headers corresponding to these validators.
+    ///
+    /// `If-Modified-Since` is only sent if there is no entity tag since it is
+    /// ignored by servers when `If-None-Match` is present, see RFC 9110
+    /// section 13.1.3.
+    pub(crate) fn apply(&self, headers: &mut HeaderMap) {
+        match (&self.etag, self.last_modified) {
+            (Some(etag), _) => {
+                headers.insert(IF_NONE_MATCH, etag.clone());
+            }
+            (None, Some(time)) => headers.typed_insert(IfModifiedSince::from(time)),
+            (None, None) => {}
+        }
+    }
+}
+
+/// The result of revalidating a cached representation
+///
+/// See [`RequestBuilder::revalidate()`].
+///
+/// [`RequestBuilder::revalidate()`]: crate::RequestBuilder::revalidate
+#[derive(Debug)]
+pub enum Revalidation<T, R = crate::Response> {
+    /// The server responded with `304 Not Modified`, the cached
+    /// representation can be used.
+    Fresh(T),
+    /// The server responded with something other than `304 Not Modified`,
+    /// usually a new representation.
+    Modified(R),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn validators() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        let time = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        headers.typed_insert(LastModified::from(time));
+        let validators = Validators::from_headers(&headers);
+        assert_eq!(validators.last_modified, Some(time));
+
+        let mut headers = HeaderMap::new();
+        validators.apply(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[IF_NONE_MATCH], "\"v1\"");
+
+        let validators = Validators {
+            etag: None,
+            last_modified: Some(time),
+        };
+        let mut headers = HeaderMap::new();
+        validators.apply(&mut headers);
+        assert_eq!(
+            headers["if-modified-since"],
+            "Sun, 13 Sep 2020 12:26:40 GMT"
+        );
+        assert!(Validators::default().is_empty());
+    }
+}
@@ -4,7 +4,10 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use crate::connector::{NetworkConnection, NetworkConnector};
+use crate::connector::resolve::GaiResolver;
+use crate::connector::{
+    ConnectPolicy, HttpInfo, NetworkConnection, NetworkConnector, Proxy, Resolve, TimeoutStream,
+};
 
 use hyper::client::connect::{Connected, Connection};
 use hyper::Uri;
@@ -14,9 +17,10 @@ use tokio::time;
 
 use std::error::Error as StdError;
 use std::future::Future;
-use std::net::Ipv6Addr;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
 use std::pin::Pin;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 use std::{fmt, io};
@@ -24,6 +28,8 @@ use std::{fmt, io};
 const DEFAULT_HTTP_PORT: u16 = 80;
 const DEFAULT_HTTPS_PORT: u16 = 443;
 
+const DENIED: &str = "connection denied by policy";
+
 /// A simple HTTP connector
 ///
 /// NOTE: this provides less functionality than [hyper's `HttpConnector`].
@@ -32,12 +38,22 @@ const DEFAULT_HTTPS_PORT: u16 = 443;
 #[derive(Clone)]
 pub struct HttpConnector {
     connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    policy: ConnectPolicy,
+    proxy: Option<Proxy>,
+    resolver: Option<Arc<dyn Resolve>>,
 }
 
 impl HttpConnector {
     pub fn new() -> Self {
         HttpConnector {
             connect_timeout: None,
+            read_timeout: None,
+            write_timeout: None,
+            policy: ConnectPolicy::new(),
+            proxy: None,
+            resolver: None,
         }
     }
 
@@ -47,10 +63,56 @@ impl HttpConnector {
         self
     }
 
+    /// Set the timeout for a read on a connection to make progress, see
+    /// [`TimeoutStream`]. Default is None.
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Set the timeout for a write on a connection to make progress, see
+    /// [`TimeoutStream`]. Default is None.
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Set the policy restricting the hosts and addresses to connect to.
+    /// Default allows all targets.
+    pub fn policy(mut self, policy: ConnectPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set the proxy to connect through, see [`Proxy`]. Default is None.
+    ///
+    /// This connector only handles `http` URIs, but `HttpsConnector` uses it
+    /// to tunnel `https` connections through the proxy. Clients built with
+    /// this connector add the proxy's headers to requests for `http` targets,
+    /// see [`NetworkConnector::forward_proxy()`].
+    ///
+    /// The [`policy()`] of the connector applies to the targets of proxied
+    /// requests as far as possible: their host names are checked, but only
+    /// IP addresses that appear in the URI are, as the proxy resolves host
+    /// names. The address of the proxy is not checked.
+    ///
+    /// [`policy()`]: HttpConnector::policy
+    pub fn proxy(mut self, proxy: Option<Proxy>) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    /// Set the resolver for host names, see [`Resolve`]. Default uses the
+    /// system resolver.
+    pub fn resolver<R: Resolve>(mut self, resolver: R) -> Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
     pub(super) async fn connect(
+        &self,
         uri: Uri,
         allow_https: bool,
-        connect_timeout: Option<Duration>,
     ) -> Result<HttpConnection, ConnectError> {
         match uri.scheme_str() {
             Some("http") => {}
@@ -65,28 +127,78 @@ impl HttpConnector {
             None => return Err(ConnectError::new("invalid URI: missing scheme")),
         }
         let host = get_host(&uri)?;
-        let port = uri.port_u16().unwrap_or_else(|| {
-            if uri.scheme_str() == Some("http") {
-                DEFAULT_HTTP_PORT
-            } else {
-                DEFAULT_HTTPS_PORT
+        self.policy.check_host(host)?;
+        let proxy = self.proxy.as_ref().filter(|proxy| proxy.intercepts(&uri));
+        let stream = match proxy {
+            Some(proxy) => {
+                // The proxy resolves the target, so only IP addresses in the
+                // URI can be checked. The proxy itself is trusted.
+                if let Ok(ip) = host.parse::<IpAddr>() {
+                    self.policy
+                        .filter_addrs([SocketAddr::new(ip, port(&uri))])?;
+                }
+                let proxy = proxy.uri();
+                self.connect_tcp(get_host(proxy)?, port(proxy), false)
+                    .await?
+            }
+            None => self.connect_tcp(host, port(&uri), true).await?,
+        };
+        let mut stream = TimeoutStream::new(stream)
+            .read_timeout(self.read_timeout)
+            .write_timeout(self.write_timeout);
+        let tunnel = uri.scheme_str() == Some("https");
+        if let (Some(proxy), true) = (proxy, tunnel) {
+            proxy.tunnel(&mut stream, &uri).await?;
+        }
+        Ok(HttpConnection {
+            stream,
+            // Requests through a tunnel are sent as if connected directly
+            proxied: proxy.is_some() && !tunnel,
+        })
+    }
+
+    async fn connect_tcp(
+        &self,
+        host: &str,
+        port: u16,
+        check_ips: bool,
+    ) -> Result<TcpStream, ConnectError> {
+        let connect = async {
+            let check_ips = check_ips && self.policy.checks_ips();
+            if self.resolver.is_none() && !check_ips {
+                return TcpStream::connect((host, port)).await.map_err(io_error);
             }
-        });
-        let connect = TcpStream::connect((host, port));
-        let stream = match connect_timeout {
+            let addrs = match host.parse::<IpAddr>() {
+                Ok(ip) => vec![SocketAddr::new(ip, port)],
+                Err(_) => {
+                    let resolver = self.resolver.as_deref().unwrap_or(&GaiResolver);
+                    resolver.resolve(host, port).await.map_err(io_error)?
+                }
+            };
+            // Only connect to the addresses that were checked, so that they
+            // cannot change through another lookup
+            let addrs = match check_ips {
+                true => self.policy.filter_addrs(addrs)?,
+                false => addrs,
+            };
+            TcpStream::connect(&addrs[..]).await.map_err(io_error)
+        };
+        match self.connect_timeout {
             Some(duration) => match time::timeout(duration, connect).await {
-                Ok(Ok(stream)) => Ok(stream),
-                Ok(Err(e)) => Err(e),
-                Err(_) => Err(io::Error::new(
+                Ok(res) => res,
+                Err(_) => Err(io_error(io::Error::new(
                     io::ErrorKind::TimedOut,
                     "connection timed out",
-                )),
+                ))),
             },
             None => connect.await,
         }
-        .map_err(|e| ConnectError::new("I/O error").cause(e))?;
+    }
+}
 
-        Ok(HttpConnection { stream })
+impl Default for HttpConnector {
+    fn default() -> Self {
+        HttpConnector::new()
     }
 }
 
@@ -97,14 +209,32 @@ impl NetworkConnector for HttpConnector {
     ) -> Pin<
         Box<dyn Future<Output = Result<NetworkConnection, Box<dyn StdError + Send + Sync>>> + Send>,
     > {
-        let connect_timeout = self.connect_timeout;
+        let connector = self.clone();
         Box::pin(async move {
-            match Self::connect(uri, false, connect_timeout).await {
+            match connector.connect(uri, false).await {
                 Ok(conn) => Ok(NetworkConnection::new(conn)),
                 Err(e) => Err(Box::new(e) as _),
             }
         })
     }
+
+    fn forward_proxy(&self) -> Option<&Proxy> {
+        self.proxy.as_ref()
+    }
+}
+
+fn port(uri: &Uri) -> u16 {
+    uri.port_u16().unwrap_or_else(|| {
+        if uri.scheme_str() == Some("http") {
+            DEFAULT_HTTP_PORT
+        } else {
+            DEFAULT_HTTPS_PORT
+        }
+    })
+}
+
+fn io_error(e: io::Error) -> ConnectError {
+    ConnectError::new("I/O error").cause(e)
 }
 
 pub(super) fn get_host(uri: &Uri) -> Result<&str, ConnectError> {
@@ -114,7 +244,7 @@ pub(super) fn get_host(uri: &Uri) -> Result<&str, ConnectError> {
 
     if host.starts_with("[") && host.ends_with("]") {
         let maybe_ipv6 = host.strip_prefix('[').unwrap().strip_suffix(']').unwrap();
-        if let Ok(_) = Ipv6Addr::from_str(maybe_ipv6) {
+        if Ipv6Addr::from_str(maybe_ipv6).is_ok() {
             return Ok(maybe_ipv6);
         }
     }
@@ -125,13 +255,23 @@ pub(super) fn get_host(uri: &Uri) -> Result<&str, ConnectError> {
 ///
 /// [`tokio::net::TcpStream`]: https://docs.rs/tokio/1.0/tokio/net/struct.TcpStream.html
 pub struct HttpConnection {
-    pub(super) stream: TcpStream,
+    pub(super) stream: TimeoutStream<TcpStream>,
+    proxied: bool,
 }
 
 impl Connection for HttpConnection {
     fn connected(&self) -> Connected {
-        // TODO: provide remote address
-        Connected::new()
+        connected(self.stream.get_ref()).proxy(self.proxied)
+    }
+}
+
+/// Returns the connection metadata of `stream`, including its addresses.
+pub(super) fn connected(stream: &TcpStream) -> Connected {
+    match (stream.peer_addr(), stream.local_addr()) {
+        (Ok(remote_addr), Ok(local_addr)) => {
+            Connected::new().extra(HttpInfo::new(remote_addr, local_addr))
+        }
+        _ => Connected::new(),
     }
 }
 
@@ -177,6 +317,40 @@ impl ConnectError {
         self.cause = Some(cause.into());
         self
     }
+
+    pub(super) fn denied<E: Into<Box<dyn StdError + Send + Sync>>>(reason: E) -> Self {
+        ConnectError::new(DENIED).cause(reason)
+    }
+
+    /// Returns the description of the failed step, e.g. `"I/O error"`.
+    pub fn message(&self) -> &'static str {
+        self.msg
+    }
+
+    /// Returns the I/O error that caused the connection to fail, if any.
+    ///
+    /// Its [`kind()`] allows distinguishing e.g. refused connections from
+    /// timeouts.
+    ///
+    /// [`kind()`]: std::io::Error::kind
+    pub fn io_error(&self) -> Option<&io::Error> {
+        self.cause.as_ref()?.downcast_ref()
+    }
+
+    /// Returns true if the connection was rejected by the [`ConnectPolicy`]
+    /// of the connector.
+    pub fn is_denied(&self) -> bool {
+        self.msg == DENIED
+    }
+
+    /// Returns true if the TLS handshake failed.
+    pub fn is_tls(&self) -> bool {
+        #[cfg(feature = "tokio-native-tls")]
+        if let Some(ref cause) = self.cause {
+            return cause.is::<tokio_native_tls::native_tls::Error>();
+        }
+        false
+    }
 }
 
 impl fmt::Debug for ConnectError {
@@ -194,7 +368,7 @@ impl fmt::Debug for ConnectError {
 
 impl fmt::Display for ConnectError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(&self.msg)?;
+        f.write_str(self.msg)?;
         if let Some(ref cause) = self.cause {
             write!(f, ": {}", cause)?;
         }
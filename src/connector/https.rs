@@ -4,13 +4,16 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use crate::connector::http::{get_host, ConnectError, HttpConnection, HttpConnector};
-use crate::connector::{NetworkConnection, NetworkConnector};
+use crate::connector::http::{connected, get_host, ConnectError, HttpConnection, HttpConnector};
+use crate::connector::{
+    ConnectPolicy, NetworkConnection, NetworkConnector, Proxy, Resolve, TimeoutStream,
+};
 
 use hyper::client::connect::{Connected, Connection};
 use hyper::Uri;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::net::TcpStream;
+use tokio_native_tls::native_tls::{self, TlsConnectorBuilder};
 use tokio_native_tls::{TlsConnector, TlsStream};
 
 use std::error::Error as StdError;
@@ -27,21 +30,89 @@ use std::time::Duration;
 pub struct HttpsConnector {
     force_tls: bool,
     tls: TlsConnector,
-    connect_timeout: Option<Duration>,
+    http: HttpConnector,
 }
 
 impl HttpsConnector {
+    /// Create a connector using `tls`.
+    ///
+    /// If `tls` requests the `h2` protocol through ALPN, connections on which
+    /// the server selects it use HTTP/2, see [`HttpsConnector::with_alpn()`].
     pub fn new(tls: TlsConnector) -> Self {
         HttpsConnector {
             tls,
             force_tls: true,
-            connect_timeout: None,
+            http: HttpConnector::new(),
         }
     }
 
+    /// Create a connector requesting `protocols` through ALPN, in order of
+    /// preference.
+    ///
+    /// Pass `&["h2", "http/1.1"]` to use HTTP/2 with servers that support
+    /// it and HTTP/1.1 with other servers. Returns an error if the TLS
+    /// connector cannot be built from `builder`.
+    ///
+    /// ```
+    /// # use simple_hyper_client::HttpsConnector;
+    /// # use tokio_native_tls::native_tls::TlsConnector;
+    /// let connector = HttpsConnector::with_alpn(&mut TlsConnector::builder(), &["h2", "http/1.1"])?;
+    /// # Ok::<(), tokio_native_tls::native_tls::Error>(())
+    /// ```
+    pub fn with_alpn(
+        builder: &mut TlsConnectorBuilder,
+        protocols: &[&str],
+    ) -> Result<Self, native_tls::Error> {
+        let tls = builder.request_alpns(protocols).build()?;
+        Ok(HttpsConnector::new(tls.into()))
+    }
+
     /// Set the connect timeout. Default is None.
     pub fn connect_timeout(mut self, timeout: Option<Duration>) -> Self {
-        self.connect_timeout = timeout;
+        self.http = self.http.connect_timeout(timeout);
+        self
+    }
+
+    /// Set the timeout for a read on a connection to make progress, see
+    /// [`TimeoutStream`]. Default is None.
+    ///
+    /// The timeout applies to the TCP stream, including the TLS handshake.
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.http = self.http.read_timeout(timeout);
+        self
+    }
+
+    /// Set the timeout for a write on a connection to make progress, see
+    /// [`TimeoutStream`]. Default is None.
+    ///
+    /// The timeout applies to the TCP stream, including the TLS handshake.
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.http = self.http.write_timeout(timeout);
+        self
+    }
+
+    /// Set the policy restricting the hosts and addresses to connect to.
+    /// Default allows all targets.
+    pub fn policy(mut self, policy: ConnectPolicy) -> Self {
+        self.http = self.http.policy(policy);
+        self
+    }
+
+    /// Set the proxy to connect through, see [`Proxy`]. Default is None.
+    ///
+    /// Connections to `https` URIs are tunneled through the proxy with a
+    /// `CONNECT` request, and TLS is negotiated with the target through the
+    /// tunnel. See [`HttpConnector::proxy()`] for how the policy of the
+    /// connector applies to proxied connections.
+    pub fn proxy(mut self, proxy: Option<Proxy>) -> Self {
+        self.http = self.http.proxy(proxy);
+        self
+    }
+
+    /// Set the resolver for host names, see [`Resolve`]. Default uses the
+    /// system resolver.
+    pub fn resolver<R: Resolve>(mut self, resolver: R) -> Self {
+        self.http = self.http.resolver(resolver);
         self
     }
 
@@ -56,14 +127,14 @@ impl HttpsConnector {
         uri: Uri,
         tls: TlsConnector,
         force_tls: bool,
-        connect_timeout: Option<Duration>,
+        http: HttpConnector,
     ) -> Result<HttpOrHttpsConnection, ConnectError> {
         let is_https = uri.scheme_str() == Some("https");
         if !is_https && force_tls {
             return Err(ConnectError::new("invalid URI: expected `https` scheme"));
         }
         let host = get_host(&uri)?.to_owned();
-        let http = HttpConnector::connect(uri, true, connect_timeout).await?;
+        let http = http.connect(uri, true).await?;
         if is_https {
             let tls = tls
                 .connect(&host, http.stream)
@@ -86,28 +157,87 @@ impl NetworkConnector for HttpsConnector {
     > {
         let tls = self.tls.clone();
         let force_tls = self.force_tls;
-        let connect_timeout = self.connect_timeout;
+        let http = self.http.clone();
         Box::pin(async move {
-            match HttpsConnector::connect(uri, tls, force_tls, connect_timeout).await {
+            match HttpsConnector::connect(uri, tls, force_tls, http).await {
                 Ok(conn) => Ok(NetworkConnection::new(conn)),
                 Err(e) => Err(Box::new(e) as _),
             }
         })
     }
+
+    fn forward_proxy(&self) -> Option<&Proxy> {
+        self.http.forward_proxy()
+    }
+}
+
+/// Details of the TLS session of a connection
+///
+/// [`HttpsConnector`] reports this through [`Connected::extra()`] for
+/// `https` connections, so it is available in the extensions of responses:
+///
+/// ```no_run
+/// # use simple_hyper_client::TlsInfo;
+/// # fn f(response: &simple_hyper_client::Response) {
+/// if let Some(info) = response.extensions().get::<TlsInfo>() {
+///     println!("{:?}", info.alpn_protocol());
+/// }
+/// # }
+/// ```
+///
+/// native-tls does not expose the negotiated protocol version, the cipher
+/// suite or the certificates of the peer other than its own, so they are
+/// not included.
+///
+/// [`Connected::extra()`]: hyper::client::connect::Connected::extra
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    alpn_protocol: Option<Vec<u8>>,
+    peer_certificate: Option<Vec<u8>>,
+}
+
+impl TlsInfo {
+    fn new<S: io::Read + io::Write>(tls: &native_tls::TlsStream<S>) -> Self {
+        TlsInfo {
+            alpn_protocol: tls.negotiated_alpn().ok().flatten(),
+            peer_certificate: match tls.peer_certificate() {
+                Ok(Some(cert)) => cert.to_der().ok(),
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns the protocol negotiated through ALPN, e.g. `b"h2"`.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.alpn_protocol.as_deref()
+    }
+
+    /// Returns the DER-encoded certificate of the peer.
+    pub fn peer_certificate(&self) -> Option<&[u8]> {
+        self.peer_certificate.as_deref()
+    }
 }
 
 /// An HTTP or HTTPS connection
 pub enum HttpOrHttpsConnection {
     Http(HttpConnection),
-    Https(TlsStream<TcpStream>),
+    Https(TlsStream<TimeoutStream<TcpStream>>),
 }
 
 impl Connection for HttpOrHttpsConnection {
     fn connected(&self) -> Connected {
-        // TODO: provide remote address
-        // TODO: provide information about http protocol version (if negotiated through
-        // ALPN)
-        Connected::new()
+        match self {
+            HttpOrHttpsConnection::Http(s) => s.connected(),
+            HttpOrHttpsConnection::Https(s) => {
+                let tls = s.get_ref();
+                let info = TlsInfo::new(tls);
+                let connected = connected(tls.get_ref().get_ref().get_ref());
+                match info.alpn_protocol() {
+                    Some(b"h2") => connected.extra(info).negotiated_h2(),
+                    _ => connected.extra(info),
+                }
+            }
+        }
     }
 }
 
@@ -4,47 +4,154 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use crate::observer::Observer;
+
 use hyper::client::connect::{Connected, Connection};
 use hyper::service::Service;
 use hyper::Uri;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Instant;
 
+use std::any::Any;
 use std::error::Error as StdError;
 use std::future::Future;
 use std::io;
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::task::{Context, Poll};
+use std::task::{ready, Context, Poll};
 
+#[cfg(feature = "tcp")]
 pub mod http;
-#[cfg(feature = "tokio-native-tls")]
+#[cfg(all(feature = "tcp", feature = "tokio-native-tls"))]
 pub mod https;
 pub mod hyper_adapter;
+#[cfg(feature = "tcp")]
+mod policy;
+#[cfg(feature = "tcp")]
+mod proxy;
+#[cfg(feature = "tcp")]
+mod resolve;
+mod timeout;
 
+#[cfg(feature = "tcp")]
 pub use self::http::{ConnectError, HttpConnection, HttpConnector};
-#[cfg(feature = "tokio-native-tls")]
-pub use self::https::{HttpOrHttpsConnection, HttpsConnector};
+#[cfg(all(feature = "tcp", feature = "tokio-native-tls"))]
+pub use self::https::{HttpOrHttpsConnection, HttpsConnector, TlsInfo};
 pub use self::hyper_adapter::HyperConnectorAdapter;
+#[cfg(feature = "tcp")]
+pub use self::policy::ConnectPolicy;
+#[cfg(feature = "tcp")]
+pub use self::proxy::Proxy;
+#[cfg(feature = "tcp")]
+pub use self::resolve::{GaiResolver, Resolve};
+pub use self::timeout::TimeoutStream;
+
+/// Addresses of the connection a response was received on
+///
+/// The built-in connectors report this through [`Connected::extra()`], so
+/// it is available in the extensions of responses, see also
+/// [`ResponseExt::remote_addr()`]. Custom connectors can report it the same
+/// way.
+///
+/// The remote address is the address the connector connected to, i.e. the
+/// address of the proxy for connections through a [`Proxy`].
+///
+/// [`Connected::extra()`]: hyper::client::connect::Connected::extra
+/// [`ResponseExt::remote_addr()`]: crate::ResponseExt::remote_addr
+/// [`Proxy`]: crate::Proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpInfo {
+    remote_addr: SocketAddr,
+    local_addr: SocketAddr,
+}
+
+impl HttpInfo {
+    pub fn new(remote_addr: SocketAddr, local_addr: SocketAddr) -> Self {
+        HttpInfo {
+            remote_addr,
+            local_addr,
+        }
+    }
+
+    /// Returns the address of the peer.
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+
+    /// Returns the local address of the connection.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+/// Returns the peer address reported by the connector of a response, either
+/// through [`HttpInfo`] or hyper's equivalent.
+pub(crate) fn remote_addr(extensions: &hyper::http::Extensions) -> Option<SocketAddr> {
+    match extensions.get::<HttpInfo>() {
+        Some(info) => Some(info.remote_addr()),
+        None => extensions
+            .get::<hyper::client::connect::HttpInfo>()
+            .map(|info| info.remote_addr()),
+    }
+}
 
 trait NetworkStream: AsyncRead + AsyncWrite + Connection + Unpin + Send + 'static {}
 
 impl<T> NetworkStream for T where T: AsyncRead + AsyncWrite + Connection + Unpin + Send + 'static {}
 
-/// A boxed network connection
-pub struct NetworkConnection(Box<dyn NetworkStream>);
+/// A type-erased network connection
+///
+/// Connections of the built-in connectors are stored inline, other
+/// connections are boxed.
+pub struct NetworkConnection(Stream);
+
+enum Stream {
+    #[cfg(feature = "tcp")]
+    Http(HttpConnection),
+    #[cfg(all(feature = "tcp", feature = "tokio-native-tls"))]
+    HttpOrHttps(HttpOrHttpsConnection),
+    Boxed(Box<dyn NetworkStream>),
+}
 
 impl NetworkConnection {
     pub fn new<S>(stream: S) -> Self
     where
         S: AsyncRead + AsyncWrite + Connection + Unpin + Send + 'static,
     {
-        NetworkConnection(Box::new(stream))
+        // Downcasting through `Option` moves the stream out without boxing it
+        let mut stream = Some(stream);
+        let any = &mut stream as &mut dyn Any;
+        if let Some(conn) = any.downcast_mut::<Option<NetworkConnection>>() {
+            return conn.take().unwrap();
+        }
+        #[cfg(feature = "tcp")]
+        if let Some(conn) = any.downcast_mut::<Option<HttpConnection>>() {
+            return NetworkConnection(Stream::Http(conn.take().unwrap()));
+        }
+        #[cfg(all(feature = "tcp", feature = "tokio-native-tls"))]
+        if let Some(conn) = any.downcast_mut::<Option<HttpOrHttpsConnection>>() {
+            return NetworkConnection(Stream::HttpOrHttps(conn.take().unwrap()));
+        }
+        NetworkConnection(Stream::Boxed(Box::new(stream.unwrap())))
     }
 }
 
+macro_rules! with_stream {
+    ($conn:expr, $stream:ident => $e:expr) => {
+        match $conn {
+            #[cfg(feature = "tcp")]
+            Stream::Http($stream) => $e,
+            #[cfg(all(feature = "tcp", feature = "tokio-native-tls"))]
+            Stream::HttpOrHttps($stream) => $e,
+            Stream::Boxed($stream) => $e,
+        }
+    };
+}
+
 impl Connection for NetworkConnection {
     fn connected(&self) -> Connected {
-        self.0.connected()
+        with_stream!(&self.0, stream => stream.connected())
     }
 }
 
@@ -54,7 +161,7 @@ impl AsyncRead for NetworkConnection {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf<'_>,
     ) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.get_mut().0).poll_read(cx, buf)
+        with_stream!(&mut self.get_mut().0, stream => Pin::new(stream).poll_read(cx, buf))
     }
 }
 
@@ -64,47 +171,112 @@ impl AsyncWrite for NetworkConnection {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        Pin::new(&mut self.get_mut().0).poll_write(cx, buf)
+        with_stream!(&mut self.get_mut().0, stream => Pin::new(stream).poll_write(cx, buf))
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.get_mut().0).poll_flush(cx)
+        with_stream!(&mut self.get_mut().0, stream => Pin::new(stream).poll_flush(cx))
     }
 
     fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.get_mut().0).poll_shutdown(cx)
+        with_stream!(&mut self.get_mut().0, stream => Pin::new(stream).poll_shutdown(cx))
     }
 }
 
 /// Network connector trait with type erasure
 pub trait NetworkConnector: Send + Sync + 'static {
+    #[allow(clippy::type_complexity)]
     fn connect(
         &self,
         uri: Uri,
     ) -> Pin<
         Box<dyn Future<Output = Result<NetworkConnection, Box<dyn StdError + Send + Sync>>> + Send>,
     >;
+
+    /// Returns the proxy this connector connects through, if any.
+    ///
+    /// The client adds the headers returned by [`Proxy::http_headers()`] to
+    /// requests for `http` targets intercepted by the proxy.
+    ///
+    /// Default is None.
+    #[cfg(feature = "tcp")]
+    fn forward_proxy(&self) -> Option<&Proxy> {
+        None
+    }
 }
 
 #[derive(Clone)]
-pub(crate) struct ConnectorAdapter(Arc<dyn NetworkConnector>);
+pub(crate) struct ConnectorAdapter {
+    connector: Arc<dyn NetworkConnector>,
+    observers: Arc<[Arc<dyn Observer>]>,
+}
 
 impl ConnectorAdapter {
-    pub fn new<T: NetworkConnector>(connector: T) -> Self {
-        Self(Arc::new(connector))
+    pub fn new<T: NetworkConnector>(connector: T, observers: Arc<[Arc<dyn Observer>]>) -> Self {
+        ConnectorAdapter {
+            connector: Arc::new(connector),
+            observers,
+        }
     }
 }
 
 impl Service<Uri> for ConnectorAdapter {
     type Response = NetworkConnection;
     type Error = Box<dyn StdError + Send + Sync>;
-    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    type Future = ConnectFuture;
 
     fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         Poll::Ready(Ok(()))
     }
 
     fn call(&mut self, uri: Uri) -> Self::Future {
-        self.0.connect(uri)
+        let observed = if self.observers.is_empty() {
+            None
+        } else {
+            Some(ObservedConnect {
+                uri: uri.clone(),
+                start: Instant::now(),
+                observers: self.observers.clone(),
+            })
+        };
+        ConnectFuture {
+            inner: self.connector.connect(uri),
+            observed,
+        }
+    }
+}
+
+type BoxConnectFuture = Pin<
+    Box<dyn Future<Output = Result<NetworkConnection, Box<dyn StdError + Send + Sync>>> + Send>,
+>;
+
+/// Future returned by [`ConnectorAdapter`], notifying observers without
+/// boxing the connector's future a second time.
+pub(crate) struct ConnectFuture {
+    inner: BoxConnectFuture,
+    observed: Option<ObservedConnect>,
+}
+
+struct ObservedConnect {
+    uri: Uri,
+    start: Instant,
+    observers: Arc<[Arc<dyn Observer>]>,
+}
+
+impl Future for ConnectFuture {
+    type Output = Result<NetworkConnection, Box<dyn StdError + Send + Sync>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let res = ready!(this.inner.as_mut().poll(cx));
+        if let Some(observed) = this.observed.take() {
+            for observer in observed.observers.iter() {
+                match res {
+                    Ok(_) => observer.on_connect(&observed.uri, observed.start.elapsed()),
+                    Err(ref e) => observer.on_connect_error(&observed.uri, &**e),
+                }
+            }
+        }
+        Poll::Ready(res)
     }
 }
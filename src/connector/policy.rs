@@ -0,0 +1,314 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::connector::http::ConnectError;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// Restricts the targets a connector may connect to
+///
+/// Services fetching user-supplied URLs can use this to guard against
+/// server-side request forgery (SSRF). Host names are checked before they
+/// are resolved, and every resolved address is checked before connecting,
+/// so a host name resolving to a denied address is rejected as well. As the
+/// connector only connects to the addresses it checked, a second DNS lookup
+/// cannot bypass the policy.
+///
+/// The policy applies to every connection the connector makes, so when
+/// redirects are followed by issuing new requests through the same client,
+/// their targets are checked the same way. Rejected connections fail with a
+/// [`ConnectError`] for which [`is_denied()`] returns true.
+///
+/// ```
+/// # use simple_hyper_client::{ConnectPolicy, HttpConnector};
+/// let policy = ConnectPolicy::new()
+///     .allow_host("*.example.com")
+///     .deny_private_ips(true);
+/// let connector = HttpConnector::new().policy(policy);
+/// ```
+///
+/// [`is_denied()`]: ConnectError::is_denied
+#[derive(Debug, Clone, Default)]
+pub struct ConnectPolicy {
+    allowed_hosts: Option<Vec<String>>,
+    denied_hosts: Vec<String>,
+    denied_ips: Vec<IpAddr>,
+    deny_private_ips: bool,
+}
+
+impl ConnectPolicy {
+    /// Create a policy allowing all targets.
+    pub fn new() -> Self {
+        ConnectPolicy::default()
+    }
+
+    /// Allow connecting to `host`. Once a host is allowed, all hosts that
+    /// are not allowed explicitly are denied.
+    ///
+    /// A pattern of the form `*.example.com` matches all subdomains of
+    /// `example.com`, but not `example.com` itself. Host names are compared
+    /// case-insensitively. IP addresses in URIs are matched as hosts too,
+    /// without brackets for IPv6 addresses.
+    pub fn allow_host<S: Into<String>>(mut self, host: S) -> Self {
+        let host = host.into().to_ascii_lowercase();
+        self.allowed_hosts.get_or_insert_with(Vec::new).push(host);
+        self
+    }
+
+    /// Deny connecting to `host`, which may be a pattern as in
+    /// [`allow_host()`]. Denied hosts take precedence over allowed ones.
+    ///
+    /// [`allow_host()`]: ConnectPolicy::allow_host
+    pub fn deny_host<S: Into<String>>(mut self, host: S) -> Self {
+        self.denied_hosts.push(host.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Deny connecting to `ip`, whether it is given in the URI or resolved
+    /// from a host name.
+    pub fn deny_ip(mut self, ip: IpAddr) -> Self {
+        self.denied_ips.push(ip);
+        self
+    }
+
+    /// If true, deny connecting to loopback, private, link-local, shared
+    /// (carrier-grade NAT), unspecified and broadcast addresses, e.g.
+    /// `127.0.0.1`, `10.0.0.1`, `169.254.169.254` or `fd00::1`. IPv4-mapped
+    /// IPv6 addresses are checked as IPv4 addresses, as are the IPv4
+    /// addresses embedded in NAT64, 6to4 and IPv4-compatible IPv6 addresses.
+    /// Default is false.
+    pub fn deny_private_ips(mut self, deny: bool) -> Self {
+        self.deny_private_ips = deny;
+        self
+    }
+
+    /// Check a host name before it is resolved.
+    pub(crate) fn check_host(&self, host: &str) -> Result<(), ConnectError> {
+        let host = host.to_ascii_lowercase();
+        let matches = |patterns: &[String]| patterns.iter().any(|p| host_matches(p, &host));
+        let allowed = !matches(&self.denied_hosts)
+            && match self.allowed_hosts {
+                Some(ref allowed) => matches(allowed),
+                None => true,
+            };
+        match allowed {
+            true => Ok(()),
+            false => Err(ConnectError::denied(format!(
+                "host `{}` is not allowed",
+                host
+            ))),
+        }
+    }
+
+    /// Returns true if addresses must be checked after resolution.
+    pub(crate) fn checks_ips(&self) -> bool {
+        self.deny_private_ips || !self.denied_ips.is_empty()
+    }
+
+    /// Returns the allowed addresses of `addrs`.
+    pub(crate) fn filter_addrs<I>(&self, addrs: I) -> Result<Vec<SocketAddr>, ConnectError>
+    where
+        I: IntoIterator<Item = SocketAddr>,
+    {
+        let mut denied = None;
+        let allowed: Vec<_> = addrs
+            .into_iter()
+            .filter(|addr| match self.is_denied_ip(addr.ip()) {
+                true => {
+                    denied.get_or_insert(addr.ip());
+                    false
+                }
+                false => true,
+            })
+            .collect();
+        match (allowed.is_empty(), denied) {
+            (true, Some(ip)) => Err(ConnectError::denied(format!(
+                "address {} is not allowed",
+                ip
+            ))),
+            _ => Ok(allowed),
+        }
+    }
+
+    fn is_denied_ip(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            IpAddr::V4(_) => ip,
+        };
+        self.denied_ips.contains(&ip) || (self.deny_private_ips && is_private(ip))
+    }
+}
+
+/// Returns true if `host` matches `pattern`. A trailing dot, as in the fully
+/// qualified form `example.com.`, is ignored on both.
+pub(super) fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.strip_suffix('.').unwrap_or(pattern);
+    let host = host.strip_suffix('.').unwrap_or(host);
+    match pattern.strip_prefix("*.") {
+        Some(domain) => match host.strip_suffix(domain) {
+            Some(sub) => sub.len() > 1 && sub.ends_with('.'),
+            None => false,
+        },
+        None => pattern == host,
+    }
+}
+
+fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => is_private_v6(ip),
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        // 0.0.0.0/8, "this network"
+        || a == 0
+        // 100.64.0.0/10, shared address space
+        || (a == 100 && (b & 0xc0) == 64)
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // fc00::/7, unique local addresses
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10, link-local unicast
+        || (first & 0xffc0) == 0xfe80
+        || embedded_ipv4(ip).into_iter().any(is_private_v4)
+}
+
+/// Returns the IPv4 addresses a translator may forward `ip` to.
+///
+/// This covers the NAT64 prefixes of RFC 6052 and RFC 8215, 6to4 addresses
+/// (RFC 3056) and the deprecated IPv4-compatible addresses. The network
+/// specific NAT64 prefix `64:ff9b:1::/48` may be used with any of the prefix
+/// lengths of RFC 6052 section 2.2, so every layout that fits is returned.
+fn embedded_ipv4(ip: Ipv6Addr) -> Vec<Ipv4Addr> {
+    let o = ip.octets();
+    let v4 = |a: usize, b: usize, c: usize, d: usize| Ipv4Addr::new(o[a], o[b], o[c], o[d]);
+    match ip.segments() {
+        // 64:ff9b::/96, well-known NAT64 prefix
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] => vec![v4(12, 13, 14, 15)],
+        // 64:ff9b:1::/48, local-use NAT64 prefix
+        [0x64, 0xff9b, 1, ..] => vec![
+            v4(6, 7, 9, 10),
+            v4(7, 9, 10, 11),
+            v4(9, 10, 11, 12),
+            v4(12, 13, 14, 15),
+        ],
+        // 2002::/16, 6to4
+        [0x2002, ..] => vec![v4(2, 3, 4, 5)],
+        // ::/96, IPv4-compatible addresses
+        [0, 0, 0, 0, 0, 0, _, _] => vec![v4(12, 13, 14, 15)],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        SocketAddr::new(s.parse().unwrap(), 80)
+    }
+
+    #[test]
+    fn hosts() {
+        let policy = ConnectPolicy::new()
+            .allow_host("*.Example.com")
+            .allow_host("example.org")
+            .deny_host("internal.example.com");
+        assert!(policy.check_host("api.example.com").is_ok());
+        assert!(policy.check_host("a.b.EXAMPLE.com").is_ok());
+        assert!(policy.check_host("example.org").is_ok());
+        assert!(policy.check_host("example.com").is_err());
+        assert!(policy.check_host("badexample.com").is_err());
+        assert!(policy.check_host("sub.example.org").is_err());
+        // Fully qualified host names match the same patterns
+        assert!(policy.check_host("api.example.com.").is_ok());
+        assert!(policy.check_host("example.org.").is_ok());
+        assert!(policy.check_host("example.com.").is_err());
+        assert!(policy.check_host("internal.example.com.").is_err());
+        let policy_fqdn = ConnectPolicy::new()
+            .allow_host("*.example.org.")
+            .deny_host("*.internal.example.org.");
+        assert!(policy_fqdn.check_host("api.example.org").is_ok());
+        assert!(policy_fqdn.check_host("db.internal.example.org").is_err());
+        let err = policy.check_host("internal.example.com").unwrap_err();
+        assert!(err.is_denied());
+        assert_eq!(
+            err.to_string(),
+            "connection denied by policy: host `internal.example.com` is not allowed"
+        );
+
+        let policy = ConnectPolicy::new().deny_host("localhost");
+        assert!(policy.check_host("example.com").is_ok());
+        assert!(policy.check_host("LocalHost").is_err());
+        assert!(policy.check_host("localhost.").is_err());
+        assert!(!policy.checks_ips());
+    }
+
+    #[test]
+    fn private_ips() {
+        let policy = ConnectPolicy::new().deny_private_ips(true);
+        assert!(policy.checks_ips());
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::7f00:1",
+            "64:ff9b::10.0.0.1",
+            "64:ff9b:1:7f00:1::",
+            "64:ff9b:1::c0a8:101",
+            "2002:7f00:1::",
+            "2002:a9fe:a9fe::1",
+            "::127.0.0.1",
+            "::192.168.1.1",
+        ] {
+            assert!(policy.filter_addrs([addr(ip)]).is_err(), "{}", ip);
+        }
+        for ip in [
+            "93.184.216.34",
+            "100.128.0.1",
+            "2606:2800:220:1::1",
+            "64:ff9b::5db8:d822",
+            "2002:5db8:d822::1",
+            "::93.184.216.34",
+        ] {
+            assert_eq!(policy.filter_addrs([addr(ip)]).unwrap(), [addr(ip)]);
+        }
+
+        // Denied addresses are skipped if others are allowed
+        let addrs = [addr("10.0.0.1"), addr("93.184.216.34")];
+        assert_eq!(policy.filter_addrs(addrs).unwrap(), [addr("93.184.216.34")]);
+        let err = policy.filter_addrs([addr("10.0.0.1")]).unwrap_err();
+        assert!(err.is_denied());
+        assert_eq!(
+            err.to_string(),
+            "connection denied by policy: address 10.0.0.1 is not allowed"
+        );
+
+        let policy = ConnectPolicy::new().deny_ip("93.184.216.34".parse().unwrap());
+        assert!(policy.filter_addrs([addr("93.184.216.34")]).is_err());
+        assert!(policy.filter_addrs([addr("127.0.0.1")]).is_ok());
+    }
+}
@@ -0,0 +1,343 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::connector::http::ConnectError;
+use crate::connector::policy::host_matches;
+use crate::error::Error;
+
+use headers::authorization::Authorization;
+use headers::{HeaderMapExt, ProxyAuthorization};
+use hyper::header::HeaderMap;
+use hyper::{StatusCode, Uri};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+
+/// An HTTP forward proxy
+///
+/// Connectors configured with a proxy connect to the proxy instead of the
+/// target of the requests it intercepts. For `https` targets, the connector
+/// asks the proxy to open a tunnel to the target with a `CONNECT` request,
+/// and runs TLS through the tunnel. For `http` targets, the connection is
+/// marked as proxied so that requests are sent with the absolute URI as the
+/// request target (e.g. `GET http://example.com/path HTTP/1.1`) rather
+/// than only the path.
+///
+/// The [`Proxy-Authorization`] header is sent with `CONNECT` requests. For
+/// `http` targets, the proxy needs it on every request rather than once per
+/// connection, so a [`Client`] built with a connector that uses the proxy
+/// adds the headers returned by [`http_headers()`] to those requests.
+///
+/// [`Proxy-Authorization`]: hyper::header::PROXY_AUTHORIZATION
+/// [`Client`]: crate::Client
+/// [`http_headers()`]: Proxy::http_headers
+#[derive(Clone)]
+pub struct Proxy {
+    uri: Uri,
+    intercept: Intercept,
+    headers: HeaderMap,
+    no_proxy: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Intercept {
+    Http,
+    Https,
+    All,
+}
+
+impl Proxy {
+    /// Create a proxy for requests with the `http` scheme.
+    ///
+    /// Returns an error if `uri` is invalid, or does not have the `http`
+    /// scheme and a host.
+    pub fn http<U>(uri: U) -> Result<Proxy, Error>
+    where
+        Uri: TryFrom<U>,
+        <Uri as TryFrom<U>>::Error: Into<http::Error>,
+    {
+        Proxy::new(uri, Intercept::Http)
+    }
+
+    /// Create a proxy tunneling requests with the `https` scheme.
+    ///
+    /// Returns an error if `uri` is invalid, or does not have the `http`
+    /// scheme and a host.
+    pub fn https<U>(uri: U) -> Result<Proxy, Error>
+    where
+        Uri: TryFrom<U>,
+        <Uri as TryFrom<U>>::Error: Into<http::Error>,
+    {
+        Proxy::new(uri, Intercept::Https)
+    }
+
+    /// Create a proxy for requests with either scheme.
+    ///
+    /// Returns an error if `uri` is invalid, or does not have the `http`
+    /// scheme and a host.
+    pub fn all<U>(uri: U) -> Result<Proxy, Error>
+    where
+        Uri: TryFrom<U>,
+        <Uri as TryFrom<U>>::Error: Into<http::Error>,
+    {
+        Proxy::new(uri, Intercept::All)
+    }
+
+    fn new<U>(uri: U, intercept: Intercept) -> Result<Proxy, Error>
+    where
+        Uri: TryFrom<U>,
+        <Uri as TryFrom<U>>::Error: Into<http::Error>,
+    {
+        let uri = Uri::try_from(uri)
+            .map_err(Into::into)
+            .map_err(Error::Http)?;
+        if uri.scheme_str() != Some("http") {
+            return Err(Error::InvalidUri("proxy URI must have the `http` scheme"));
+        }
+        if uri.host().is_none() {
+            return Err(Error::InvalidUri("proxy URI must have a host"));
+        }
+        Ok(Proxy {
+            uri,
+            intercept,
+            headers: HeaderMap::new(),
+            no_proxy: Vec::new(),
+        })
+    }
+
+    /// Authenticate to the proxy using HTTP Basic authentication.
+    pub fn basic_auth(mut self, username: &str, password: &str) -> Self {
+        let credentials = Authorization::basic(username, password).0;
+        self.headers.typed_insert(ProxyAuthorization(credentials));
+        self
+    }
+
+    /// Connect to `host` directly rather than through the proxy.
+    ///
+    /// A pattern of the form `*.example.com` matches all subdomains of
+    /// `example.com`, but not `example.com` itself. Host names are compared
+    /// case-insensitively.
+    pub fn no_proxy<S: Into<String>>(mut self, host: S) -> Self {
+        self.no_proxy.push(host.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Returns the URI of the proxy.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+
+    /// Returns true if connections for `uri` go through the proxy.
+    pub fn intercepts(&self, uri: &Uri) -> bool {
+        let host = match uri.host() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+        let scheme = match uri.scheme_str() {
+            Some("http") => Intercept::Http,
+            Some("https") => Intercept::Https,
+            _ => return false,
+        };
+        (self.intercept == Intercept::All || self.intercept == scheme)
+            && !self.no_proxy.iter().any(|p| host_matches(p, &host))
+    }
+
+    /// Returns the headers to add to a request for `uri`, if it is an
+    /// `http` URI that goes through the proxy.
+    pub fn http_headers(&self, uri: &Uri) -> Option<&HeaderMap> {
+        match uri.scheme_str() == Some("http") && self.intercepts(uri) {
+            true => Some(&self.headers),
+            false => None,
+        }
+    }
+
+    /// Open a tunnel to the authority of `uri` on `stream`, a connection to
+    /// the proxy.
+    pub(super) async fn tunnel<S>(&self, stream: &mut S, uri: &Uri) -> Result<(), ConnectError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let host = uri
+            .host()
+            .ok_or(ConnectError::new("invalid URI: missing host"))?;
+        let authority = format!("{}:{}", host, uri.port_u16().unwrap_or(443));
+        let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority).into_bytes();
+        for (name, value) in &self.headers {
+            request.extend_from_slice(name.as_str().as_bytes());
+            request.extend_from_slice(b": ");
+            request.extend_from_slice(value.as_bytes());
+            request.extend_from_slice(b"\r\n");
+        }
+        request.extend_from_slice(b"\r\n");
+        stream.write_all(&request).await.map_err(tunnel_error)?;
+
+        // The proxy must not send anything after the response until the
+        // client starts the TLS handshake, so this reads exactly the response
+        let mut response = Vec::new();
+        let mut buf = [0; 1024];
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() > MAX_RESPONSE_SIZE {
+                return Err(ConnectError::new("proxy CONNECT response too large"));
+            }
+            let n = stream.read(&mut buf).await.map_err(tunnel_error)?;
+            if n == 0 {
+                return Err(tunnel_error(io::ErrorKind::UnexpectedEof.into()));
+            }
+            response.extend_from_slice(&buf[..n]);
+            if let Some(end) = find_end(&response) {
+                if end != response.len() {
+                    return Err(ConnectError::new(
+                        "unexpected data after proxy CONNECT response",
+                    ));
+                }
+            }
+        }
+        match parse_status(&response) {
+            Some(status) if status.is_success() => Ok(()),
+            Some(status) => Err(ConnectError::new("proxy refused to open a tunnel")
+                .cause(format!("unexpected status: {}", status))),
+            None => Err(ConnectError::new("invalid proxy CONNECT response")),
+        }
+    }
+}
+
+// The size limit of the response to a `CONNECT` request
+const MAX_RESPONSE_SIZE: usize = 16 * 1024;
+
+fn tunnel_error(e: io::Error) -> ConnectError {
+    ConnectError::new("proxy CONNECT failed").cause(e)
+}
+
+fn find_end(response: &[u8]) -> Option<usize> {
+    response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|i| i + 4)
+}
+
+/// Parse the status code of a status line such as `HTTP/1.1 200 OK`.
+fn parse_status(response: &[u8]) -> Option<StatusCode> {
+    let rest = response
+        .strip_prefix(b"HTTP/1.1 ")
+        .or_else(|| response.strip_prefix(b"HTTP/1.0 "))?;
+    StatusCode::from_bytes(rest.get(..3)?).ok()
+}
+
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("uri", &self.uri)
+            .field("intercept", &self.intercept)
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intercepts() {
+        let proxy = Proxy::http("http://proxy:3128")
+            .unwrap()
+            .no_proxy("localhost")
+            .no_proxy("*.Internal");
+        let intercepts = |uri| proxy.intercepts(&Uri::from_static(uri));
+        assert!(intercepts("http://example.com/"));
+        assert!(!intercepts("https://example.com/"));
+        assert!(!intercepts("http://LOCALHOST:8080/"));
+        assert!(!intercepts("http://api.internal/"));
+        assert!(intercepts("http://internal/"));
+
+        let proxy = Proxy::https("http://proxy").unwrap();
+        assert!(proxy.intercepts(&Uri::from_static("https://example.com/")));
+        assert!(!proxy.intercepts(&Uri::from_static("http://example.com/")));
+        let proxy = Proxy::all("http://proxy").unwrap();
+        assert!(proxy.intercepts(&Uri::from_static("https://example.com/")));
+        assert!(proxy.intercepts(&Uri::from_static("http://example.com/")));
+
+        assert!(Proxy::http("https://proxy").is_err());
+        assert!(Proxy::http("/proxy").is_err());
+        assert!(Proxy::http("http://[bad").is_err());
+    }
+
+    #[test]
+    fn http_headers() {
+        let proxy = Proxy::all("http://proxy").unwrap();
+        let uri = Uri::from_static("http://example.com/");
+        assert!(proxy.http_headers(&uri).unwrap().is_empty());
+
+        let proxy = proxy.basic_auth("foo", "bar");
+        let headers = proxy.http_headers(&uri).unwrap();
+        assert_eq!(headers["proxy-authorization"], "Basic Zm9vOmJhcg==");
+        assert!(!format!("{:?}", proxy).contains("Zm9v"));
+        assert!(proxy
+            .http_headers(&Uri::from_static("https://example.com/"))
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn tunnel() {
+        use crate::connector::HttpConnector;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 1024];
+            let n = stream.read(&mut buf).await.unwrap();
+            let request = String::from_utf8(buf[..n].to_vec()).unwrap();
+            assert!(request
+                .starts_with("CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n"));
+            assert!(request.contains("proxy-authorization: Basic Zm9vOmJhcg==\r\n"));
+            let response = b"HTTP/1.1 200 Connection established\r\n\r\n";
+            stream.write_all(response).await.unwrap();
+            // Echo through the tunnel
+            let n = stream.read(&mut buf).await.unwrap();
+            stream.write_all(&buf[..n]).await.unwrap();
+        });
+
+        let proxy = Proxy::https(format!("http://{}", addr))
+            .unwrap()
+            .basic_auth("foo", "bar");
+        let connector = HttpConnector::new().proxy(Some(proxy));
+        let uri = Uri::from_static("https://example.com/");
+        let mut conn = connector.connect(uri, true).await.unwrap();
+        conn.write_all(b"hello").await.unwrap();
+        let mut buf = [0; 5];
+        conn.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[tokio::test]
+    async fn tunnel_rejected() {
+        let proxy = Proxy::https("http://proxy").unwrap();
+        let uri = Uri::from_static("https://example.com:8443/");
+        for (response, msg) in [
+            (
+                &b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n"[..],
+                "proxy refused to open a tunnel: unexpected status: 407 Proxy Authentication Required",
+            ),
+            (b"SSH-2.0\r\n\r\n", "invalid proxy CONNECT response"),
+            (
+                b"HTTP/1.1 200 OK\r\n\r\nextra",
+                "unexpected data after proxy CONNECT response",
+            ),
+        ] {
+            let (mut client, mut server) = tokio::io::duplex(1024);
+            server.write_all(response).await.unwrap();
+            let err = proxy.tunnel(&mut client, &uri).await.unwrap_err();
+            assert_eq!(err.to_string(), msg);
+            let mut buf = vec![0; 1024];
+            let n = server.read(&mut buf).await.unwrap();
+            assert!(buf[..n].starts_with(b"CONNECT example.com:8443 HTTP/1.1\r\n"));
+        }
+    }
+}
@@ -0,0 +1,114 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use tokio::net::lookup_host;
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+/// Resolves host names to socket addresses
+///
+/// [`HttpConnector::resolver()`] makes the built-in connectors use a custom
+/// resolver, e.g. one based on service discovery, instead of the system
+/// resolver. The connector tries the returned addresses in order until a
+/// connection succeeds. IP addresses in URIs are connected to directly
+/// without asking the resolver.
+///
+/// [`HttpConnector::resolver()`]: crate::HttpConnector::resolver
+pub trait Resolve: Send + Sync + 'static {
+    /// Resolve `host`, to connect to `port`.
+    #[allow(clippy::type_complexity)]
+    fn resolve<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send + 'a>>;
+}
+
+/// A resolver using the system resolver through `getaddrinfo`
+///
+/// This is what the connectors use by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GaiResolver;
+
+impl GaiResolver {
+    pub fn new() -> Self {
+        GaiResolver
+    }
+}
+
+impl Resolve for GaiResolver {
+    fn resolve<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send + 'a>> {
+        Box::pin(async move { Ok(lookup_host((host, port)).await?.collect()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, HttpConnector};
+    use hyper::StatusCode;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    struct StaticResolver(Vec<SocketAddr>);
+
+    impl Resolve for StaticResolver {
+        fn resolve<'a>(
+            &'a self,
+            host: &'a str,
+            _port: u16,
+        ) -> Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send + 'a>> {
+            Box::pin(async move {
+                match host {
+                    "service.internal" => Ok(self.0.clone()),
+                    _ => Err(io::Error::new(io::ErrorKind::NotFound, "unknown host")),
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_resolver() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf).await.unwrap();
+            let response = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+            stream.write_all(response).await.unwrap();
+        });
+
+        // The addresses are tried in order
+        let unreachable = "127.0.0.1:1".parse().unwrap();
+        let resolver = StaticResolver(vec![unreachable, addr]);
+        let client = Client::with_connector(HttpConnector::new().resolver(resolver));
+        let response = client.get("http://service.internal/").unwrap().send();
+        assert_eq!(response.await.unwrap().status(), StatusCode::OK);
+
+        let err = client
+            .get("http://other.internal/")
+            .unwrap()
+            .send()
+            .await
+            .unwrap_err();
+        let io_error = err.as_connect_error().unwrap().io_error().unwrap();
+        assert_eq!(io_error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn gai_resolver() {
+        let addrs = GaiResolver::new().resolve("127.0.0.1", 80).await.unwrap();
+        assert_eq!(addrs, ["127.0.0.1:80".parse().unwrap()]);
+    }
+}
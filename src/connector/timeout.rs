@@ -0,0 +1,217 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use hyper::client::connect::{Connected, Connection};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{sleep_until, Instant, Sleep};
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// A stream wrapper enforcing read and write inactivity timeouts
+///
+/// A read or write fails with an I/O error of kind [`TimedOut`] if it makes
+/// no progress for the configured duration, catching stalled connections in
+/// the middle of a response that neither a connect timeout nor a total
+/// request timeout handle well, e.g. when streaming large bodies. Writing
+/// data also restarts the read timeout, as the peer is not expected to
+/// respond before the request is sent.
+///
+/// Note that idle pooled connections are polled for reads, so they are
+/// closed when the read timeout elapses.
+///
+/// [`TimedOut`]: std::io::ErrorKind::TimedOut
+pub struct TimeoutStream<S> {
+    stream: S,
+    read: Timer,
+    write: Timer,
+}
+
+struct Timer {
+    timeout: Option<Duration>,
+    // Allocated on first use and reset afterwards
+    sleep: Option<Pin<Box<Sleep>>>,
+    active: bool,
+}
+
+impl Timer {
+    fn new(timeout: Option<Duration>) -> Self {
+        Timer {
+            timeout,
+            sleep: None,
+            active: false,
+        }
+    }
+
+    /// Stop the timer if `poll` completed, otherwise start it if it is not
+    /// running and fail if it elapsed.
+    fn track<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<T>>,
+        msg: &'static str,
+    ) -> Poll<io::Result<T>> {
+        if poll.is_ready() {
+            self.stop();
+            return poll;
+        }
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return Poll::Pending,
+        };
+        let deadline = Instant::now() + timeout;
+        let sleep = self
+            .sleep
+            .get_or_insert_with(|| Box::pin(sleep_until(deadline)));
+        if !self.active {
+            sleep.as_mut().reset(deadline);
+            self.active = true;
+        }
+        match sleep.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, msg))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn stop(&mut self) {
+        self.active = false;
+    }
+}
+
+impl<S> TimeoutStream<S> {
+    /// Wrap `stream` without any timeouts.
+    pub fn new(stream: S) -> Self {
+        TimeoutStream {
+            stream,
+            read: Timer::new(None),
+            write: Timer::new(None),
+        }
+    }
+
+    /// Set the read timeout. Default is None.
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.read = Timer::new(timeout);
+        self
+    }
+
+    /// Set the write timeout. Default is None.
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.write = Timer::new(timeout);
+        self
+    }
+
+    /// Returns a reference to the wrapped stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the wrapped stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Returns the wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: Connection> Connection for TimeoutStream<S> {
+    fn connected(&self) -> Connected {
+        self.stream.connected()
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TimeoutStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.stream).poll_read(cx, buf);
+        this.read.track(cx, poll, "read timed out")
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TimeoutStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.stream).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            if n > 0 {
+                this.read.stop();
+            }
+        }
+        this.write.track(cx, poll, "write timed out")
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.stream).poll_flush(cx);
+        this.write.track(cx, poll, "write timed out")
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.stream).poll_shutdown(cx);
+        this.write.track(cx, poll, "write timed out")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test(start_paused = true)]
+    async fn read_timeout() {
+        let (client, mut server) = duplex(64);
+        let mut stream = TimeoutStream::new(client).read_timeout(Some(Duration::from_secs(5)));
+
+        let read = async {
+            let mut buf = [0u8; 5];
+            stream.read_exact(&mut buf).await.map(|_| buf)
+        };
+        let write = async {
+            // Each chunk arrives within the timeout
+            for chunk in [&b"he"[..], b"ll", b"o"] {
+                tokio::time::sleep(Duration::from_secs(4)).await;
+                server.write_all(chunk).await.unwrap();
+            }
+        };
+        let (buf, ()) = tokio::join!(read, write);
+        assert_eq!(&buf.unwrap(), b"hello");
+
+        let mut buf = [0u8; 1];
+        let err = stream.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(err.to_string(), "read timed out");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn write_timeout() {
+        let (client, mut server) = duplex(4);
+        let mut stream = TimeoutStream::new(client).write_timeout(Some(Duration::from_secs(5)));
+        stream.write_all(b"abcd").await.unwrap();
+        let err = stream.write_all(b"e").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        // Without timeouts, pending operations wait indefinitely
+        let mut stream = TimeoutStream::new(stream.into_inner());
+        let mut buf = [0u8; 4];
+        server.read_exact(&mut buf).await.unwrap();
+        let read = tokio::time::timeout(Duration::from_secs(60), stream.read(&mut buf));
+        assert!(read.await.is_err());
+    }
+}
@@ -0,0 +1,125 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::header_params::{decode_ext_value, parse_params};
+
+use hyper::header::{HeaderMap, CONTENT_DISPOSITION};
+
+/// A parsed `Content-Disposition` response header, see RFC 6266
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentDisposition {
+    /// The disposition type, lowercased, e.g. `attachment` or `inline`.
+    pub disposition: String,
+    /// The file name from the `filename*` parameter if present and decodable,
+    /// otherwise from the `filename` parameter.
+    ///
+    /// The name is returned as sent by the server and may contain path
+    /// separators, see [`ContentDisposition::safe_filename()`].
+    pub filename: Option<String>,
+}
+
+impl ContentDisposition {
+    /// Parse the `Content-Disposition` header in `headers`.
+    ///
+    /// Returns `None` if the header is missing or malformed.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        // Servers commonly send UTF-8 in `filename` even though only ASCII
+        // is allowed, so the value is decoded leniently.
+        let value = String::from_utf8_lossy(headers.get(CONTENT_DISPOSITION)?.as_bytes());
+        let end = value.find(';').unwrap_or(value.len());
+        let disposition = value[..end].trim().to_ascii_lowercase();
+        if disposition.is_empty() {
+            return None;
+        }
+        let (params, rest) = parse_params(&value[end..])?;
+        if !rest.is_empty() {
+            return None;
+        }
+        let ext_filename = params
+            .iter()
+            .find(|(name, _)| name == "filename*")
+            .and_then(|(_, value)| decode_ext_value(value));
+        let filename = ext_filename.or_else(|| {
+            params
+                .into_iter()
+                .find(|(name, _)| name == "filename")
+                .map(|(_, value)| value)
+        });
+        Some(ContentDisposition {
+            disposition,
+            filename,
+        })
+    }
+
+    /// Returns the file name reduced to its last path component, with
+    /// control characters removed.
+    ///
+    /// Returns `None` if there is no file name or nothing usable remains,
+    /// e.g. for `..`.
+    pub fn safe_filename(&self) -> Option<String> {
+        sanitize_filename(self.filename.as_deref()?)
+    }
+}
+
+/// Reduce `name` to a file name that is safe to join to a directory path.
+pub(crate) fn sanitize_filename(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next()?;
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    let name = name.trim();
+    match name {
+        "" | "." | ".." => None,
+        name => Some(name.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn parse(value: &'static str) -> Option<ContentDisposition> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_DISPOSITION, HeaderValue::from_static(value));
+        ContentDisposition::from_headers(&headers)
+    }
+
+    #[test]
+    fn parse_filename() {
+        let cd = parse("Attachment; filename=\"report 1.pdf\"").unwrap();
+        assert_eq!(cd.disposition, "attachment");
+        assert_eq!(cd.filename.as_deref(), Some("report 1.pdf"));
+
+        let cd = parse("attachment; filename=rates.txt; filename*=UTF-8''%e2%82%ac%20rates.txt")
+            .unwrap();
+        assert_eq!(cd.filename.as_deref(), Some("\u{20ac} rates.txt"));
+
+        // Fall back to `filename` if `filename*` cannot be decoded
+        let cd = parse("attachment; filename*=utf-16''abc; filename=plain.txt").unwrap();
+        assert_eq!(cd.filename.as_deref(), Some("plain.txt"));
+
+        let cd = parse("inline").unwrap();
+        assert_eq!(cd.disposition, "inline");
+        assert!(cd.filename.is_none());
+
+        assert!(parse("; filename=a").is_none());
+        assert!(parse("attachment; filename=\"a").is_none());
+    }
+
+    #[test]
+    fn safe_filename() {
+        let cases = [
+            ("report.pdf", Some("report.pdf")),
+            ("../../etc/passwd", Some("passwd")),
+            ("C:\\Users\\a\\file.txt", Some("file.txt")),
+            ("a\u{0}b\nc", Some("abc")),
+            ("..", None),
+            ("dir/", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(sanitize_filename(name).as_deref(), expected, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,333 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::async_client::Client;
+use crate::content_disposition::{sanitize_filename, ContentDisposition};
+use crate::error::Error;
+use crate::response_ext::write_body;
+use crate::Response;
+
+use headers::{ContentRange, ETag, HeaderMap, HeaderMapExt, LastModified, Range};
+use hyper::header::{HeaderValue, ACCEPT_ENCODING, ETAG, IF_RANGE, LAST_MODIFIED};
+use hyper::{StatusCode, Uri};
+use tokio::fs::{self, File, OpenOptions};
+
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+impl Client {
+    /// Download the resource at `uri` to the file at `path`.
+    ///
+    /// The response body is streamed to `<path>.part` which is renamed to
+    /// `path` once the download is complete and the file is synced to disk.
+    /// If a previous download was interrupted, this will try to resume it
+    /// by sending a `Range` request conditioned on the resource not having
+    /// changed (using `If-Range` with the `ETag` or `Last-Modified` value of
+    /// the original response, which is stored in `<path>.part.validator`).
+    /// If the server does not support range requests or the resource has
+    /// changed, the download starts over.
+    ///
+    /// Since ranges refer to the encoded representation, the request asks
+    /// for the unencoded resource with `Accept-Encoding: identity`, and the
+    /// body is saved as received even if the client is configured with
+    /// `ClientBuilder::accept_encodings()`.
+    ///
+    /// Returns the size of the downloaded file. Returns
+    /// [`Error::UnexpectedStatus`] if the server responds with an error
+    /// status code or an unexpected partial response.
+    pub async fn download<U, P>(&self, uri: U, path: P) -> Result<u64, Error>
+    where
+        Uri: TryFrom<U>,
+        <Uri as TryFrom<U>>::Error: Into<http::Error>,
+        P: AsRef<Path>,
+    {
+        let uri = Uri::try_from(uri)
+            .map_err(Into::into)
+            .map_err(Error::Http)?;
+        let path = path.as_ref();
+        let part_path = append_extension(path, ".part");
+        let validator_path = append_extension(path, ".part.validator");
+
+        let resume = resume_state(&part_path, &validator_path).await?;
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+        if let Some((offset, ref validator)) = resume {
+            headers.typed_insert(Range::bytes(offset..).expect("valid range"));
+            headers.insert(IF_RANGE, validator.clone());
+        }
+        let response = self.get::<Uri>(uri)?.headers(headers).send().await?;
+        save_download(response, resume, path).await
+    }
+
+    /// Download the resource at `uri` into the directory `dir`.
+    ///
+    /// The file name is taken from the `Content-Disposition` header of the
+    /// response, see [`ContentDisposition::safe_filename()`], or else from
+    /// the last segment of the URI path. If neither is usable, the file is
+    /// named `download`. An existing file with the same name is replaced.
+    ///
+    /// Unlike [`download()`], interrupted downloads are not resumed since
+    /// the file name is only known once the response has been received.
+    ///
+    /// Returns the path and the size of the downloaded file.
+    ///
+    /// [`download()`]: Client::download
+    pub async fn download_to_dir<U, P>(&self, uri: U, dir: P) -> Result<(PathBuf, u64), Error>
+    where
+        Uri: TryFrom<U>,
+        <Uri as TryFrom<U>>::Error: Into<http::Error>,
+        P: AsRef<Path>,
+    {
+        let uri = Uri::try_from(uri)
+            .map_err(Into::into)
+            .map_err(Error::Http)?;
+        let response = self.get::<Uri>(uri.clone())?.send().await?;
+        let name = ContentDisposition::from_headers(response.headers())
+            .and_then(|cd| cd.safe_filename())
+            .or_else(|| sanitize_filename(uri.path()))
+            .unwrap_or_else(|| DEFAULT_FILENAME.to_owned());
+        let path = dir.as_ref().join(name);
+        let len = save_download(response, None, &path).await?;
+        Ok((path, len))
+    }
+}
+
+const DEFAULT_FILENAME: &str = "download";
+
+/// Write the body of `response` to `path` through `<path>.part`, appending
+/// to the partial file if `response` resumes the download described by
+/// `resume`.
+async fn save_download(
+    response: Response,
+    resume: Option<(u64, HeaderValue)>,
+    path: &Path,
+) -> Result<u64, Error> {
+    let part_path = append_extension(path, ".part");
+    let validator_path = append_extension(path, ".part.validator");
+    let mut file = match (response.status(), resume) {
+        (StatusCode::PARTIAL_CONTENT, Some((offset, _)))
+            if content_range_start(response.headers()) == Some(offset) =>
+        {
+            OpenOptions::new().append(true).open(&part_path).await?
+        }
+        (status, _) if status.is_success() && status != StatusCode::PARTIAL_CONTENT => {
+            match validator(response.headers()) {
+                Some(validator) => fs::write(&validator_path, validator.as_bytes()).await?,
+                None => remove_if_exists(&validator_path).await?,
+            }
+            File::create(&part_path).await?
+        }
+        (status, _) => return Err(Error::UnexpectedStatus(status)),
+    };
+    write_body(response.into_body(), &mut file).await?;
+    let len = file.metadata().await?.len();
+    drop(file);
+
+    fs::rename(&part_path, path).await?;
+    remove_if_exists(&validator_path).await?;
+    Ok(len)
+}
+
+fn append_extension(path: &Path, ext: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(ext);
+    path.into()
+}
+
+/// Returns the length of the partially downloaded file and the validator to
+/// use in `If-Range`, if an interrupted download can be resumed.
+async fn resume_state(
+    part_path: &Path,
+    validator_path: &Path,
+) -> io::Result<Option<(u64, HeaderValue)>> {
+    let validator = match fs::read(validator_path).await {
+        Ok(validator) => validator,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let validator = match HeaderValue::from_bytes(&validator) {
+        Ok(validator) => validator,
+        Err(_) => return Ok(None),
+    };
+    match fs::metadata(part_path).await {
+        Ok(metadata) if metadata.len() > 0 => Ok(Some((metadata.len(), validator))),
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the value to use in `If-Range` for resuming a download of the
+/// response with the given headers.
+///
+/// Weak entity tags cannot be used in `If-Range`, see RFC 7233 section 3.2.
+fn validator(headers: &HeaderMap) -> Option<HeaderValue> {
+    if headers.typed_get::<ETag>().is_some() {
+        let etag = headers.get(ETAG)?;
+        if !etag.as_bytes().starts_with(b"W/") {
+            return Some(etag.clone());
+        }
+    }
+    headers.typed_get::<LastModified>()?;
+    headers.get(LAST_MODIFIED).cloned()
+}
+
+fn content_range_start(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .typed_get::<ContentRange>()
+        .and_then(|range| range.bytes_range())
+        .map(|(start, _)| start)
+}
+
+async fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path).await {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::*;
+    use crate::async_client::tests::test_http_server_capture_request;
+    use crate::connector::HttpConnector;
+
+    fn test_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "simple-hyper-client-download-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[tokio::test]
+    async fn fresh_download() {
+        let (addr, _) = test_http_server_capture_request(
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 13\r\n\r\nhello, world!",
+        )
+        .await;
+        let path = test_path("fresh");
+        let client = Client::with_connector(HttpConnector::new());
+        let len = client
+            .download(format!("http://{}/", addr), &path)
+            .await
+            .unwrap();
+
+        assert_eq!(len, 13);
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello, world!");
+        assert!(!append_extension(&path, ".part").exists());
+        assert!(!append_extension(&path, ".part.validator").exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_download() {
+        let (addr, rx) = test_http_server_capture_request(
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-12/13\r\nContent-Length: 8\r\n\r\n, world!",
+        )
+        .await;
+        let path = test_path("resume");
+        std::fs::write(append_extension(&path, ".part"), b"hello").unwrap();
+        std::fs::write(append_extension(&path, ".part.validator"), b"\"v1\"").unwrap();
+
+        let client = Client::with_connector(HttpConnector::new());
+        let len = client
+            .download(format!("http://{}/", addr), &path)
+            .await
+            .unwrap();
+
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("\r\nrange: bytes=5-\r\n"));
+        assert!(request.contains("\r\nif-range: \"v1\"\r\n"));
+        assert_eq!(len, 13);
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello, world!");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn resume_download_with_compression() {
+        use crate::Encoding;
+
+        let (addr, rx) = test_http_server_capture_request(
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-12/13\r\nContent-Length: 8\r\n\r\n, world!",
+        )
+        .await;
+        let path = test_path("resume-compression");
+        std::fs::write(append_extension(&path, ".part"), b"hello").unwrap();
+        std::fs::write(append_extension(&path, ".part.validator"), b"\"v1\"").unwrap();
+
+        let client = Client::builder()
+            .accept_encodings(&[Encoding::Gzip])
+            .build(HttpConnector::new());
+        let len = client
+            .download(format!("http://{}/", addr), &path)
+            .await
+            .unwrap();
+
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("\r\naccept-encoding: identity\r\n"));
+        assert!(!request.contains("gzip"));
+        assert_eq!(len, 13);
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello, world!");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_download_restarts_when_modified() {
+        let (addr, _) = test_http_server_capture_request(
+            "HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nContent-Length: 6\r\n\r\nnew v2",
+        )
+        .await;
+        let path = test_path("restart");
+        std::fs::write(append_extension(&path, ".part"), b"hello").unwrap();
+        std::fs::write(append_extension(&path, ".part.validator"), b"\"v1\"").unwrap();
+
+        let client = Client::with_connector(HttpConnector::new());
+        let len = client
+            .download(format!("http://{}/", addr), &path)
+            .await
+            .unwrap();
+
+        assert_eq!(len, 6);
+        assert_eq!(std::fs::read(&path).unwrap(), b"new v2");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn download_to_dir() {
+        let (addr, _) = test_http_server_capture_request(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Disposition: attachment; filename=\"../rates.txt\"; \
+             filename*=UTF-8''%e2%82%ac%20rates.txt\r\n\
+             Content-Length: 5\r\n\r\nrates",
+        )
+        .await;
+        let dir = test_path("dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        let client = Client::with_connector(HttpConnector::new());
+        let (path, len) = client
+            .download_to_dir(format!("http://{}/export", addr), &dir)
+            .await
+            .unwrap();
+        assert_eq!(path, dir.join("\u{20ac} rates.txt"));
+        assert_eq!(len, 5);
+        assert_eq!(std::fs::read(&path).unwrap(), b"rates");
+
+        // Without `Content-Disposition` the name is taken from the URI
+        let (addr, _) =
+            test_http_server_capture_request("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                .await;
+        let (path, _) = client
+            .download_to_dir(format!("http://{}/files/report.csv", addr), &dir)
+            .await
+            .unwrap();
+        assert_eq!(path, dir.join("report.csv"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -4,15 +4,70 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use hyper::Method;
+#[cfg(feature = "tcp")]
+use crate::connector::ConnectError;
+#[cfg(feature = "serde")]
+use crate::error_report::ErrorReport;
+#[cfg(feature = "graphql")]
+use crate::graphql::GraphQlError;
+use crate::request_ext::redact_uri;
+use crate::request_id::RequestId;
 
-use std::{error, fmt};
+use hyper::header::HeaderValue;
+use hyper::{Method, StatusCode, Uri, Version};
 
+use std::time::Duration;
+use std::{error, fmt, io};
+
+/// The error type of this crate
+///
+/// Some variants only exist with the cargo feature they belong to, e.g.
+/// `Json` requires the `json` feature, so matches must have a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     Http(http::Error),
     Hyper(hyper::Error),
+    Connect(hyper::Error),
+    Timeout(hyper::Error),
+    Tls(hyper::Error),
+    Body(hyper::Error),
     BodyNotAllowed(Method),
+    TunnelRejected(StatusCode),
+    ResponseHeadersTooLarge(usize),
+    InvalidResponse(&'static str),
+    InvalidUri(&'static str),
+    UnsupportedContentType(Option<HeaderValue>),
+    Io(io::Error),
+    UnexpectedStatus(StatusCode),
+    UnexpectedVersion(Version),
+    ResponseBodyTooLarge(u64),
+    ChecksumMismatch(&'static str),
+    WithRequestId(RequestId, Box<Error>),
+    WithContext(RequestContext, Box<Error>),
+    InvalidConfig(&'static str),
+    RequestTimeout(Duration),
+    InFlightLimit(Duration),
+    Cancelled,
+    RuntimeStopped,
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+    #[cfg(feature = "graphql")]
+    GraphQl(Vec<GraphQlError>),
+    #[cfg(feature = "xml")]
+    XmlSerialize(quick_xml::SeError),
+    #[cfg(feature = "xml")]
+    XmlDeserialize(quick_xml::DeError),
+    #[cfg(feature = "cbor")]
+    CborSerialize(ciborium::ser::Error<io::Error>),
+    #[cfg(feature = "cbor")]
+    CborDeserialize(ciborium::de::Error<io::Error>),
+    #[cfg(feature = "netrc")]
+    InvalidNetrc(&'static str),
+    #[cfg(feature = "msgpack")]
+    MsgPackSerialize(rmp_serde::encode::Error),
+    #[cfg(feature = "msgpack")]
+    MsgPackDeserialize(rmp_serde::decode::Error),
 }
 
 impl From<http::Error> for Error {
@@ -21,9 +76,211 @@ impl From<http::Error> for Error {
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
 impl From<hyper::Error> for Error {
     fn from(e: hyper::Error) -> Self {
-        Error::Hyper(e)
+        if e.is_timeout() || has_timed_out_source(&e) {
+            Error::Timeout(e)
+        } else if e.is_connect() && has_tls_source(&e) {
+            Error::Tls(e)
+        } else if e.is_connect() {
+            Error::Connect(e)
+        } else if e.is_body_write_aborted() || (e.is_user() && error::Error::source(&e).is_some()) {
+            // NOTE: the only user errors with a cause are errors returned by
+            // the request body stream.
+            Error::Body(e)
+        } else {
+            Error::Hyper(e)
+        }
+    }
+}
+
+fn has_timed_out_source(e: &(dyn error::Error + 'static)) -> bool {
+    let mut source = e.source();
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<io::Error>() {
+            if e.kind() == io::ErrorKind::TimedOut {
+                return true;
+            }
+        }
+        source = e.source();
+    }
+    false
+}
+
+#[cfg(feature = "tcp")]
+fn has_tls_source(e: &(dyn error::Error + 'static)) -> bool {
+    let mut source = e.source();
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<ConnectError>() {
+            return e.is_tls();
+        }
+        source = e.source();
+    }
+    false
+}
+
+#[cfg(not(feature = "tcp"))]
+fn has_tls_source(_e: &(dyn error::Error + 'static)) -> bool {
+    false
+}
+
+impl Error {
+    /// Convert an error returned while reading a response body.
+    ///
+    /// Timeouts are classified as by `From<hyper::Error>`, other errors are
+    /// reported as [`Error::Body`].
+    pub(crate) fn from_body(e: hyper::Error) -> Self {
+        match Error::from(e) {
+            Error::Hyper(e) => Error::Body(e),
+            e => e,
+        }
+    }
+
+    /// Returns true if the error was caused by a timeout, such as the
+    /// connect timeout of [`HttpConnector`].
+    ///
+    /// [`HttpConnector`]: crate::HttpConnector
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            *self.without_context(),
+            Error::Timeout(_) | Error::RequestTimeout(_) | Error::InFlightLimit(_)
+        )
+    }
+
+    /// Returns true if the error occurred while establishing a connection.
+    ///
+    /// This includes connect timeouts and TLS errors.
+    pub fn is_connect(&self) -> bool {
+        match *self.without_context() {
+            Error::Connect(_) | Error::Tls(_) => true,
+            Error::Timeout(ref e) => e.is_connect(),
+            _ => false,
+        }
+    }
+
+    /// Returns true if the TLS handshake failed, e.g. because the server
+    /// certificate is invalid or does not match the hostname.
+    ///
+    /// The error of the TLS implementation is part of the [`source()`]
+    /// chain.
+    ///
+    /// [`source()`]: std::error::Error::source
+    pub fn is_tls(&self) -> bool {
+        matches!(*self.without_context(), Error::Tls(_))
+    }
+
+    /// Returns true if the error was caused by an unexpected response status.
+    pub fn is_status(&self) -> bool {
+        self.status().is_some()
+    }
+
+    /// Returns true if the error occurred while sending the request body or
+    /// receiving the response body.
+    pub fn is_body(&self) -> bool {
+        matches!(*self.without_context(), Error::Body(_))
+    }
+
+    /// Returns true if the request was cancelled, see
+    /// [`blocking::CancelHandle`].
+    ///
+    /// [`blocking::CancelHandle`]: crate::blocking::CancelHandle
+    pub fn is_cancelled(&self) -> bool {
+        matches!(*self.without_context(), Error::Cancelled)
+    }
+
+    /// Returns the unexpected response status that caused the error, if any.
+    pub fn status(&self) -> Option<StatusCode> {
+        match *self.without_context() {
+            Error::UnexpectedStatus(status) | Error::TunnelRejected(status) => Some(status),
+            _ => None,
+        }
+    }
+
+    /// Returns the error of [`HttpConnector`] or [`HttpsConnector`] if the
+    /// connection could not be established.
+    ///
+    /// [`HttpConnector`]: crate::HttpConnector
+    /// [`HttpsConnector`]: crate::HttpsConnector
+    #[cfg(feature = "tcp")]
+    pub fn as_connect_error(&self) -> Option<&ConnectError> {
+        let mut source: Option<&(dyn error::Error + 'static)> = Some(self);
+        while let Some(e) = source {
+            if let Some(e) = e.downcast_ref::<ConnectError>() {
+                return Some(e);
+            }
+            source = e.source();
+        }
+        None
+    }
+
+    /// Returns a serializable description of the error, see
+    /// [`ErrorReport`].
+    #[cfg(feature = "serde")]
+    pub fn report(&self) -> ErrorReport {
+        ErrorReport::new(self)
+    }
+
+    /// Returns the method and URI of the failed request, see
+    /// [`ClientBuilder::error_context()`].
+    ///
+    /// [`ClientBuilder::error_context()`]: crate::ClientBuilder::error_context
+    pub fn context(&self) -> Option<&RequestContext> {
+        match *self {
+            Error::WithContext(ref context, _) => Some(context),
+            Error::WithRequestId(_, ref e) => e.context(),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without the [`Error::WithRequestId`] and
+    /// [`Error::WithContext`] wrappers.
+    pub(crate) fn without_context(&self) -> &Error {
+        match *self {
+            Error::WithRequestId(_, ref e) | Error::WithContext(_, ref e) => e.without_context(),
+            ref e => e,
+        }
+    }
+}
+
+/// The method and URI of a failed request
+///
+/// Errors are wrapped in [`Error::WithContext`] with the request they
+/// occurred for if [`ClientBuilder::error_context()`] is enabled.
+///
+/// [`ClientBuilder::error_context()`]: crate::ClientBuilder::error_context
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    method: Method,
+    uri: Uri,
+}
+
+impl RequestContext {
+    pub(crate) fn new(method: Method, uri: &Uri, redact_query: bool) -> Self {
+        let uri = redact_uri(uri, redact_query);
+        RequestContext { method, uri }
+    }
+
+    /// Returns the method of the request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the URI of the request, without credentials in the userinfo
+    /// and without the query if it was redacted.
+    pub fn uri(&self) -> &Uri {
+        &self.uri
+    }
+}
+
+impl fmt::Display for RequestContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.uri)
     }
 }
 
@@ -32,9 +289,81 @@ impl fmt::Display for Error {
         match *self {
             Error::Http(ref e) => write!(f, "{}", e),
             Error::Hyper(ref e) => write!(f, "{}", e),
+            Error::Connect(ref e) => write!(f, "{}", e),
+            Error::Timeout(ref e) => write!(f, "{}", e),
+            Error::Tls(ref e) => write!(f, "{}", e),
+            Error::Body(ref e) => write!(f, "{}", e),
             Error::BodyNotAllowed(ref m) => {
                 write!(f, "{} requests are not allowed to have a body", m)
             }
+            Error::TunnelRejected(ref status) => {
+                write!(f, "tunnel was not established: {}", status)
+            }
+            Error::ResponseHeadersTooLarge(limit) => write!(
+                f,
+                "response headers exceed the maximum allowed size of {} bytes",
+                limit
+            ),
+            Error::InvalidResponse(msg) => write!(f, "invalid response: {}", msg),
+            Error::InvalidUri(msg) => write!(f, "invalid URI: {}", msg),
+            Error::UnsupportedContentType(Some(ref value)) => {
+                write!(f, "unsupported response content type: {:?}", value)
+            }
+            Error::UnsupportedContentType(None) => write!(f, "response has no content type"),
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::UnexpectedStatus(ref status) => {
+                write!(f, "unexpected response status: {}", status)
+            }
+            Error::UnexpectedVersion(version) => {
+                write!(f, "unexpected HTTP version: {:?}", version)
+            }
+            Error::ResponseBodyTooLarge(limit) => write!(
+                f,
+                "response body exceeds the maximum allowed size of {} bytes",
+                limit
+            ),
+            Error::ChecksumMismatch(algorithm) => {
+                write!(f, "response body does not match its {} checksum", algorithm)
+            }
+            Error::WithRequestId(ref id, ref e) => write!(f, "{} (request ID: {})", e, id),
+            Error::WithContext(ref context, ref e) => write!(f, "{}: {}", context, e),
+            Error::InvalidConfig(msg) => write!(f, "invalid client configuration: {}", msg),
+            Error::RequestTimeout(duration) => write!(f, "request timed out after {:?}", duration),
+            Error::InFlightLimit(duration) => write!(
+                f,
+                "timed out after {:?} waiting for an in-flight request slot",
+                duration
+            ),
+            Error::Cancelled => write!(f, "request was cancelled"),
+            Error::RuntimeStopped => {
+                write!(
+                    f,
+                    "the client runtime stopped before completing the request"
+                )
+            }
+            #[cfg(feature = "json")]
+            Error::Json(ref e) => write!(f, "JSON error: {}", e),
+            #[cfg(feature = "graphql")]
+            Error::GraphQl(ref errors) => match errors.len() {
+                1 => write!(f, "GraphQL error: {}", errors[0]),
+                n => write!(f, "GraphQL error: {} (and {} more)", errors[0], n - 1),
+            },
+            #[cfg(feature = "xml")]
+            Error::XmlSerialize(ref e) => write!(f, "XML serialization error: {}", e),
+            #[cfg(feature = "xml")]
+            Error::XmlDeserialize(ref e) => write!(f, "XML deserialization error: {}", e),
+            #[cfg(feature = "cbor")]
+            Error::CborSerialize(ref e) => write!(f, "CBOR serialization error: {}", e),
+            #[cfg(feature = "cbor")]
+            Error::CborDeserialize(ref e) => write!(f, "CBOR deserialization error: {}", e),
+            #[cfg(feature = "netrc")]
+            Error::InvalidNetrc(msg) => write!(f, "invalid .netrc file: {}", msg),
+            #[cfg(feature = "msgpack")]
+            Error::MsgPackSerialize(ref e) => write!(f, "MessagePack serialization error: {}", e),
+            #[cfg(feature = "msgpack")]
+            Error::MsgPackDeserialize(ref e) => {
+                write!(f, "MessagePack deserialization error: {}", e)
+            }
         }
     }
 }
@@ -44,7 +373,46 @@ impl error::Error for Error {
         match *self {
             Error::Http(ref e) => Some(e),
             Error::Hyper(ref e) => Some(e),
+            Error::Connect(ref e) => Some(e),
+            Error::Timeout(ref e) => Some(e),
+            Error::Tls(ref e) => Some(e),
+            Error::Body(ref e) => Some(e),
             Error::BodyNotAllowed(_) => None,
+            Error::TunnelRejected(_) => None,
+            Error::ResponseHeadersTooLarge(_) => None,
+            Error::InvalidResponse(_) => None,
+            Error::InvalidUri(_) => None,
+            Error::UnsupportedContentType(_) => None,
+            Error::Io(ref e) => Some(e),
+            Error::UnexpectedStatus(_) => None,
+            Error::UnexpectedVersion(_) => None,
+            Error::ResponseBodyTooLarge(_) => None,
+            Error::ChecksumMismatch(_) => None,
+            Error::WithRequestId(_, ref e) => Some(&**e),
+            Error::WithContext(_, ref e) => Some(&**e),
+            Error::InvalidConfig(_) => None,
+            Error::RequestTimeout(_) => None,
+            Error::InFlightLimit(_) => None,
+            Error::Cancelled => None,
+            Error::RuntimeStopped => None,
+            #[cfg(feature = "json")]
+            Error::Json(ref e) => Some(e),
+            #[cfg(feature = "graphql")]
+            Error::GraphQl(_) => None,
+            #[cfg(feature = "xml")]
+            Error::XmlSerialize(ref e) => Some(e),
+            #[cfg(feature = "xml")]
+            Error::XmlDeserialize(ref e) => Some(e),
+            #[cfg(feature = "cbor")]
+            Error::CborSerialize(ref e) => Some(e),
+            #[cfg(feature = "cbor")]
+            Error::CborDeserialize(ref e) => Some(e),
+            #[cfg(feature = "netrc")]
+            Error::InvalidNetrc(_) => None,
+            #[cfg(feature = "msgpack")]
+            Error::MsgPackSerialize(ref e) => Some(e),
+            #[cfg(feature = "msgpack")]
+            Error::MsgPackDeserialize(ref e) => Some(e),
         }
     }
 }
@@ -0,0 +1,179 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+use crate::request_id::RequestId;
+
+use hyper::Uri;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use std::error::Error as StdError;
+use std::time::Duration;
+
+/// A serializable description of an [`Error`] for structured logging
+///
+/// This is created with [`Error::report()`]. The duration of the failed
+/// request is not part of [`Error`], and neither is the URI unless
+/// [`ClientBuilder::error_context()`] is enabled, but they can be added if
+/// known, e.g. in [`Observer::on_error()`].
+///
+/// The report serializes to a structure with the following fields:
+///
+/// - `kind`: the error variant in snake case, e.g. `"timeout"`,
+/// - `message`: the error message,
+/// - `sources`: the messages of the [`source()`] chain,
+/// - `request_id`: see [`ClientBuilder::request_id_header()`] (optional),
+/// - `uri`: the request URI (optional),
+/// - `elapsed_secs`: the time until the error occurred (optional).
+///
+/// [`Observer::on_error()`]: crate::Observer::on_error
+/// [`source()`]: std::error::Error::source
+/// [`ClientBuilder::request_id_header()`]: crate::ClientBuilder::request_id_header
+/// [`ClientBuilder::error_context()`]: crate::ClientBuilder::error_context
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    kind: &'static str,
+    message: String,
+    sources: Vec<String>,
+    request_id: Option<String>,
+    uri: Option<String>,
+    elapsed: Option<Duration>,
+}
+
+impl ErrorReport {
+    pub(crate) fn new(error: &Error) -> Self {
+        let request_id = request_id(error).map(ToString::to_string);
+        let uri = error.context().map(|context| context.uri().to_string());
+        let inner = error.without_context();
+        let mut sources = Vec::new();
+        let mut source = inner.source();
+        while let Some(e) = source {
+            sources.push(e.to_string());
+            source = e.source();
+        }
+        ErrorReport {
+            kind: kind(inner),
+            message: inner.to_string(),
+            sources,
+            request_id,
+            uri,
+            elapsed: None,
+        }
+    }
+
+    /// Set the URI of the failed request.
+    pub fn uri(mut self, uri: &Uri) -> Self {
+        self.uri = Some(uri.to_string());
+        self
+    }
+
+    /// Set the time from sending the request until the error occurred.
+    pub fn elapsed(mut self, elapsed: Duration) -> Self {
+        self.elapsed = Some(elapsed);
+        self
+    }
+}
+
+fn request_id(error: &Error) -> Option<&RequestId> {
+    match *error {
+        Error::WithRequestId(ref id, _) => Some(id),
+        Error::WithContext(_, ref e) => request_id(e),
+        _ => None,
+    }
+}
+
+fn kind(error: &Error) -> &'static str {
+    match *error {
+        Error::Http(_) => "http",
+        Error::Hyper(_) => "hyper",
+        Error::Connect(_) => "connect",
+        Error::Timeout(_) => "timeout",
+        Error::Tls(_) => "tls",
+        Error::Body(_) => "body",
+        Error::BodyNotAllowed(_) => "body_not_allowed",
+        Error::TunnelRejected(_) => "tunnel_rejected",
+        Error::ResponseHeadersTooLarge(_) => "response_headers_too_large",
+        Error::InvalidResponse(_) => "invalid_response",
+        Error::InvalidUri(_) => "invalid_uri",
+        Error::UnsupportedContentType(_) => "unsupported_content_type",
+        Error::Io(_) => "io",
+        Error::UnexpectedStatus(_) => "unexpected_status",
+        Error::UnexpectedVersion(_) => "unexpected_version",
+        Error::ResponseBodyTooLarge(_) => "response_body_too_large",
+        Error::ChecksumMismatch(_) => "checksum_mismatch",
+        Error::WithRequestId(_, ref e) | Error::WithContext(_, ref e) => kind(e),
+        Error::InvalidConfig(_) => "invalid_config",
+        Error::RequestTimeout(_) => "request_timeout",
+        Error::InFlightLimit(_) => "in_flight_limit",
+        Error::Cancelled => "cancelled",
+        Error::RuntimeStopped => "runtime_stopped",
+        #[cfg(feature = "json")]
+        Error::Json(_) => "json",
+        #[cfg(feature = "graphql")]
+        Error::GraphQl(_) => "graphql",
+        #[cfg(feature = "xml")]
+        Error::XmlSerialize(_) | Error::XmlDeserialize(_) => "xml",
+        #[cfg(feature = "cbor")]
+        Error::CborSerialize(_) | Error::CborDeserialize(_) => "cbor",
+        #[cfg(feature = "netrc")]
+        Error::InvalidNetrc(_) => "invalid_netrc",
+        #[cfg(feature = "msgpack")]
+        Error::MsgPackSerialize(_) | Error::MsgPackDeserialize(_) => "msgpack",
+    }
+}
+
+impl Serialize for ErrorReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("ErrorReport", 6)?;
+        s.serialize_field("kind", self.kind)?;
+        s.serialize_field("message", &self.message)?;
+        s.serialize_field("sources", &self.sources)?;
+        match self.request_id {
+            Some(ref id) => s.serialize_field("request_id", id)?,
+            None => s.skip_field("request_id")?,
+        }
+        match self.uri {
+            Some(ref uri) => s.serialize_field("uri", uri)?,
+            None => s.skip_field("uri")?,
+        }
+        match self.elapsed {
+            Some(elapsed) => s.serialize_field("elapsed_secs", &elapsed.as_secs_f64())?,
+            None => s.skip_field("elapsed_secs")?,
+        }
+        s.end()
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::error::RequestContext;
+    use std::io;
+
+    #[test]
+    fn serialize() {
+        let err = Error::Io(io::Error::other("oops"));
+        let report = err
+            .report()
+            .uri(&Uri::from_static("http://example.com/"))
+            .elapsed(Duration::from_millis(1500));
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"kind":"io","message":"I/O error: oops","sources":["oops"],"uri":"http://example.com/","elapsed_secs":1.5}"#
+        );
+    }
+
+    #[test]
+    fn serialize_with_context() {
+        let uri = Uri::from_static("http://example.com/items?token=secret");
+        let context = RequestContext::new(hyper::Method::GET, &uri, true);
+        let err = Error::WithContext(context, Box::new(Error::Io(io::Error::other("oops"))));
+        assert_eq!(
+            serde_json::to_string(&err.report()).unwrap(),
+            r#"{"kind":"io","message":"I/O error: oops","sources":["oops"],"uri":"http://example.com/items"}"#
+        );
+    }
+}
@@ -0,0 +1,244 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+use crate::shared_body::SharedBody;
+
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE};
+use hyper::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+/// The media types accepted for GraphQL responses, see the GraphQL over HTTP
+/// specification. `application/json` is included for servers that predate
+/// `application/graphql-response+json`.
+const GRAPHQL_ACCEPT: &str = "application/graphql-response+json, application/json";
+
+#[derive(Serialize)]
+struct GraphQlRequest<'a, V: ?Sized> {
+    query: &'a str,
+    variables: &'a V,
+}
+
+/// Set the body of a GraphQL request for `query` and `variables` and the
+/// corresponding `Content-Type` and `Accept` headers.
+pub(crate) fn set_request<V: Serialize + ?Sized>(
+    headers: &mut HeaderMap,
+    body: &mut Option<SharedBody>,
+    query: &str,
+    variables: &V,
+) -> Result<(), Error> {
+    let json = serde_json::to_vec(&GraphQlRequest { query, variables }).map_err(Error::Json)?;
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(ACCEPT, HeaderValue::from_static(GRAPHQL_ACCEPT));
+    *body = Some(SharedBody::from(json));
+    Ok(())
+}
+
+/// Parse the body of a response to a GraphQL request.
+///
+/// Servers may respond with an error status along with a GraphQL response
+/// describing the errors. Other responses with an error status are returned
+/// as [`Error::UnexpectedStatus`].
+pub(crate) fn parse_response<T: DeserializeOwned>(
+    status: StatusCode,
+    body: &[u8],
+) -> Result<GraphQlResponse<T>, Error> {
+    match serde_json::from_slice(body) {
+        Ok(response) => Ok(response),
+        Err(_) if !status.is_success() => Err(Error::UnexpectedStatus(status)),
+        Err(e) => Err(Error::Json(e)),
+    }
+}
+
+/// A GraphQL response
+///
+/// A response can contain both data and errors if the request was partially
+/// successful, in which case the fields that could not be resolved are
+/// `null`. Use [`into_data()`] if any error should fail the request.
+///
+/// [`into_data()`]: GraphQlResponse::into_data
+#[derive(Debug, Clone, Deserialize)]
+pub struct GraphQlResponse<T> {
+    /// The result of the operation, `None` if an error prevented it from
+    /// being executed.
+    pub data: Option<T>,
+    /// The errors raised during the operation, empty if there were none.
+    #[serde(default)]
+    pub errors: Vec<GraphQlError>,
+    /// Implementation-specific additional information.
+    pub extensions: Option<serde_json::Value>,
+}
+
+impl<T> GraphQlResponse<T> {
+    /// Returns the data if there were no errors.
+    ///
+    /// Returns [`Error::GraphQl`] if there were any errors, or
+    /// [`Error::InvalidResponse`] if the response has neither data nor
+    /// errors.
+    pub fn into_data(self) -> Result<T, Error> {
+        match (self.data, self.errors.is_empty()) {
+            (Some(data), true) => Ok(data),
+            (None, true) => Err(Error::InvalidResponse(
+                "GraphQL response has neither data nor errors",
+            )),
+            (_, false) => Err(Error::GraphQl(self.errors)),
+        }
+    }
+}
+
+/// An error in a [`GraphQlResponse`]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GraphQlError {
+    /// A description of the error.
+    pub message: String,
+    /// The locations in the query document the error refers to.
+    #[serde(default)]
+    pub locations: Vec<GraphQlLocation>,
+    /// The path of the response field the error occurred in, made up of
+    /// field names and list indices.
+    #[serde(default)]
+    pub path: Vec<serde_json::Value>,
+    /// Implementation-specific additional information, such as an error
+    /// code.
+    pub extensions: Option<serde_json::Value>,
+}
+
+impl fmt::Display for GraphQlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if !self.path.is_empty() {
+            let path: Vec<_> = self
+                .path
+                .iter()
+                .map(|segment| match segment {
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect();
+            write!(f, " (at {})", path.join("."))?;
+        }
+        Ok(())
+    }
+}
+
+/// A location in a GraphQL query document, see [`GraphQlError::locations`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct GraphQlLocation {
+    pub line: u32,
+    pub column: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::to_bytes;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Hero {
+        name: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct HeroData {
+        hero: Option<Hero>,
+    }
+
+    #[tokio::test]
+    async fn request_envelope() {
+        let mut headers = HeaderMap::new();
+        let mut body = None;
+        let query = "query($id: ID!) { hero(id: $id) { name } }";
+        set_request(&mut headers, &mut body, query, &json!({ "id": "1000" })).unwrap();
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+        assert_eq!(headers[ACCEPT], GRAPHQL_ACCEPT);
+        let body = to_bytes(body.unwrap()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({ "query": query, "variables": { "id": "1000" } })
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let body = br#"{"data": {"hero": {"name": "Luke"}}}"#;
+        let response = parse_response::<HeroData>(StatusCode::OK, body).unwrap();
+        assert!(response.errors.is_empty());
+        assert_eq!(response.into_data().unwrap().hero.unwrap().name, "Luke");
+
+        // Partial success
+        let body = br#"{
+            "data": {"hero": null},
+            "errors": [{
+                "message": "Hero not found",
+                "locations": [{"line": 1, "column": 19}],
+                "path": ["hero", 0, "name"],
+                "extensions": {"code": "NOT_FOUND"}
+            }]
+        }"#;
+        let response = parse_response::<HeroData>(StatusCode::OK, body).unwrap();
+        assert_eq!(response.data, Some(HeroData { hero: None }));
+        let error = &response.errors[0];
+        assert_eq!(
+            error.locations,
+            [GraphQlLocation {
+                line: 1,
+                column: 19
+            }]
+        );
+        assert_eq!(error.to_string(), "Hero not found (at hero.0.name)");
+        assert_eq!(error.extensions, Some(json!({ "code": "NOT_FOUND" })));
+        match response.into_data() {
+            Err(Error::GraphQl(errors)) => assert_eq!(errors.len(), 1),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        // Request errors are returned with an error status
+        let body = br#"{"errors": [{"message": "Syntax Error"}]}"#;
+        let response = parse_response::<HeroData>(StatusCode::BAD_REQUEST, body).unwrap();
+        assert!(response.data.is_none());
+        assert_eq!(response.errors[0].message, "Syntax Error");
+
+        let err = parse_response::<HeroData>(StatusCode::BAD_GATEWAY, b"<html>").unwrap_err();
+        assert_eq!(err.status(), Some(StatusCode::BAD_GATEWAY));
+        let err = parse_response::<HeroData>(StatusCode::OK, b"<html>").unwrap_err();
+        assert!(matches!(err, Error::Json(_)));
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn graphql_request() {
+        use crate::async_client::tests::test_http_server_capture_request;
+        use crate::{Client, HttpConnector, ResponseExt};
+
+        let (addr, rx) = test_http_server_capture_request(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/graphql-response+json\r\n\
+             Content-Length: 36\r\n\r\n{\"data\": {\"hero\": {\"name\": \"Luke\"}}}",
+        )
+        .await;
+
+        let client = Client::with_connector(HttpConnector::new());
+        let response = client
+            .post(format!("http://{}/graphql", addr))
+            .unwrap()
+            .graphql("{ hero { name } }", &json!({}))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let data = response.graphql::<HeroData>().await.unwrap().into_data();
+        assert_eq!(data.unwrap().hero.unwrap().name, "Luke");
+
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.starts_with("POST /graphql HTTP/1.1\r\n"));
+        assert!(request.contains("\r\ncontent-type: application/json\r\n"));
+        assert!(request.ends_with(r#"{"query":"{ hero { name } }","variables":{}}"#));
+    }
+}
@@ -0,0 +1,129 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Parsing of header parameters as used by `Link` and `Content-Disposition`.
+
+/// Parse parameters of the form `*( ";" name [ "=" value ] )` with optional
+/// whitespace, where `value` is a token or a quoted string.
+///
+/// Returns the parameters with lowercased names and unquoted values, and
+/// the rest of `s` starting at the first character that does not belong to
+/// a parameter. Parameters without a value have an empty value. Returns
+/// `None` on syntax errors.
+pub(crate) fn parse_params(mut s: &str) -> Option<(Vec<(String, String)>, &str)> {
+    let mut params = Vec::new();
+    loop {
+        s = s.trim_start();
+        match s.strip_prefix(';') {
+            Some(rest) => s = rest.trim_start(),
+            None => return Some((params, s)),
+        }
+        let name_end = s
+            .find(|c: char| c == '=' || c == ';' || c == ',' || c.is_ascii_whitespace())
+            .unwrap_or(s.len());
+        let name = s[..name_end].to_ascii_lowercase();
+        if name.is_empty() {
+            return None;
+        }
+        s = s[name_end..].trim_start();
+        let value = match s.strip_prefix('=') {
+            Some(rest) => {
+                let (value, rest) = parse_value(rest.trim_start())?;
+                s = rest;
+                value
+            }
+            None => String::new(),
+        };
+        params.push((name, value));
+    }
+}
+
+/// Parse a token or quoted string, returning the value and the rest of `s`.
+fn parse_value(s: &str) -> Option<(String, &str)> {
+    let rest = match s.strip_prefix('"') {
+        Some(rest) => rest,
+        None => {
+            let end = s
+                .find(|c: char| c == ';' || c == ',' || c.is_ascii_whitespace())
+                .unwrap_or(s.len());
+            return Some((s[..end].to_owned(), &s[end..]));
+        }
+    };
+    let mut value = String::new();
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &rest[i + 1..])),
+            '\\' => value.push(chars.next()?.1),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// Decode an extended parameter value as defined in RFC 8187 (formerly RFC
+/// 5987), e.g. `UTF-8''%e2%82%ac%20rates`.
+///
+/// Only the `UTF-8` and `ISO-8859-1` character sets are supported.
+pub(crate) fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?;
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params() {
+        let (params, rest) = parse_params("; a=1;B=\"x; \\\"y\\\"\" ; c, next").unwrap();
+        let params: Vec<_> = params
+            .iter()
+            .map(|(n, v)| (n.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(params, [("a", "1"), ("b", "x; \"y\""), ("c", "")]);
+        assert_eq!(rest, ", next");
+
+        assert!(parse_params("; a=\"unterminated").is_none());
+        assert!(parse_params("; =1").is_none());
+    }
+
+    #[test]
+    fn ext_value() {
+        assert_eq!(
+            decode_ext_value("UTF-8''%e2%82%ac%20rates").unwrap(),
+            "\u{20ac} rates"
+        );
+        assert_eq!(
+            decode_ext_value("iso-8859-1'en'%A3%20rates").unwrap(),
+            "\u{a3} rates"
+        );
+        assert!(decode_ext_value("UTF-8''%zz").is_none());
+        assert!(decode_ext_value("utf-16''abc").is_none());
+        assert!(decode_ext_value("no quotes").is_none());
+    }
+}
@@ -0,0 +1,254 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Conversions to and from the types of the `http` 1.0 and `http-body` 1.0
+//! crates.
+//!
+//! Request and response heads are converted field by field, extensions are
+//! not carried over. Bodies are wrapped rather than collected, so their data
+//! is not copied.
+
+use crate::error::Error;
+use crate::shared_body::SharedBody;
+use crate::{Request, Response};
+
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Method, StatusCode, Uri, Version};
+
+use std::error::Error as StdError;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Convert an `http` 1.0 request into a [`Request`] that can be sent with
+/// [`Client::send()`].
+///
+/// The body is streamed through [`SharedBody::from_http1_body()`].
+///
+/// [`Client::send()`]: crate::Client::send
+pub fn request_from_http1<B>(request: http_1::Request<B>) -> Result<Request, Error>
+where
+    B: http_body_1::Body + Send + 'static,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    let (parts, body) = request.into_parts();
+    let mut request = Request::new(SharedBody::from_http1_body(body));
+    *request.method_mut() =
+        Method::from_bytes(parts.method.as_str().as_bytes()).map_err(http::Error::from)?;
+    *request.uri_mut() = parts
+        .uri
+        .to_string()
+        .parse::<Uri>()
+        .map_err(http::Error::from)?;
+    *request.version_mut() = version_from_http1(parts.version);
+    *request.headers_mut() = headers_from_http1(&parts.headers)?;
+    Ok(request)
+}
+
+/// Convert a [`Request`] into an `http` 1.0 request.
+///
+/// [`SharedBody`] implements `http_body` 1.0 `Body`, so the body is passed
+/// on as is.
+pub fn request_into_http1(request: Request) -> http_1::Request<SharedBody> {
+    let (parts, body) = request.into_parts();
+    let mut request = http_1::Request::new(body);
+    *request.method_mut() = http_1::Method::from_bytes(parts.method.as_str().as_bytes())
+        .expect("valid http 0.2 method");
+    *request.uri_mut() = parts.uri.to_string().parse().expect("valid http 0.2 URI");
+    *request.version_mut() = version_into_http1(parts.version);
+    *request.headers_mut() = headers_into_http1(&parts.headers);
+    request
+}
+
+/// Convert a [`Response`] into an `http` 1.0 response.
+pub fn response_into_http1(response: Response) -> http_1::Response<Http1Body> {
+    let (parts, body) = response.into_parts();
+    let mut response = http_1::Response::new(Http1Body::new(body));
+    *response.status_mut() =
+        http_1::StatusCode::from_u16(parts.status.as_u16()).expect("valid http 0.2 status code");
+    *response.version_mut() = version_into_http1(parts.version);
+    *response.headers_mut() = headers_into_http1(&parts.headers);
+    response
+}
+
+/// Convert an `http` 1.0 response into a [`hyper::Response`].
+pub fn response_from_http1<B>(response: http_1::Response<B>) -> Result<hyper::Response<B>, Error> {
+    let (parts, body) = response.into_parts();
+    let mut response = hyper::Response::new(body);
+    *response.status_mut() =
+        StatusCode::from_u16(parts.status.as_u16()).map_err(http::Error::from)?;
+    *response.version_mut() = version_from_http1(parts.version);
+    *response.headers_mut() = headers_from_http1(&parts.headers)?;
+    Ok(response)
+}
+
+/// An `http-body` 1.0 `Body` wrapping the body of a [`Response`]
+///
+/// Data is yielded as received from the connection, followed by trailers
+/// if there are any.
+#[derive(Debug)]
+pub struct Http1Body {
+    inner: hyper::Body,
+    data_done: bool,
+}
+
+impl Http1Body {
+    pub fn new(inner: hyper::Body) -> Self {
+        Http1Body {
+            inner,
+            data_done: false,
+        }
+    }
+
+    /// Returns the wrapped body.
+    pub fn into_inner(self) -> hyper::Body {
+        self.inner
+    }
+}
+
+impl http_body_1::Body for Http1Body {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body_1::Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if !this.data_done {
+            match ready!(Pin::new(&mut this.inner).poll_data(cx)) {
+                Some(res) => return Poll::Ready(Some(res.map(http_body_1::Frame::data))),
+                None => this.data_done = true,
+            }
+        }
+        let res = match ready!(Pin::new(&mut this.inner).poll_trailers(cx)) {
+            Ok(Some(trailers)) => Some(Ok(http_body_1::Frame::trailers(headers_into_http1(
+                &trailers,
+            )))),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        };
+        Poll::Ready(res)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body_1::SizeHint {
+        let hint = self.inner.size_hint();
+        let mut res = http_body_1::SizeHint::new();
+        res.set_lower(hint.lower());
+        if let Some(upper) = hint.upper() {
+            res.set_upper(upper);
+        }
+        res
+    }
+}
+
+fn version_from_http1(version: http_1::Version) -> Version {
+    match version {
+        http_1::Version::HTTP_09 => Version::HTTP_09,
+        http_1::Version::HTTP_10 => Version::HTTP_10,
+        http_1::Version::HTTP_2 => Version::HTTP_2,
+        http_1::Version::HTTP_3 => Version::HTTP_3,
+        _ => Version::HTTP_11,
+    }
+}
+
+fn version_into_http1(version: Version) -> http_1::Version {
+    match version {
+        Version::HTTP_09 => http_1::Version::HTTP_09,
+        Version::HTTP_10 => http_1::Version::HTTP_10,
+        Version::HTTP_2 => http_1::Version::HTTP_2,
+        Version::HTTP_3 => http_1::Version::HTTP_3,
+        _ => http_1::Version::HTTP_11,
+    }
+}
+
+fn headers_from_http1(headers: &http_1::HeaderMap) -> Result<HeaderMap, http::Error> {
+    let mut res = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        res.append(
+            HeaderName::from_bytes(name.as_str().as_bytes())?,
+            HeaderValue::from_bytes(value.as_bytes())?,
+        );
+    }
+    Ok(res)
+}
+
+fn headers_into_http1(headers: &HeaderMap) -> http_1::HeaderMap {
+    let mut res = http_1::HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        res.append(
+            http_1::HeaderName::from_bytes(name.as_str().as_bytes())
+                .expect("valid http 0.2 header name"),
+            http_1::HeaderValue::from_bytes(value.as_bytes()).expect("valid http 0.2 header value"),
+        );
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::to_bytes;
+    use std::future::poll_fn;
+
+    async fn next_frame(
+        body: &mut Http1Body,
+    ) -> Option<Result<http_body_1::Frame<Bytes>, hyper::Error>> {
+        poll_fn(|cx| http_body_1::Body::poll_frame(Pin::new(&mut *body), cx)).await
+    }
+
+    #[tokio::test]
+    async fn request_round_trip() {
+        let request = http_1::Request::post("http://example.com/path?q=1")
+            .header("x-custom", "a")
+            .header("x-custom", "b")
+            .body(SharedBody::from("hello"))
+            .unwrap();
+        let request = request_from_http1(request).unwrap();
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(request.uri(), "http://example.com/path?q=1");
+        let values: Vec<_> = request.headers().get_all("x-custom").iter().collect();
+        assert_eq!(values, ["a", "b"]);
+        assert!(request.body().is_streaming());
+        assert_eq!(request.body().content_length(), Some(5));
+
+        let request = request_into_http1(request);
+        assert_eq!(request.method(), http_1::Method::POST);
+        assert_eq!(request.headers().get_all("x-custom").iter().count(), 2);
+        let body = to_bytes(request.into_body()).await.unwrap();
+        assert_eq!(body, "hello");
+    }
+
+    #[tokio::test]
+    async fn response_into_http1_body() {
+        let (mut sender, body) = hyper::Body::channel();
+        let response = hyper::Response::builder()
+            .status(StatusCode::CREATED)
+            .header("x-custom", "a")
+            .body(body)
+            .unwrap();
+        let response = response_into_http1(response);
+        assert_eq!(response.status(), http_1::StatusCode::CREATED);
+        assert_eq!(response.headers()["x-custom"], "a");
+
+        tokio::spawn(async move {
+            sender.send_data(Bytes::from("hello")).await.unwrap();
+            let mut trailers = HeaderMap::new();
+            trailers.insert("x-trailer", HeaderValue::from_static("t"));
+            sender.send_trailers(trailers).await.unwrap();
+        });
+        let mut body = response.into_body();
+        let frame = next_frame(&mut body).await.unwrap().unwrap();
+        assert_eq!(frame.into_data().unwrap(), "hello");
+        let frame = next_frame(&mut body).await.unwrap().unwrap();
+        assert_eq!(frame.into_trailers().unwrap()["x-trailer"], "t");
+        assert!(next_frame(&mut body).await.is_none());
+    }
+}
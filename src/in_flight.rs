@@ -0,0 +1,129 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+use crate::Response;
+
+use hyper::body::Bytes;
+use hyper::Body;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time;
+use tokio_stream::Stream;
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Limits the number of concurrent requests of a client, see
+/// [`ClientBuilder::max_in_flight()`].
+///
+/// [`ClientBuilder::max_in_flight()`]: crate::ClientBuilder::max_in_flight
+#[derive(Clone)]
+pub(crate) struct InFlightLimit {
+    semaphore: Arc<Semaphore>,
+    max: usize,
+    timeout: Option<Duration>,
+}
+
+impl InFlightLimit {
+    pub fn new(max: usize, timeout: Option<Duration>) -> Self {
+        let max = max.min(Semaphore::MAX_PERMITS);
+        InFlightLimit {
+            semaphore: Arc::new(Semaphore::new(max)),
+            max,
+            timeout,
+        }
+    }
+
+    /// Returns a limit with the same `max` that shares the slots of `self`,
+    /// or a new limit if `max` differs.
+    pub fn derive(&self, max: usize, timeout: Option<Duration>) -> Self {
+        match max.min(Semaphore::MAX_PERMITS) == self.max {
+            true => InFlightLimit {
+                semaphore: self.semaphore.clone(),
+                max: self.max,
+                timeout,
+            },
+            false => InFlightLimit::new(max, timeout),
+        }
+    }
+
+    /// Wait for a free slot.
+    ///
+    /// Returns [`Error::InFlightLimit`] if no slot becomes free within the
+    /// timeout.
+    pub async fn acquire(&self) -> Result<OwnedSemaphorePermit, Error> {
+        let acquire = self.semaphore.clone().acquire_owned();
+        let permit = match self.timeout {
+            Some(timeout) => time::timeout(timeout, acquire)
+                .await
+                .map_err(|_| Error::InFlightLimit(timeout))?,
+            None => acquire.await,
+        };
+        // The semaphore is never closed
+        Ok(permit.expect("semaphore closed"))
+    }
+}
+
+/// Holds `permit` until the body of `response` is read to the end or
+/// dropped.
+pub(crate) fn attach_permit(response: Response, permit: OwnedSemaphorePermit) -> Response {
+    let (parts, body) = response.into_parts();
+    let body = Body::wrap_stream(PermitBody {
+        inner: body,
+        permit: Some(permit),
+    });
+    Response::from_parts(parts, body)
+}
+
+struct PermitBody {
+    inner: Body,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Stream for PermitBody {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_next(cx);
+        if let Poll::Ready(None) = res {
+            this.permit = None;
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::to_bytes;
+
+    #[tokio::test(start_paused = true)]
+    async fn limit() {
+        let limit = InFlightLimit::new(1, Some(Duration::from_secs(1)));
+        let permit = limit.acquire().await.unwrap();
+        let err = limit.acquire().await.unwrap_err();
+        assert!(matches!(err, Error::InFlightLimit(_)));
+        assert!(err.is_timeout());
+
+        // The slot is released once the body is read
+        let response = attach_permit(Response::new(Body::from("hello")), permit);
+        let derived = limit.derive(1, Some(Duration::from_secs(1)));
+        assert!(derived.acquire().await.is_err());
+        assert_eq!(to_bytes(response.into_body()).await.unwrap(), "hello");
+        let permit = limit.acquire().await.unwrap();
+
+        // or dropped
+        let response = attach_permit(Response::new(Body::from("hello")), permit);
+        drop(response);
+        let _permit = limit.acquire().await.unwrap();
+
+        let other = limit.derive(2, None);
+        let _other = other.acquire().await.unwrap();
+    }
+}
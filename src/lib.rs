@@ -6,19 +6,105 @@
 
 mod async_client;
 pub mod blocking;
+#[cfg(feature = "cache")]
+mod cache;
+mod capture;
+#[cfg(feature = "cbor")]
+mod cbor;
+#[cfg(feature = "checksum")]
+mod checksum;
+mod clock;
+#[cfg(feature = "compression")]
+mod compression;
+mod conditional;
 mod connector;
+mod content_disposition;
+mod download;
 mod error;
+#[cfg(feature = "serde")]
+mod error_report;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod header_params;
+#[cfg(feature = "http-1")]
+pub mod http_compat;
+mod in_flight;
+mod local_client;
+mod middleware;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+#[cfg(any(
+    feature = "cbor",
+    feature = "json",
+    feature = "msgpack",
+    feature = "xml"
+))]
+mod negotiate;
+#[cfg(feature = "netrc")]
+mod netrc;
+mod observer;
+mod pagination;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+mod request_ext;
+mod request_id;
+mod response_ext;
+mod retry;
+mod retry_budget;
+mod session;
 mod shared_body;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod uri_builder;
+mod validation;
+#[cfg(feature = "log")]
+mod wire_log;
+#[cfg(feature = "xml")]
+mod xml;
 
 pub use self::async_client::*;
+#[cfg(feature = "cache")]
+pub use self::cache::{CacheKey, CacheStore, CachedResponse, DiskCacheStore, MemoryCacheStore};
+pub use self::capture::CapturedBodies;
+#[cfg(feature = "checksum")]
+pub use self::checksum::{ChecksumAlgorithm, CONTENT_DIGEST, CONTENT_MD5};
+pub use self::clock::{Clock, SystemClock, TokioClock};
+#[cfg(feature = "compression")]
+pub use self::compression::Encoding;
+pub use self::conditional::{Revalidation, Validators};
+#[cfg(feature = "tcp")]
 pub use self::connector::{
-    ConnectError, HttpConnection, HttpConnector, HyperConnectorAdapter, NetworkConnection,
-    NetworkConnector,
+    ConnectError, ConnectPolicy, GaiResolver, HttpConnection, HttpConnector, Proxy, Resolve,
 };
-#[cfg(feature = "tokio-native-tls")]
-pub use self::connector::{HttpOrHttpsConnection, HttpsConnector};
-pub use self::error::Error;
+pub use self::connector::{
+    HttpInfo, HyperConnectorAdapter, NetworkConnection, NetworkConnector, TimeoutStream,
+};
+#[cfg(all(feature = "tcp", feature = "tokio-native-tls"))]
+pub use self::connector::{HttpOrHttpsConnection, HttpsConnector, TlsInfo};
+pub use self::content_disposition::ContentDisposition;
+pub use self::error::{Error, RequestContext};
+#[cfg(feature = "serde")]
+pub use self::error_report::ErrorReport;
+#[cfg(feature = "graphql")]
+pub use self::graphql::{GraphQlError, GraphQlLocation, GraphQlResponse};
+pub use self::local_client::{LocalClient, LocalNetworkConnector};
+pub use self::middleware::{Middleware, MiddlewareFuture, Next};
+#[cfg(feature = "netrc")]
+pub use self::netrc::Netrc;
+pub use self::observer::Observer;
+pub use self::pagination::{Link, Pages};
+#[cfg(feature = "prometheus")]
+pub use self::prometheus::PrometheusObserver;
+pub use self::request_ext::RequestExt;
+pub use self::request_id::{RequestId, X_REQUEST_ID};
+#[cfg(feature = "json")]
+pub use self::response_ext::JsonLines;
+pub use self::response_ext::{BodyReader, BytesStream, ResponseExt};
+pub use self::retry::RetryPolicy;
+pub use self::retry_budget::RetryBudget;
+pub use self::session::{AuthProvider, CookieJar, Session};
 pub use self::shared_body::SharedBody;
+pub use self::uri_builder::UriBuilder;
 
 pub use hyper::body::{aggregate, to_bytes, Buf, Bytes, HttpBody};
 pub use hyper::{self, Method, StatusCode, Uri, Version};
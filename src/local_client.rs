@@ -0,0 +1,153 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::connector::{NetworkConnection, NetworkConnector};
+use crate::error::Error;
+use crate::shared_body::SharedBody;
+use crate::Response;
+
+use hyper::client::conn;
+use hyper::header::{HeaderValue, HOST};
+use hyper::{Request, Uri};
+
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// Network connector trait for [`LocalClient`]
+///
+/// Unlike [`NetworkConnector`], neither the connector nor the future
+/// returned by [`connect()`] need to be `Send`, so the connector can hold
+/// e.g. `Rc`s or FFI handles. The returned connection must still be `Send`.
+///
+/// [`connect()`]: LocalNetworkConnector::connect
+pub trait LocalNetworkConnector: 'static {
+    #[allow(clippy::type_complexity)]
+    fn connect(
+        &self,
+        uri: Uri,
+    ) -> Pin<Box<dyn Future<Output = Result<NetworkConnection, Box<dyn StdError + Send + Sync>>>>>;
+}
+
+/// A minimal HTTP/1 client for connectors that are not `Send`
+///
+/// The client must be used inside a `tokio::task::LocalSet`, as the tasks
+/// driving connections are spawned with `tokio::task::spawn_local()`. Each
+/// request is sent over a new connection, and none of the options of
+/// [`ClientBuilder`] are available. Prefer [`Client`] if the connector
+/// implements [`NetworkConnector`].
+///
+/// Errors returned by the connector are returned as [`Error::Io`].
+///
+/// [`ClientBuilder`]: crate::ClientBuilder
+/// [`Client`]: crate::Client
+#[derive(Clone)]
+pub struct LocalClient {
+    connector: Rc<dyn LocalNetworkConnector>,
+}
+
+impl LocalClient {
+    pub fn new<C: LocalNetworkConnector>(connector: C) -> Self {
+        LocalClient {
+            connector: Rc::new(connector),
+        }
+    }
+
+    /// Send `request` over a new connection and return the response.
+    pub async fn send(&self, mut request: Request<SharedBody>) -> Result<Response, Error> {
+        let uri = request.uri().clone();
+        let stream = self
+            .connector
+            .connect(uri.clone())
+            .await
+            .map_err(|e| Error::Io(io::Error::other(e)))?;
+        let (mut sender, connection) = conn::Builder::new().handshake(stream).await?;
+        tokio::task::spawn_local(async move {
+            let _ = connection.await;
+        });
+
+        if let Some(authority) = uri.authority() {
+            if !request.headers().contains_key(HOST) {
+                let host = HeaderValue::from_str(authority.as_str()).map_err(http::Error::from)?;
+                request.headers_mut().insert(HOST, host);
+            }
+        }
+        // HTTP/1 requests use the origin form of the URI, see RFC 9112
+        // section 3.2.1
+        let origin = uri.path_and_query().map_or("/", |pq| pq.as_str());
+        *request.uri_mut() = origin.parse::<Uri>().map_err(http::Error::from)?;
+        Ok(sender.send_request(request).await?)
+    }
+}
+
+/// Every [`NetworkConnector`] can be used with [`LocalClient`].
+impl<T: NetworkConnector> LocalNetworkConnector for T {
+    fn connect(
+        &self,
+        uri: Uri,
+    ) -> Pin<Box<dyn Future<Output = Result<NetworkConnection, Box<dyn StdError + Send + Sync>>>>>
+    {
+        NetworkConnector::connect(self, uri)
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::*;
+    use crate::connector::HttpConnector;
+    use hyper::body::to_bytes;
+    use std::cell::Cell;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    struct CountingConnector {
+        inner: HttpConnector,
+        count: Rc<Cell<usize>>,
+    }
+
+    impl LocalNetworkConnector for CountingConnector {
+        fn connect(
+            &self,
+            uri: Uri,
+        ) -> Pin<Box<dyn Future<Output = Result<NetworkConnection, Box<dyn StdError + Send + Sync>>>>>
+        {
+            self.count.set(self.count.get() + 1);
+            NetworkConnector::connect(&self.inner, uri)
+        }
+    }
+
+    #[tokio::test]
+    async fn local_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut input = [0u8; 1024];
+            let n = stream.read(&mut input).await.unwrap();
+            let request = String::from_utf8_lossy(&input[..n]).into_owned();
+            assert!(request.starts_with("GET /path?q=1 HTTP/1.1\r\n"));
+            assert!(request.contains(&format!("host: {}\r\n", addr)));
+            let resp = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+            stream.write_all(resp.as_bytes()).await.unwrap();
+        });
+
+        let count = Rc::new(Cell::new(0));
+        let client = LocalClient::new(CountingConnector {
+            inner: HttpConnector::new(),
+            count: count.clone(),
+        });
+        let request = Request::get(format!("http://{}/path?q=1", addr))
+            .body(SharedBody::empty())
+            .unwrap();
+        let local = tokio::task::LocalSet::new();
+        let response = local.run_until(client.send(request)).await.unwrap();
+        assert_eq!(count.get(), 1);
+        let body = local.run_until(to_bytes(response.into_body())).await;
+        assert_eq!(body.unwrap(), "hello");
+    }
+}
@@ -0,0 +1,82 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::async_client::Client;
+use crate::error::Error;
+use crate::shared_body::SharedBody;
+use crate::Response;
+
+use hyper::Request;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// A future returned by [`Middleware::handle()`] and [`Next::run()`]
+pub type MiddlewareFuture<'a> = Pin<Box<dyn Future<Output = Result<Response, Error>> + Send + 'a>>;
+
+/// Intercepts the requests sent by a [`Client`] and their responses
+///
+/// Middleware is registered with [`ClientBuilder::with_middleware()`]. Each
+/// middleware receives the request and the rest of the chain as [`Next`],
+/// so it can modify the request, e.g. to inject credentials, call
+/// [`Next::run()`] to send it, and then inspect or modify the response or
+/// error, e.g. for logging or metrics. It may also respond without calling
+/// `next`.
+///
+/// Middleware runs before anything else the client does with a request,
+/// such as adding the [`request_id_header()`] or notifying observers, and
+/// sees a single response for all [retries].
+///
+/// ```
+/// # use simple_hyper_client::hyper::header::{HeaderValue, AUTHORIZATION};
+/// # use simple_hyper_client::hyper::Request;
+/// # use simple_hyper_client::{Middleware, MiddlewareFuture, Next, SharedBody};
+/// struct BearerAuth(HeaderValue);
+///
+/// impl Middleware for BearerAuth {
+///     fn handle<'a>(
+///         &'a self,
+///         mut request: Request<SharedBody>,
+///         next: Next<'a>,
+///     ) -> MiddlewareFuture<'a> {
+///         request.headers_mut().insert(AUTHORIZATION, self.0.clone());
+///         next.run(request)
+///     }
+/// }
+/// ```
+///
+/// [`Client`]: crate::Client
+/// [`ClientBuilder::with_middleware()`]: crate::ClientBuilder::with_middleware
+/// [`request_id_header()`]: crate::ClientBuilder::request_id_header
+/// [retries]: crate::ClientBuilder::retry_policy
+pub trait Middleware: Send + Sync + 'static {
+    /// Handle `request`, usually by passing it to `next`.
+    fn handle<'a>(&'a self, request: Request<SharedBody>, next: Next<'a>) -> MiddlewareFuture<'a>;
+}
+
+/// The rest of the middleware chain of a [`Client`]
+///
+/// [`Client`]: crate::Client
+pub struct Next<'a> {
+    client: &'a Client,
+    middleware: &'a [Arc<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(client: &'a Client, middleware: &'a [Arc<dyn Middleware>]) -> Self {
+        Next { client, middleware }
+    }
+
+    /// Pass `request` to the next middleware, or send it if this is the end
+    /// of the chain.
+    pub fn run(self, request: Request<SharedBody>) -> MiddlewareFuture<'a> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.handle(request, Next::new(self.client, rest)),
+            None => Box::pin(self.client.send_without_middleware(request)),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+use crate::shared_body::SharedBody;
+
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Set the body to the MessagePack encoding of `value` and the
+/// `Content-Type` header to `application/msgpack`.
+///
+/// Structs are encoded as maps keyed by field name rather than as arrays, so
+/// that the body can be decoded by implementations in other languages and by
+/// servers that do not know the field order.
+pub(crate) fn set_request<T: Serialize + ?Sized>(
+    headers: &mut HeaderMap,
+    body: &mut Option<SharedBody>,
+    value: &T,
+) -> Result<(), Error> {
+    let buf = rmp_serde::to_vec_named(value).map_err(Error::MsgPackSerialize)?;
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/msgpack"),
+    );
+    *body = Some(SharedBody::from(buf));
+    Ok(())
+}
+
+/// Deserialize a MessagePack response body.
+///
+/// Structs can be encoded either as maps or as arrays.
+pub(crate) fn parse_response<T: DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+    rmp_serde::from_slice(body).map_err(Error::MsgPackDeserialize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::to_bytes;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: String,
+        value: u16,
+    }
+
+    #[tokio::test]
+    async fn request_body() {
+        let mut headers = HeaderMap::new();
+        let mut body = None;
+        let reading = Reading {
+            sensor: "t1".to_owned(),
+            value: 300,
+        };
+        set_request(&mut headers, &mut body, &reading).unwrap();
+        assert_eq!(headers[CONTENT_TYPE], "application/msgpack");
+        let body = to_bytes(body.unwrap()).await.unwrap();
+        // {"sensor": "t1", "value": 300}
+        assert_eq!(&body[..], b"\x82\xa6sensor\xa2t1\xa5value\xcd\x01\x2c");
+        assert_eq!(parse_response::<Reading>(&body).unwrap(), reading);
+    }
+
+    #[test]
+    fn parse() {
+        // Structs encoded as arrays are accepted too
+        let reading = parse_response::<Reading>(b"\x92\xa2t1\x07").unwrap();
+        assert_eq!(reading.sensor, "t1");
+        assert_eq!(reading.value, 7);
+
+        let err = parse_response::<Reading>(b"\x92\xa2t1").unwrap_err();
+        assert!(matches!(err, Error::MsgPackDeserialize(_)));
+    }
+}
@@ -0,0 +1,169 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+
+use hyper::header::{HeaderMap, CONTENT_TYPE};
+use serde::de::DeserializeOwned;
+
+/// The serialization formats supported by the enabled features
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    #[cfg(feature = "json")]
+    Json,
+    #[cfg(feature = "xml")]
+    Xml,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "msgpack")]
+    MsgPack,
+}
+
+impl Format {
+    /// Returns the format of a body with the given media type, ignoring its
+    /// parameters.
+    ///
+    /// Structured syntax suffixes such as `+json` are recognized, see
+    /// RFC 6839.
+    fn from_media_type(media_type: &str) -> Option<Format> {
+        let essence = media_type.split(';').next()?.trim().to_ascii_lowercase();
+        let (kind, subtype) = essence.split_once('/')?;
+        let suffix = subtype.rsplit_once('+').map(|(_, suffix)| suffix);
+        match (kind, subtype, suffix) {
+            #[cfg(feature = "json")]
+            ("application", "json", _) | (_, _, Some("json")) => Some(Format::Json),
+            #[cfg(feature = "xml")]
+            ("application" | "text", "xml", _) | (_, _, Some("xml")) => Some(Format::Xml),
+            #[cfg(feature = "cbor")]
+            ("application", "cbor", _) | (_, _, Some("cbor")) => Some(Format::Cbor),
+            #[cfg(feature = "msgpack")]
+            ("application", "msgpack" | "x-msgpack" | "vnd.msgpack", _) => Some(Format::MsgPack),
+            _ => None,
+        }
+    }
+}
+
+/// Deserialize `body` according to the `Content-Type` in `headers`.
+///
+/// Returns [`Error::UnsupportedContentType`] if the header is missing or
+/// names a format that is not supported by the enabled features.
+pub(crate) fn deserialize<T: DeserializeOwned>(
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<T, Error> {
+    let content_type = headers.get(CONTENT_TYPE);
+    let format = content_type
+        .and_then(|value| value.to_str().ok())
+        .and_then(Format::from_media_type)
+        .ok_or_else(|| Error::UnsupportedContentType(content_type.cloned()))?;
+    match format {
+        #[cfg(feature = "json")]
+        Format::Json => serde_json::from_slice(body).map_err(Error::Json),
+        #[cfg(feature = "xml")]
+        Format::Xml => crate::xml::parse_response(body),
+        #[cfg(feature = "cbor")]
+        Format::Cbor => crate::cbor::parse_response(body),
+        #[cfg(feature = "msgpack")]
+        Format::MsgPack => crate::msgpack::parse_response(body),
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "json",
+    feature = "xml",
+    feature = "cbor",
+    feature = "msgpack"
+))]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+    use serde::Deserialize;
+
+    #[test]
+    fn media_types() {
+        let cases = [
+            ("application/json", Some(Format::Json)),
+            ("Application/JSON; charset=utf-8", Some(Format::Json)),
+            ("application/problem+json", Some(Format::Json)),
+            ("text/xml", Some(Format::Xml)),
+            ("application/soap+xml; charset=utf-8", Some(Format::Xml)),
+            ("application/cbor", Some(Format::Cbor)),
+            ("application/x-msgpack", Some(Format::MsgPack)),
+            ("text/plain", None),
+            ("json", None),
+        ];
+        for (media_type, format) in cases {
+            assert_eq!(
+                Format::from_media_type(media_type),
+                format,
+                "{}",
+                media_type
+            );
+        }
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Status {
+        up: bool,
+    }
+
+    #[test]
+    fn deserialize_by_content_type() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let status: Status = deserialize(&headers, br#"{"up": true}"#).unwrap();
+        assert!(status.up);
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/xml"));
+        let status: Status = deserialize(&headers, b"<Status><up>false</up></Status>").unwrap();
+        assert!(!status.up);
+
+        // {"up": true}
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/cbor"));
+        let status: Status = deserialize(&headers, b"\xa1\x62up\xf5").unwrap();
+        assert!(status.up);
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        match deserialize::<Status>(&headers, b"<html>") {
+            Err(Error::UnsupportedContentType(Some(value))) => assert_eq!(value, "text/html"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        let err = deserialize::<Status>(&HeaderMap::new(), b"").unwrap_err();
+        assert!(matches!(err, Error::UnsupportedContentType(None)));
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn default_accept() {
+        use crate::async_client::tests::test_http_server_capture_request;
+        use crate::{Client, HttpConnector, ResponseExt};
+
+        // {"up": true}
+        let (addr, rx) = test_http_server_capture_request(
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/msgpack\r\n\
+              Content-Length: 5\r\n\r\n\x81\xa2up\xc3",
+        )
+        .await;
+
+        let client = Client::builder()
+            .default_accept(Some(HeaderValue::from_static(
+                "application/msgpack, application/json;q=0.5",
+            )))
+            .build(HttpConnector::new());
+        let response = client
+            .get(format!("http://{}/status", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let status: Status = response.deserialize().await.unwrap();
+        assert!(status.up);
+
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("\r\naccept: application/msgpack, application/json;q=0.5\r\n"));
+    }
+}
@@ -0,0 +1,284 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+use crate::session::AuthProvider;
+
+use headers::authorization::Authorization;
+use headers::Header;
+use hyper::header::HeaderValue;
+use hyper::Uri;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Credentials read from a `.netrc` file
+///
+/// This implements [`AuthProvider`], so a [`Session`] can send the
+/// credentials of the host of each request as HTTP Basic authentication,
+/// as curl does with `--netrc`. Hosts are matched by name, regardless of
+/// the scheme and port, falling back to the `default` entry if there is
+/// one. `macdef` entries are ignored.
+///
+/// ```ignore
+/// let mut session = Session::new(client);
+/// if let Some(netrc) = Netrc::load()? {
+///     session.set_auth(netrc);
+/// }
+/// ```
+///
+/// [`Session`]: crate::Session
+#[derive(Clone, Default)]
+pub struct Netrc {
+    machines: Vec<(String, Credentials)>,
+    default: Option<Credentials>,
+}
+
+#[derive(Clone, Default)]
+struct Credentials {
+    login: String,
+    password: String,
+}
+
+impl Netrc {
+    /// Load the `.netrc` file of the current user.
+    ///
+    /// The file is read from the path in the `NETRC` environment variable
+    /// if it is set, otherwise from `.netrc` in the home directory, or
+    /// `_netrc` on Windows. Returns `None` if the file does not exist.
+    pub fn load() -> Result<Option<Netrc>, Error> {
+        let path = match env::var_os("NETRC") {
+            Some(path) => PathBuf::from(path),
+            None => match default_path() {
+                Some(path) => path,
+                None => return Ok(None),
+            },
+        };
+        match Netrc::from_file(&path) {
+            Ok(netrc) => Ok(Some(netrc)),
+            Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read a `.netrc` file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Netrc, Error> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Returns the login and password for `host`, if any.
+    pub fn credentials(&self, host: &str) -> Option<(&str, &str)> {
+        self.machines
+            .iter()
+            .find(|(machine, _)| machine.eq_ignore_ascii_case(host))
+            .map(|(_, credentials)| credentials)
+            .or(self.default.as_ref())
+            .map(|c| (c.login.as_str(), c.password.as_str()))
+    }
+}
+
+#[cfg(windows)]
+fn default_path() -> Option<PathBuf> {
+    let home = PathBuf::from(env::var_os("USERPROFILE")?);
+    let netrc = home.join("_netrc");
+    match netrc.exists() {
+        true => Some(netrc),
+        false => Some(home.join(".netrc")),
+    }
+}
+
+#[cfg(not(windows))]
+fn default_path() -> Option<PathBuf> {
+    Some(PathBuf::from(env::var_os("HOME")?).join(".netrc"))
+}
+
+impl FromStr for Netrc {
+    type Err = Error;
+
+    /// Parse the contents of a `.netrc` file.
+    ///
+    /// Returns [`Error::InvalidNetrc`] if the file is malformed.
+    fn from_str(s: &str) -> Result<Netrc, Error> {
+        let mut netrc = Netrc::default();
+        let mut tokens = Tokens { rest: s };
+        // The entry whose login and password are being read
+        let mut current: Option<&mut Credentials> = None;
+        while let Some(token) = tokens.next() {
+            match token.as_str() {
+                "machine" => {
+                    let name = tokens
+                        .next()
+                        .ok_or(Error::InvalidNetrc("missing machine name"))?;
+                    netrc.machines.push((name, Credentials::default()));
+                    current = netrc.machines.last_mut().map(|(_, c)| c);
+                }
+                "default" => {
+                    current = Some(netrc.default.insert(Credentials::default()));
+                }
+                "login" | "password" | "account" => {
+                    let value = tokens.next().ok_or(Error::InvalidNetrc("missing value"))?;
+                    let entry = current
+                        .as_deref_mut()
+                        .ok_or(Error::InvalidNetrc("value outside of an entry"))?;
+                    match token.as_str() {
+                        "login" => entry.login = value,
+                        "password" => entry.password = value,
+                        _ => {}
+                    }
+                }
+                "macdef" => {
+                    tokens.skip_macro();
+                    current = None;
+                }
+                _ => return Err(Error::InvalidNetrc("unexpected token")),
+            }
+        }
+        Ok(netrc)
+    }
+}
+
+/// Splits the contents of a `.netrc` file into whitespace separated tokens,
+/// which may be double-quoted.
+struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl Tokens<'_> {
+    fn next(&mut self) -> Option<String> {
+        self.rest = self.rest.trim_start();
+        if let Some(quoted) = self.rest.strip_prefix('"') {
+            let mut token = String::new();
+            let mut chars = quoted.char_indices();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        self.rest = &quoted[i + 1..];
+                        return Some(token);
+                    }
+                    '\\' => token.extend(chars.next().map(|(_, c)| c)),
+                    c => token.push(c),
+                }
+            }
+            self.rest = "";
+            return Some(token);
+        }
+        let end = self
+            .rest
+            .find(char::is_whitespace)
+            .unwrap_or(self.rest.len());
+        let (token, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        match token.is_empty() {
+            true => None,
+            false => Some(token.to_owned()),
+        }
+    }
+
+    /// Skip a macro definition, which ends with an empty line.
+    fn skip_macro(&mut self) {
+        let mut end = 0;
+        // The first line holds the macro name
+        for (i, line) in self.rest.split_inclusive('\n').enumerate() {
+            if i > 0 && line.trim().is_empty() {
+                break;
+            }
+            end += line.len();
+        }
+        self.rest = &self.rest[end..];
+    }
+}
+
+impl AuthProvider for Netrc {
+    fn authorization(&self, uri: &Uri) -> Option<HeaderValue> {
+        let (login, password) = self.credentials(uri.host()?)?;
+        let mut values = Vec::with_capacity(1);
+        Authorization::basic(login, password).encode(&mut values);
+        values.pop()
+    }
+}
+
+impl fmt::Debug for Netrc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let machines: Vec<_> = self.machines.iter().map(|(name, _)| name).collect();
+        f.debug_struct("Netrc")
+            .field("machines", &machines)
+            .field("default", &self.default.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETRC: &str = r#"
+machine api.example.com login alice password "s3cret pass"
+macdef init
+cd /pub
+login mallory
+
+machine Other.Example.com
+    login bob
+    password hunter2
+    account ignored
+default login anonymous password guest@
+"#;
+
+    #[test]
+    fn parse() {
+        let netrc: Netrc = NETRC.parse().unwrap();
+        assert_eq!(
+            netrc.credentials("api.example.com"),
+            Some(("alice", "s3cret pass"))
+        );
+        assert_eq!(
+            netrc.credentials("other.example.com"),
+            Some(("bob", "hunter2"))
+        );
+        assert_eq!(
+            netrc.credentials("unknown.example.com"),
+            Some(("anonymous", "guest@"))
+        );
+        assert!(!format!("{:?}", netrc).contains("hunter2"));
+
+        let netrc: Netrc = "machine a login x password y".parse().unwrap();
+        assert_eq!(netrc.credentials("b"), None);
+
+        for invalid in ["machine", "login x", "machine a login", "machine a user x"] {
+            let err = invalid.parse::<Netrc>().unwrap_err();
+            assert!(matches!(err, Error::InvalidNetrc(_)), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn authorization() {
+        let netrc: Netrc = "machine example.com login foo password bar"
+            .parse()
+            .unwrap();
+        let value = netrc.authorization(&Uri::from_static("https://example.com:8443/x"));
+        assert_eq!(value.unwrap(), "Basic Zm9vOmJhcg==");
+        assert!(netrc
+            .authorization(&Uri::from_static("https://example.org/"))
+            .is_none());
+    }
+
+    #[test]
+    fn load() {
+        let path =
+            std::env::temp_dir().join(format!("simple-hyper-client-netrc-{}", std::process::id()));
+        fs::write(&path, NETRC).unwrap();
+        let netrc = Netrc::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(netrc.credentials("api.example.com").unwrap().0, "alice");
+
+        let err = Netrc::from_file(&path).unwrap_err();
+        assert!(matches!(err, Error::Io(_)));
+    }
+}
@@ -0,0 +1,143 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+use crate::shared_body::SharedBody;
+use crate::Response;
+
+use hyper::{Method, Request, Uri};
+use tokio::time::Instant;
+
+use std::error::Error as StdError;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Receives notifications about the lifecycle of requests sent by a
+/// [`Client`]
+///
+/// Observers are registered with [`ClientBuilder::observer()`] and are
+/// useful for auditing and alerting. All methods have empty default
+/// implementations. They are called from the task sending the request, so
+/// they should return quickly.
+///
+/// [`Client`]: crate::Client
+/// [`ClientBuilder::observer()`]: crate::ClientBuilder::observer
+pub trait Observer: Send + Sync + 'static {
+    /// Called after a new connection for a request to `uri` has been
+    /// established by the connector, which includes the TLS handshake for
+    /// HTTPS connectors.
+    fn on_connect(&self, uri: &Uri, elapsed: Duration) {
+        let _ = (uri, elapsed);
+    }
+
+    /// Called if the connector failed to establish a connection.
+    fn on_connect_error(&self, uri: &Uri, error: &(dyn StdError + Send + Sync)) {
+        let _ = (uri, error);
+    }
+
+    /// Called before a request is sent.
+    fn on_request(&self, request: &Request<SharedBody>) {
+        let _ = request;
+    }
+
+    /// Called when the response head to a request has been received, or a
+    /// response was found in the cache.
+    fn on_response(&self, method: &Method, uri: &Uri, response: &Response, elapsed: Duration) {
+        let _ = (method, uri, response, elapsed);
+    }
+
+    /// Called before a request is sent again according to the
+    /// [`RetryPolicy`] of the client.
+    ///
+    /// `attempt` is the number of the attempt that failed, counting from 1,
+    /// and `outcome` is its result, i.e. an error or a response with a 5xx
+    /// status code. [`on_request()`] is not called again for retries.
+    ///
+    /// [`RetryPolicy`]: crate::RetryPolicy
+    /// [`on_request()`]: Observer::on_request
+    fn on_retry(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        attempt: u32,
+        outcome: &Result<Response, Error>,
+    ) {
+        let _ = (method, uri, attempt, outcome);
+    }
+
+    /// Called if sending a request failed.
+    ///
+    /// This is also called with [`Error::Cancelled`] if the request is
+    /// dropped before it completes, e.g. because the caller stopped waiting
+    /// for it, so every call to [`on_request()`] is followed by a call to
+    /// either [`on_response()`] or `on_error()`.
+    ///
+    /// [`on_request()`]: Observer::on_request
+    /// [`on_response()`]: Observer::on_response
+    fn on_error(&self, method: &Method, uri: &Uri, error: &Error, elapsed: Duration) {
+        let _ = (method, uri, error, elapsed);
+    }
+}
+
+/// Notifies observers about the outcome of a request
+///
+/// If the request is dropped before [`finish()`] is called, the observers
+/// are notified with [`Error::Cancelled`].
+///
+/// [`finish()`]: ObservedRequest::finish
+pub(crate) struct ObservedRequest<'a> {
+    observers: &'a [Arc<dyn Observer>],
+    method: Method,
+    uri: Uri,
+    start: Instant,
+    finished: bool,
+}
+
+impl<'a> ObservedRequest<'a> {
+    /// Notify `observers` that `request` is about to be sent. Returns `None`
+    /// if there are no observers.
+    pub(crate) fn start(
+        observers: &'a [Arc<dyn Observer>],
+        request: &Request<SharedBody>,
+    ) -> Option<Self> {
+        if observers.is_empty() {
+            return None;
+        }
+        for observer in observers {
+            observer.on_request(request);
+        }
+        Some(ObservedRequest {
+            observers,
+            method: request.method().clone(),
+            uri: request.uri().clone(),
+            start: Instant::now(),
+            finished: false,
+        })
+    }
+
+    /// Notify the observers about the result of the request.
+    pub(crate) fn finish(mut self, res: &Result<Response, Error>) {
+        self.finished = true;
+        let elapsed = self.start.elapsed();
+        for observer in self.observers {
+            match res {
+                Ok(response) => observer.on_response(&self.method, &self.uri, response, elapsed),
+                Err(e) => observer.on_error(&self.method, &self.uri, e, elapsed),
+            }
+        }
+    }
+}
+
+impl Drop for ObservedRequest<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let elapsed = self.start.elapsed();
+            for observer in self.observers {
+                observer.on_error(&self.method, &self.uri, &Error::Cancelled, elapsed);
+            }
+        }
+    }
+}
@@ -0,0 +1,345 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::async_client::Client;
+use crate::error::Error;
+use crate::header_params::parse_params;
+use crate::shared_body::SharedBody;
+use crate::Response;
+
+use hyper::header::{HeaderMap, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, LINK};
+use hyper::{Method, Request, Uri};
+use tokio_stream::Stream;
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// A link parsed from a `Link` header, see RFC 8288
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// The link target as it appears in the header, which may be a relative
+    /// reference.
+    pub target: String,
+    /// The link relation types, lowercased.
+    pub rel: Vec<String>,
+    /// The other target attributes with lowercased names and unquoted
+    /// values. Attributes without a value have an empty value.
+    pub params: Vec<(String, String)>,
+}
+
+impl Link {
+    /// Parse the links in all `Link` headers of `headers`.
+    ///
+    /// Parsing of a header value stops at the first syntax error, the links
+    /// before it are still returned.
+    pub fn from_headers(headers: &HeaderMap) -> Vec<Link> {
+        let mut links = Vec::new();
+        for value in headers.get_all(LINK) {
+            if let Ok(value) = value.to_str() {
+                parse_links(value, &mut links);
+            }
+        }
+        links
+    }
+
+    /// Returns true if `rel` is one of the relation types of this link.
+    pub fn has_rel(&self, rel: &str) -> bool {
+        self.rel.iter().any(|r| r.eq_ignore_ascii_case(rel))
+    }
+}
+
+/// Returns the target of the first link with relation type `next`.
+pub(crate) fn next_link(headers: &HeaderMap) -> Option<String> {
+    Link::from_headers(headers)
+        .into_iter()
+        .find(|link| link.has_rel("next"))
+        .map(|link| link.target)
+}
+
+fn parse_links(mut s: &str, links: &mut Vec<Link>) {
+    loop {
+        s = s.trim_start_matches(|c: char| c == ',' || c.is_ascii_whitespace());
+        let rest = match s.strip_prefix('<') {
+            Some(rest) => rest,
+            None => return,
+        };
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => return,
+        };
+        let target = rest[..end].trim().to_owned();
+        let (params, rest) = match parse_params(&rest[end + 1..]) {
+            Some(res) => res,
+            None => return,
+        };
+        s = rest;
+        let mut link = Link {
+            target,
+            rel: Vec::new(),
+            params: Vec::new(),
+        };
+        for (name, value) in params {
+            // Occurrences of `rel` after the first are ignored, see RFC 8288
+            // section 3.3.
+            if name == "rel" {
+                if link.rel.is_empty() {
+                    link.rel = value
+                        .split_ascii_whitespace()
+                        .map(str::to_ascii_lowercase)
+                        .collect();
+                }
+            } else {
+                link.params.push((name, value));
+            }
+        }
+        links.push(link);
+    }
+}
+
+/// Resolve `reference` against `base` as described in RFC 3986 section 5.2,
+/// without removing dot segments.
+pub(crate) fn resolve(base: &Uri, reference: &str) -> Option<Uri> {
+    if let Ok(uri) = reference.parse::<Uri>() {
+        if uri.scheme().is_some() {
+            return Some(uri);
+        }
+    }
+    let scheme = base.scheme_str()?;
+    let authority = base.authority()?.as_str();
+    let resolved = if let Some(rest) = reference.strip_prefix("//") {
+        format!("{}://{}", scheme, rest)
+    } else if reference.starts_with('/') {
+        format!("{}://{}{}", scheme, authority, reference)
+    } else if reference.starts_with('?') {
+        format!("{}://{}{}{}", scheme, authority, base.path(), reference)
+    } else {
+        let path = base.path();
+        let dir = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+        let dir = if dir.is_empty() { "/" } else { dir };
+        format!("{}://{}{}{}", scheme, authority, dir, reference)
+    };
+    resolved.parse().ok()
+}
+
+/// The request for a page, from which requests for later pages are derived
+pub(crate) struct PageRequest {
+    uri: Uri,
+    headers: HeaderMap,
+}
+
+impl PageRequest {
+    /// Returns the request for the first page and the template for later
+    /// pages.
+    ///
+    /// Later pages are requested with `GET` and the headers of `request`
+    /// except those describing its body.
+    pub(crate) fn first(request: &Request<SharedBody>) -> Self {
+        let mut headers = request.headers().clone();
+        for name in [CONTENT_LENGTH, CONTENT_TYPE, CONTENT_ENCODING] {
+            headers.remove(name);
+        }
+        PageRequest {
+            uri: request.uri().clone(),
+            headers,
+        }
+    }
+
+    /// Returns the request for the page linked as `next` in
+    /// `response_headers`, if any.
+    ///
+    /// Returns `None` if the link points back to this page.
+    pub(crate) fn next(&self, response_headers: &HeaderMap) -> Option<PageRequest> {
+        let uri = resolve(&self.uri, &next_link(response_headers)?)?;
+        if uri == self.uri {
+            return None;
+        }
+        Some(PageRequest {
+            uri,
+            headers: self.headers.clone(),
+        })
+    }
+
+    pub(crate) fn to_request(&self) -> Request<SharedBody> {
+        let mut request = Request::new(SharedBody::empty());
+        *request.method_mut() = Method::GET;
+        *request.uri_mut() = self.uri.clone();
+        *request.headers_mut() = self.headers.clone();
+        request
+    }
+}
+
+type ResponseFuture = Pin<Box<dyn Future<Output = Result<Response, Error>> + Send>>;
+
+/// A stream of the pages of a paginated resource
+///
+/// This is created by [`Client::paginate()`]. Each response is yielded as
+/// soon as its headers have been received, and the request for the next
+/// page is sent when the stream is polled again. The stream ends after the
+/// first error or when a response has no `Link` header with relation type
+/// `next`.
+pub struct Pages {
+    client: Client,
+    // the page whose response is yielded next
+    page: Option<PageRequest>,
+    pending: Option<ResponseFuture>,
+    first: Option<Request<SharedBody>>,
+}
+
+impl fmt::Debug for Pages {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pages")
+            .field("uri", &self.page.as_ref().map(|page| &page.uri))
+            .finish()
+    }
+}
+
+impl Pages {
+    pub(crate) fn new(client: Client, request: Request<SharedBody>) -> Self {
+        Pages {
+            client,
+            page: Some(PageRequest::first(&request)),
+            pending: None,
+            first: Some(request),
+        }
+    }
+}
+
+impl Stream for Pages {
+    type Item = Result<Response, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.pending.is_none() {
+            let request = match (this.first.take(), &this.page) {
+                (Some(request), _) => request,
+                (None, Some(page)) => page.to_request(),
+                (None, None) => return Poll::Ready(None),
+            };
+            let client = this.client.clone();
+            this.pending = Some(Box::pin(async move { client.send(request).await }));
+        }
+        let res = ready!(this.pending.as_mut().unwrap().as_mut().poll(cx));
+        this.pending = None;
+        this.page = match res {
+            Ok(ref response) => this
+                .page
+                .take()
+                .and_then(|page| page.next(response.headers())),
+            Err(_) => None,
+        };
+        Poll::Ready(Some(res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn parse_header(value: &'static str) -> Vec<Link> {
+        let mut headers = HeaderMap::new();
+        headers.insert(LINK, HeaderValue::from_static(value));
+        Link::from_headers(&headers)
+    }
+
+    #[test]
+    fn parse() {
+        let links = parse_header(
+            "<https://api.example.com/items?page=2>; rel=\"next\", \
+             </items?page=5>; REL=\"last  Prev\"; title=\"a \\\"b\\\"\"; hreflang=de; x",
+        );
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].target, "https://api.example.com/items?page=2");
+        assert_eq!(links[0].rel, ["next"]);
+        assert_eq!(links[1].target, "/items?page=5");
+        assert_eq!(links[1].rel, ["last", "prev"]);
+        assert!(links[1].has_rel("Prev"));
+        let params: Vec<_> = links[1]
+            .params
+            .iter()
+            .map(|(n, v)| (n.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            params,
+            [("title", "a \"b\""), ("hreflang", "de"), ("x", "")]
+        );
+
+        // Links before a syntax error are kept
+        let links = parse_header("<a>; rel=next, <b>; rel=\"next");
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].target, "a");
+    }
+
+    #[test]
+    fn resolve_reference() {
+        let base: Uri = "https://example.com/api/items?page=1".parse().unwrap();
+        let cases = [
+            ("http://other.com/x", "http://other.com/x"),
+            ("//cdn.example.com/x", "https://cdn.example.com/x"),
+            ("/items?page=2", "https://example.com/items?page=2"),
+            ("?page=2", "https://example.com/api/items?page=2"),
+            ("more?page=2", "https://example.com/api/more?page=2"),
+        ];
+        for (reference, expected) in cases {
+            assert_eq!(resolve(&base, reference).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn next_page() {
+        let request = Request::post("https://example.com/items")
+            .header(CONTENT_TYPE, "application/json")
+            .header("x-custom", "a")
+            .body(SharedBody::from("{}"))
+            .unwrap();
+        let page = PageRequest::first(&request);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(LINK, HeaderValue::from_static("</items?page=2>; rel=next"));
+        let next = page.next(&headers).unwrap().to_request();
+        assert_eq!(next.method(), Method::GET);
+        assert_eq!(next.uri(), "https://example.com/items?page=2");
+        assert_eq!(next.headers()["x-custom"], "a");
+        assert!(!next.headers().contains_key(CONTENT_TYPE));
+
+        // A link to the same page ends the pagination
+        headers.insert(LINK, HeaderValue::from_static("</items>; rel=next"));
+        assert!(page.next(&headers).is_none());
+        assert!(page.next(&HeaderMap::new()).is_none());
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn paginate() {
+        use crate::async_client::tests::test_http_server_responses;
+        use crate::connector::HttpConnector;
+        use hyper::body::to_bytes;
+        use tokio_stream::StreamExt;
+
+        let (addr, server) = test_http_server_responses(vec![
+            "HTTP/1.1 200 OK\r\nLink: <?page=2>; rel=\"next\"\r\nContent-Length: 1\r\n\r\n1",
+            "HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\n2",
+        ])
+        .await;
+
+        let client = Client::with_connector(HttpConnector::new());
+        let request = Request::get(format!("http://{}/items", addr))
+            .body(SharedBody::empty())
+            .unwrap();
+        let mut pages = client.paginate(request);
+        let mut bodies = Vec::new();
+        while let Some(response) = pages.next().await {
+            bodies.push(to_bytes(response.unwrap().into_body()).await.unwrap());
+        }
+        assert_eq!(bodies, ["1", "2"]);
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with(b"GET /items HTTP/1.1\r\n"));
+        assert!(requests[1].starts_with(b"GET /items?page=2 HTTP/1.1\r\n"));
+    }
+}
@@ -0,0 +1,203 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+use crate::observer::Observer;
+use crate::shared_body::SharedBody;
+use crate::Response;
+
+use hyper::{Method, Request, Uri};
+use prometheus::{
+    HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+};
+
+use std::error::Error as StdError;
+use std::time::Duration;
+
+/// An [`Observer`] recording Prometheus metrics
+///
+/// The following metrics are registered:
+///
+/// - `http_client_requests_total`: counter of completed requests labeled by
+///   `method` and `status` (the status class such as `2xx`, or `error` for
+///   requests that failed without a response),
+/// - `http_client_request_duration_seconds`: histogram of the time until
+///   the response head was received, labeled by `method`,
+/// - `http_client_requests_in_flight`: gauge of requests waiting for a
+///   response,
+/// - `http_client_retries_total`: counter of requests sent again by the
+///   [`RetryPolicy`] of the client, labeled by `method`,
+/// - `http_client_connections_total`: counter of established connections,
+/// - `http_client_connection_errors_total`: counter of failed connection
+///   attempts.
+///
+/// Register it with [`ClientBuilder::observer()`].
+///
+/// [`ClientBuilder::observer()`]: crate::ClientBuilder::observer
+/// [`RetryPolicy`]: crate::RetryPolicy
+pub struct PrometheusObserver {
+    requests: IntCounterVec,
+    duration: HistogramVec,
+    in_flight: IntGauge,
+    retries: IntCounterVec,
+    connections: IntCounter,
+    connection_errors: IntCounter,
+}
+
+impl PrometheusObserver {
+    /// Create the metrics and register them with `registry`.
+    ///
+    /// Returns an error if metrics with the same names are already
+    /// registered, use [`Registry::new_custom()`] with a prefix to record
+    /// metrics of multiple clients.
+    ///
+    /// [`Registry::new_custom()`]: https://docs.rs/prometheus/0.13/prometheus/struct.Registry.html#method.new_custom
+    pub fn new(registry: &Registry) -> prometheus::Result<Self> {
+        let observer = PrometheusObserver {
+            requests: IntCounterVec::new(
+                Opts::new("http_client_requests_total", "Completed HTTP requests"),
+                &["method", "status"],
+            )?,
+            duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "http_client_request_duration_seconds",
+                    "Time until the HTTP response head was received",
+                ),
+                &["method"],
+            )?,
+            in_flight: IntGauge::new(
+                "http_client_requests_in_flight",
+                "HTTP requests waiting for a response",
+            )?,
+            retries: IntCounterVec::new(
+                Opts::new("http_client_retries_total", "Retried HTTP requests"),
+                &["method"],
+            )?,
+            connections: IntCounter::new(
+                "http_client_connections_total",
+                "Established connections",
+            )?,
+            connection_errors: IntCounter::new(
+                "http_client_connection_errors_total",
+                "Failed connection attempts",
+            )?,
+        };
+        registry.register(Box::new(observer.requests.clone()))?;
+        registry.register(Box::new(observer.duration.clone()))?;
+        registry.register(Box::new(observer.in_flight.clone()))?;
+        registry.register(Box::new(observer.retries.clone()))?;
+        registry.register(Box::new(observer.connections.clone()))?;
+        registry.register(Box::new(observer.connection_errors.clone()))?;
+        Ok(observer)
+    }
+
+    fn record(&self, method: &Method, status: &str, elapsed: Duration) {
+        self.in_flight.dec();
+        self.requests
+            .with_label_values(&[method.as_str(), status])
+            .inc();
+        self.duration
+            .with_label_values(&[method.as_str()])
+            .observe(elapsed.as_secs_f64());
+    }
+}
+
+impl Observer for PrometheusObserver {
+    fn on_connect(&self, _uri: &Uri, _elapsed: Duration) {
+        self.connections.inc();
+    }
+
+    fn on_connect_error(&self, _uri: &Uri, _error: &(dyn StdError + Send + Sync)) {
+        self.connection_errors.inc();
+    }
+
+    fn on_request(&self, _request: &Request<SharedBody>) {
+        self.in_flight.inc();
+    }
+
+    fn on_response(&self, method: &Method, _uri: &Uri, response: &Response, elapsed: Duration) {
+        let status = match response.status().as_u16() / 100 {
+            1 => "1xx",
+            2 => "2xx",
+            3 => "3xx",
+            4 => "4xx",
+            _ => "5xx",
+        };
+        self.record(method, status, elapsed);
+    }
+
+    fn on_retry(
+        &self,
+        method: &Method,
+        _uri: &Uri,
+        _attempt: u32,
+        _outcome: &Result<Response, Error>,
+    ) {
+        self.retries.with_label_values(&[method.as_str()]).inc();
+    }
+
+    fn on_error(&self, method: &Method, _uri: &Uri, _error: &Error, elapsed: Duration) {
+        self.record(method, "error", elapsed);
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::*;
+    use crate::connector::HttpConnector;
+    use crate::Client;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn metrics() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut input = [0u8; 1024];
+            let _ = stream.read(&mut input).await.unwrap();
+            let resp = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+            stream.write_all(resp.as_bytes()).await.unwrap();
+        });
+
+        let registry = Registry::new();
+        let observer = PrometheusObserver::new(&registry).unwrap();
+        assert!(PrometheusObserver::new(&registry).is_err());
+        let client = Client::builder()
+            .observer(observer)
+            .build(HttpConnector::new());
+        client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+
+        let families = registry.gather();
+        let value = |name: &str| {
+            let family = families.iter().find(|f| f.get_name() == name).unwrap();
+            family.get_metric()[0].clone()
+        };
+        let requests = value("http_client_requests_total");
+        assert_eq!(requests.get_counter().get_value(), 1.0);
+        assert_eq!(requests.get_label()[1].get_value(), "4xx");
+        let duration = value("http_client_request_duration_seconds");
+        assert_eq!(duration.get_histogram().get_sample_count(), 1);
+        assert_eq!(
+            value("http_client_requests_in_flight")
+                .get_gauge()
+                .get_value(),
+            0.0
+        );
+        assert_eq!(
+            value("http_client_connections_total")
+                .get_counter()
+                .get_value(),
+            1.0
+        );
+    }
+}
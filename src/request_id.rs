@@ -0,0 +1,91 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The `X-Request-Id` header, commonly used for correlating requests with
+/// server logs
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// The ID of a request, see
+/// [`ClientBuilder::request_id_header()`](crate::ClientBuilder::request_id_header)
+///
+/// This is stored in the extensions of responses.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(HeaderValue);
+
+impl RequestId {
+    /// Generate a random (version 4) UUID.
+    fn generate() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        // `RandomState` is seeded randomly, which is good enough for
+        // correlating requests without depending on an RNG.
+        let state = RandomState::new();
+        let mut bytes = [0u8; 16];
+        for (i, half) in bytes.chunks_mut(8).enumerate() {
+            let hash = state.hash_one((COUNTER.fetch_add(1, Ordering::Relaxed), i));
+            half.copy_from_slice(&hash.to_le_bytes());
+        }
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let uuid = format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        );
+        RequestId(HeaderValue::from_str(&uuid).expect("UUIDs are valid header values"))
+    }
+
+    /// Returns the ID set in `headers`, generating and inserting a new one
+    /// if the header is absent.
+    pub(crate) fn apply(header: &HeaderName, headers: &mut HeaderMap) -> Self {
+        let value = headers
+            .entry(header)
+            .or_insert_with(|| RequestId::generate().0);
+        RequestId(value.clone())
+    }
+
+    /// Returns the ID as sent in the request header.
+    pub fn as_header_value(&self) -> &HeaderValue {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(self.0.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply() {
+        let mut headers = HeaderMap::new();
+        let id = RequestId::apply(&X_REQUEST_ID, &mut headers);
+        assert_eq!(headers[X_REQUEST_ID], *id.as_header_value());
+        let id = id.to_string();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert_ne!(RequestId::generate().to_string(), id);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(X_REQUEST_ID, HeaderValue::from_static("abc"));
+        let id = RequestId::apply(&X_REQUEST_ID, &mut headers);
+        assert_eq!(id.to_string(), "abc");
+    }
+}
@@ -0,0 +1,546 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#[cfg(feature = "cbor")]
+use crate::cbor;
+use crate::connector::remote_addr;
+use crate::error::Error;
+#[cfg(feature = "graphql")]
+use crate::graphql::{parse_response, GraphQlResponse};
+#[cfg(feature = "msgpack")]
+use crate::msgpack;
+#[cfg(any(
+    feature = "cbor",
+    feature = "json",
+    feature = "msgpack",
+    feature = "xml"
+))]
+use crate::negotiate;
+use crate::pagination::next_link;
+#[cfg(doc)]
+use crate::pagination::Link;
+#[cfg(feature = "xml")]
+use crate::xml;
+use crate::Response;
+
+use hyper::body::Bytes;
+use hyper::{Body, Uri, Version};
+#[cfg(any(
+    feature = "cbor",
+    feature = "json",
+    feature = "msgpack",
+    feature = "xml"
+))]
+use serde::de::DeserializeOwned;
+use tokio::fs::File;
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWriteExt, ReadBuf};
+use tokio_stream::adapters::Map;
+use tokio_stream::{Stream, StreamExt};
+use tokio_util::io::StreamReader;
+
+use std::future::Future;
+#[cfg(feature = "json")]
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::Pin;
+#[cfg(feature = "json")]
+use std::task::ready;
+use std::task::{Context, Poll};
+use std::{fmt, io};
+
+/// Extension methods for [`Response`]
+///
+/// [`Response`]: type.Response.html
+pub trait ResponseExt {
+    /// Convert the response body into a stream of chunks.
+    fn bytes_stream(self) -> BytesStream;
+
+    /// Convert the response body into a reader implementing [`AsyncRead`]
+    /// and [`AsyncBufRead`].
+    ///
+    /// Errors encountered while reading the body are returned as
+    /// `std::io::Error`s with [`Error`] as their inner error.
+    ///
+    /// [`AsyncRead`]: https://docs.rs/tokio/1.0/tokio/io/trait.AsyncRead.html
+    /// [`AsyncBufRead`]: https://docs.rs/tokio/1.0/tokio/io/trait.AsyncBufRead.html
+    fn into_async_read(self) -> BodyReader;
+
+    /// Write the response body to the file at `path`, creating the file if
+    /// it does not exist and truncating it otherwise.
+    ///
+    /// The file is synced to disk before returning. Returns the number of
+    /// bytes written.
+    fn save_to_file<P: AsRef<Path>>(
+        self,
+        path: P,
+    ) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send>>;
+
+    /// Convert a newline-delimited JSON (NDJSON / JSON Lines) response body
+    /// into a stream of values.
+    ///
+    /// Each non-empty line is deserialized into a `T` as soon as it has been
+    /// received, which makes this suitable for long-running or very large
+    /// responses. Lines that fail to deserialize are returned as
+    /// [`Error::Json`] without ending the stream.
+    #[cfg(feature = "json")]
+    fn json_lines<T: DeserializeOwned>(self) -> JsonLines<T>;
+
+    /// Read the response body and parse it as a [`GraphQlResponse`].
+    ///
+    /// GraphQL servers may report errors with an error status, so the
+    /// status is only checked if the body is not a GraphQL response, in
+    /// which case [`Error::UnexpectedStatus`] is returned.
+    #[cfg(feature = "graphql")]
+    fn graphql<T: DeserializeOwned>(
+        self,
+    ) -> Pin<Box<dyn Future<Output = Result<GraphQlResponse<T>, Error>> + Send>>;
+
+    /// Returns the target of the first link with relation type `next` in
+    /// the `Link` headers of the response, see [`Link`].
+    ///
+    /// Relative references are returned unresolved, e.g. as a URI with only
+    /// a path and query. [`Client::paginate()`] resolves them against the
+    /// request URI.
+    ///
+    /// [`Client::paginate()`]: crate::Client::paginate
+    fn next_page_uri(&self) -> Option<Uri>;
+
+    /// Returns the address of the peer the response was received from.
+    ///
+    /// This is reported by the built-in connectors and by hyper's
+    /// `HttpConnector`, see [`HttpInfo`]. Returns `None` for other
+    /// connectors and for responses served from the cache.
+    ///
+    /// [`HttpInfo`]: crate::HttpInfo
+    fn remote_addr(&self) -> Option<SocketAddr>;
+
+    /// Returns the HTTP version of the connection the response was received
+    /// on, e.g. `HTTP/2.0` if it was negotiated through ALPN.
+    ///
+    /// See also [`RequestBuilder::require_version()`].
+    ///
+    /// [`RequestBuilder::require_version()`]: crate::RequestBuilder::require_version
+    fn effective_version(&self) -> Version;
+
+    /// Read the response body and deserialize it from XML.
+    ///
+    /// The response status is not checked. Returns [`Error::XmlDeserialize`]
+    /// if the body cannot be deserialized into a `T`.
+    #[cfg(feature = "xml")]
+    fn xml<T: DeserializeOwned>(self) -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send>>;
+
+    /// Read the response body and deserialize it from CBOR.
+    ///
+    /// The response status is not checked. Returns
+    /// [`Error::CborDeserialize`] if the body cannot be deserialized into a
+    /// `T`.
+    #[cfg(feature = "cbor")]
+    fn cbor<T: DeserializeOwned>(self) -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send>>;
+
+    /// Read the response body and deserialize it from MessagePack.
+    ///
+    /// The response status is not checked. Returns
+    /// [`Error::MsgPackDeserialize`] if the body cannot be deserialized into
+    /// a `T`.
+    #[cfg(feature = "msgpack")]
+    fn msgpack<T: DeserializeOwned>(self)
+        -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send>>;
+
+    /// Read the response body and deserialize it according to the
+    /// `Content-Type` header of the response.
+    ///
+    /// JSON, XML, CBOR and MessagePack are supported if the corresponding
+    /// features are enabled, including media types with a structured syntax
+    /// suffix such as `application/problem+json`. Other content types are
+    /// returned as [`Error::UnsupportedContentType`]. See also
+    /// [`ClientBuilder::default_accept()`].
+    ///
+    /// [`ClientBuilder::default_accept()`]: crate::ClientBuilder::default_accept
+    #[cfg(any(
+        feature = "cbor",
+        feature = "json",
+        feature = "msgpack",
+        feature = "xml"
+    ))]
+    fn deserialize<T: DeserializeOwned>(
+        self,
+    ) -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send>>;
+}
+
+impl ResponseExt for Response {
+    fn bytes_stream(self) -> BytesStream {
+        BytesStream(self.into_body())
+    }
+
+    fn into_async_read(self) -> BodyReader {
+        BodyReader(StreamReader::new(
+            self.bytes_stream().map(|res| res.map_err(io::Error::other)),
+        ))
+    }
+
+    fn save_to_file<P: AsRef<Path>>(
+        self,
+        path: P,
+    ) -> Pin<Box<dyn Future<Output = Result<u64, Error>> + Send>> {
+        let path = path.as_ref().to_owned();
+        Box::pin(async move {
+            let mut file = File::create(path).await?;
+            write_body(self.into_body(), &mut file).await
+        })
+    }
+
+    #[cfg(feature = "json")]
+    fn json_lines<T: DeserializeOwned>(self) -> JsonLines<T> {
+        JsonLines {
+            body: self.bytes_stream(),
+            buf: Vec::new(),
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+
+    #[cfg(feature = "graphql")]
+    fn graphql<T: DeserializeOwned>(
+        self,
+    ) -> Pin<Box<dyn Future<Output = Result<GraphQlResponse<T>, Error>> + Send>> {
+        Box::pin(async move {
+            let status = self.status();
+            let body = hyper::body::to_bytes(self.into_body())
+                .await
+                .map_err(Error::from_body)?;
+            parse_response(status, &body)
+        })
+    }
+
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        remote_addr(self.extensions())
+    }
+
+    fn effective_version(&self) -> Version {
+        self.version()
+    }
+
+    fn next_page_uri(&self) -> Option<Uri> {
+        next_link(self.headers())?.parse().ok()
+    }
+
+    #[cfg(feature = "xml")]
+    fn xml<T: DeserializeOwned>(self) -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send>> {
+        Box::pin(async move {
+            let body = hyper::body::to_bytes(self.into_body())
+                .await
+                .map_err(Error::from_body)?;
+            xml::parse_response(&body)
+        })
+    }
+
+    #[cfg(feature = "cbor")]
+    fn cbor<T: DeserializeOwned>(self) -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send>> {
+        Box::pin(async move {
+            let body = hyper::body::to_bytes(self.into_body())
+                .await
+                .map_err(Error::from_body)?;
+            cbor::parse_response(&body)
+        })
+    }
+
+    #[cfg(feature = "msgpack")]
+    fn msgpack<T: DeserializeOwned>(
+        self,
+    ) -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send>> {
+        Box::pin(async move {
+            let body = hyper::body::to_bytes(self.into_body())
+                .await
+                .map_err(Error::from_body)?;
+            msgpack::parse_response(&body)
+        })
+    }
+
+    #[cfg(any(
+        feature = "cbor",
+        feature = "json",
+        feature = "msgpack",
+        feature = "xml"
+    ))]
+    fn deserialize<T: DeserializeOwned>(
+        self,
+    ) -> Pin<Box<dyn Future<Output = Result<T, Error>> + Send>> {
+        Box::pin(async move {
+            let (parts, body) = self.into_parts();
+            let body = hyper::body::to_bytes(body)
+                .await
+                .map_err(Error::from_body)?;
+            negotiate::deserialize(&parts.headers, &body)
+        })
+    }
+}
+
+/// Write `body` to `file` and sync the file to disk.
+///
+/// Returns the number of bytes written.
+pub(crate) async fn write_body(mut body: Body, file: &mut File) -> Result<u64, Error> {
+    let mut written = 0;
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(Error::from_body)?;
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    file.sync_all().await?;
+    Ok(written)
+}
+
+/// A stream of response body chunks
+///
+/// This is created through [`ResponseExt::bytes_stream()`].
+pub struct BytesStream(Body);
+
+impl fmt::Debug for BytesStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BytesStream").finish()
+    }
+}
+
+impl Stream for BytesStream {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().0)
+            .poll_next(cx)
+            .map(|opt| opt.map(|res| res.map_err(Error::from_body)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// A stream of values deserialized from a newline-delimited JSON response
+/// body
+///
+/// This is created through [`ResponseExt::json_lines()`].
+#[cfg(feature = "json")]
+pub struct JsonLines<T> {
+    body: BytesStream,
+    buf: Vec<u8>,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "json")]
+impl<T> fmt::Debug for JsonLines<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonLines").finish()
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T> JsonLines<T> {
+    /// Remove the next complete line from the buffer. Once the body has been
+    /// read completely, the remaining data is treated as the last line.
+    fn next_line(&mut self) -> Option<Vec<u8>> {
+        let end = match self.buf.iter().position(|b| *b == b'\n') {
+            Some(pos) => pos + 1,
+            None if self.done && !self.buf.is_empty() => self.buf.len(),
+            None => return None,
+        };
+        Some(self.buf.drain(..end).collect())
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: DeserializeOwned> Stream for JsonLines<T> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            while let Some(line) = this.next_line() {
+                if line.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                return Poll::Ready(Some(serde_json::from_slice(&line).map_err(Error::Json)));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            match ready!(Pin::new(&mut this.body).poll_next(cx)) {
+                Some(Ok(chunk)) => this.buf.extend_from_slice(&chunk),
+                Some(Err(e)) => {
+                    this.done = true;
+                    this.buf.clear();
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => this.done = true,
+            }
+        }
+    }
+}
+
+type IoStream = Map<BytesStream, fn(Result<Bytes, Error>) -> io::Result<Bytes>>;
+
+/// An [`AsyncRead`] and [`AsyncBufRead`] adapter for response bodies
+///
+/// This is created through [`ResponseExt::into_async_read()`].
+///
+/// [`AsyncRead`]: https://docs.rs/tokio/1.0/tokio/io/trait.AsyncRead.html
+/// [`AsyncBufRead`]: https://docs.rs/tokio/1.0/tokio/io/trait.AsyncBufRead.html
+pub struct BodyReader(StreamReader<IoStream, Bytes>);
+
+impl fmt::Debug for BodyReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyReader").finish()
+    }
+}
+
+impl AsyncRead for BodyReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncBufRead for BodyReader {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().0).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut self.get_mut().0).consume(amt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    #[tokio::test]
+    async fn bytes_stream() {
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            sender.send_data("hello".into()).await.unwrap();
+            sender.send_data(", world!".into()).await.unwrap();
+        });
+
+        let mut stream = Response::new(body).bytes_stream();
+        let mut chunks = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            chunks.push(chunk.unwrap());
+        }
+        assert_eq!(chunks, vec!["hello", ", world!"]);
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn body_read_timeout() {
+        use crate::{Client, HttpConnector};
+        use std::time::Duration;
+        use tokio::io::AsyncWriteExt;
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut input = [0u8; 1024];
+            let _ = stream.read(&mut input).await.unwrap();
+            let response = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello";
+            stream.write_all(response).await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let connector = HttpConnector::new().read_timeout(Some(Duration::from_millis(100)));
+        let client = Client::with_connector(connector);
+        let response = client
+            .get(format!("http://{}/", addr))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let mut stream = response.bytes_stream();
+        assert_eq!(stream.next().await.unwrap().unwrap(), "hello");
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(err.is_timeout(), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn async_read() {
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            sender.send_data("first ".into()).await.unwrap();
+            sender
+                .send_data("line\nsecond line\n".into())
+                .await
+                .unwrap();
+        });
+
+        let mut reader = Response::new(body).into_async_read();
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "first line\n");
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).await.unwrap();
+        assert_eq!(rest, "second line\n");
+    }
+
+    #[tokio::test]
+    async fn save_to_file() {
+        let path = std::env::temp_dir().join(format!(
+            "simple-hyper-client-save-to-file-{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"previous contents").unwrap();
+
+        let response = Response::new(Body::from("hello, world!"));
+        assert_eq!(response.save_to_file(&path).await.unwrap(), 13);
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello, world!");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn async_read_error() {
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            sender.send_data("hello".into()).await.unwrap();
+            sender.abort();
+        });
+
+        let mut reader = Response::new(body).into_async_read();
+        let mut bytes = Vec::new();
+        let err = reader.read_to_end(&mut bytes).await.unwrap_err();
+        assert!(err.into_inner().unwrap().downcast::<Error>().is_ok());
+        assert_eq!(bytes, b"hello");
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_lines() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Entry {
+            id: u32,
+        }
+
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            sender.send_data("{\"id\": 1}\n{\"i".into()).await.unwrap();
+            sender
+                .send_data("d\": 2}\r\n\nnot json\n{\"id\": 3}".into())
+                .await
+                .unwrap();
+        });
+
+        let mut stream = Response::new(body).json_lines::<Entry>();
+        assert_eq!(stream.next().await.unwrap().unwrap(), Entry { id: 1 });
+        assert_eq!(stream.next().await.unwrap().unwrap(), Entry { id: 2 });
+        assert!(matches!(stream.next().await, Some(Err(Error::Json(_)))));
+        assert_eq!(stream.next().await.unwrap().unwrap(), Entry { id: 3 });
+        assert!(stream.next().await.is_none());
+    }
+}
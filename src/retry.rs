@@ -0,0 +1,223 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::async_client::copy_extensions;
+use crate::error::Error;
+use crate::shared_body::SharedBody;
+use crate::Response;
+
+use hyper::{Method, Request};
+
+use std::collections::hash_map::RandomState;
+use std::error::Error as StdError;
+use std::hash::BuildHasher;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Retries failed requests with jittered exponential backoff
+///
+/// A [`Client`] configured with [`ClientBuilder::retry_policy()`] sends
+/// idempotent requests (`GET`, `HEAD`, `OPTIONS`, `TRACE`, `PUT` and
+/// `DELETE`) again if they fail with
+///
+/// - a connection error, except for TLS errors and connections denied by a
+///   [`ConnectPolicy`],
+/// - a reset of the connection before a response was received, e.g. because
+///   the server closed a pooled connection, or
+/// - a response with a 5xx status code.
+///
+/// Requests with a streaming body, see [`SharedBody::is_streaming()`], are
+/// never retried as their body cannot be sent twice.
+///
+/// Before retry number `n`, the client waits for a random duration between
+/// zero and `base_delay * 2^(n - 1)`, capped at `max_delay`. If the client
+/// has a [`RetryBudget`], each retry is withdrawn from it and requests are
+/// not retried once it is exhausted.
+///
+/// [`Client`]: crate::Client
+/// [`ClientBuilder::retry_policy()`]: crate::ClientBuilder::retry_policy
+/// [`ConnectPolicy`]: crate::ConnectPolicy
+/// [`RetryBudget`]: crate::RetryBudget
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Create a policy sending a request at most `max_attempts` times,
+    /// including the first attempt.
+    ///
+    /// The base delay is 100 milliseconds and the maximum delay 10 seconds.
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+
+    /// Set the upper bound of the delay before the first retry.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Set the upper bound of the delay before any retry.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Returns the maximum number of times a request is sent.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Returns true if `request` may be retried.
+    pub(crate) fn applies_to(&self, request: &Request<SharedBody>) -> bool {
+        self.max_attempts > 1 && is_idempotent(request.method()) && !request.body().is_streaming()
+    }
+
+    /// Returns true if a request should be sent again after `res`.
+    pub(crate) fn should_retry(&self, res: &Result<Response, Error>) -> bool {
+        match res {
+            Ok(response) => response.status().is_server_error(),
+            Err(e) => is_retryable(e),
+        }
+    }
+
+    /// Returns the delay before retry number `retry`, counting from 1.
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let exp = retry.saturating_sub(1).min(31);
+        let max = self.base_delay.saturating_mul(1 << exp).min(self.max_delay);
+        max.mul_f64(jitter())
+    }
+}
+
+/// Returns 3 attempts.
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(3)
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE | Method::PUT | Method::DELETE
+    )
+}
+
+fn is_retryable(e: &Error) -> bool {
+    match *e.without_context() {
+        Error::Connect(_) => !is_denied(e),
+        Error::Timeout(ref e) => e.is_connect(),
+        Error::Hyper(ref e) => e.is_incomplete_message() || e.is_canceled() || has_reset_source(e),
+        _ => false,
+    }
+}
+
+#[cfg(feature = "tcp")]
+fn is_denied(e: &Error) -> bool {
+    match e.as_connect_error() {
+        Some(e) => e.is_denied(),
+        None => false,
+    }
+}
+
+#[cfg(not(feature = "tcp"))]
+fn is_denied(_e: &Error) -> bool {
+    false
+}
+
+fn has_reset_source(e: &(dyn StdError + 'static)) -> bool {
+    let mut source = e.source();
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<io::Error>() {
+            return matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+            );
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// Returns a random number in `[0, 1)`.
+fn jitter() -> f64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    // `RandomState` is seeded randomly, which is good enough for spreading
+    // retries without depending on an RNG.
+    let hash = RandomState::new().hash_one(COUNTER.fetch_add(1, Ordering::Relaxed));
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Copy `request` to send it again.
+///
+/// Only the extensions set by this crate are copied.
+pub(crate) fn copy_request(request: &Request<SharedBody>) -> Request<SharedBody> {
+    let mut copy = Request::new(request.body().clone());
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.version_mut() = request.version();
+    *copy.headers_mut() = request.headers().clone();
+    copy_extensions(request.extensions(), copy.extensions_mut());
+    copy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::StatusCode;
+
+    #[test]
+    fn backoff() {
+        let policy = RetryPolicy::new(5)
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(250));
+        for _ in 0..100 {
+            assert!(policy.backoff(1) < Duration::from_millis(100));
+            assert!(policy.backoff(2) < Duration::from_millis(200));
+            assert!(policy.backoff(3) < Duration::from_millis(250));
+            assert!(policy.backoff(u32::MAX) < Duration::from_millis(250));
+        }
+        let delays: Vec<_> = (0..10).map(|_| policy.backoff(3)).collect();
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+
+    #[test]
+    fn applies_to() {
+        let policy = RetryPolicy::default();
+        let request = |method| {
+            let mut request = Request::new(SharedBody::from("body"));
+            *request.method_mut() = method;
+            request
+        };
+        assert!(policy.applies_to(&request(Method::GET)));
+        assert!(policy.applies_to(&request(Method::PUT)));
+        assert!(!policy.applies_to(&request(Method::POST)));
+        assert!(!policy.applies_to(&request(Method::PATCH)));
+        assert!(!RetryPolicy::new(1).applies_to(&request(Method::GET)));
+
+        let body = SharedBody::from_reader(tokio::io::empty(), None);
+        assert!(!policy.applies_to(&Request::new(body)));
+
+        let response = |status| {
+            let mut response = Response::new(hyper::Body::empty());
+            *response.status_mut() = status;
+            Ok(response)
+        };
+        assert!(policy.should_retry(&response(StatusCode::SERVICE_UNAVAILABLE)));
+        assert!(!policy.should_retry(&response(StatusCode::NOT_FOUND)));
+        assert!(!policy.should_retry(&Err(Error::InvalidResponse("x"))));
+    }
+}
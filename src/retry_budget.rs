@@ -0,0 +1,194 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use tokio::time::Instant;
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// number of slots the window is divided into
+const SLOTS: usize = 10;
+
+/// A budget limiting retries to a fraction of the requests sent in a
+/// sliding time window
+///
+/// Every request sent by a [`Client`] configured with
+/// [`ClientBuilder::retry_budget()`] is deposited in the budget, and every
+/// retry must be withdrawn from it. Within the last `ttl`, the number of
+/// retries is limited to `retry_ratio` times the number of requests plus
+/// `min_retries_per_sec` per second, the latter allowing clients with little
+/// traffic to retry at all. This prevents retries from amplifying the load
+/// on a server that is failing most requests.
+///
+/// Clones share the same budget, so a clone can be kept to observe the
+/// budget of a client, e.g. for metrics, see [`available()`].
+///
+/// [`Client`]: crate::Client
+/// [`ClientBuilder::retry_budget()`]: crate::ClientBuilder::retry_budget
+/// [`available()`]: RetryBudget::available
+#[derive(Clone)]
+pub struct RetryBudget {
+    inner: Arc<Mutex<Window>>,
+    slot_len: Duration,
+    reserve: f64,
+    retry_ratio: f64,
+}
+
+struct Window {
+    start: Instant,
+    // (slot number, deposits, withdrawals), indexed by slot number modulo
+    // `SLOTS`
+    slots: [(u64, u64, u64); SLOTS],
+}
+
+impl RetryBudget {
+    /// Create a budget allowing `retry_ratio` retries per request (e.g.
+    /// `0.2` for 20% extra load) plus `min_retries_per_sec` retries per
+    /// second within a sliding window of `ttl`.
+    ///
+    /// `ttl` is clamped to at least 1 second and `retry_ratio` to at least 0.
+    pub fn new(ttl: Duration, min_retries_per_sec: u32, retry_ratio: f64) -> Self {
+        let ttl = ttl.max(Duration::from_secs(1));
+        RetryBudget {
+            inner: Arc::new(Mutex::new(Window {
+                start: Instant::now(),
+                slots: [(0, 0, 0); SLOTS],
+            })),
+            slot_len: ttl / SLOTS as u32,
+            reserve: f64::from(min_retries_per_sec) * ttl.as_secs_f64(),
+            retry_ratio: retry_ratio.max(0.0),
+        }
+    }
+
+    /// Record a request.
+    pub fn deposit(&self) {
+        let mut window = self.inner.lock().unwrap();
+        let current = self.current_slot(&window);
+        window.slot_mut(current).1 += 1;
+    }
+
+    /// Withdraw a retry from the budget.
+    ///
+    /// Returns false, without withdrawing, if the budget is exhausted and
+    /// the request should not be retried.
+    pub fn withdraw(&self) -> bool {
+        let mut window = self.inner.lock().unwrap();
+        let current = self.current_slot(&window);
+        if self.available_in(&window, current) == 0 {
+            return false;
+        }
+        window.slot_mut(current).2 += 1;
+        true
+    }
+
+    /// Returns the number of retries currently allowed.
+    pub fn available(&self) -> u64 {
+        let window = self.inner.lock().unwrap();
+        let current = self.current_slot(&window);
+        self.available_in(&window, current)
+    }
+
+    fn current_slot(&self, window: &Window) -> u64 {
+        (window.start.elapsed().as_nanos() / self.slot_len.as_nanos()) as u64
+    }
+
+    fn available_in(&self, window: &Window, current: u64) -> u64 {
+        let (deposits, withdrawals) = window
+            .slots
+            .iter()
+            .filter(|(slot, _, _)| slot + SLOTS as u64 > current)
+            .fold((0, 0), |(d, w), (_, deposits, withdrawals)| {
+                (d + deposits, w + withdrawals)
+            });
+        let allowed = (self.reserve + self.retry_ratio * deposits as f64) as u64;
+        allowed.saturating_sub(withdrawals)
+    }
+}
+
+impl Window {
+    fn slot_mut(&mut self, current: u64) -> &mut (u64, u64, u64) {
+        let slot = &mut self.slots[(current % SLOTS as u64) as usize];
+        if slot.0 != current {
+            *slot = (current, 0, 0);
+        }
+        slot
+    }
+}
+
+/// Returns a budget allowing 20% extra load and 10 retries per second in a
+/// 10 second window.
+impl Default for RetryBudget {
+    fn default() -> Self {
+        RetryBudget::new(Duration::from_secs(10), 10, 0.2)
+    }
+}
+
+impl fmt::Debug for RetryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryBudget")
+            .field("available", &self.available())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn budget() {
+        let budget = RetryBudget::new(Duration::from_secs(10), 0, 0.2);
+        assert_eq!(budget.available(), 0);
+        assert!(!budget.withdraw());
+
+        for _ in 0..10 {
+            budget.deposit();
+        }
+        assert_eq!(budget.available(), 2);
+        assert!(budget.withdraw());
+        assert!(budget.clone().withdraw());
+        assert!(!budget.withdraw());
+
+        // Deposits and withdrawals expire after `ttl`
+        tokio::time::advance(Duration::from_secs(5)).await;
+        for _ in 0..5 {
+            budget.deposit();
+        }
+        assert_eq!(budget.available(), 1);
+        tokio::time::advance(Duration::from_secs(6)).await;
+        assert_eq!(budget.available(), 1);
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert_eq!(budget.available(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reserve() {
+        let budget = RetryBudget::new(Duration::from_secs(2), 3, 0.0);
+        assert_eq!(budget.available(), 6);
+        for _ in 0..6 {
+            assert!(budget.withdraw());
+        }
+        assert!(!budget.withdraw());
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn client_deposits() {
+        use crate::{Client, HttpConnector};
+
+        let budget = RetryBudget::new(Duration::from_secs(10), 0, 1.0);
+        let client = Client::builder()
+            .retry_budget(Some(budget.clone()))
+            .build(HttpConnector::new());
+        // Failed requests are deposited too
+        for _ in 0..2 {
+            let _ = client.get("http://127.0.0.1:1/").unwrap().send().await;
+        }
+        assert_eq!(budget.available(), 2);
+        assert_eq!(client.retry_budget().unwrap().available(), 2);
+    }
+}
@@ -0,0 +1,762 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::async_client::{Client, RequestBuilder, RequestDetails};
+use crate::error::Error;
+use crate::Response;
+
+use headers::{Expires, Header};
+use http::uri::InvalidUri;
+use hyper::header::{HeaderMap, HeaderValue, AUTHORIZATION, COOKIE, SET_COOKIE};
+use hyper::{Method, Uri};
+
+use std::cmp::Reverse;
+use std::fmt;
+#[cfg(feature = "json")]
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Provides the `Authorization` header for requests sent through a
+/// [`Session`]
+///
+/// This is implemented for closures taking the request URI.
+pub trait AuthProvider: Send + Sync + 'static {
+    /// Returns the value of the `Authorization` header for a request to
+    /// `uri`, or `None` to send the request without credentials.
+    fn authorization(&self, uri: &Uri) -> Option<HeaderValue>;
+}
+
+impl<F> AuthProvider for F
+where
+    F: Fn(&Uri) -> Option<HeaderValue> + Send + Sync + 'static,
+{
+    fn authorization(&self, uri: &Uri) -> Option<HeaderValue> {
+        self(uri)
+    }
+}
+
+/// A [`Client`] bundled with state shared by a series of requests
+///
+/// A session holds a base URL that relative request paths are resolved
+/// against, default headers, an optional [`AuthProvider`] and a
+/// [`CookieJar`] that is updated with the cookies set by responses. Cloned
+/// sessions share the cookie jar.
+///
+/// The session state is applied when the request is sent, so it is not
+/// included in the result of [`RequestBuilder::build()`].
+#[derive(Clone)]
+pub struct Session {
+    client: Client,
+    base_url: Option<Uri>,
+    default_headers: HeaderMap,
+    auth: Option<Arc<dyn AuthProvider>>,
+    cookies: Arc<CookieJar>,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("base_url", &self.base_url)
+            .field("default_headers", &self.default_headers.len())
+            .field("auth", &self.auth.as_ref().map_or("None", |_| "Some(...)"))
+            .finish()
+    }
+}
+
+macro_rules! define_method_fn {
+    (@internal $name:ident, $method:ident, $method_str:expr) => {
+        #[doc = "Initiate a "]
+        #[doc = $method_str]
+        #[doc = " request with the specified URI or path."]
+        ///
+        /// See [`Session::request()`].
+        pub fn $name(&self, uri: &str) -> Result<RequestBuilder<'_>, Error> {
+            self.request(Method::$method, uri)
+        }
+    };
+
+    ($name:ident, $method:ident) => {
+        define_method_fn!(@internal $name, $method, stringify!($method));
+    };
+}
+
+impl Session {
+    /// Create a new session sending requests through `client`.
+    pub fn new(client: Client) -> Self {
+        Session {
+            client,
+            base_url: None,
+            default_headers: HeaderMap::new(),
+            auth: None,
+            cookies: Arc::new(CookieJar::new()),
+        }
+    }
+
+    /// Set the URL that relative request paths are resolved against.
+    pub fn set_base_url(&mut self, base_url: Uri) -> &mut Self {
+        self.base_url = Some(base_url);
+        self
+    }
+
+    /// Returns the headers added to every request.
+    ///
+    /// Headers set on individual requests take precedence.
+    pub fn default_headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.default_headers
+    }
+
+    /// Set the provider of the `Authorization` header for requests that
+    /// don't set it explicitly.
+    pub fn set_auth<A: AuthProvider>(&mut self, auth: A) -> &mut Self {
+        self.auth = Some(Arc::new(auth));
+        self
+    }
+
+    /// Returns the cookies stored by the session.
+    pub fn cookies(&self) -> &CookieJar {
+        &self.cookies
+    }
+
+    /// Replace the cookie jar of the session, e.g. with one created by
+    /// [`CookieJar::with_public_suffix_list()`].
+    ///
+    /// Sessions cloned before keep sharing the previous jar.
+    pub fn set_cookie_jar(&mut self, jar: CookieJar) -> &mut Self {
+        self.cookies = Arc::new(jar);
+        self
+    }
+
+    /// Returns the underlying client.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Initiate a request with the specified method and URI or path.
+    ///
+    /// If `uri` is not an absolute URI, it is resolved against the base URL
+    /// of the session: paths starting with `/` replace the path of the base
+    /// URL, other paths are appended to it.
+    ///
+    /// Returns an error if `uri` is invalid or relative without a base URL.
+    pub fn request(&self, method: Method, uri: &str) -> Result<RequestBuilder<'_>, Error> {
+        let uri = self.resolve(uri)?;
+        Ok(self.client.request(method, uri)?.session(self))
+    }
+
+    define_method_fn!(get, GET);
+    define_method_fn!(head, HEAD);
+    define_method_fn!(post, POST);
+    define_method_fn!(patch, PATCH);
+    define_method_fn!(put, PUT);
+    define_method_fn!(delete, DELETE);
+
+    fn resolve(&self, uri: &str) -> Result<Uri, Error> {
+        let parsed = uri.parse::<Uri>();
+        let base = match (&self.base_url, parsed) {
+            (_, Ok(parsed)) if parsed.scheme().is_some() => return Ok(parsed),
+            (Some(base), _) => base,
+            (None, parsed) => return parsed.map_err(|e| Error::Http(e.into())),
+        };
+        let path = match uri.starts_with('/') {
+            true => uri.to_owned(),
+            false => format!("{}/{}", base.path().trim_end_matches('/'), uri),
+        };
+        let mut parts = base.clone().into_parts();
+        parts.path_and_query = Some(
+            path.parse()
+                .map_err(|e: InvalidUri| Error::Http(e.into()))?,
+        );
+        Uri::from_parts(parts).map_err(|e| Error::Http(e.into()))
+    }
+
+    pub(crate) async fn send(&self, mut details: RequestDetails) -> Result<Response, Error> {
+        self.prepare(&details.uri, &mut details.headers);
+        let uri = details.uri.clone();
+        let response = details.send(&self.client).await?;
+        let now = self.client.clock().now();
+        self.cookies
+            .store_response_cookies_at(&uri, response.headers(), now);
+        Ok(response)
+    }
+
+    /// Add the default headers, credentials and cookies to a request.
+    fn prepare(&self, uri: &Uri, headers: &mut HeaderMap) {
+        for name in self.default_headers.keys() {
+            if !headers.contains_key(name) {
+                for value in self.default_headers.get_all(name) {
+                    headers.append(name, value.clone());
+                }
+            }
+        }
+        if !headers.contains_key(AUTHORIZATION) {
+            if let Some(value) = self.auth.as_ref().and_then(|auth| auth.authorization(uri)) {
+                headers.insert(AUTHORIZATION, value);
+            }
+        }
+        if !headers.contains_key(COOKIE) {
+            let now = self.client.clock().now();
+            if let Some(value) = self.cookies.cookie_header_at(uri, now) {
+                headers.insert(COOKIE, value);
+            }
+        }
+    }
+}
+
+/// A simple cookie store
+///
+/// Cookies are stored from `Set-Cookie` response headers as described in
+/// RFC 6265 and sent to matching hosts and paths.
+///
+/// Cookies setting the `Domain` attribute to a public suffix such as `com`
+/// are rejected unless they are set by that host itself, in which case they
+/// are only sent back to that host. Without a public suffix list, only
+/// single-label domains are treated as public suffixes, use
+/// [`with_public_suffix_list()`] to reject domains such as `co.uk` as well.
+///
+/// Cookies expire according to the system time, or the clock of the client
+/// when the jar belongs to a [`Session`], see [`ClientBuilder::clock()`].
+///
+/// With the `json` feature, persistent cookies can be saved with
+/// [`save()`] and restored with [`load()`], e.g. to keep a login across
+/// invocations of a command line tool.
+///
+/// [`ClientBuilder::clock()`]: crate::ClientBuilder::clock
+/// [`with_public_suffix_list()`]: CookieJar::with_public_suffix_list
+/// [`save()`]: CookieJar::save
+/// [`load()`]: CookieJar::load
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Mutex<Vec<Cookie>>,
+    #[cfg(feature = "public-suffix")]
+    public_suffixes: Option<publicsuffix::List>,
+}
+
+/// The maximum lifetime of a cookie, 400 days
+const MAX_COOKIE_AGE: Duration = Duration::from_secs(400 * 24 * 60 * 60);
+
+#[derive(Debug, Clone)]
+struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    expires: Option<SystemTime>,
+}
+
+impl Cookie {
+    fn parse(set_cookie: &str, uri: &Uri, now: SystemTime) -> Option<Self> {
+        let host = uri.host()?.to_ascii_lowercase();
+        let mut attrs = set_cookie.split(';');
+        let (name, value) = attrs.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let mut cookie = Cookie {
+            name: name.to_owned(),
+            value: value.trim().to_owned(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(uri.path()).to_owned(),
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attr in attrs {
+            let (key, value) = match attr.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attr.trim(), ""),
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "domain" if !value.is_empty() => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain_match(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_owned(),
+                "secure" => cookie.secure = true,
+                "max-age" => {
+                    max_age = value.parse::<i64>().ok();
+                }
+                "expires" => {
+                    let value = HeaderValue::from_str(value).ok();
+                    if let Ok(expires) = Expires::decode(&mut value.iter()) {
+                        cookie.expires = Some(expires.into());
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Max-Age takes precedence over Expires, see RFC 6265 section 5.3.
+        // Expiry dates are capped as recommended by RFC 6265bis, which also
+        // keeps huge values from overflowing.
+        let max_expires = now + MAX_COOKIE_AGE;
+        match max_age {
+            Some(secs) if secs <= 0 => cookie.expires = Some(SystemTime::UNIX_EPOCH),
+            Some(secs) => {
+                let expires = now.checked_add(Duration::from_secs(secs as u64));
+                cookie.expires = Some(expires.unwrap_or(max_expires));
+            }
+            None => {}
+        }
+        if let Some(expires) = cookie.expires {
+            cookie.expires = Some(expires.min(max_expires));
+        }
+        Some(cookie)
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        matches!(self.expires, Some(expires) if expires <= now)
+    }
+
+    fn matches(&self, uri: &Uri, host: &str) -> bool {
+        let domain_ok = match self.host_only {
+            true => self.domain == host,
+            false => domain_match(host, &self.domain),
+        };
+        domain_ok
+            && path_match(uri.path(), &self.path)
+            && (!self.secure || uri.scheme_str() == Some("https"))
+    }
+}
+
+/// See RFC 6265 section 5.1.3
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host[..host.len() - domain.len()].ends_with('.')
+            && host.parse::<std::net::IpAddr>().is_err())
+}
+
+/// See RFC 6265 section 5.1.4
+fn default_path(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(i) => &path[..i],
+    }
+}
+
+/// See RFC 6265 section 5.1.4
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || (path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/')))
+}
+
+impl CookieJar {
+    /// Create an empty cookie jar.
+    pub fn new() -> Self {
+        CookieJar::default()
+    }
+
+    /// Create an empty cookie jar that checks the `Domain` attribute of
+    /// cookies against `list`, e.g. the list published at
+    /// [`publicsuffix::LIST_URL`].
+    #[cfg(feature = "public-suffix")]
+    pub fn with_public_suffix_list(list: publicsuffix::List) -> Self {
+        CookieJar {
+            cookies: Mutex::default(),
+            public_suffixes: Some(list),
+        }
+    }
+
+    /// Returns true if `domain` is a public suffix, see RFC 6265 section 5.3.
+    fn is_public_suffix(&self, domain: &str) -> bool {
+        #[cfg(feature = "public-suffix")]
+        if let Some(ref list) = self.public_suffixes {
+            use publicsuffix::Psl;
+            return list
+                .suffix(domain.as_bytes())
+                .is_some_and(|suffix| suffix.as_bytes() == domain.as_bytes());
+        }
+        !domain.contains('.')
+    }
+
+    /// Store the cookies set by a response to a request to `uri`.
+    pub fn store_response_cookies(&self, uri: &Uri, headers: &HeaderMap) {
+        self.store_response_cookies_at(uri, headers, SystemTime::now())
+    }
+
+    fn store_response_cookies_at(&self, uri: &Uri, headers: &HeaderMap, now: SystemTime) {
+        let mut cookies = self.cookies.lock().unwrap();
+        for value in headers.get_all(SET_COOKIE) {
+            let cookie = value.to_str().ok().and_then(|v| Cookie::parse(v, uri, now));
+            let mut cookie = match cookie {
+                Some(cookie) => cookie,
+                None => continue,
+            };
+            if !cookie.host_only && self.is_public_suffix(&cookie.domain) {
+                // `Domain` matched the host, so the host is the public suffix
+                match uri.host().map(str::to_ascii_lowercase) == Some(cookie.domain.clone()) {
+                    true => cookie.host_only = true,
+                    false => continue,
+                }
+            }
+            cookies.retain(|c| {
+                !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path)
+            });
+            if !cookie.is_expired(now) {
+                cookies.push(cookie);
+            }
+        }
+    }
+
+    /// Returns the value of the `Cookie` header for a request to `uri`.
+    pub fn cookie_header(&self, uri: &Uri) -> Option<HeaderValue> {
+        self.cookie_header_at(uri, SystemTime::now())
+    }
+
+    fn cookie_header_at(&self, uri: &Uri, now: SystemTime) -> Option<HeaderValue> {
+        let host = uri.host()?.to_ascii_lowercase();
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|c| !c.is_expired(now));
+        let mut matching: Vec<_> = cookies.iter().filter(|c| c.matches(uri, &host)).collect();
+        if matching.is_empty() {
+            return None;
+        }
+        // Cookies with longer paths are listed first, see RFC 6265 section 5.4
+        matching.sort_by_key(|c| Reverse(c.path.len()));
+        let value = matching
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        HeaderValue::from_str(&value).ok()
+    }
+
+    /// Remove all cookies.
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+
+    /// Write the persistent cookies to `writer` as JSON.
+    ///
+    /// Session cookies, which have neither `Expires` nor `Max-Age`, and
+    /// expired cookies are skipped. Use [`load()`] to restore the cookies.
+    ///
+    /// [`load()`]: CookieJar::load
+    #[cfg(feature = "json")]
+    pub fn save<W: io::Write>(&self, writer: W) -> Result<(), Error> {
+        let now = SystemTime::now();
+        let cookies = self.cookies.lock().unwrap();
+        let cookies: Vec<_> = cookies
+            .iter()
+            .filter(|c| !c.is_expired(now))
+            .filter_map(|c| {
+                let expires = c.expires?.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+                Some(serde_json::json!({
+                    "name": c.name,
+                    "value": c.value,
+                    "domain": c.domain,
+                    "host_only": c.host_only,
+                    "path": c.path,
+                    "secure": c.secure,
+                    "expires": expires.as_secs(),
+                }))
+            })
+            .collect();
+        serde_json::to_writer(writer, &cookies).map_err(Error::Json)
+    }
+
+    /// Add the cookies written by [`save()`] to the jar.
+    ///
+    /// Stored cookies with the same name, domain and path are replaced, and
+    /// cookies that have expired since they were saved are skipped.
+    ///
+    /// [`save()`]: CookieJar::save
+    #[cfg(feature = "json")]
+    pub fn load<R: io::Read>(&self, reader: R) -> Result<(), Error> {
+        use serde::de::Error as _;
+        use serde_json::Value;
+
+        let values: Vec<Value> = serde_json::from_reader(reader).map_err(Error::Json)?;
+        let invalid = || Error::Json(serde_json::Error::custom("invalid cookie"));
+        let string = |value: &Value, key| value[key].as_str().map(str::to_owned);
+        let now = SystemTime::now();
+        let mut cookies = self.cookies.lock().unwrap();
+        for value in &values {
+            let expires = value["expires"].as_u64().ok_or_else(invalid)?;
+            let cookie = Cookie {
+                name: string(value, "name").ok_or_else(invalid)?,
+                value: string(value, "value").ok_or_else(invalid)?,
+                domain: string(value, "domain").ok_or_else(invalid)?,
+                host_only: value["host_only"].as_bool().ok_or_else(invalid)?,
+                path: string(value, "path").ok_or_else(invalid)?,
+                secure: value["secure"].as_bool().ok_or_else(invalid)?,
+                expires: SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(expires)),
+            };
+            if cookie.expires.is_none() || cookie.is_expired(now) {
+                continue;
+            }
+            cookies.retain(|c| {
+                !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path)
+            });
+            cookies.push(cookie);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(set_cookies: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in set_cookies {
+            headers.append(SET_COOKIE, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn cookie_jar() {
+        let jar = CookieJar::new();
+        let uri: Uri = "https://www.example.com/app/login".parse().unwrap();
+        jar.store_response_cookies(
+            &uri,
+            &headers(&[
+                "session=abc; Path=/; Secure",
+                "pref=dark; Domain=example.com; Path=/",
+                "app=1",
+                "other=1; Domain=example.org",
+            ]),
+        );
+
+        let header = jar.cookie_header(&"https://www.example.com/app/x".parse().unwrap());
+        assert_eq!(header.unwrap(), "app=1; session=abc; pref=dark");
+        let header = jar.cookie_header(&"http://api.example.com/".parse().unwrap());
+        assert_eq!(header.unwrap(), "pref=dark");
+        assert!(jar
+            .cookie_header(&"https://example.org/".parse().unwrap())
+            .is_none());
+
+        // Huge lifetimes are capped instead of overflowing
+        jar.store_response_cookies(
+            &uri,
+            &headers(&[
+                "long=1; Path=/app; Max-Age=9223372036854775807",
+                "later=1; Path=/app; Expires=Fri, 31 Dec 9999 23:59:59 GMT",
+            ]),
+        );
+        let header = jar.cookie_header(&"https://www.example.com/app".parse().unwrap());
+        assert_eq!(
+            header.unwrap(),
+            "app=1; long=1; later=1; session=abc; pref=dark"
+        );
+        let max_expires = SystemTime::now() + MAX_COOKIE_AGE;
+        let cookies = jar.cookies.lock().unwrap();
+        assert!(!cookies
+            .iter()
+            .any(|c| matches!(c.expires, Some(e) if e > max_expires)));
+        drop(cookies);
+
+        jar.store_response_cookies(&uri, &headers(&["session=; Path=/; Max-Age=0"]));
+        let header = jar.cookie_header(&"https://www.example.com/".parse().unwrap());
+        assert_eq!(header.unwrap(), "pref=dark");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn save_and_load() {
+        let jar = CookieJar::new();
+        let uri: Uri = "https://www.example.com/app/login".parse().unwrap();
+        jar.store_response_cookies(
+            &uri,
+            &headers(&[
+                "session=abc; Path=/",
+                "token=xyz; Domain=example.com; Path=/; Secure; Max-Age=3600",
+                "pref=dark; Expires=Fri, 31 Dec 9999 23:59:59 GMT",
+            ]),
+        );
+        let mut saved = Vec::new();
+        jar.save(&mut saved).unwrap();
+
+        let restored = CookieJar::new();
+        restored.load(&saved[..]).unwrap();
+        let header = restored.cookie_header(&"https://www.example.com/app/x".parse().unwrap());
+        assert_eq!(header.unwrap(), "pref=dark; token=xyz");
+        let header = restored.cookie_header(&"http://api.example.com/".parse().unwrap());
+        assert!(header.is_none());
+
+        // Loading replaces cookies with the same name, domain and path
+        restored.load(&saved[..]).unwrap();
+        let header = restored.cookie_header(&"https://api.example.com/".parse().unwrap());
+        assert_eq!(header.unwrap(), "token=xyz");
+
+        let saved = r#"[{"name": "old", "value": "1", "domain": "example.org",
+            "host_only": true, "path": "/", "secure": false, "expires": 1}]"#;
+        restored.load(saved.as_bytes()).unwrap();
+        let header = restored.cookie_header(&"https://example.org/".parse().unwrap());
+        assert!(header.is_none());
+
+        let err = restored.load(&br#"[{"name": "a"}]"#[..]).unwrap_err();
+        assert!(matches!(err, Error::Json(_)));
+        assert!(restored.load(&b"{"[..]).is_err());
+    }
+
+    #[test]
+    fn public_suffix_domains() {
+        let jar = CookieJar::new();
+        let uri: Uri = "https://www.example.com/".parse().unwrap();
+        jar.store_response_cookies(&uri, &headers(&["a=1; Domain=com", "b=1; Domain=.com"]));
+        assert!(jar
+            .cookie_header(&"https://other.com/".parse().unwrap())
+            .is_none());
+
+        // The host itself may set a cookie for its own single-label domain
+        let uri: Uri = "http://localhost/".parse().unwrap();
+        jar.store_response_cookies(&uri, &headers(&["c=1; Domain=localhost"]));
+        assert_eq!(jar.cookie_header(&uri).unwrap(), "c=1");
+    }
+
+    #[cfg(feature = "public-suffix")]
+    #[test]
+    fn public_suffix_list() {
+        let list = "// ===BEGIN ICANN DOMAINS===\ncom\nco.uk\n*.ck\n!www.ck\n"
+            .parse()
+            .unwrap();
+        let jar = CookieJar::with_public_suffix_list(list);
+        let uri: Uri = "https://www.example.co.uk/".parse().unwrap();
+        jar.store_response_cookies(
+            &uri,
+            &headers(&["a=1; Domain=co.uk", "b=1; Domain=example.co.uk"]),
+        );
+        let header = jar.cookie_header(&"https://other.co.uk/".parse().unwrap());
+        assert!(header.is_none());
+        let header = jar.cookie_header(&"https://api.example.co.uk/".parse().unwrap());
+        assert_eq!(header.unwrap(), "b=1");
+
+        let uri: Uri = "https://foo.bar.ck/".parse().unwrap();
+        jar.store_response_cookies(&uri, &headers(&["c=1; Domain=bar.ck"]));
+        let uri: Uri = "https://www.ck/".parse().unwrap();
+        jar.store_response_cookies(&uri, &headers(&["d=1; Domain=www.ck"]));
+        assert!(jar
+            .cookie_header(&"https://baz.bar.ck/".parse().unwrap())
+            .is_none());
+        assert_eq!(jar.cookie_header(&uri).unwrap(), "d=1");
+
+        // A public suffix host may set host-only cookies for itself
+        let uri: Uri = "https://co.uk/".parse().unwrap();
+        jar.store_response_cookies(&uri, &headers(&["e=1; Domain=co.uk"]));
+        assert_eq!(jar.cookie_header(&uri).unwrap(), "e=1");
+        let header = jar.cookie_header(&"https://www.example.co.uk/".parse().unwrap());
+        assert_eq!(header.unwrap(), "b=1");
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn resolve() {
+        use crate::connector::HttpConnector;
+
+        let mut session = Session::new(Client::with_connector(HttpConnector::new()));
+        assert!(session.resolve("v1/items").is_err());
+        session.set_base_url("https://example.com/api/".parse().unwrap());
+        assert_eq!(
+            session.resolve("v1/items?x=1").unwrap(),
+            "https://example.com/api/v1/items?x=1"
+        );
+        assert_eq!(
+            session.resolve("/health").unwrap(),
+            "https://example.com/health"
+        );
+        assert_eq!(
+            session.resolve("http://other.com/").unwrap(),
+            "http://other.com/"
+        );
+        assert_eq!(
+            session.resolve("redirect?to=http://other.com/").unwrap(),
+            "https://example.com/api/redirect?to=http://other.com/"
+        );
+        assert_eq!(
+            session.resolve("/redirect?to=http://other.com/").unwrap(),
+            "https://example.com/redirect?to=http://other.com/"
+        );
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn session() {
+        use crate::async_client::tests::test_http_server_responses;
+        use crate::connector::HttpConnector;
+
+        let (addr, server) = test_http_server_responses(vec![
+            "HTTP/1.1 200 OK\r\nSet-Cookie: id=42\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+        ])
+        .await;
+
+        let mut session = Session::new(Client::with_connector(HttpConnector::new()));
+        session.set_base_url(format!("http://{}/api", addr).parse().unwrap());
+        session
+            .default_headers_mut()
+            .insert("x-api-version", HeaderValue::from_static("2"));
+        session.set_auth(|_: &Uri| Some(HeaderValue::from_static("Bearer token")));
+        session.get("login").unwrap().send().await.unwrap();
+        session.get("items").unwrap().send().await.unwrap();
+
+        let requests: Vec<_> = server
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|request| String::from_utf8(request).unwrap())
+            .collect();
+        assert!(requests[0].starts_with("GET /api/login HTTP/1.1\r\n"));
+        assert!(requests[0].contains("\r\nx-api-version: 2\r\n"));
+        assert!(requests[0].contains("\r\nauthorization: Bearer token\r\n"));
+        assert!(!requests[0].contains("cookie"));
+        assert!(requests[1].starts_with("GET /api/items HTTP/1.1\r\n"));
+        assert!(requests[1].contains("\r\ncookie: id=42\r\n"));
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn cookie_expiry_uses_client_clock() {
+        use crate::async_client::tests::test_http_server_responses;
+        use crate::connector::HttpConnector;
+        use crate::Clock;
+
+        #[derive(Clone)]
+        struct TestClock(Arc<Mutex<SystemTime>>);
+
+        impl Clock for TestClock {
+            fn now(&self) -> SystemTime {
+                *self.0.lock().unwrap()
+            }
+        }
+
+        let (addr, server) = test_http_server_responses(vec![
+            "HTTP/1.1 200 OK\r\nSet-Cookie: id=42; Max-Age=60\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+        ])
+        .await;
+
+        let clock = TestClock(Arc::new(Mutex::new(SystemTime::UNIX_EPOCH)));
+        let client = Client::builder()
+            .clock(clock.clone())
+            .build(HttpConnector::new());
+        let session = Session::new(client);
+        let uri = format!("http://{}/", addr);
+        for secs in [0, 59, 60] {
+            *clock.0.lock().unwrap() = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+            session.get(&uri).unwrap().send().await.unwrap();
+        }
+
+        let requests = server.await.unwrap();
+        let has_cookie =
+            |request: &[u8]| String::from_utf8_lossy(request).contains("cookie: id=42");
+        assert!(!has_cookie(&requests[0]));
+        assert!(has_cookie(&requests[1]));
+        assert!(!has_cookie(&requests[2]));
+    }
+}
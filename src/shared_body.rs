@@ -5,45 +5,376 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use headers::HeaderMap;
-use hyper::body::{Buf, HttpBody};
+#[cfg(feature = "http-1")]
+use hyper::body::Buf;
+use hyper::body::{Bytes, HttpBody, SizeHint};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+use tokio_stream::{Stream, StreamExt};
+use tokio_util::io::ReaderStream;
 
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::error::Error as StdError;
+use std::io::SeekFrom;
+use std::ops::{Bound, RangeBounds};
 use std::pin::Pin;
-use std::sync::Arc;
-use std::task::{Context, Poll};
-use std::{cmp, io};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{ready, Context, Poll};
+use std::{io, mem};
 
 /// This is an alternative to `hyper::Body` for use with HTTP `Request`s
 ///
 /// This can be constructed from `Arc<Vec<u8>>` while `hyper::Body` cannot.
 /// Additionally this type provides a method to get its length.
-pub struct SharedBody(Option<InnerBuf>);
+///
+/// Buffered bodies are backed by [`Bytes`], so cloning and slicing them does
+/// not copy the underlying data.
+///
+/// A `SharedBody` can also be composed of multiple buffers, see
+/// [`SharedBody::from_chunks()`], or stream its contents, see
+/// [`SharedBody::wrap_stream()`] and [`SharedBody::from_reader()`].
+#[derive(Clone)]
+pub struct SharedBody {
+    inner: Inner,
+    progress: Option<UploadProgress>,
+}
+
+#[derive(Clone)]
+enum Inner {
+    Buffered(Bytes),
+    Chunks(VecDeque<Bytes>, OnceLock<Bytes>),
+    Streaming(SharedStream, Option<u64>),
+}
+
+type BoxStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
+
+/// A callback invoked with the number of bytes sent so far and the total
+/// length of the body (if known).
+pub(crate) type ProgressCallback = Arc<dyn Fn(u64, Option<u64>) + Send + Sync>;
+
+#[derive(Clone)]
+struct UploadProgress {
+    callback: ProgressCallback,
+    sent: u64,
+    total: Option<u64>,
+}
+
+/// A stream shared between clones of a streaming `SharedBody`.
+///
+/// The stream can only be consumed once: the first clone that is polled
+/// takes the stream, other clones fail when polled.
+struct SharedStream {
+    shared: Arc<Mutex<Option<BoxStream>>>,
+    taken: Option<BoxStream>,
+}
+
+impl SharedStream {
+    fn new(stream: BoxStream) -> Self {
+        SharedStream {
+            shared: Arc::new(Mutex::new(Some(stream))),
+            taken: None,
+        }
+    }
+
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<Bytes>>> {
+        if self.taken.is_none() {
+            self.taken = self.shared.lock().unwrap().take();
+        }
+        match self.taken {
+            Some(ref mut stream) => stream.as_mut().poll_next(cx),
+            None => Poll::Ready(Some(Err(io::Error::other(
+                "streaming body has already been consumed",
+            )))),
+        }
+    }
+}
 
-enum InnerBuf {
-    Arc(Arc<Vec<u8>>),
-    Static(&'static [u8]),
+impl Clone for SharedStream {
+    fn clone(&self) -> Self {
+        SharedStream {
+            shared: self.shared.clone(),
+            taken: None,
+        }
+    }
 }
 
 impl AsRef<[u8]> for SharedBody {
+    /// Returns the contents of a buffered body.
+    ///
+    /// For bodies composed of multiple chunks, the first call concatenates
+    /// the chunks into a single buffer. Streaming bodies return an empty
+    /// slice since their contents are not available without consuming them.
+    /// Relying on this for streaming bodies is deprecated, use
+    /// [`SharedBody::as_bytes()`] instead, which returns `None` for them.
     fn as_ref(&self) -> &[u8] {
-        match self.0.as_ref() {
-            Some(InnerBuf::Arc(vec)) => vec,
-            Some(InnerBuf::Static(slice)) => slice,
-            None => &[],
+        match &self.inner {
+            Inner::Buffered(bytes) => bytes,
+            Inner::Chunks(chunks, contiguous) => contiguous.get_or_init(|| {
+                chunks
+                    .iter()
+                    .flat_map(|chunk| chunk.iter())
+                    .copied()
+                    .collect()
+            }),
+            Inner::Streaming(..) => &[],
         }
     }
 }
 
 impl SharedBody {
+    fn new(inner: Inner) -> Self {
+        SharedBody {
+            inner,
+            progress: None,
+        }
+    }
+
+    /// Returns the length of a buffered body.
+    ///
+    /// The length of streaming bodies is not available through this method,
+    /// so this always returns 0 for them. Use [`SharedBody::content_length()`]
+    /// to get the length of any body if it is known in advance.
     pub fn len(&self) -> usize {
-        match self.0.as_ref() {
-            Some(InnerBuf::Arc(vec)) => vec.len(),
-            Some(InnerBuf::Static(slice)) => slice.len(),
-            None => 0,
+        match &self.inner {
+            Inner::Buffered(bytes) => bytes.len(),
+            Inner::Chunks(chunks, _) => chunks.iter().map(Bytes::len).sum(),
+            Inner::Streaming(..) => 0,
+        }
+    }
+
+    /// Returns true if the body is known to be empty.
+    ///
+    /// Streaming bodies are only empty if they were created with a length of
+    /// zero, since their contents are not known before they are consumed.
+    pub fn is_empty(&self) -> bool {
+        match self.inner {
+            Inner::Buffered(_) | Inner::Chunks(..) => self.len() == 0,
+            Inner::Streaming(_, len) => len == Some(0),
+        }
+    }
+
+    /// Returns the contents of the body without consuming it, or None if
+    /// this is a streaming body.
+    ///
+    /// This allows inspecting the body of a request, e.g. for logging, while
+    /// leaving it intact for sending. See the `AsRef<[u8]>` implementation
+    /// regarding bodies composed of multiple chunks.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self.inner {
+            Inner::Streaming(..) => None,
+            _ => Some(self.as_ref()),
         }
     }
 
     pub fn empty() -> Self {
-        SharedBody(None)
+        SharedBody::new(Inner::Buffered(Bytes::new()))
+    }
+
+    /// Create a body composed of multiple buffers.
+    ///
+    /// The buffers are sent one after the other without being concatenated
+    /// into a single allocation, which is useful for composing framed
+    /// payloads, e.g. a header followed by a shared payload.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the chunks is a streaming body.
+    pub fn from_chunks<I>(chunks: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<SharedBody>,
+    {
+        let mut bufs = VecDeque::new();
+        for chunk in chunks {
+            match chunk.into().inner {
+                Inner::Buffered(bytes) => bufs.push_back(bytes),
+                Inner::Chunks(chunks, _) => bufs.extend(chunks),
+                Inner::Streaming(..) => panic!("streaming bodies cannot be used as chunks"),
+            }
+        }
+        bufs.retain(|bytes| !bytes.is_empty());
+        SharedBody::new(Inner::Chunks(bufs, OnceLock::new()))
+    }
+
+    /// Create a body that streams the chunks yielded by `stream`.
+    ///
+    /// Since the length of the body is not known in advance, it is sent
+    /// using chunked transfer-encoding.
+    ///
+    /// NOTE: the stream can only be consumed once. It is shared between
+    /// clones of the returned body, and polling a clone after the stream has
+    /// been taken by another clone results in an error.
+    pub fn wrap_stream<S, O, E>(stream: S) -> Self
+    where
+        S: Stream<Item = Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: Into<Box<dyn StdError + Send + Sync>> + 'static,
+    {
+        let stream = stream.map(|res| res.map(Into::into).map_err(io::Error::other));
+        SharedBody::new(Inner::Streaming(SharedStream::new(Box::pin(stream)), None))
+    }
+
+    /// Create a body that streams the contents of `reader`.
+    ///
+    /// If `len` is provided, the body is sent with a `Content-Length` header
+    /// and `reader` must produce exactly `len` bytes, otherwise sending the
+    /// request fails. If `len` is `None`, the body is sent using chunked
+    /// transfer-encoding.
+    ///
+    /// See [`SharedBody::wrap_stream()`] regarding cloning streaming bodies.
+    pub fn from_reader<R>(reader: R, len: Option<u64>) -> Self
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        let stream = SharedStream::new(Box::pin(ReaderStream::new(reader)));
+        SharedBody::new(Inner::Streaming(stream, len))
+    }
+
+    /// Create a body that streams the contents of `file` within `range`.
+    ///
+    /// Pass `..` to stream the whole file. The body is sent with a
+    /// `Content-Length` header. Returns an error if `range` is out of the
+    /// bounds of the file.
+    ///
+    /// See [`SharedBody::wrap_stream()`] regarding cloning streaming bodies.
+    pub async fn from_file<R: RangeBounds<u64>>(mut file: File, range: R) -> io::Result<Self> {
+        let file_len = file.metadata().await?.len();
+        let begin = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.saturating_add(1),
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => file_len,
+        };
+        if begin > end || end > file_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "range {}..{} out of bounds for file of length {}",
+                    begin, end, file_len
+                ),
+            ));
+        }
+        file.seek(SeekFrom::Start(begin)).await?;
+        let len = end - begin;
+        Ok(SharedBody::from_reader(file.take(len), Some(len)))
+    }
+
+    /// Create a body that streams the data frames of an http-body 1.0
+    /// `Body`.
+    ///
+    /// Data frames are passed on without copying if `B::Data` is [`Bytes`].
+    /// Trailers are discarded. If the size hint of `body` is exact, the body
+    /// is sent with a `Content-Length` header.
+    ///
+    /// See [`SharedBody::wrap_stream()`] regarding cloning streaming bodies.
+    #[cfg(feature = "http-1")]
+    pub fn from_http1_body<B>(body: B) -> Self
+    where
+        B: http_body_1::Body + Send + 'static,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        let len = body.size_hint().exact();
+        let stream = SharedStream::new(Box::pin(Http1Stream(Box::pin(body))));
+        SharedBody::new(Inner::Streaming(stream, len))
+    }
+
+    /// Returns true if this body was created through
+    /// [`SharedBody::wrap_stream()`], [`SharedBody::from_reader()`] or
+    /// [`SharedBody::from_file()`].
+    pub fn is_streaming(&self) -> bool {
+        matches!(self.inner, Inner::Streaming(..))
+    }
+
+    /// Returns the buffers of a body that is not streaming.
+    pub(crate) fn buffers(&self) -> Option<Vec<Bytes>> {
+        match &self.inner {
+            Inner::Buffered(bytes) => Some(vec![bytes.clone()]),
+            Inner::Chunks(chunks, _) => Some(chunks.iter().cloned().collect()),
+            Inner::Streaming(..) => None,
+        }
+    }
+
+    /// Returns the length of the body if it is known in advance.
+    ///
+    /// For bodies that have been partially polled, this returns the length
+    /// of the remaining data.
+    pub fn content_length(&self) -> Option<u64> {
+        match self.inner {
+            Inner::Buffered(_) | Inner::Chunks(..) => Some(self.len() as u64),
+            Inner::Streaming(_, len) => len,
+        }
+    }
+
+    /// Register a callback that is invoked each time a chunk of this body is
+    /// handed over to the connection for writing.
+    ///
+    /// The callback receives the number of bytes sent so far and the total
+    /// length of the body if it is known. This replaces any previously
+    /// registered callback.
+    pub fn on_progress<F>(self, callback: F) -> Self
+    where
+        F: Fn(u64, Option<u64>) + Send + Sync + 'static,
+    {
+        self.with_progress_callback(Arc::new(callback))
+    }
+
+    pub(crate) fn with_progress_callback(mut self, callback: ProgressCallback) -> Self {
+        self.progress = Some(UploadProgress {
+            callback,
+            sent: 0,
+            total: self.content_length(),
+        });
+        self
+    }
+
+    /// Returns a slice of this body without copying the underlying data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or if this is a streaming body.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> Self {
+        let len = self.len();
+        let begin = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => len,
+        };
+        assert!(
+            begin <= end && end <= len,
+            "range {}..{} out of bounds for body of length {}",
+            begin,
+            end,
+            len
+        );
+        match &self.inner {
+            Inner::Buffered(bytes) => SharedBody::new(Inner::Buffered(bytes.slice(begin..end))),
+            Inner::Chunks(chunks, _) => {
+                let mut offset = 0;
+                let mut sliced = VecDeque::new();
+                for chunk in chunks {
+                    let (chunk_begin, chunk_end) = (offset, offset + chunk.len());
+                    offset = chunk_end;
+                    if chunk_end <= begin || chunk_begin >= end {
+                        continue;
+                    }
+                    let from = begin.saturating_sub(chunk_begin);
+                    let to = end.min(chunk_end) - chunk_begin;
+                    sliced.push_back(chunk.slice(from..to));
+                }
+                SharedBody::new(Inner::Chunks(sliced, OnceLock::new()))
+            }
+            Inner::Streaming(..) => panic!("streaming bodies cannot be sliced"),
+        }
     }
 }
 
@@ -55,51 +386,132 @@ impl Default for SharedBody {
     }
 }
 
+// `Arc<Vec<u8>>` and `Arc<str>` do not implement `AsRef<[u8]>` which is
+// required by `Bytes::from_owner()`.
+struct ArcVec(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for ArcVec {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+struct ArcStr(Arc<str>);
+
+impl AsRef<[u8]> for ArcStr {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
 impl From<Arc<Vec<u8>>> for SharedBody {
     fn from(arc: Arc<Vec<u8>>) -> Self {
-        SharedBody(Some(InnerBuf::Arc(arc)))
+        SharedBody::new(Inner::Buffered(Bytes::from_owner(ArcVec(arc))))
     }
 }
 
 impl From<Vec<u8>> for SharedBody {
     fn from(vec: Vec<u8>) -> Self {
-        SharedBody(Some(InnerBuf::Arc(Arc::new(vec))))
+        SharedBody::new(Inner::Buffered(Bytes::from(vec)))
     }
 }
 
 impl From<String> for SharedBody {
     fn from(s: String) -> Self {
-        SharedBody(Some(InnerBuf::Arc(Arc::new(s.into_bytes()))))
+        SharedBody::new(Inner::Buffered(Bytes::from(s)))
     }
 }
 
 impl From<&'static [u8]> for SharedBody {
     fn from(slice: &'static [u8]) -> Self {
-        SharedBody(Some(InnerBuf::Static(slice)))
+        SharedBody::new(Inner::Buffered(Bytes::from_static(slice)))
     }
 }
 
 impl From<&'static str> for SharedBody {
     fn from(s: &'static str) -> Self {
-        SharedBody(Some(InnerBuf::Static(s.as_bytes())))
+        SharedBody::new(Inner::Buffered(Bytes::from_static(s.as_bytes())))
+    }
+}
+
+impl From<Bytes> for SharedBody {
+    fn from(bytes: Bytes) -> Self {
+        SharedBody::new(Inner::Buffered(bytes))
+    }
+}
+
+impl From<Arc<[u8]>> for SharedBody {
+    fn from(arc: Arc<[u8]>) -> Self {
+        SharedBody::new(Inner::Buffered(Bytes::from_owner(arc)))
+    }
+}
+
+impl From<Arc<str>> for SharedBody {
+    fn from(arc: Arc<str>) -> Self {
+        SharedBody::new(Inner::Buffered(Bytes::from_owner(ArcStr(arc))))
+    }
+}
+
+impl From<Cow<'static, [u8]>> for SharedBody {
+    fn from(cow: Cow<'static, [u8]>) -> Self {
+        match cow {
+            Cow::Borrowed(slice) => SharedBody::from(slice),
+            Cow::Owned(vec) => SharedBody::from(vec),
+        }
+    }
+}
+
+impl Inner {
+    fn poll_data(&mut self, cx: &mut Context<'_>) -> Poll<Option<io::Result<Bytes>>> {
+        match self {
+            Inner::Buffered(bytes) if bytes.is_empty() => Poll::Ready(None),
+            Inner::Buffered(bytes) => Poll::Ready(Some(Ok(mem::take(bytes)))),
+            Inner::Chunks(chunks, _) => Poll::Ready(chunks.pop_front().map(Ok)),
+            Inner::Streaming(stream, remaining) => loop {
+                match ready!(stream.poll_next(cx)) {
+                    Some(Ok(chunk)) if chunk.is_empty() => continue,
+                    Some(Ok(chunk)) => {
+                        if let Some(remaining) = remaining {
+                            *remaining = remaining.saturating_sub(chunk.len() as u64);
+                        }
+                        return Poll::Ready(Some(Ok(chunk)));
+                    }
+                    res => return Poll::Ready(res),
+                }
+            },
+        }
     }
 }
 
 impl HttpBody for SharedBody {
-    type Data = SharedBuf;
+    type Data = Bytes;
     type Error = io::Error;
 
     fn poll_data(
         self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
+        cx: &mut Context<'_>,
     ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
-        let opt = self
-            .get_mut()
-            .0
-            .take()
-            .map(|bytes| SharedBuf { bytes, pos: 0 })
-            .map(Ok);
-        Poll::Ready(opt)
+        let this = self.get_mut();
+        let res = ready!(this.inner.poll_data(cx));
+        if let (Some(progress), Some(Ok(chunk))) = (&mut this.progress, &res) {
+            progress.sent += chunk.len() as u64;
+            (progress.callback)(progress.sent, progress.total);
+        }
+        Poll::Ready(res)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self.inner {
+            Inner::Buffered(_) | Inner::Chunks(..) => self.is_empty(),
+            Inner::Streaming(_, remaining) => remaining == Some(0),
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self.content_length() {
+            Some(len) => SizeHint::with_exact(len),
+            None => SizeHint::default(),
+        }
     }
 
     fn poll_trailers(
@@ -110,33 +522,251 @@ impl HttpBody for SharedBody {
     }
 }
 
-pub struct SharedBuf {
-    bytes: InnerBuf,
-    pos: usize,
+#[cfg(feature = "http-1")]
+impl http_body_1::Body for SharedBody {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<http_body_1::Frame<Self::Data>, Self::Error>>> {
+        HttpBody::poll_data(self, cx).map(|res| res.map(|res| res.map(http_body_1::Frame::data)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        HttpBody::is_end_stream(self)
+    }
+
+    fn size_hint(&self) -> http_body_1::SizeHint {
+        match self.content_length() {
+            Some(len) => http_body_1::SizeHint::with_exact(len),
+            None => http_body_1::SizeHint::default(),
+        }
+    }
 }
 
-impl SharedBuf {
-    fn len(&self) -> usize {
-        match self.bytes {
-            InnerBuf::Arc(ref bytes) => bytes.len(),
-            InnerBuf::Static(ref bytes) => bytes.len(),
+/// Adapts an http-body 1.0 `Body` to a stream of its data frames.
+#[cfg(feature = "http-1")]
+struct Http1Stream<B>(Pin<Box<B>>);
+
+#[cfg(feature = "http-1")]
+impl<B> Stream for Http1Stream<B>
+where
+    B: http_body_1::Body,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match ready!(this.0.as_mut().poll_frame(cx)) {
+                Some(Ok(frame)) => {
+                    if let Ok(mut data) = frame.into_data() {
+                        let len = data.remaining();
+                        return Poll::Ready(Some(Ok(data.copy_to_bytes(len))));
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(io::Error::other(e)))),
+                None => return Poll::Ready(None),
+            }
         }
     }
 }
 
-impl Buf for SharedBuf {
-    fn remaining(&self) -> usize {
-        self.len() - self.pos
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::to_bytes;
+
+    #[tokio::test]
+    async fn conversions() {
+        let bodies = vec![
+            SharedBody::from("hello"),
+            SharedBody::from(b"hello".to_vec()),
+            SharedBody::from(Arc::new(b"hello".to_vec())),
+            SharedBody::from(String::from("hello")),
+            SharedBody::from(Bytes::from_static(b"hello")),
+            SharedBody::from(Arc::<[u8]>::from(&b"hello"[..])),
+            SharedBody::from(Arc::<str>::from("hello")),
+            SharedBody::from(Cow::Borrowed(&b"hello"[..])),
+            SharedBody::from(Cow::<[u8]>::Owned(b"hello".to_vec())),
+        ];
+        for body in bodies {
+            assert_eq!(body.len(), 5);
+            assert_eq!(body.as_ref(), b"hello");
+            assert_eq!(to_bytes(body).await.unwrap(), "hello");
+        }
+    }
+
+    #[test]
+    fn conversions_do_not_copy() {
+        let bytes = Bytes::from(b"hello".to_vec());
+        assert_eq!(
+            SharedBody::from(bytes.clone()).as_ref().as_ptr(),
+            bytes.as_ptr()
+        );
+        let arc = Arc::<[u8]>::from(&b"hello"[..]);
+        assert_eq!(
+            SharedBody::from(arc.clone()).as_ref().as_ptr(),
+            arc.as_ptr()
+        );
+        let arc = Arc::<str>::from("hello");
+        assert_eq!(
+            SharedBody::from(arc.clone()).as_ref().as_ptr(),
+            arc.as_ptr()
+        );
     }
 
-    fn chunk(&self) -> &[u8] {
-        match self.bytes {
-            InnerBuf::Arc(ref bytes) => &bytes[self.pos..],
-            InnerBuf::Static(ref bytes) => &bytes[self.pos..],
+    #[tokio::test]
+    async fn multiple_chunks() {
+        let payload = Arc::new(b"payload".to_vec());
+        let mut body = SharedBody::from_chunks(vec![
+            SharedBody::from("header:"),
+            SharedBody::empty(),
+            SharedBody::from(payload),
+            SharedBody::from(String::from(":trailer")),
+        ]);
+        assert_eq!(body.len(), 22);
+        assert_eq!(body.content_length(), Some(22));
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = body.data().await {
+            chunks.push(chunk.unwrap().to_vec());
         }
+        assert_eq!(
+            chunks,
+            vec![
+                b"header:".to_vec(),
+                b"payload".to_vec(),
+                b":trailer".to_vec()
+            ]
+        );
+
+        let body = SharedBody::from_chunks(vec!["hello", ", ", "world!"]);
+        assert_eq!(body.as_ref(), b"hello, world!");
+    }
+
+    #[tokio::test]
+    async fn clone_and_slice() {
+        let body = SharedBody::from(b"hello, world!".to_vec());
+        let clone = body.clone();
+        assert_eq!(body.slice(7..).as_ref(), b"world!");
+        assert_eq!(body.slice(..=4).as_ref(), b"hello");
+        assert_eq!(to_bytes(clone).await.unwrap(), "hello, world!");
+        assert_eq!(to_bytes(body).await.unwrap(), "hello, world!");
+
+        let body = SharedBody::from_chunks(vec!["hello", ", ", "world!"]);
+        let sliced = body.slice(3..9);
+        assert_eq!(sliced.len(), 6);
+        assert_eq!(to_bytes(sliced).await.unwrap(), "lo, wo");
+        assert_eq!(body.slice(5..7).as_ref(), b", ");
+        assert!(body.slice(5..5).is_empty());
+    }
+
+    #[tokio::test]
+    async fn as_bytes() {
+        let body = SharedBody::from_chunks(vec!["hello", ", ", "world!"]);
+        assert_eq!(body.as_bytes(), Some(&b"hello, world!"[..]));
+        assert!(!body.is_empty());
+        assert_eq!(to_bytes(body).await.unwrap(), "hello, world!");
+        assert_eq!(SharedBody::empty().as_bytes(), Some(&[][..]));
+        assert!(SharedBody::empty().is_empty());
+
+        let body = SharedBody::from_reader(&b"hello"[..], Some(5));
+        assert_eq!(body.as_bytes(), None);
+        assert!(!body.is_empty());
+        assert_eq!(to_bytes(body).await.unwrap(), "hello");
+
+        let body = SharedBody::wrap_stream(tokio_stream::iter(vec![Ok::<_, io::Error>("hello")]));
+        assert_eq!(body.as_bytes(), None);
+        assert!(!body.is_empty());
+        assert!(SharedBody::from_reader(&b""[..], Some(0)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn from_file() {
+        let path = std::env::temp_dir().join(format!(
+            "simple-hyper-client-from-file-{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"hello, world!").unwrap();
+
+        let body = SharedBody::from_file(File::open(&path).await.unwrap(), ..)
+            .await
+            .unwrap();
+        assert_eq!(body.content_length(), Some(13));
+        assert_eq!(to_bytes(body).await.unwrap(), "hello, world!");
+
+        let body = SharedBody::from_file(File::open(&path).await.unwrap(), 7..12)
+            .await
+            .unwrap();
+        assert_eq!(body.content_length(), Some(5));
+        assert_eq!(to_bytes(body).await.unwrap(), "world");
+
+        match SharedBody::from_file(File::open(&path).await.unwrap(), 7..14).await {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+            Ok(_) => panic!("expected an error"),
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn size_hint() {
+        let mut body = SharedBody::from("hello");
+        assert_eq!(body.size_hint().exact(), Some(5));
+        assert!(!body.is_end_stream());
+        body.data().await.unwrap().unwrap();
+        assert_eq!(body.size_hint().exact(), Some(0));
+        assert!(body.is_end_stream());
+
+        assert!(SharedBody::empty().is_end_stream());
+        assert!(SharedBody::from_chunks(vec![""; 3]).is_end_stream());
+
+        let mut body = SharedBody::from_chunks(vec!["hello", ", ", "world!"]);
+        assert_eq!(body.size_hint().exact(), Some(13));
+        body.data().await.unwrap().unwrap();
+        assert_eq!(body.size_hint().exact(), Some(8));
+
+        let mut body = SharedBody::from_reader(&b"hello"[..], Some(5));
+        assert_eq!(body.size_hint().exact(), Some(5));
+        assert!(!body.is_end_stream());
+        body.data().await.unwrap().unwrap();
+        assert_eq!(body.size_hint().exact(), Some(0));
+        assert!(body.is_end_stream());
+
+        let body = SharedBody::from_reader(&b"hello"[..], None);
+        assert_eq!(body.size_hint().exact(), None);
+        assert!(!body.is_end_stream());
+    }
+
+    #[tokio::test]
+    async fn progress() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let calls2 = calls.clone();
+        let body = SharedBody::from_chunks(vec!["hello", ", ", "world!"])
+            .on_progress(move |sent, total| calls2.lock().unwrap().push((sent, total)));
+        assert_eq!(to_bytes(body).await.unwrap(), "hello, world!");
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![(5, Some(13)), (7, Some(13)), (13, Some(13))]
+        );
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let calls2 = calls.clone();
+        let body = SharedBody::from_reader(&b"hello"[..], None)
+            .on_progress(move |sent, total| calls2.lock().unwrap().push((sent, total)));
+        assert_eq!(to_bytes(body).await.unwrap(), "hello");
+        assert_eq!(*calls.lock().unwrap(), vec![(5, None)]);
     }
 
-    fn advance(&mut self, cnt: usize) {
-        self.pos = cmp::min(self.len(), self.pos + cnt);
+    #[tokio::test]
+    async fn clone_streaming_body() {
+        let body = SharedBody::from_reader(&b"hello"[..], Some(5));
+        let clone = body.clone();
+        assert_eq!(to_bytes(body).await.unwrap(), "hello");
+        assert!(to_bytes(clone).await.is_err());
     }
 }
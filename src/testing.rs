@@ -0,0 +1,486 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Utilities for testing code that uses [`Client`] without network access.
+//!
+//! [`Client`]: crate::Client
+
+use crate::async_client::TokioExecutor;
+use crate::connector::{ConnectorAdapter, NetworkConnection, NetworkConnector};
+
+use headers::{Header, HeaderMapExt};
+use hyper::body::{to_bytes, Bytes};
+use hyper::client::connect::{Connected, Connection};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Client as HyperClient, Method, Request, Response, StatusCode, Uri};
+use tokio::io::{self, AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+
+use std::convert::Infallible;
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// A [`NetworkConnector`] serving scripted responses from memory
+///
+/// Responses are added with [`mock()`] or loaded from exchanges recorded
+/// with [`RecordingConnector`] by [`replay()`]. Each request is answered
+/// with the first [`Mock`] matching its method and path, regardless of the
+/// host in the request URI. Requests without a matching mock get a
+/// `404 Not Found` response.
+///
+/// The connector can be cloned to add mocks after a client has been built
+/// with it:
+///
+/// ```ignore
+/// let connector = MockConnector::new();
+/// let client = Client::with_connector(connector.clone());
+/// connector.mock(Mock::new(Method::GET, "/status").body("ok"));
+/// let response = client.get("http://example.com/status")?.send().await?;
+/// ```
+///
+/// [`mock()`]: MockConnector::mock
+/// [`replay()`]: MockConnector::replay
+#[derive(Clone, Default)]
+pub struct MockConnector {
+    mocks: Arc<Mutex<Vec<Mock>>>,
+}
+
+impl MockConnector {
+    pub fn new() -> Self {
+        MockConnector::default()
+    }
+
+    /// Add a scripted response.
+    pub fn mock(&self, mock: Mock) -> &Self {
+        self.mocks.lock().unwrap().push(mock);
+        self
+    }
+
+    /// Create a connector replaying the exchanges recorded to `dir` by a
+    /// [`RecordingConnector`].
+    ///
+    /// Each recorded response is only used once, so repeated requests are
+    /// answered with the responses in the order they were recorded.
+    pub fn replay<P: AsRef<Path>>(dir: P) -> std::io::Result<Self> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension() == Some(RECORDING_EXTENSION.as_ref()) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        let connector = MockConnector::new();
+        for path in paths {
+            connector.mock(parse_exchange(&std::fs::read(path)?)?.once());
+        }
+        Ok(connector)
+    }
+
+    fn respond(&self, request: &Request<Body>) -> Option<Mock> {
+        let mut mocks = self.mocks.lock().unwrap();
+        let i = mocks.iter().position(|mock| mock.matches(request))?;
+        match mocks[i].once {
+            true => Some(mocks.remove(i)),
+            false => Some(mocks[i].clone()),
+        }
+    }
+}
+
+impl NetworkConnector for MockConnector {
+    fn connect(
+        &self,
+        _uri: Uri,
+    ) -> Pin<
+        Box<dyn Future<Output = Result<NetworkConnection, Box<dyn StdError + Send + Sync>>> + Send>,
+    > {
+        let (client, server) = io::duplex(64 * 1024);
+        let connector = self.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |request| {
+                let mock = connector.respond(&request);
+                async move {
+                    let mock = match mock {
+                        Some(mock) => mock,
+                        None => return Ok::<_, Infallible>(not_found(&request)),
+                    };
+                    if let Some(delay) = mock.delay {
+                        tokio::time::sleep(delay).await;
+                    }
+                    Ok(mock.response())
+                }
+            });
+            let _ = Http::new()
+                .http1_only(true)
+                .serve_connection(server, service)
+                .await;
+        });
+        Box::pin(async move { Ok(NetworkConnection::new(MockStream(client))) })
+    }
+}
+
+fn not_found(request: &Request<Body>) -> Response<Body> {
+    let mut response = Response::new(Body::from(format!(
+        "no mock matches {} {}",
+        request.method(),
+        request.uri().path()
+    )));
+    *response.status_mut() = StatusCode::NOT_FOUND;
+    response
+}
+
+/// A scripted response of a [`MockConnector`]
+#[derive(Clone, Debug)]
+pub struct Mock {
+    method: Method,
+    path: String,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    delay: Option<Duration>,
+    once: bool,
+}
+
+impl Mock {
+    /// Create a mock that responds to requests with the specified method and
+    /// path with an empty `200 OK` response.
+    ///
+    /// If `path` contains a query, it must match the query of the request
+    /// as well.
+    pub fn new<P: Into<String>>(method: Method, path: P) -> Self {
+        Mock {
+            method,
+            path: path.into(),
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+            delay: None,
+            once: false,
+        }
+    }
+
+    /// Set the response status.
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Set the response headers. This replaces previously set headers.
+    pub fn headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Add a response header.
+    pub fn header<H: Header>(mut self, header: H) -> Self {
+        self.headers.typed_insert(header);
+        self
+    }
+
+    /// Set the response body.
+    pub fn body<B: Into<Bytes>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Wait for `delay` before sending the response.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Only respond to the first matching request. Subsequent requests are
+    /// answered by the next matching mock.
+    pub fn once(mut self) -> Self {
+        self.once = true;
+        self
+    }
+
+    fn matches(&self, request: &Request<Body>) -> bool {
+        let uri = request.uri();
+        let path = match self.path.contains('?') {
+            true => uri.path_and_query().map_or(uri.path(), |pq| pq.as_str()),
+            false => uri.path(),
+        };
+        *request.method() == self.method && path == self.path
+    }
+
+    fn response(self) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        response
+    }
+}
+
+const RECORDING_EXTENSION: &str = "http";
+
+/// A [`NetworkConnector`] recording the exchanges of another connector
+///
+/// Each request is sent with the wrapped connector and the response is
+/// written to a file in the recording directory once it has been received
+/// completely. The recorded exchanges can be replayed without network
+/// access with [`MockConnector::replay()`]. The recording directory should
+/// be empty, as files of previous recordings are overwritten but not
+/// removed.
+///
+/// Requests are not recorded; only the method and path are stored for
+/// matching requests when replaying.
+#[derive(Clone)]
+pub struct RecordingConnector {
+    client: HyperClient<ConnectorAdapter, Body>,
+    dir: PathBuf,
+    count: Arc<AtomicUsize>,
+}
+
+impl RecordingConnector {
+    pub fn new<C: NetworkConnector, P: Into<PathBuf>>(connector: C, dir: P) -> Self {
+        let client = HyperClient::builder()
+            .executor(TokioExecutor)
+            .build(ConnectorAdapter::new(connector, Arc::new([])));
+        RecordingConnector {
+            client,
+            dir: dir.into(),
+            count: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    async fn forward(
+        self,
+        authority: Uri,
+        request: Request<Body>,
+    ) -> Result<Response<Body>, Box<dyn StdError + Send + Sync>> {
+        let (mut parts, body) = request.into_parts();
+        let path = parts.uri.path_and_query().cloned();
+        let mut uri = authority.into_parts();
+        uri.path_and_query = path;
+        parts.uri = Uri::from_parts(uri)?;
+        let method = parts.method.clone();
+        let path = parts.uri.path_and_query().map_or("/", |pq| pq.as_str());
+        let path = path.to_owned();
+
+        let response = self
+            .client
+            .request(Request::from_parts(parts, body))
+            .await?;
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body).await?;
+
+        let mut data = Vec::new();
+        writeln!(data, "{} {}\n{}", method, path, parts.status.as_u16())?;
+        for (name, value) in &parts.headers {
+            data.extend_from_slice(name.as_str().as_bytes());
+            data.extend_from_slice(b": ");
+            data.extend_from_slice(value.as_bytes());
+            data.push(b'\n');
+        }
+        data.push(b'\n');
+        data.extend_from_slice(&body);
+        let n = self.count.fetch_add(1, Ordering::SeqCst);
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.dir.join(format!("{:04}.{}", n, RECORDING_EXTENSION));
+        tokio::fs::write(path, data).await?;
+
+        Ok(Response::from_parts(parts, Body::from(body)))
+    }
+}
+
+impl NetworkConnector for RecordingConnector {
+    fn connect(
+        &self,
+        uri: Uri,
+    ) -> Pin<
+        Box<dyn Future<Output = Result<NetworkConnection, Box<dyn StdError + Send + Sync>>> + Send>,
+    > {
+        let (client, server) = io::duplex(64 * 1024);
+        let connector = self.clone();
+        tokio::spawn(async move {
+            let service =
+                service_fn(move |request| connector.clone().forward(uri.clone(), request));
+            let _ = Http::new()
+                .http1_only(true)
+                .serve_connection(server, service)
+                .await;
+        });
+        Box::pin(async move { Ok(NetworkConnection::new(MockStream(client))) })
+    }
+}
+
+/// Parse an exchange written by [`RecordingConnector::forward()`].
+fn parse_exchange(data: &[u8]) -> std::io::Result<Mock> {
+    fn split_line(data: &[u8]) -> Option<(&[u8], &[u8])> {
+        let i = data.iter().position(|&b| b == b'\n')?;
+        Some((&data[..i], &data[i + 1..]))
+    }
+    fn parse(data: &[u8]) -> Option<Mock> {
+        let (line, data) = split_line(data)?;
+        let line = std::str::from_utf8(line).ok()?;
+        let (method, path) = line.split_once(' ')?;
+        let method = Method::from_bytes(method.as_bytes()).ok()?;
+        let (line, mut data) = split_line(data)?;
+        let status = StatusCode::from_bytes(line).ok()?;
+        let mut headers = HeaderMap::new();
+        loop {
+            let (line, rest) = split_line(data)?;
+            data = rest;
+            if line.is_empty() {
+                break;
+            }
+            let i = line.iter().position(|&b| b == b':')?;
+            let name = HeaderName::from_bytes(&line[..i]).ok()?;
+            let value = HeaderValue::from_bytes(line[i + 1..].strip_prefix(b" ")?).ok()?;
+            headers.append(name, value);
+        }
+        Some(
+            Mock::new(method, path)
+                .status(status)
+                .headers(headers)
+                .body(Bytes::copy_from_slice(data)),
+        )
+    }
+    parse(data).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid recorded exchange")
+    })
+}
+
+struct MockStream(DuplexStream);
+
+impl Connection for MockStream {
+    fn connected(&self) -> Connected {
+        Connected::new()
+    }
+}
+
+impl AsyncRead for MockStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for MockStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_shutdown(cx)
+    }
+}
+
+#[cfg(all(test, feature = "tcp"))]
+mod tests {
+    use super::*;
+    use crate::connector::HttpConnector;
+    use crate::Client;
+    use headers::ContentType;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn mock_connector() {
+        let connector = MockConnector::new();
+        let client = Client::with_connector(connector.clone());
+        connector
+            .mock(
+                Mock::new(Method::POST, "/items")
+                    .status(StatusCode::CREATED)
+                    .header(ContentType::json())
+                    .body(r#"{"id":1}"#),
+            )
+            .mock(Mock::new(Method::GET, "/slow").delay(Duration::from_millis(50)));
+
+        let response = client
+            .post("http://example.com/items")
+            .unwrap()
+            .body("{}")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers().typed_get(), Some(ContentType::json()));
+        assert_eq!(to_bytes(response.into_body()).await.unwrap(), r#"{"id":1}"#);
+
+        let response = client.get("http://example.com/slow").unwrap().send().await;
+        assert_eq!(response.unwrap().status(), StatusCode::OK);
+
+        let response = client.get("http://example.com/items").unwrap().send().await;
+        assert_eq!(response.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn record_and_replay() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for body in ["first", "second"] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut input = [0u8; 1024];
+                let _ = stream.read(&mut input).await.unwrap();
+                let resp = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nX-Test: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body,
+                    body
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+
+        let dir = std::env::temp_dir().join(format!(
+            "simple-hyper-client-recording-{}",
+            std::process::id()
+        ));
+        let client = Client::with_connector(RecordingConnector::new(HttpConnector::new(), &dir));
+        for body in ["first", "second"] {
+            let response = client
+                .get(format!("http://{}/items?page=1", addr))
+                .unwrap()
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(to_bytes(response.into_body()).await.unwrap(), body);
+        }
+
+        let client = Client::with_connector(MockConnector::replay(&dir).unwrap());
+        for body in ["first", "second"] {
+            let response = client
+                .get("http://example.com/items?page=1")
+                .unwrap()
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.headers()["x-test"], body);
+            assert_eq!(to_bytes(response.into_body()).await.unwrap(), body);
+        }
+        let response = client
+            .get("http://example.com/items?page=1")
+            .unwrap()
+            .send()
+            .await;
+        assert_eq!(response.unwrap().status(), StatusCode::NOT_FOUND);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
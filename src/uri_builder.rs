@@ -0,0 +1,155 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+
+use hyper::Uri;
+
+use std::fmt::Write;
+
+/// A builder for URIs that appends percent-encoded path segments and query
+/// parameters to a base URI
+///
+/// Every character except the unreserved characters of RFC 3986 is
+/// percent-encoded, including `/`, `?` and `#`, so a segment cannot add
+/// further segments or a query to the URI. The segments `.` and `..` are
+/// encoded as well so they are not treated as dot segments.
+///
+/// ```
+/// # use simple_hyper_client::{Uri, UriBuilder};
+/// let base: Uri = "https://example.com/api/".parse().unwrap();
+/// let uri = UriBuilder::new(base)
+///     .path_segments(["users", "a/b?c"])
+///     .query_pair("q", "x&y")
+///     .build()
+///     .unwrap();
+/// assert_eq!(uri, "https://example.com/api/users/a%2Fb%3Fc?q=x%26y");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UriBuilder {
+    base: Uri,
+    path: String,
+    query: Option<String>,
+}
+
+impl UriBuilder {
+    /// Create a builder appending to the path and query of `base`.
+    pub fn new(base: Uri) -> Self {
+        let path = base.path().trim_end_matches('/').to_owned();
+        let query = base.query().map(str::to_owned);
+        UriBuilder { base, path, query }
+    }
+
+    /// Append a single path segment.
+    pub fn path_segment(mut self, segment: &str) -> Self {
+        self.path.push('/');
+        match segment {
+            "." => self.path.push_str("%2E"),
+            ".." => self.path.push_str("%2E%2E"),
+            _ => encode(segment, &mut self.path),
+        }
+        self
+    }
+
+    /// Append path segments in order.
+    pub fn path_segments<I, S>(self, segments: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        segments.into_iter().fold(self, |builder, segment| {
+            builder.path_segment(segment.as_ref())
+        })
+    }
+
+    /// Append a query parameter.
+    pub fn query_pair(mut self, name: &str, value: &str) -> Self {
+        let query = self.query.get_or_insert_with(String::new);
+        if !query.is_empty() {
+            query.push('&');
+        }
+        encode(name, query);
+        query.push('=');
+        encode(value, query);
+        self
+    }
+
+    /// Build the URI.
+    ///
+    /// Returns [`Error::Http`] if the resulting URI is invalid, e.g. if the
+    /// base URI has an authority but no scheme.
+    pub fn build(self) -> Result<Uri, Error> {
+        let mut path_and_query = match self.path.is_empty() {
+            true => "/".to_owned(),
+            false => self.path,
+        };
+        if let Some(query) = self.query {
+            path_and_query.push('?');
+            path_and_query.push_str(&query);
+        }
+        let mut parts = self.base.into_parts();
+        parts.path_and_query = Some(path_and_query.parse().map_err(http::Error::from)?);
+        Uri::from_parts(parts).map_err(|e| Error::Http(e.into()))
+    }
+}
+
+fn encode(s: &str, out: &mut String) {
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(b as char)
+            }
+            _ => write!(out, "%{:02X}", b).expect("writing to a String"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder(base: &str) -> UriBuilder {
+        UriBuilder::new(base.parse().unwrap())
+    }
+
+    #[test]
+    fn path_segments() {
+        let uri = builder("https://example.com")
+            .path_segment("files")
+            .path_segment("../etc/passwd")
+            .build()
+            .unwrap();
+        assert_eq!(uri, "https://example.com/files/..%2Fetc%2Fpasswd");
+
+        let uri = builder("http://example.com/v1/?a=1")
+            .path_segments(["..", ".", "caf\u{e9} #1", ""])
+            .build()
+            .unwrap();
+        assert_eq!(
+            uri,
+            "http://example.com/v1/%2E%2E/%2E/caf%C3%A9%20%231/?a=1"
+        );
+
+        let uri = builder("http://example.com/").build().unwrap();
+        assert_eq!(uri, "http://example.com/");
+    }
+
+    #[test]
+    fn query_pairs() {
+        let uri = builder("http://example.com/search?page=2")
+            .query_pair("q", "a+b=c&d")
+            .query_pair("empty", "")
+            .build()
+            .unwrap();
+        assert_eq!(
+            uri,
+            "http://example.com/search?page=2&q=a%2Bb%3Dc%26d&empty="
+        );
+
+        let uri = builder("/relative").query_pair("x", "1").build().unwrap();
+        assert_eq!(uri, "/relative?x=1");
+    }
+}
@@ -0,0 +1,78 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::request_ext::{redact_uri, REDACTED};
+use crate::shared_body::SharedBody;
+use crate::Response;
+
+use hyper::header::{HeaderMap, HeaderName};
+use hyper::Request;
+
+use std::fmt::Write;
+
+/// Logs request and response heads, see
+/// [`ClientBuilder::log_wire()`](crate::ClientBuilder::log_wire).
+pub(crate) struct WireLog {
+    redacted_headers: Vec<HeaderName>,
+}
+
+impl WireLog {
+    pub fn new(redacted_headers: Vec<HeaderName>) -> Self {
+        WireLog { redacted_headers }
+    }
+
+    pub fn request(&self, request: &Request<SharedBody>) {
+        if log::log_enabled!(log::Level::Debug) {
+            let start = format!(
+                "{} {} {:?}",
+                request.method(),
+                redact_uri(request.uri(), false),
+                request.version()
+            );
+            log::debug!("{}", self.format('>', &start, request.headers()));
+        }
+    }
+
+    pub fn response(&self, response: &Response) {
+        if log::log_enabled!(log::Level::Debug) {
+            let start = format!("{:?} {}", response.version(), response.status());
+            log::debug!("{}", self.format('<', &start, response.headers()));
+        }
+    }
+
+    fn format(&self, prefix: char, start: &str, headers: &HeaderMap) -> String {
+        let mut out = format!("{} {}", prefix, start);
+        for (name, value) in headers {
+            let value = match self.redacted_headers.contains(name) {
+                true => REDACTED.into(),
+                false => String::from_utf8_lossy(value.as_bytes()),
+            };
+            let _ = write!(out, "\n{} {}: {}", prefix, name, value);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+
+    #[test]
+    fn format() {
+        let log = WireLog::new(vec![AUTHORIZATION]);
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_static("Basic Zm9vOmJhcg=="),
+        );
+        assert_eq!(
+            log.format('>', "GET / HTTP/1.1", &headers),
+            "> GET / HTTP/1.1\n> content-type: text/plain\n> authorization: <redacted>"
+        );
+    }
+}
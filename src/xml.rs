@@ -0,0 +1,132 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::error::Error;
+use crate::shared_body::SharedBody;
+
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+const XML_DECLARATION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>";
+
+/// Set the body to the XML serialization of `value` and the `Content-Type`
+/// header to `application/xml`, see RFC 7303.
+pub(crate) fn set_request<T: Serialize + ?Sized>(
+    headers: &mut HeaderMap,
+    body: &mut Option<SharedBody>,
+    value: &T,
+) -> Result<(), Error> {
+    let xml = quick_xml::se::to_string(value).map_err(Error::XmlSerialize)?;
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/xml"));
+    *body = Some(SharedBody::from(format!("{}{}", XML_DECLARATION, xml)));
+    Ok(())
+}
+
+/// Deserialize an XML response body.
+pub(crate) fn parse_response<T: DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+    quick_xml::de::from_reader(body).map_err(Error::XmlDeserialize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::to_bytes;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize)]
+    struct CreateBucketConfiguration {
+        #[serde(rename = "LocationConstraint")]
+        location_constraint: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct ListAllMyBucketsResult {
+        buckets: Buckets,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Buckets {
+        #[serde(default)]
+        bucket: Vec<Bucket>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Bucket {
+        name: String,
+    }
+
+    #[tokio::test]
+    async fn request_body() {
+        let mut headers = HeaderMap::new();
+        let mut body = None;
+        let config = CreateBucketConfiguration {
+            location_constraint: "eu-west-1".to_owned(),
+        };
+        set_request(&mut headers, &mut body, &config).unwrap();
+        assert_eq!(headers[CONTENT_TYPE], "application/xml");
+        let body = to_bytes(body.unwrap()).await.unwrap();
+        assert_eq!(
+            body,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <CreateBucketConfiguration>\
+             <LocationConstraint>eu-west-1</LocationConstraint>\
+             </CreateBucketConfiguration>"
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let body = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <ListAllMyBucketsResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\n\
+              <Buckets>\n\
+                <Bucket><Name>a</Name></Bucket>\n\
+                <Bucket><Name>b &amp; c</Name></Bucket>\n\
+              </Buckets>\n\
+            </ListAllMyBucketsResult>";
+        let result: ListAllMyBucketsResult = parse_response(body).unwrap();
+        let names: Vec<_> = result.buckets.bucket.iter().map(|b| &b.name).collect();
+        assert_eq!(names, ["a", "b & c"]);
+
+        let err = parse_response::<ListAllMyBucketsResult>(b"<Buckets>").unwrap_err();
+        assert!(matches!(err, Error::XmlDeserialize(_)));
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn xml_request() {
+        use crate::async_client::tests::test_http_server_capture_request;
+        use crate::{Client, HttpConnector, ResponseExt};
+
+        let (addr, rx) = test_http_server_capture_request(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/xml\r\nContent-Length: 59\r\n\r\n\
+             <ListAllMyBucketsResult><Buckets/></ListAllMyBucketsResult>",
+        )
+        .await;
+
+        let client = Client::with_connector(HttpConnector::new());
+        let config = CreateBucketConfiguration {
+            location_constraint: "eu-west-1".to_owned(),
+        };
+        let response = client
+            .put(format!("http://{}/bucket", addr))
+            .unwrap()
+            .xml(&config)
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let result: ListAllMyBucketsResult = response.xml().await.unwrap();
+        assert!(result.buckets.bucket.is_empty());
+
+        let request = String::from_utf8(rx.await.unwrap()).unwrap();
+        assert!(request.contains("\r\ncontent-type: application/xml\r\n"));
+        assert!(request.ends_with("</CreateBucketConfiguration>"));
+    }
+}